@@ -0,0 +1,122 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use repo_searcher_github_and_gitlab::filter::{self, Candidate, FilterOptions, IncrementalFilter};
+use std::hint::black_box;
+use std::time::Duration;
+
+const WORDS: [&str; 16] = [
+    "api", "web", "repo", "searcher", "github", "gitlab", "cli", "server", "client", "data",
+    "pipeline", "tools", "docs", "config", "mobile", "core",
+];
+const SEPARATORS: [&str; 4] = ["-", "_", ".", ""];
+const OWNERS: [&str; 5] = ["dima", "acme-corp", "group/subgroup", "open-source", "infra"];
+
+/// Queries of length 1 to 10, prefixes of a typical search
+const QUERY: &str = "reposearch";
+
+/// Generates repository-like candidates with two or three word names, camelCase and
+/// numbered variants and short descriptions
+fn corpus(count: usize) -> Vec<Candidate> {
+    // Small linear congruential generator, so every run benchmarks the same corpus
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move |bound: usize| {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+        (state >> 33) as usize % bound
+    };
+
+    (0..count)
+        .map(|i| {
+            let separator = SEPARATORS[next(SEPARATORS.len())];
+            let mut words: Vec<String> =
+                (0..2 + next(2)).map(|_| WORDS[next(WORDS.len())].to_string()).collect();
+            if separator.is_empty() {
+                // Capitalize the words after the first, like `repoSearcherGithub`
+                for word in words.iter_mut().skip(1) {
+                    word[..1].make_ascii_uppercase();
+                }
+            }
+            let mut name = words.join(separator);
+            if next(4) == 0 {
+                name.push_str(&format!("-v{}", i % 10));
+            }
+            let description = format!(
+                "A {} for the {} {}",
+                WORDS[next(WORDS.len())],
+                WORDS[next(WORDS.len())],
+                WORDS[next(WORDS.len())]
+            );
+            Candidate::repository(&name, OWNERS[next(OWNERS.len())], &description)
+        })
+        .collect()
+}
+
+/// Filters realistic corpora with queries growing from 1 to 10 characters
+fn bench_query_lengths(c: &mut Criterion) {
+    let options = FilterOptions::default();
+
+    for size in [10_000, 50_000, 200_000] {
+        let candidates = corpus(size);
+        let mut group = c.benchmark_group(format!("filter_{}k", size / 1000));
+        if size > 50_000 {
+            group.sample_size(10).measurement_time(Duration::from_secs(10));
+        }
+
+        for length in 1..=QUERY.len() {
+            let query = &QUERY[..length];
+            group.bench_with_input(BenchmarkId::new("query_length", length), query, |b, query| {
+                b.iter(|| black_box(filter::filter_candidates(&candidates, query, options)))
+            });
+        }
+        group.finish();
+    }
+}
+
+/// Repeated characters make every position a possible match, the worst case of the
+/// alignment
+fn bench_repeated_characters(c: &mut Criterion) {
+    let candidates: Vec<Candidate> = (0..10_000)
+        .map(|i| Candidate::from_text(format!("{}{}", "a".repeat(40 + i % 24), "b")))
+        .collect();
+    let options = FilterOptions::default();
+
+    let mut group = c.benchmark_group("repeated_characters_10k");
+    for query in ["aaaaaaaaaa", "aaaaab"] {
+        group.bench_with_input(BenchmarkId::from_parameter(query), query, |b, query| {
+            b.iter(|| black_box(filter::filter_candidates(&candidates, query, options)))
+        });
+    }
+    group.finish();
+}
+
+/// Full scans over texts folded on every call against texts prepared once, the way the
+/// finder filters them on each keystroke
+fn bench_prepared_texts(c: &mut Criterion) {
+    let candidates = corpus(50_000);
+    let options = FilterOptions::default();
+
+    let mut incremental = IncrementalFilter::new();
+    incremental.filter(&candidates, "", options).unwrap();
+
+    let mut group = c.benchmark_group("prepared_50k");
+    for length in [1, 3, 6, 10] {
+        let query = &QUERY[..length];
+        group.bench_with_input(BenchmarkId::new("raw", length), query, |b, query| {
+            b.iter(|| black_box(filter::filter_candidates(&candidates, query, options)))
+        });
+        group.bench_with_input(BenchmarkId::new("prepared", length), query, |b, query| {
+            b.iter(|| {
+                // Forgets the matches, so every iteration scans all candidates again
+                incremental.candidates_appended();
+                black_box(incremental.filter(&candidates, query, options).unwrap())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_query_lengths,
+    bench_repeated_characters,
+    bench_prepared_texts
+);
+criterion_main!(benches);
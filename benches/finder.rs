@@ -0,0 +1,135 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use repo_searcher_github_and_gitlab::filter::{self, Candidate, FilterOptions, IncrementalFilter};
+use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinder;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts allocations, so benchmarks can report them next to the timings
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made by `f`
+fn allocations<R>(f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+const CATEGORIES: [&str; 10] = [
+    "api", "web", "mobile", "backend", "frontend", "database", "utils", "tools", "docs", "test",
+];
+
+/// Generates repository-like display strings
+fn repo_names(start: usize, count: usize) -> Vec<String> {
+    (start..start + count)
+        .map(|i| {
+            let category = CATEGORIES[i % CATEGORIES.len()];
+            format!("{}-project-{} [GH] (A {} project)", category, i, category)
+        })
+        .collect()
+}
+
+/// Compares adding one page of 100 repositories to a finder that already holds 10k
+fn bench_page_update(c: &mut Criterion) {
+    let existing = repo_names(0, 10_000);
+    let page = repo_names(10_000, 100);
+
+    let mut group = c.benchmark_group("page_update_10k");
+
+    group.bench_function("update_items", |b| {
+        b.iter_batched(
+            || {
+                let finder = FuzzyFinder::builder().initial_query("api").build(existing.clone());
+                let mut all = existing.clone();
+                all.extend(page.clone());
+                (finder, all)
+            },
+            |(mut finder, all)| {
+                finder.update_items(all);
+                black_box(finder)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("append_items", |b| {
+        b.iter_batched(
+            || {
+                let finder = FuzzyFinder::builder().initial_query("api").build(existing.clone());
+                (finder, page.clone())
+            },
+            |(mut finder, page)| {
+                finder.append_items(page);
+                black_box(finder)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+/// Compares a full scan with re-checking the previous matches after typing `p` behind
+/// `api-` on 50k repositories
+fn bench_extend_query(c: &mut Criterion) {
+    let candidates: Vec<Candidate> =
+        repo_names(0, 50_000).into_iter().map(Candidate::from_text).collect();
+    let options = FilterOptions::default();
+
+    let mut group = c.benchmark_group("extend_query_50k");
+
+    group.bench_function("full_scan", |b| {
+        b.iter(|| black_box(filter::filter_candidates(&candidates, "api-p", options)))
+    });
+
+    group.bench_function("incremental", |b| {
+        b.iter_batched(
+            || {
+                let mut incremental = IncrementalFilter::new();
+                incremental.filter(&candidates, "api-", options).unwrap();
+                incremental
+            },
+            |mut incremental| black_box(incremental.filter(&candidates, "api-p", options)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+/// Compares filtering 20k strings into clones with filtering them into indices
+fn bench_filter_indices(c: &mut Criterion) {
+    let items = repo_names(0, 20_000);
+    let options = FilterOptions::default();
+    let clones = || filter::filter_scored(&items, "pro", options, |s| s.clone());
+    let indices = || filter::filter_indices(&items, "pro", options, |s| s);
+    println!(
+        "filter_20k allocations: {} cloned, {} indices",
+        allocations(clones),
+        allocations(indices)
+    );
+
+    let mut group = c.benchmark_group("filter_20k");
+    group.bench_function("cloned_strings", |b| b.iter(clones));
+    group.bench_function("indices", |b| b.iter(indices));
+    group.finish();
+}
+
+criterion_group!(benches, bench_page_update, bench_extend_query, bench_filter_indices);
+criterion_main!(benches);
@@ -0,0 +1,478 @@
+//! Fetching repositories from Bitbucket Cloud
+//!
+//! The repositories are those of `/2.0/repositories?role=member`, every workspace the
+//! user is a member of. A page links to the next one with the `next` URL in its JSON
+//! body rather than a header. Requests are authenticated with a username and an app
+//! password, or with a workspace, project or repository access token.
+//!
+//! Bitbucket has no stars and no fork flag: a fork is a repository with a `parent`, and
+//! the star count stays unknown rather than 0.
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::io::Write;
+
+use crate::formatter::{self, RepoSource};
+use crate::hosts;
+use crate::pagination::PageCallback;
+use crate::repo_info::{Affiliation, RepoInfo};
+use crate::retry::{self, RetryPolicy};
+
+// The API of Bitbucket Cloud
+const API: &str = "https://api.bitbucket.org/2.0";
+
+// The most repositories Bitbucket sends in a page
+const PAGE_LENGTH: &str = "100";
+
+/// How requests to Bitbucket are authenticated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// A username with an app password, sent with basic auth
+    AppPassword { username: String, password: String },
+    /// A workspace, project or repository access token, sent as a bearer token
+    AccessToken(String),
+}
+
+impl Credentials {
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            Credentials::AppPassword { username, password } => {
+                request.basic_auth(username, Some(password))
+            }
+            Credentials::AccessToken(token) => request.bearer_auth(token),
+        }
+    }
+}
+
+// A page of a list, the last one has no next URL
+#[derive(Debug, Deserialize)]
+struct BitbucketPage<T> {
+    values: Vec<T>,
+    next: Option<String>,
+    // The number of items in all pages, not every list reports it
+    size: Option<usize>,
+}
+
+// Bitbucket API response structures. Only the name is required, what is missing is
+// unknown rather than failing the whole page
+#[derive(Debug, Deserialize, Clone)]
+struct BitbucketRepo {
+    name: String,
+    // Like workspace/slug
+    full_name: Option<String>,
+    workspace: Option<BitbucketWorkspace>,
+    description: Option<String>,
+    // Forks have a parent, there is no fork flag
+    parent: Option<BitbucketParent>,
+    #[serde(default)]
+    is_private: bool,
+    // Lowercase, like python, and empty when it was never set
+    language: Option<String>,
+    // In bytes
+    size: Option<u64>,
+    // Missing for repositories without commits
+    mainbranch: Option<BitbucketBranch>,
+    updated_on: Option<DateTime<Utc>>,
+    created_on: Option<DateTime<Utc>>,
+    #[serde(default)]
+    links: BitbucketLinks,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BitbucketWorkspace {
+    slug: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BitbucketParent {
+    full_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BitbucketBranch {
+    name: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct BitbucketLinks {
+    #[serde(default)]
+    clone: Vec<BitbucketCloneLink>,
+    html: Option<BitbucketLink>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BitbucketCloneLink {
+    // https or ssh
+    name: String,
+    href: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BitbucketLink {
+    href: String,
+}
+
+// The clone URL of a protocol. HTTPS URLs have the username of whoever fetched them in
+// them, which is left out so they can be shared
+fn clone_url(links: &BitbucketLinks, protocol: &str) -> String {
+    let Some(link) = links.clone.iter().find(|link| link.name == protocol) else {
+        return String::new();
+    };
+    let Ok(mut url) = reqwest::Url::parse(&link.href) else {
+        return link.href.clone();
+    };
+    if protocol == "https" && url.set_username("").is_ok() {
+        return url.to_string();
+    }
+    link.href.clone()
+}
+
+// Converts a repository from the API, owned by the workspace it is in
+fn convert_repo(repo: BitbucketRepo, username: &str) -> RepoInfo {
+    let owner = match (repo.workspace, &repo.full_name) {
+        (Some(workspace), _) => workspace.slug,
+        (None, Some(full_name)) => full_name.split('/').next().unwrap_or(username).to_string(),
+        (None, None) => username.to_string(),
+    };
+    let default_branch = repo.mainbranch.map(|branch| branch.name);
+    RepoInfo {
+        ssh_url: clone_url(&repo.links, "ssh"),
+        description: formatter::sanitize_text(&repo.description.unwrap_or_default()),
+        is_fork: repo.parent.is_some(),
+        fork_parent: repo.parent.and_then(|parent| parent.full_name),
+        visibility: formatter::Visibility::from_private(repo.is_private),
+        is_empty: repo.size == Some(0) || default_branch.is_none(),
+        language: repo.language.filter(|language| !language.is_empty()),
+        default_branch,
+        // Bitbucket has no stars, unknown sorts after every starred repository
+        stars: None,
+        size_kb: repo.size.map(|bytes| bytes / 1024),
+        // Bitbucket has no push time, a push updates the repository
+        pushed_at: repo.updated_on,
+        created_at: repo.created_on,
+        https_url: clone_url(&repo.links, "https"),
+        web_url: repo.links.html.map(|link| link.href).unwrap_or_default(),
+        ..RepoInfo::new(&repo.name, &owner, RepoSource::Bitbucket)
+    }
+}
+
+// Fetches a page of a list
+async fn fetch_page<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    credentials: &Credentials,
+    url: &str,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<BitbucketPage<T>, Box<dyn std::error::Error>> {
+    let request = credentials.authorize(client.get(url)).build()?;
+    let response = retry::send("Bitbucket", client, request, policy, on_status).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await?;
+        return Err(format!("Bitbucket API error: {} - {}", status, text).into());
+    }
+    Ok(response.json().await?)
+}
+
+// Fetches the user the credentials belong to, which tells apart Bitbucket not being
+// reachable from credentials it turns down
+async fn fetch_user(
+    client: &reqwest::Client,
+    credentials: &Credentials,
+    api: &str,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let request = credentials.authorize(client.get(format!("{}/user", api))).build()?;
+    let response = retry::send("Bitbucket", client, request, policy, on_status)
+        .await
+        .map_err(|e| format!("Could not reach Bitbucket: {}", e))?;
+
+    let status = response.status();
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err(format!(
+            "Bitbucket rejected the credentials ({}), check that the app password or token \
+             is valid and can read repositories and the account",
+            status
+        )
+        .into());
+    }
+    if !status.is_success() {
+        let text = response.text().await?;
+        return Err(format!("Bitbucket API error: {} - {}", status, text).into());
+    }
+    Ok(response.json().await?)
+}
+
+/// Fetches the repositories in every workspace the user is a member of
+///
+/// Only those with the `affiliations` asked for are kept: the repositories of the
+/// user's own workspace are theirs, the others are an organization's. `on_page` gets
+/// every page as it is fetched, `on_status` how long is left to wait for a rate limit.
+pub async fn fetch_repos(
+    credentials: &Credentials,
+    affiliations: &[Affiliation],
+    policy: &RetryPolicy,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(String, Vec<RepoInfo>), Box<dyn std::error::Error>> {
+    fetch_from(API, credentials, affiliations, policy, on_page, on_status).await
+}
+
+// fetch_repos with the API at another address, like a test server
+async fn fetch_from(
+    api: &str,
+    credentials: &Credentials,
+    affiliations: &[Affiliation],
+    policy: &RetryPolicy,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(String, Vec<RepoInfo>), Box<dyn std::error::Error>> {
+    eprint!("Fetching Bitbucket user information... ");
+    std::io::stderr().flush().unwrap();
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(retry::CONNECT_TIMEOUT)
+        .timeout(retry::REQUEST_TIMEOUT)
+        .build()?;
+
+    let user = fetch_user(&client, credentials, api, policy, on_status).await?;
+    let username = user["username"]
+        .as_str()
+        .ok_or("Failed to get the Bitbucket username. Please check your Bitbucket credentials.")?
+        .to_string();
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprint!("Fetching repositories for Bitbucket user {}... ", username);
+    std::io::stderr().flush().unwrap();
+
+    let mut all_repos = Vec::new();
+    let mut url = format!("{}/repositories?role=member&pagelen={}", api, PAGE_LENGTH);
+    let mut page_count = 0;
+    loop {
+        let page: BitbucketPage<BitbucketRepo> =
+            fetch_page(&client, credentials, &url, policy, on_status).await?;
+        page_count += 1;
+
+        let repos: Vec<RepoInfo> = page
+            .values
+            .into_iter()
+            .map(|repo| convert_repo(repo, &username))
+            .filter_map(|repo| {
+                let owned = repo.owner.eq_ignore_ascii_case(&username);
+                let affiliation = Affiliation::infer(owned, !owned, affiliations);
+                affiliations
+                    .contains(&affiliation)
+                    .then_some(RepoInfo { affiliation: Some(affiliation), ..repo })
+            })
+            .collect();
+        on_page(&username, &repos, page.size);
+        all_repos.extend(repos);
+
+        match page.next {
+            Some(next) => url = next,
+            None => break,
+        }
+    }
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} Bitbucket repositories from {} pages", all_repos.len(), page_count);
+    Ok((username, all_repos))
+}
+
+pub fn extract_repo_info(
+    selection: &str,
+    username: &str,
+    source_tag: &str,
+) -> Option<(String, String, Option<String>)> {
+    // Extract the repository name, everything after it is indicators, badges and the description
+    let repo_name = formatter::selected_name(selection, source_tag);
+
+    // A name prefixed with its workspace, like team/api, belongs to that workspace
+    let (workspace, repo_name) = repo_name.rsplit_once('/').unwrap_or((username, repo_name));
+    let (url, browser_url) = repo_urls(workspace, repo_name);
+
+    Some((repo_name.to_string(), url, Some(browser_url)))
+}
+
+/// The clone and browser URLs of the repository of a workspace
+pub fn repo_urls(workspace: &str, repo_name: &str) -> (String, String) {
+    // Bitbucket's slugs are the names in lowercase with hyphens for spaces
+    let slug = repo_name.to_lowercase().replace(" ", "-");
+
+    let url = format!("git@{}:{}/{}.git", hosts::ssh_host(hosts::BITBUCKET_ORG), workspace, slug);
+    let browser_url = format!("{}/{}/{}", hosts::BITBUCKET_ORG, workspace, slug);
+    (url, browser_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::NO_WAIT;
+    use crate::test_server::{Response, TestServer};
+
+    fn payload() -> serde_json::Value {
+        serde_json::json!({
+            "type": "repository",
+            "uuid": "{b6a7}",
+            "name": "Legacy App",
+            "slug": "legacy-app",
+            "full_name": "team/legacy-app",
+            "workspace": { "type": "workspace", "slug": "team", "name": "Team" },
+            "description": "The old\nstorefront",
+            "is_private": true,
+            "fork_policy": "allow_forks",
+            "parent": {
+                "type": "repository",
+                "full_name": "agency/storefront",
+                "name": "storefront"
+            },
+            "language": "php",
+            "size": 4_194_304,
+            "mainbranch": { "type": "branch", "name": "master" },
+            "updated_on": "2019-03-01T12:30:00.123456+00:00",
+            "created_on": "2015-06-01T08:00:00+00:00",
+            "links": {
+                "clone": [
+                    { "name": "https", "href": "https://dima@bitbucket.org/team/legacy-app.git" },
+                    { "name": "ssh", "href": "git@bitbucket.org:team/legacy-app.git" }
+                ],
+                "html": { "href": "https://bitbucket.org/team/legacy-app" }
+            }
+        })
+    }
+
+    #[test]
+    fn test_convert_repo_from_api_payload() {
+        let repo: BitbucketRepo = serde_json::from_value(payload()).unwrap();
+        assert_eq!(
+            convert_repo(repo, "dima"),
+            RepoInfo {
+                ssh_url: "git@bitbucket.org:team/legacy-app.git".to_string(),
+                description: "The old storefront".to_string(),
+                is_fork: true,
+                fork_parent: Some("agency/storefront".to_string()),
+                visibility: formatter::Visibility::Private,
+                language: Some("php".to_string()),
+                default_branch: Some("master".to_string()),
+                size_kb: Some(4096),
+                pushed_at: Some("2019-03-01T12:30:00.123456Z".parse().unwrap()),
+                created_at: Some("2015-06-01T08:00:00Z".parse().unwrap()),
+                https_url: "https://bitbucket.org/team/legacy-app.git".to_string(),
+                web_url: "https://bitbucket.org/team/legacy-app".to_string(),
+                ..RepoInfo::new("Legacy App", "team", RepoSource::Bitbucket)
+            }
+        );
+    }
+
+    #[test]
+    fn test_forks_and_missing_stars() {
+        // Without a parent a repository is not a fork, whatever its fork policy says
+        let mut original = payload();
+        original.as_object_mut().unwrap().remove("parent");
+        let converted = convert_repo(serde_json::from_value(original).unwrap(), "dima");
+        assert!(!converted.is_fork);
+        assert_eq!(converted.fork_parent, None);
+
+        // A parent that isn't shown is still a fork, of an unknown repository
+        let mut hidden_parent = payload();
+        hidden_parent["parent"] = serde_json::json!({ "type": "repository" });
+        let converted = convert_repo(serde_json::from_value(hidden_parent).unwrap(), "dima");
+        assert!(converted.is_fork);
+        assert_eq!(converted.fork_parent, None);
+
+        // There is no star count to report, which is unknown rather than none
+        assert_eq!(converted.stars, None);
+        let bare: BitbucketRepo = serde_json::from_value(serde_json::json!({
+            "name": "notes",
+            "language": ""
+        }))
+        .unwrap();
+        let converted = convert_repo(bare, "dima");
+        assert_eq!((converted.owner.as_str(), converted.stars), ("dima", None));
+        assert!(converted.is_empty);
+        assert_eq!(converted.language, None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_next_urls() {
+        let server = TestServer::start(|request| {
+            let repository = |name: &str, workspace: &str| {
+                serde_json::json!({ "name": name, "workspace": { "slug": workspace } })
+            };
+            let second_page = "/repositories?role=member&pagelen=100&page=2";
+            match request.target.as_str() {
+                "/user" => Response::json(r#"{"username": "dima", "display_name": "Dima"}"#),
+                "/repositories?role=member&pagelen=100" => Response::json(serde_json::json!({
+                    "pagelen": 100,
+                    "size": 3,
+                    "values": [repository("api", "dima"), repository("shop", "team")],
+                    "next": format!("{}{}", request.base_url(), second_page),
+                })),
+                target if target == second_page => Response::json(serde_json::json!({
+                    "pagelen": 100,
+                    "size": 3,
+                    "values": [repository("blog", "dima")],
+                })),
+                _ => Response::json("{}").status(404),
+            }
+        })
+        .await;
+
+        let credentials = Credentials::AppPassword {
+            username: "dima".to_string(),
+            password: "secret".to_string(),
+        };
+        let mut pages = Vec::new();
+        let (username, repos) = fetch_from(
+            &server.url,
+            &credentials,
+            &[Affiliation::Owner],
+            &NO_WAIT,
+            &mut |_, repos, total| pages.push((repos.len(), total)),
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(username, "dima");
+        let names: Vec<_> = repos.iter().map(|repo| repo.name.as_str()).collect();
+        assert_eq!(names, ["api", "blog"]);
+        assert!(repos.iter().all(|repo| repo.affiliation == Some(Affiliation::Owner)));
+        assert_eq!(pages, [(1, Some(3)), (1, Some(3))]);
+        // dima:secret in basic auth
+        let requests = server.requests();
+        assert!(requests.iter().all(|request| {
+            request.header("authorization") == Some("Basic ZGltYTpzZWNyZXQ=")
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_user_with_rejected_credentials() {
+        let rejected = |_: &_| Response::json(r#"{"type": "error"}"#).status(401);
+        let server = TestServer::start(rejected).await;
+        let client = reqwest::Client::new();
+        let credentials = Credentials::AccessToken("revoked".to_string());
+        let error = fetch_user(&client, &credentials, &server.url, &NO_WAIT, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Bitbucket rejected the credentials (401"));
+        assert_eq!(server.requests()[0].header("authorization"), Some("Bearer revoked"));
+    }
+
+    #[test]
+    fn test_extract_repo_info() {
+        assert_eq!(
+            extract_repo_info("team/Legacy App 🔒 [BB] (The old storefront)", "dima", "[BB]"),
+            Some((
+                "Legacy App".to_string(),
+                "git@bitbucket.org:team/legacy-app.git".to_string(),
+                Some("https://bitbucket.org/team/legacy-app".to_string())
+            ))
+        );
+        let (_, url, _) = extract_repo_info("notes [BB]", "dima", "[BB]").unwrap();
+        assert_eq!(url, "git@bitbucket.org:dima/notes.git");
+    }
+}
@@ -1,6 +1,5 @@
 use std::process;
 use std::time::Duration;
-use tokio;
 
 /// Opens a URL in the default browser
 pub async fn open_in_browser(url: &str) -> Result<(), Box<dyn std::error::Error>> {
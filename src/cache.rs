@@ -1,10 +1,10 @@
-use crate::github::Repository as GitHubRepo;
-use crate::gitlab::Repository as GitLabRepo;
-use crate::formatter::RepoSource;
+use crate::repo_info::{Affiliation, FetchOptions, Inclusion, RepoInfo};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::Path;
+use std::sync::Mutex;
 use std::time::{Duration, SystemTime};
 
 const CACHE_FILE: &str = ".repo-cache.json";
@@ -16,10 +16,31 @@ pub struct SourceCache {
     pub username: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct CacheData {
     pub github: Option<SourceData>,
     pub gitlab: Option<SourceData>,
+    #[serde(default)]
+    pub gitea: Option<SourceData>,
+    #[serde(default)]
+    pub bitbucket: Option<SourceData>,
+    /// Whether archived repositories were fetched, the cache only has what was fetched
+    #[serde(default)]
+    pub archived: Inclusion,
+    /// Whether forks were fetched, like archived
+    #[serde(default)]
+    pub forks: Inclusion,
+    /// Whether starred repositories were fetched too
+    #[serde(default)]
+    pub starred: bool,
+    /// The affiliations the repositories were fetched for, all of them in caches
+    /// written before they were kept
+    #[serde(default = "all_affiliations")]
+    pub affiliations: Vec<Affiliation>,
+    /// The GitHub API pages of the last fetch by URL, which are asked for again with
+    /// their ETags. They are kept when the repositories expire
+    #[serde(default, skip_serializing_if = "Pages::is_empty")]
+    pub pages: Pages,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -28,15 +49,58 @@ pub struct SourceData {
     pub repositories: Vec<RepoData>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct RepoData {
-    pub name: String,
-    pub url: String,
-    pub description: String,
-    pub owner: String,
-    pub is_fork: bool,
-    pub is_private: bool,
-    pub source: RepoSource,
+/// The repositories are cached as they were fetched
+pub type RepoData = RepoInfo;
+
+fn all_affiliations() -> Vec<Affiliation> {
+    Affiliation::ALL.to_vec()
+}
+
+/// An API response as it was fetched, with the ETag it was sent with
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CachedPage {
+    pub etag: String,
+    /// The Link header to the other pages
+    #[serde(default)]
+    pub link: Option<String>,
+    pub body: String,
+}
+
+/// Cached pages by their request URL
+pub type Pages = BTreeMap<String, CachedPage>;
+
+/// The pages of the last fetch and those fetched since, which replace them in the cache
+///
+/// Pages that weren't asked for again are left out, like those of an organization the
+/// user left.
+#[derive(Default)]
+pub struct PageCache {
+    previous: Pages,
+    fetched: Mutex<Pages>,
+}
+
+impl PageCache {
+    pub fn new(previous: Pages) -> Self {
+        Self {
+            previous,
+            fetched: Mutex::new(Pages::new()),
+        }
+    }
+
+    /// The page fetched from the URL last time
+    pub fn previous(&self, url: &str) -> Option<&CachedPage> {
+        self.previous.get(url)
+    }
+
+    /// Keeps the page fetched from the URL, or the previous one when it didn't change
+    pub fn insert(&self, url: &str, page: CachedPage) {
+        self.fetched.lock().unwrap().insert(url.to_string(), page);
+    }
+
+    /// The pages fetched since
+    pub fn into_fetched(self) -> Pages {
+        self.fetched.into_inner().unwrap()
+    }
 }
 
 impl SourceCache {
@@ -64,10 +128,7 @@ impl SourceCache {
 
 impl CacheData {
     pub fn new() -> Self {
-        Self {
-            github: None,
-            gitlab: None,
-        }
+        Self::default()
     }
 
     pub fn is_expired(&self) -> bool {
@@ -84,8 +145,38 @@ impl CacheData {
             }
         }
 
+        if let Some(gitea) = &self.gitea {
+            if gitea.cache_info.is_expired() {
+                return true;
+            }
+        }
+
+        if let Some(bitbucket) = &self.bitbucket {
+            if bitbucket.cache_info.is_expired() {
+                return true;
+            }
+        }
+
         // If no sources are present, consider it expired
-        self.github.is_none() && self.gitlab.is_none()
+        self.github.is_none()
+            && self.gitlab.is_none()
+            && self.gitea.is_none()
+            && self.bitbucket.is_none()
+    }
+
+    /// Whether the repositories are still fresh and were fetched with the same
+    /// `--archived` and `--forks` as `options` asks for, the cache of others is missing
+    /// repositories or has too many
+    ///
+    /// Starred repositories and affiliations that aren't asked for are left out when the
+    /// cache is loaded, so only a cache missing some of them isn't fresh.
+    pub fn is_fresh_for(&self, options: &FetchOptions) -> bool {
+        let wanted = &options.affiliations;
+        !self.is_expired()
+            && self.archived == options.filter.archived
+            && self.forks == options.filter.forks
+            && (self.starred || !options.starred)
+            && wanted.iter().all(|affiliation| self.affiliations.contains(affiliation))
     }
 
     pub fn update_github(&mut self, username: String, repositories: Vec<RepoData>) {
@@ -102,6 +193,20 @@ impl CacheData {
         });
     }
 
+    pub fn update_gitea(&mut self, username: String, repositories: Vec<RepoData>) {
+        self.gitea = Some(SourceData {
+            cache_info: SourceCache::new(username),
+            repositories,
+        });
+    }
+
+    pub fn update_bitbucket(&mut self, username: String, repositories: Vec<RepoData>) {
+        self.bitbucket = Some(SourceData {
+            cache_info: SourceCache::new(username),
+            repositories,
+        });
+    }
+
     pub fn get_all_repositories(&self) -> Vec<RepoData> {
         let mut all_repos = Vec::new();
 
@@ -113,35 +218,15 @@ impl CacheData {
             all_repos.extend(gitlab.repositories.clone());
         }
 
-        all_repos
-    }
-}
+        if let Some(gitea) = &self.gitea {
+            all_repos.extend(gitea.repositories.clone());
+        }
 
-// Convert GitHub repository format to our unified RepoData format
-pub fn github_repo_to_repo_data(repo: &GitHubRepo) -> RepoData {
-    let (name, url, description, owner, is_fork, is_private) = repo.clone();
-    RepoData {
-        name,
-        url,
-        description,
-        owner,
-        is_fork,
-        is_private,
-        source: RepoSource::GitHub,
-    }
-}
+        if let Some(bitbucket) = &self.bitbucket {
+            all_repos.extend(bitbucket.repositories.clone());
+        }
 
-// Convert GitLab repository format to our unified RepoData format
-pub fn gitlab_repo_to_repo_data(repo: &GitLabRepo) -> RepoData {
-    let (name, url, description, owner, is_fork, is_private) = repo.clone();
-    RepoData {
-        name,
-        url,
-        description,
-        owner,
-        is_fork,
-        is_private,
-        source: RepoSource::GitLab,
+        all_repos
     }
 }
 
@@ -170,3 +255,72 @@ pub fn load_cache() -> Option<CacheData> {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_info::FetchFilter;
+
+    #[test]
+    fn test_caches_of_other_modes_are_not_fresh() {
+        let mut cache_data = CacheData::new();
+        cache_data.update_github("dima".to_string(), Vec::new());
+        cache_data.archived = Inclusion::Exclude;
+        cache_data.forks = Inclusion::Only;
+        cache_data.affiliations = Affiliation::ALL.to_vec();
+        let json = serde_json::to_string(&cache_data).unwrap();
+
+        let loaded: CacheData = serde_json::from_str(&json).unwrap();
+        let fresh = |archived, forks| {
+            let filter = FetchFilter { archived, forks };
+            loaded.is_fresh_for(&FetchOptions { filter, ..Default::default() })
+        };
+        assert!(fresh(Inclusion::Exclude, Inclusion::Only));
+        assert!(!fresh(Inclusion::Only, Inclusion::Only));
+        assert!(!fresh(Inclusion::Include, Inclusion::Only));
+        assert!(!fresh(Inclusion::Exclude, Inclusion::Include));
+        assert!(!fresh(Inclusion::Exclude, Inclusion::Exclude));
+
+        // Caches written before the modes were kept have every repository
+        let older = json.replace(r#","archived":"exclude","forks":"only""#, "");
+        let older: CacheData = serde_json::from_str(&older).unwrap();
+        assert!(older.is_fresh_for(&FetchOptions::default()));
+    }
+
+    #[test]
+    fn test_caches_missing_starred_or_affiliations_are_not_fresh() {
+        let mut cache_data = CacheData::new();
+        cache_data.update_github("dima".to_string(), Vec::new());
+        cache_data.affiliations = vec![Affiliation::Owner, Affiliation::Collaborator];
+        let json = serde_json::to_string(&cache_data).unwrap();
+
+        let loaded: CacheData = serde_json::from_str(&json).unwrap();
+        let fresh = |starred, affiliations: &[Affiliation]| {
+            let affiliations = affiliations.to_vec();
+            loaded.is_fresh_for(&FetchOptions { starred, affiliations, ..Default::default() })
+        };
+        assert!(fresh(false, &[Affiliation::Owner, Affiliation::Collaborator]));
+        // What wasn't asked for is left out on loading
+        assert!(fresh(false, &[Affiliation::Owner]));
+        assert!(!fresh(true, &[Affiliation::Owner]));
+        assert!(!fresh(false, &Affiliation::ALL));
+
+        let mut cache_data = loaded;
+        cache_data.starred = true;
+        let json = serde_json::to_string(&cache_data).unwrap();
+        let loaded: CacheData = serde_json::from_str(&json).unwrap();
+        let owned = |starred| FetchOptions {
+            starred,
+            affiliations: vec![Affiliation::Owner],
+            ..Default::default()
+        };
+        assert!(loaded.is_fresh_for(&owned(true)));
+        assert!(loaded.is_fresh_for(&owned(false)));
+
+        // Caches written before they were kept have no starred ones but every affiliation
+        let older = json.replace(r#","starred":true,"affiliations":["owner","collaborator"]"#, "");
+        let older: CacheData = serde_json::from_str(&older).unwrap();
+        assert!(!older.starred);
+        assert_eq!(older.affiliations, Affiliation::ALL);
+    }
+}
@@ -9,16 +9,113 @@
 //!
 //! ## Status Indicators
 //!
-//! - (fork) or (fork: description) - Fork of another repository
+//! - (fork of owner/repo: description) - Fork of another repository, a bare (fork)
+//!   when the parent is unknown
 //! - 🔒 - Private repository (shown at the end of repository name)
+//! - 🏢 - Internal GitLab project (shown in place of the private indicator)
+//! - 📦 - Archived repository (shown after the private indicator)
+//! - ▤ - Template repository
+//! - ∅ - Empty repository without commits
+//! - ⭐ - Starred repository of someone else, fetched with `--starred`
+//! - ⊙ 12 ⇵ 3 - Open issues and pull or merge requests, when there are any
+//!
+//! Every indicator can be replaced in the config file, see [`config`](crate::config).
+
+use std::time::Duration;
+
+use clap::{Arg, ArgMatches, Command};
 
-use clap::{Arg, Command};
+use crate::bitbucket;
+use crate::config;
+use crate::formatter::{
+    self, Column, IconSet, Icons, LicenseBadges, OwnerPrefix, RepoSource, SourceLabels,
+};
+use crate::github::{self, OrgRepos};
+use crate::gitea;
+use crate::gitlab;
+use crate::hosts::{self, Hosts};
+use crate::keychain;
+use crate::rate_limit;
+use crate::repo_info::{Affiliation, Inclusion, SortDirection, SortKey};
+use crate::retry::{self, RetryPolicy};
+use crate::template::{Template, DEFAULT_TEMPLATE};
+use crate::tokens::TokenResolver;
+
+/// Whether repositories are picked in the fuzzy finder or printed for other tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Finder,
+    /// One JSON object per repository and line, see
+    /// [`format_repositories_json`](formatter::format_repositories_json)
+    Json,
+    /// Tab-separated columns with a header row, see
+    /// [`format_repositories_tsv`](formatter::format_repositories_tsv)
+    Tsv,
+}
+
+impl OutputFormat {
+    /// Looks up a format by its name as given on the command line
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "finder" => Some(OutputFormat::Finder),
+            "json" => Some(OutputFormat::Json),
+            "tsv" => Some(OutputFormat::Tsv),
+            _ => None,
+        }
+    }
+}
 
 pub struct AppArgs {
     pub use_dummy: bool,
     pub github_token: Option<String>,
     pub gitlab_token: Option<String>,
+    /// The token of a Gitea or Forgejo instance, Codeberg unless another host is given
+    pub gitea_token: Option<String>,
+    /// The app password or access token for Bitbucket Cloud
+    pub bitbucket: Option<bitbucket::Credentials>,
     pub force_download: bool,
+    pub numbered_export: bool,
+    pub output: OutputFormat,
+    /// The columns of the tab-separated output
+    pub columns: Vec<Column>,
+    pub regex: bool,
+    pub typos: bool,
+    pub debug_input_log: Option<String>,
+    pub pushed_time: bool,
+    pub default_branch: bool,
+    pub max_description_len: Option<usize>,
+    pub licenses: LicenseBadges,
+    pub size_threshold_kb: u64,
+    pub popularity: Option<formatter::Tiers>,
+    pub owner_prefix: OwnerPrefix,
+    pub sort: SortKey,
+    pub sort_direction: SortDirection,
+    pub template: Template,
+    pub aligned: bool,
+    pub right_aligned: bool,
+    pub icon_set: IconSet,
+    /// The icons of the icon set with the replacements from the config file
+    pub icons: Icons,
+    pub source_labels: SourceLabels,
+    pub fork_parents: bool,
+    pub merge_request_counts: bool,
+    /// Where the providers are, for self-hosted instances
+    pub hosts: Hosts,
+    /// Which of the repositories the user has access to are fetched
+    pub affiliations: Vec<Affiliation>,
+    /// Which GitHub organizations' repositories are fetched next to the user's own
+    pub orgs: OrgRepos,
+    /// Whether the projects of every GitLab group the user can access are fetched too
+    pub gitlab_groups: bool,
+    /// Whether the repositories the user starred are fetched too
+    pub starred: bool,
+    /// Whether archived repositories are fetched, left out or the only ones fetched
+    pub archived: Inclusion,
+    /// Whether forks are fetched, like archived repositories
+    pub forks: Inclusion,
+    /// How failed requests are retried and how long rate limits are waited for
+    pub retry: RetryPolicy,
 }
 
 pub fn parse_args() -> AppArgs {
@@ -31,17 +128,171 @@ pub fn parse_args() -> AppArgs {
                 .short('g')
                 .long("github-token")
                 .value_name("GITHUB_TOKEN")
-                .help("GitHub personal access token")
+                .help("GitHub personal access token, GITHUB_TOKEN, GH_TOKEN or \"github_token\" in the config file otherwise")
                 .conflicts_with("dummy"),
         )
+        .arg(
+            Arg::new("github-host")
+                .long("github-host")
+                .value_name("URL")
+                .help("Address or API base of a GitHub Enterprise Server, like https://github.mycorp.com/api/v3, also read from GITHUB_HOST [default: https://github.com]"),
+        )
+        .arg(
+            Arg::new("fork-parents")
+                .long("fork-parents")
+                .help("Look up what GitHub forks are forks of, with the GraphQL API or one more request per fork")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("merge-request-counts")
+                .long("merge-request-counts")
+                .help("Count open pull and merge requests, one more request per repository outside of GitHub's GraphQL API")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("affiliation")
+                .long("affiliation")
+                .value_name("AFFILIATION")
+                .value_parser(["owner", "collaborator", "organization_member"])
+                .help("Only fetch repositories you own, were added to as a collaborator or have through an organization or GitLab group, repeatable or comma-separated [default: all of them]")
+                .action(clap::ArgAction::Append)
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("no-org-repos")
+                .long("no-org-repos")
+                .help("Leave out the repositories of your GitHub organizations")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("org"),
+        )
+        .arg(
+            Arg::new("org")
+                .long("org")
+                .value_name("ORG")
+                .help("Only fetch the repositories of these GitHub organizations next to your own, repeatable or comma-separated")
+                .action(clap::ArgAction::Append)
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("gitlab-groups")
+                .long("gitlab-groups")
+                .help("Also fetch the projects of every GitLab group you can access, subgroups included, not just those you are a member of")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("starred")
+                .long("starred")
+                .help("Also fetch the repositories you starred, marked with ⭐ so is:starred finds them")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("archived")
+                .long("archived")
+                .value_name("MODE")
+                .value_parser(["include", "exclude", "only"])
+                .help("Fetch archived repositories, leave them out or fetch nothing else, overrides the config file [default: include]"),
+        )
+        .arg(
+            Arg::new("forks")
+                .long("forks")
+                .value_name("MODE")
+                .value_parser(["include", "exclude", "only"])
+                .help("Fetch forks, leave them out or fetch nothing else, overrides the config file [default: include]"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .value_name("COUNT")
+                .value_parser(clap::value_parser!(u32))
+                .help(format!(
+                    "Send requests that failed with a network or server error again this \
+                     often, waiting longer every time [default: {}]",
+                    retry::DEFAULT_RETRIES
+                )),
+        )
+        .arg(
+            Arg::new("max-rate-limit-wait")
+                .long("max-rate-limit-wait")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64))
+                .help(format!(
+                    "Wait this long at most for a rate limit to reset, fail with the reset \
+                     time when it resets later [default: {}]",
+                    rate_limit::DEFAULT_MAX_WAIT_SECS
+                )),
+        )
+        .arg(
+            Arg::new("github-label")
+                .long("github-label")
+                .value_name("LABEL")
+                .default_value(formatter::DEFAULT_GITHUB_LABEL)
+                .help("Label in the source tag of GitHub repositories, like GHE for [GHE]"),
+        )
         .arg(
             Arg::new("gitlab-token")
                 .short('l')
                 .long("gitlab-token")
                 .value_name("GITLAB_TOKEN")
-                .help("GitLab personal access token")
+                .help("GitLab personal access token, GITLAB_TOKEN, GL_TOKEN or \"gitlab_token\" in the config file otherwise")
                 .conflicts_with("dummy"),
         )
+        .arg(
+            Arg::new("gitlab-host")
+                .long("gitlab-host")
+                .value_name("URL")
+                .help("Address of a self-hosted GitLab, like https://gitlab.mycompany.io, also read from GITLAB_HOST [default: https://gitlab.com]"),
+        )
+        .arg(
+            Arg::new("gitlab-label")
+                .long("gitlab-label")
+                .value_name("LABEL")
+                .default_value(formatter::DEFAULT_GITLAB_LABEL)
+                .help("Label in the source tag of GitLab repositories, like GL:work for [GL:work]"),
+        )
+        .arg(
+            Arg::new("gitea-token")
+                .long("gitea-token")
+                .value_name("GITEA_TOKEN")
+                .help("Gitea or Forgejo access token, for Codeberg unless --gitea-host says otherwise")
+                .conflicts_with("dummy"),
+        )
+        .arg(
+            Arg::new("gitea-host")
+                .long("gitea-host")
+                .value_name("URL")
+                .help(format!(
+                    "Address of a Gitea or Forgejo instance, like https://gitea.mycompany.io, \
+                     also read from GITEA_HOST [default: {}]",
+                    hosts::CODEBERG
+                )),
+        )
+        .arg(
+            Arg::new("gitea-label")
+                .long("gitea-label")
+                .value_name("LABEL")
+                .default_value(formatter::DEFAULT_GITEA_LABEL)
+                .help("Label in the source tag of Gitea repositories, like CB for [CB]"),
+        )
+        .arg(
+            Arg::new("bitbucket-token")
+                .long("bitbucket-token")
+                .value_name("BITBUCKET_TOKEN")
+                .help("Bitbucket Cloud app password with --bitbucket-username, otherwise a workspace, project or repository access token")
+                .conflicts_with("dummy"),
+        )
+        .arg(
+            Arg::new("bitbucket-username")
+                .long("bitbucket-username")
+                .value_name("USERNAME")
+                .help("Bitbucket username the app password in --bitbucket-token belongs to"),
+        )
+        .arg(
+            Arg::new("bitbucket-label")
+                .long("bitbucket-label")
+                .value_name("LABEL")
+                .default_value(formatter::DEFAULT_BITBUCKET_LABEL)
+                .help("Label in the source tag of Bitbucket repositories, like BB:old for [BB:old]"),
+        )
         .arg(
             Arg::new("dummy")
                 .short('d')
@@ -56,38 +307,548 @@ pub fn parse_args() -> AppArgs {
                 .help("Force download repositories from GitHub, ignoring cache")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("numbered-export")
+                .short('n')
+                .long("numbered-export")
+                .help("Prefix each line printed by Ctrl+E (export filtered list) with its index")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("regex")
+                .short('r')
+                .long("regex")
+                .help("Treat the query as a regular expression instead of fuzzy matching it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("typos")
+                .short('t')
+                .long("typos")
+                .help("Show matches with one typo per word when nothing matches the query")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-pushed-time")
+                .long("no-pushed-time")
+                .help("Hide how long ago each repository was last pushed to")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("default-branch")
+                .long("default-branch")
+                .help("Show the default branch of each repository, like (main)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-description-len")
+                .long("max-description-len")
+                .value_name("CHARS")
+                .value_parser(clap::value_parser!(usize))
+                .help(format!(
+                    "Cut descriptions after the last word that fits this many characters, \
+                     0 keeps them whole [default: {}]",
+                    formatter::DEFAULT_MAX_DESCRIPTION_LEN
+                )),
+        )
+        .arg(
+            Arg::new("size-threshold")
+                .long("size-threshold")
+                .value_name("MB")
+                .value_parser(clap::value_parser!(u64))
+                .help(format!(
+                    "Show the size of repositories of at least this many megabytes, 0 shows \
+                     every size [default: {}]",
+                    formatter::DEFAULT_SIZE_THRESHOLD_KB / 1024
+                )),
+        )
+        .arg(
+            Arg::new("license")
+                .long("license")
+                .value_name("WHICH")
+                .value_parser(["never", "known", "all"])
+                .default_value("never")
+                .help("Show license badges like ‹MIT›, all also marks unrecognized licenses as ‹?›"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FORMAT")
+                .value_parser(["finder", "json", "tsv"])
+                .default_value("finder")
+                .help("Print every repository as a line of JSON or tab-separated values instead of opening the finder"),
+        )
+        .arg(
+            Arg::new("columns")
+                .long("columns")
+                .value_name("COLUMNS")
+                .help("Comma-separated columns of --output tsv, like name,url,stars [default: name,owner,source,url]"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("KEY")
+                .value_parser(["none", "name", "activity", "stars", "owner"])
+                .help("Order the list by name, last push, stars or owner, overrides the config file [default: none]"),
+        )
+        .arg(
+            Arg::new("sort-direction")
+                .long("sort-direction")
+                .value_name("DIRECTION")
+                .value_parser(["asc", "desc"])
+                .help("Sort ascending or descending, by default activity and stars are descending and names ascending"),
+        )
+        .arg(
+            Arg::new("popularity")
+                .long("popularity")
+                .help("Mark popular repositories, ☆ from 50 stars, ★ from 500 and 🔥 above 5k unless the config file sets other tiers")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("owner-prefix")
+                .long("owner-prefix")
+                .value_name("WHEN")
+                .value_parser(["always", "never", "auto"])
+                .default_value("never")
+                .help("Prefix names with their owner or namespace, auto only does it for names that appear more than once"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("TEMPLATE")
+                .help(format!(
+                    "Layout of each repository with placeholders like {{name}}, see the README, \
+                     defaults to \"{}\"",
+                    DEFAULT_TEMPLATE
+                )),
+        )
+        .arg(
+            Arg::new("aligned")
+                .long("aligned")
+                .help("Show repositories in aligned columns instead of the --format layout")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("right-align")
+                .long("right-align")
+                .help("Show stars, language and push time at the right edge of each row")
+                .conflicts_with("aligned")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("nerd-font")
+                .long("nerd-font")
+                .help("Put Nerd Font glyphs in front of languages, for terminals with a patched font")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ascii")
+                .long("ascii")
+                .help("Draw indicators as plain text, like [private] instead of 🔒")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("debug-input-log")
+                .long("debug-input-log")
+                .value_name("FILE")
+                .help("Append unrecognized terminal input sequences to FILE for bug reports"),
+        )
+        .subcommand(
+            Command::new("auth")
+                .about("Store the tokens of the providers in the system keychain")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set")
+                        .about("Prompt for the token of a provider and store it")
+                        .arg(provider_arg()),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete the stored token of a provider")
+                        .arg(provider_arg()),
+                ),
+        )
         .get_matches();
 
+    // Store or delete a token instead of searching
+    if let Some(auth) = matches.subcommand_matches("auth") {
+        run_auth(auth);
+    }
+
     // Check if dummy mode is enabled
     let use_dummy = matches.get_flag("dummy");
 
-    // Get GitHub and GitLab tokens
-    let github_token = if !use_dummy {
-        matches.get_one::<String>("github-token").cloned()
-    } else {
-        None
+    // Read the config file, a broken one is reported before fetching anything
+    let config = config::load_config().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+
+    // Get where the providers are, flags win over the environment and the config
+    let host = |flag: &str, variable: &str, configured: &Option<String>, api_path: &str| {
+        let host = matches
+            .get_one::<String>(flag)
+            .cloned()
+            .or_else(|| std::env::var(variable).ok().filter(|host| !host.is_empty()))
+            .or(configured.clone())?;
+        Some(hosts::normalize(&host, api_path).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }))
     };
+    let mut hosts = Hosts::default();
+    if let Some(host) = host("github-host", "GITHUB_HOST", &config.github_host, github::API_PATH) {
+        hosts.github = host;
+    }
+    if let Some(host) = host("gitlab-host", "GITLAB_HOST", &config.gitlab_host, gitlab::API_PATH) {
+        hosts.gitlab = host;
+    }
+    if let Some(host) = host("gitea-host", "GITEA_HOST", &config.gitea_host, gitea::API_PATH) {
+        hosts.gitea = host;
+    }
 
-    let gitlab_token = if !use_dummy {
-        matches.get_one::<String>("gitlab-token").cloned()
-    } else {
-        None
+    // Get the tokens of the providers from the flags, the environment, the config file
+    // or the keychain
+    let flag = |flag: &str| matches.get_one::<String>(flag).cloned();
+    let resolver = TokenResolver::new(flag, &config, &hosts);
+    let token = |provider| {
+        (!use_dummy).then(|| resolver.resolve(provider)).flatten().map(|token| token.value)
     };
+    let github_token = token(RepoSource::GitHub);
+    let gitlab_token = token(RepoSource::GitLab);
+    let gitea_token = token(RepoSource::Gitea);
+
+    // An app password goes with the username it belongs to, a token without one is an
+    // access token
+    let bitbucket = token(RepoSource::Bitbucket).map(|token| {
+        match matches.get_one::<String>("bitbucket-username") {
+            Some(username) => bitbucket::Credentials::AppPassword {
+                username: username.clone(),
+                password: token,
+            },
+            None => bitbucket::Credentials::AccessToken(token),
+        }
+    });
+
+    // Without a keychain the other sources are enough
+    if let Some(e) = resolver.keychain_error() {
+        eprintln!("Warning: {}, tokens stored with `auth set` can't be read", e);
+    }
 
     // Validate that at least one token is provided if not in dummy mode
-    if !use_dummy && github_token.is_none() && gitlab_token.is_none() {
-        eprintln!("Error: At least one of --github-token or --gitlab-token must be provided");
+    let no_token = github_token.is_none()
+        && gitlab_token.is_none()
+        && gitea_token.is_none()
+        && bitbucket.is_none();
+    if !use_dummy && no_token {
+        eprintln!("Error: {}", resolver.missing_message());
         eprintln!("       Alternatively, use --dummy for testing with sample data");
         std::process::exit(1);
     }
+    // The resolver borrows the hosts, which end up in the arguments
+    drop(resolver);
 
     // Check if force download is enabled
     let force_download = matches.get_flag("force-download");
 
+    // Check if exported lists should be numbered
+    let numbered_export = matches.get_flag("numbered-export");
+
+    // Check if the query is a regular expression
+    let regex = matches.get_flag("regex");
+
+    // Check if typos should be tolerated
+    let typos = matches.get_flag("typos");
+
+    // Get the optional file for logging unrecognized input
+    let debug_input_log = matches.get_one::<String>("debug-input-log").cloned();
+
+    // Check if the time since the last push should be shown
+    let pushed_time = !matches.get_flag("no-pushed-time");
+
+    // Check if the default branch is shown
+    let default_branch = matches.get_flag("default-branch");
+
+    // Get the description cap, 0 turns it off
+    let max_description_len = matches
+        .get_one::<usize>("max-description-len")
+        .copied()
+        .map_or(Some(formatter::DEFAULT_MAX_DESCRIPTION_LEN), |max_len| {
+            (max_len > 0).then_some(max_len)
+        });
+
+    // Get from which size on repositories get a size badge
+    let size_threshold_kb = matches
+        .get_one::<u64>("size-threshold")
+        .map_or(formatter::DEFAULT_SIZE_THRESHOLD_KB, |megabytes| megabytes.saturating_mul(1024));
+
+    // Get which repositories get a license badge, clap only accepts known modes
+    let licenses = matches
+        .get_one::<String>("license")
+        .and_then(|name| LicenseBadges::from_name(name))
+        .unwrap_or_default();
+
+    // Get when names are prefixed with their owner, clap only accepts known modes
+    let owner_prefix = matches
+        .get_one::<String>("owner-prefix")
+        .and_then(|name| OwnerPrefix::from_name(name))
+        .unwrap_or_default();
+
+    // Get what is done with the repositories, clap only accepts known formats
+    let output = matches
+        .get_one::<String>("output")
+        .and_then(|name| OutputFormat::from_name(name))
+        .unwrap_or_default();
+
+    // Get the columns of the tab-separated output, unknown ones are listed with the valid ones
+    let columns = match matches.get_one::<String>("columns") {
+        Some(list) => Column::parse_list(list).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }),
+        None => Column::DEFAULT.to_vec(),
+    };
+
+    // Get the list order, flags win over the config and clap only accepts known names
+    let sort = matches
+        .get_one::<String>("sort")
+        .or(config.sort.as_ref())
+        .map_or(Some(SortKey::default()), |name| SortKey::from_name(name))
+        .unwrap_or_else(|| {
+            eprintln!("Error: Unknown sort key in {}", config::CONFIG_FILE);
+            std::process::exit(1);
+        });
+    let sort_direction = matches
+        .get_one::<String>("sort-direction")
+        .or(config.sort_direction.as_ref())
+        .map_or(Some(sort.default_direction()), |name| SortDirection::from_name(name))
+        .unwrap_or_else(|| {
+            eprintln!("Error: Unknown sort direction in {}, use asc or desc", config::CONFIG_FILE);
+            std::process::exit(1);
+        });
+
+    // Get the popularity tiers if popular repositories are marked
+    let popularity = matches.get_flag("popularity").then(|| {
+        config.tiers().unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        })
+    });
+
+    // Check if indicators are drawn as plain text
+    let icon_set = if matches.get_flag("ascii") {
+        IconSet::Ascii
+    } else {
+        IconSet::Unicode
+    };
+
+    // Replace the icons the config file sets
+    let icon_overrides = &config.icons;
+    let mut icons = icon_overrides.apply(icon_set).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    if matches.get_flag("nerd-font") {
+        icons.language_glyphs = true;
+    }
+
+    // Parse the display template once, an invalid one is reported before fetching anything
+    let template = match matches.get_one::<String>("format") {
+        Some(format) => Template::parse(format)
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            })
+            .icons(icons.clone()),
+        None => Template::default_with(&icons),
+    };
+
+    // Get the labels of the source tags, they have to stay apart to look up selections
+    let label = |name: &str| matches.get_one::<String>(name).map_or("", String::as_str);
+    let source_labels = SourceLabels::new(
+        label("github-label"),
+        label("gitlab-label"),
+        label("gitea-label"),
+        label("bitbucket-label"),
+    )
+    .and_then(|labels| match icon_overrides {
+        config::IconOverrides { github: None, gitlab: None, gitea: None, bitbucket: None, .. } => {
+            Ok(labels)
+        }
+        config::IconOverrides { github, gitlab, gitea, bitbucket, .. } => SourceLabels::with_tags(
+            github.as_deref().unwrap_or(labels.tag(RepoSource::GitHub)),
+            gitlab.as_deref().unwrap_or(labels.tag(RepoSource::GitLab)),
+            gitea.as_deref().unwrap_or(labels.tag(RepoSource::Gitea)),
+            bitbucket.as_deref().unwrap_or(labels.tag(RepoSource::Bitbucket)),
+        ),
+    })
+    .map(|labels| labels.markers(&icons))
+    .unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+
+    // Check if repositories are shown in columns
+    let aligned = matches.get_flag("aligned");
+
+    // Check if the trailing badges are moved to the right edge
+    let right_aligned = matches.get_flag("right-align");
+
+    // Check if the parents of GitHub forks are looked up
+    let fork_parents = matches.get_flag("fork-parents");
+
+    // Check if open pull and merge requests are counted
+    let merge_request_counts = matches.get_flag("merge-request-counts");
+
+    // Get which organizations' repositories are fetched, flags win over the config
+    let orgs = match matches.get_many::<String>("org") {
+        Some(orgs) => OrgRepos::Only(orgs.cloned().collect()),
+        None if matches.get_flag("no-org-repos") => OrgRepos::Exclude,
+        None => config.org_repos(),
+    };
+
+    // Get which repositories the user has access to are fetched, all of them by default
+    let affiliations = match matches.get_many::<String>("affiliation") {
+        Some(names) => names.filter_map(|name| Affiliation::from_name(name)).collect(),
+        None => Affiliation::ALL.to_vec(),
+    };
+
+    // Check if the projects of GitLab groups are fetched too
+    let gitlab_groups = matches.get_flag("gitlab-groups");
+
+    // Check if starred repositories are fetched too, the flag or the config turns it on
+    let starred = matches.get_flag("starred") || config.starred.unwrap_or(false);
+
+    // Get whether archived repositories and forks are fetched, flags win over the config
+    let inclusion = |name: &str, configured: &Option<String>| {
+        matches
+            .get_one::<String>(name)
+            .or(configured.as_ref())
+            .map_or(Some(Inclusion::default()), |mode| Inclusion::from_name(mode))
+            .unwrap_or_else(|| {
+                eprintln!(
+                    "Error: Unknown \"{}\" in {}, use include, exclude or only",
+                    name,
+                    config::CONFIG_FILE
+                );
+                std::process::exit(1);
+            })
+    };
+    let archived = inclusion("archived", &config.archived);
+    let forks = inclusion("forks", &config.forks);
+
+    // Get how often failed requests are retried and how long a rate limit is waited for
+    let mut retry = RetryPolicy::default();
+    if let Some(retries) = matches.get_one::<u32>("retries") {
+        retry.retries = *retries;
+    }
+    if let Some(seconds) = matches.get_one::<u64>("max-rate-limit-wait") {
+        retry.max_rate_limit_wait = Duration::from_secs(*seconds);
+    }
+
     AppArgs {
         use_dummy,
         github_token,
         gitlab_token,
+        gitea_token,
+        bitbucket,
         force_download,
+        numbered_export,
+        output,
+        columns,
+        regex,
+        typos,
+        debug_input_log,
+        pushed_time,
+        default_branch,
+        max_description_len,
+        licenses,
+        size_threshold_kb,
+        popularity,
+        owner_prefix,
+        sort,
+        sort_direction,
+        template,
+        aligned,
+        right_aligned,
+        icon_set,
+        icons,
+        source_labels,
+        fork_parents,
+        merge_request_counts,
+        hosts,
+        affiliations,
+        orgs,
+        gitlab_groups,
+        starred,
+        archived,
+        forks,
+        retry,
+    }
+}
+
+// The provider of an `auth` subcommand, like `github`
+fn provider_arg() -> Arg {
+    Arg::new("provider")
+        .required(true)
+        .value_parser(["github", "gitlab", "gitea", "bitbucket"])
+        .help("The provider the token is for")
+}
+
+// Runs `auth set` or `auth remove` and exits
+fn run_auth(matches: &ArgMatches) -> ! {
+    let (action, args) = matches.subcommand().expect("clap requires a subcommand");
+    let name = args.get_one::<String>("provider").expect("clap requires a provider");
+    let provider = RepoSource::ALL
+        .into_iter()
+        .find(|&provider| keychain::account(provider) == *name)
+        .expect("clap only accepts known providers");
+
+    let result = match action {
+        "set" => prompt_token(provider).and_then(|token| {
+            keychain::set(provider, &token)?;
+            Ok(format!("Stored the {} token in the keychain", provider.name()))
+        }),
+        _ => keychain::remove(provider).map(|removed| match removed {
+            true => format!("Deleted the {} token from the keychain", provider.name()),
+            false => format!("There is no {} token in the keychain", provider.name()),
+        }),
+    };
+    match result {
+        Ok(message) => {
+            println!("{}", message);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Reads a token without echoing it, piped tokens are read as they are
+fn prompt_token(provider: RepoSource) -> Result<String, String> {
+    use std::io::{self, Write};
+    use termion::input::TermRead;
+
+    let mut stdin = io::stdin();
+    let token = if termion::is_tty(&stdin) {
+        let mut stderr = io::stderr();
+        eprint!("{} token: ", provider.name());
+        let _ = stderr.flush();
+        let token = stdin.read_passwd(&mut stderr);
+        eprintln!();
+        token.map_err(|e| format!("Failed to read the token: {}", e))?
+    } else {
+        let mut line = String::new();
+        // Stdin's own read_line, not the one of TermRead
+        io::Stdin::read_line(&stdin, &mut line)
+            .map_err(|e| format!("Failed to read the token: {}", e))?;
+        Some(line)
+    };
+
+    match token.as_deref().map(str::trim) {
+        Some(token) if !token.is_empty() => Ok(token.to_string()),
+        _ => Err("No token given, nothing was stored".to_string()),
     }
 }
@@ -0,0 +1,42 @@
+use std::io::Write;
+use std::process::{self, Stdio};
+
+/// Copies text to the system clipboard
+pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // Pick the clipboard tool based on the operating system
+    #[cfg(target_os = "macos")]
+    let candidates: &[(&str, &[&str])] = &[("pbcopy", &[])];
+
+    #[cfg(target_os = "windows")]
+    let candidates: &[(&str, &[&str])] = &[("clip", &[])];
+
+    #[cfg(target_os = "linux")]
+    let candidates: &[(&str, &[&str])] = &[
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+    ];
+
+    for (program, args) in candidates {
+        // Try the next tool if this one is not installed
+        let Ok(mut child) = process::Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .spawn()
+        else {
+            continue;
+        };
+
+        if let Some(stdin) = child.stdin.as_mut() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| format!("Failed to write to {}: {}", program, e))?;
+        }
+        child
+            .wait()
+            .map_err(|e| format!("Failed to wait on {} process: {}", program, e))?;
+        return Ok(());
+    }
+
+    Err("No clipboard tool found".into())
+}
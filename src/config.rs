@@ -0,0 +1,292 @@
+//! Settings from the config file that are too fiddly for command line flags
+//!
+//! The config is a JSON file in the working directory, like the cache and the UI state.
+//! Every setting is optional and a missing file is the same as an empty one:
+//!
+//! ```json
+//! {
+//!   "sort": "activity",
+//!   "orgs": ["acme", "acme-labs"],
+//!   "archived": "exclude",
+//!   "github_host": "https://github.mycorp.com/api/v3",
+//!   "gitlab_host": "https://gitlab.mycompany.io",
+//!   "gitea_host": "https://gitea.mycompany.io",
+//!   "gitlab_token": "glpat-...",
+//!   "popularity_tiers": [
+//!     { "stars": 100, "marker": "+" },
+//!     { "stars": 1000, "marker": "++" }
+//!   ],
+//!   "icons": {
+//!     "private": "P",
+//!     "archived": "",
+//!     "star": "☆",
+//!     "github": "",
+//!     "gitlab": ""
+//!   }
+//! }
+//! ```
+
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::formatter::{IconSet, Icons, Tiers};
+use crate::github::OrgRepos;
+
+pub const CONFIG_FILE: &str = ".repo-searcher-config.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The default for `--sort`, like `stars`
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// The default for `--sort-direction`, `asc` or `desc`
+    #[serde(default)]
+    pub sort_direction: Option<String>,
+    /// Replaces the default tiers of `--popularity`
+    #[serde(default)]
+    pub popularity_tiers: Option<Vec<PopularityTier>>,
+    /// Whether the repositories of the user's GitHub organizations are fetched, `false`
+    /// is the same as `--no-org-repos`
+    #[serde(default)]
+    pub org_repos: Option<bool>,
+    /// The only GitHub organizations whose repositories are fetched, like `--org`
+    #[serde(default)]
+    pub orgs: Option<Vec<String>>,
+    /// Fetch the repositories the user starred, like `--starred`
+    #[serde(default)]
+    pub starred: Option<bool>,
+    /// The default for `--archived`, `include`, `exclude` or `only`
+    #[serde(default)]
+    pub archived: Option<String>,
+    /// The default for `--forks`, like `archived`
+    #[serde(default)]
+    pub forks: Option<String>,
+    /// The address of a GitHub Enterprise Server, like `--github-host`
+    #[serde(default)]
+    pub github_host: Option<String>,
+    /// The address of a self-hosted GitLab, like `--gitlab-host`
+    #[serde(default)]
+    pub gitlab_host: Option<String>,
+    /// The address of a Gitea or Forgejo instance, like `--gitea-host`
+    #[serde(default)]
+    pub gitea_host: Option<String>,
+    /// The GitHub token, used when neither `--github-token` nor the environment has one
+    #[serde(default)]
+    pub github_token: Option<String>,
+    /// The GitLab token, like `github_token`
+    #[serde(default)]
+    pub gitlab_token: Option<String>,
+    /// The Gitea or Forgejo token, like `github_token`
+    #[serde(default)]
+    pub gitea_token: Option<String>,
+    /// The Bitbucket app password or access token, like `github_token`
+    #[serde(default)]
+    pub bitbucket_token: Option<String>,
+    /// Use the tokens of `gh auth login` and `glab auth login` when nothing else has one
+    #[serde(default)]
+    pub cli_credentials: Option<bool>,
+    #[serde(default)]
+    pub icons: IconOverrides,
+}
+
+/// A popularity tier, starting at a number of stars
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PopularityTier {
+    pub stars: u32,
+    pub marker: String,
+}
+
+impl Config {
+    /// The popularity tiers of the config file, the default ones when it has none
+    pub fn tiers(&self) -> Result<Tiers, String> {
+        let Some(tiers) = &self.popularity_tiers else {
+            return Ok(Tiers::default());
+        };
+        for tier in tiers {
+            check_icon("popularity", &tier.marker)?;
+        }
+        Tiers::new(tiers.iter().map(|tier| (tier.stars, tier.marker.clone())).collect())
+            .map_err(|e| format!("{} in {}", e, CONFIG_FILE))
+    }
+
+    /// Which organizations' repositories are fetched, named organizations win over
+    /// `org_repos`
+    pub fn org_repos(&self) -> OrgRepos {
+        match (&self.orgs, self.org_repos) {
+            (Some(orgs), _) => OrgRepos::Only(orgs.clone()),
+            (None, Some(false)) => OrgRepos::Exclude,
+            (None, _) => OrgRepos::All,
+        }
+    }
+}
+
+/// Icons replacing the ones of the icon set, an empty one leaves its indicator out
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconOverrides {
+    pub private: Option<String>,
+    pub internal: Option<String>,
+    pub archived: Option<String>,
+    pub mirror: Option<String>,
+    pub template: Option<String>,
+    pub empty: Option<String>,
+    pub starred: Option<String>,
+    pub fork: Option<String>,
+    pub star: Option<String>,
+    pub issues: Option<String>,
+    pub merge_requests: Option<String>,
+    pub separator: Option<String>,
+    pub license_start: Option<String>,
+    pub license_end: Option<String>,
+    pub ellipsis: Option<String>,
+    /// The whole GitHub source tag, like `[GH]`, which selections are looked up by
+    pub github: Option<String>,
+    /// The whole GitLab source tag, like `[GL]`
+    pub gitlab: Option<String>,
+    /// The whole Gitea source tag, like `[GT]`
+    pub gitea: Option<String>,
+    /// The whole Bitbucket source tag, like `[BB]`
+    pub bitbucket: Option<String>,
+    /// Put a Nerd Font glyph in front of languages, like `--nerd-font`
+    pub language_glyphs: Option<bool>,
+}
+
+impl IconOverrides {
+    /// The icons of an icon set with these replacements
+    ///
+    /// Icons are drawn inside a single line and end up in the default template, so
+    /// control characters, newlines and braces are rejected.
+    pub fn apply(&self, icon_set: IconSet) -> Result<Icons, String> {
+        let mut icons = Icons::from(icon_set);
+        let replacements = [
+            ("private", &self.private, &mut icons.private),
+            ("internal", &self.internal, &mut icons.internal),
+            ("archived", &self.archived, &mut icons.archived),
+            ("mirror", &self.mirror, &mut icons.mirror),
+            ("template", &self.template, &mut icons.template),
+            ("empty", &self.empty, &mut icons.empty),
+            ("starred", &self.starred, &mut icons.starred),
+            ("fork", &self.fork, &mut icons.fork),
+            ("star", &self.star, &mut icons.star),
+            ("issues", &self.issues, &mut icons.issues),
+            ("merge_requests", &self.merge_requests, &mut icons.merge_requests),
+            ("separator", &self.separator, &mut icons.separator),
+            ("license_start", &self.license_start, &mut icons.license_start),
+            ("license_end", &self.license_end, &mut icons.license_end),
+            ("ellipsis", &self.ellipsis, &mut icons.ellipsis),
+        ];
+        for (name, replacement, icon) in replacements {
+            if let Some(replacement) = replacement {
+                check_icon(name, replacement)?;
+                *icon = replacement.clone();
+            }
+        }
+        if let Some(language_glyphs) = self.language_glyphs {
+            icons.language_glyphs = language_glyphs;
+        }
+        let tags = [
+            ("github", &self.github),
+            ("gitlab", &self.gitlab),
+            ("gitea", &self.gitea),
+            ("bitbucket", &self.bitbucket),
+        ];
+        for (name, tag) in tags {
+            if let Some(tag) = tag {
+                check_icon(name, tag)?;
+            }
+        }
+        Ok(icons)
+    }
+}
+
+fn check_icon(name: &str, icon: &str) -> Result<(), String> {
+    if icon.chars().any(char::is_control) {
+        return Err(format!(
+            "The {} icon {:?} in {} contains control characters or newlines",
+            name, icon, CONFIG_FILE
+        ));
+    }
+    if icon.contains(['{', '}']) {
+        return Err(format!("The {} icon {:?} in {} contains braces", name, icon, CONFIG_FILE));
+    }
+    Ok(())
+}
+
+/// Reads the config file, the defaults when there is none
+pub fn load_config() -> Result<Config, String> {
+    if !Path::new(CONFIG_FILE).exists() {
+        return Ok(Config::default());
+    }
+    let json = fs::read_to_string(CONFIG_FILE)
+        .map_err(|e| format!("Error reading config file {}: {}", CONFIG_FILE, e))?;
+    parse_config(&json)
+}
+
+fn parse_config(json: &str) -> Result<Config, String> {
+    serde_json::from_str(json)
+        .map_err(|e| format!("Error parsing config file {}: {}", CONFIG_FILE, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_the_icon_set() {
+        let config = parse_config("{}").unwrap();
+        assert_eq!(config.icons.apply(IconSet::Unicode).unwrap(), Icons::from(IconSet::Unicode));
+        assert_eq!(config.icons.apply(IconSet::Ascii).unwrap(), Icons::from(IconSet::Ascii));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let config = parse_config(r#"{"icons": {"private": "P", "archived": ""}}"#).unwrap();
+        let icons = config.icons.apply(IconSet::Ascii).unwrap();
+        assert_eq!(icons.private, "P");
+        assert_eq!(icons.archived, "");
+        assert_eq!(icons.star, "*");
+        assert!(!icons.language_glyphs);
+
+        let config = parse_config(r#"{"icons": {"language_glyphs": true}}"#).unwrap();
+        assert!(config.icons.apply(IconSet::Unicode).unwrap().language_glyphs);
+    }
+
+    #[test]
+    fn test_popularity_tiers() {
+        assert_eq!(parse_config("{}").unwrap().tiers().unwrap(), Tiers::default());
+        let config = parse_config(
+            r#"{"popularity_tiers": [{"stars": 1000, "marker": "++"}, {"stars": 100, "marker": "+"}]}"#,
+        )
+        .unwrap();
+        let tiers = config.tiers().unwrap();
+        assert_eq!(crate::formatter::popularity_marker(999, &tiers), Some("+"));
+
+        let config = parse_config(r#"{"popularity_tiers": [{"stars": 5, "marker": " "}]}"#).unwrap();
+        assert!(config.tiers().unwrap_err().contains("need a marker"));
+    }
+
+    #[test]
+    fn test_org_repos() {
+        assert_eq!(parse_config("{}").unwrap().org_repos(), OrgRepos::All);
+        let config = parse_config(r#"{"org_repos": false}"#).unwrap();
+        assert_eq!(config.org_repos(), OrgRepos::Exclude);
+        let config = parse_config(r#"{"org_repos": false, "orgs": ["acme"]}"#).unwrap();
+        assert_eq!(config.org_repos(), OrgRepos::Only(vec!["acme".to_string()]));
+    }
+
+    #[test]
+    fn test_invalid_icons() {
+        let config = parse_config(r#"{"icons": {"star": "★\n"}}"#).unwrap();
+        assert!(config.icons.apply(IconSet::Unicode).unwrap_err().contains("star icon"));
+        let config = parse_config(r#"{"icons": {"github": "\u001b[1m"}}"#).unwrap();
+        assert!(config.icons.apply(IconSet::Unicode).unwrap_err().contains("github icon"));
+        let config = parse_config(r#"{"icons": {"license_start": "{"}}"#).unwrap();
+        assert!(config.icons.apply(IconSet::Unicode).unwrap_err().contains("braces"));
+
+        // Misspelled icons are reported instead of silently ignored
+        assert!(parse_config(r#"{"icons": {"stars": "+"}}"#).unwrap_err().contains("stars"));
+    }
+}
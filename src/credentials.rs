@@ -0,0 +1,173 @@
+//! Tokens of the `gh` and `glab` command line tools
+//!
+//! With `"cli_credentials": true` in the config file, the tokens `gh auth login` and
+//! `glab auth login` stored are used when no other source has one. The host of the
+//! provider picks the entry, so a GitHub Enterprise Server or a self-hosted GitLab gets
+//! the token the tools have for it. Tokens in `gh`'s `hosts.yml` and `glab`'s
+//! `config.yml` are read directly, the ones the tools keep in the system keyring are
+//! asked for with `gh auth token` and `glab config get token`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::formatter::RepoSource;
+use crate::hosts::{self, Hosts};
+
+/// The token the command line tool of a provider has for its host, only `gh` for GitHub
+/// and `glab` for GitLab are known
+pub fn token(provider: RepoSource, hosts: &Hosts) -> Option<String> {
+    match provider {
+        RepoSource::GitHub => gh_token(hosts::ssh_host(&hosts.github)),
+        RepoSource::GitLab => glab_token(hosts::ssh_host(&hosts.gitlab)),
+        RepoSource::Gitea | RepoSource::Bitbucket => None,
+    }
+}
+
+fn gh_token(host: &str) -> Option<String> {
+    read_config("GH_CONFIG_DIR", "gh", "hosts.yml")
+        .and_then(|yaml| yaml_value(&yaml, &[host, "oauth_token"]))
+        .or_else(|| command_token("gh", &["auth", "token", "--hostname", host]))
+}
+
+fn glab_token(host: &str) -> Option<String> {
+    read_config("GLAB_CONFIG_DIR", "glab-cli", "config.yml")
+        .and_then(|yaml| yaml_value(&yaml, &["hosts", host, "token"]))
+        .or_else(|| command_token("glab", &["config", "get", "token", "--host", host]))
+}
+
+// Reads a file of a tool's config directory, which `variable` overrides and which is
+// below `XDG_CONFIG_HOME` or `~/.config` otherwise
+fn read_config(variable: &str, name: &str, file: &str) -> Option<String> {
+    let non_empty = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+    let dir = match non_empty(variable) {
+        Some(dir) => PathBuf::from(dir),
+        None => non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".config")))?
+            .join(name),
+    };
+    fs::read_to_string(dir.join(file)).ok()
+}
+
+// What a tool prints as the token, `None` when it isn't installed or has none
+fn command_token(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !token.is_empty()).then_some(token)
+}
+
+/// The value at a path of keys in the YAML `gh` and `glab` write, nested maps of plain
+/// or quoted values
+///
+/// Empty values and tags like `!!null` count as missing.
+pub fn yaml_value(yaml: &str, path: &[&str]) -> Option<String> {
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    for line in yaml.lines() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') || content.starts_with('-') {
+            continue;
+        }
+        let Some((key, value)) = split_entry(content) else {
+            continue;
+        };
+        let indent = line.len() - content.len();
+        while keys.last().is_some_and(|(parent, _)| *parent >= indent) {
+            keys.pop();
+        }
+        keys.push((indent, key));
+
+        let matches = keys.len() == path.len()
+            && keys.iter().zip(path).all(|((_, key), wanted)| key == wanted);
+        if matches && !value.is_empty() && !value.starts_with("!!") {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// Splits `key: value` into the unquoted key and value
+fn split_entry(content: &str) -> Option<(String, String)> {
+    let (key, value) = match content.split_once(": ") {
+        Some((key, value)) => (key, value),
+        None => (content.strip_suffix(':')?, ""),
+    };
+    let value = match value.trim() {
+        quoted if quoted.len() >= 2 && (quoted.starts_with('"') || quoted.starts_with('\'')) => {
+            unquote(quoted)
+        }
+        plain => plain.split(" #").next().unwrap_or_default().trim(),
+    };
+    Some((unquote(key.trim()).to_string(), value.to_string()))
+}
+
+fn unquote(text: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| text.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gh_hosts() {
+        // File-backed, with an account section like newer versions write
+        let hosts = "\
+github.com:
+    users:
+        dima:
+            oauth_token: gho_user
+    git_protocol: ssh
+    oauth_token: gho_active
+    user: dima
+github.mycorp.com:
+    oauth_token: \"gho_enterprise\"
+";
+        assert_eq!(yaml_value(hosts, &["github.com", "oauth_token"]), Some("gho_active".into()));
+        assert_eq!(
+            yaml_value(hosts, &["github.mycorp.com", "oauth_token"]),
+            Some("gho_enterprise".into())
+        );
+        assert_eq!(yaml_value(hosts, &["ghe.other.io", "oauth_token"]), None);
+
+        // Keyring-backed, the token has to be asked for
+        let keyring = "github.com:\n    git_protocol: https\n    user: dima\n";
+        assert_eq!(yaml_value(keyring, &["github.com", "oauth_token"]), None);
+    }
+
+    #[test]
+    fn test_glab_config() {
+        let config = "\
+# What protocol to use when performing git operations
+git_protocol: ssh
+hosts:
+  gitlab.com:
+    token: glpat-public # set by glab auth login
+    api_host: gitlab.com
+    user: dima
+  gitlab.mycompany.io:
+    token: 'glpat-company'
+  gitlab.keyring.io:
+    token: !!null
+";
+        assert_eq!(
+            yaml_value(config, &["hosts", "gitlab.com", "token"]),
+            Some("glpat-public".into())
+        );
+        assert_eq!(
+            yaml_value(config, &["hosts", "gitlab.mycompany.io", "token"]),
+            Some("glpat-company".into())
+        );
+        assert_eq!(yaml_value(config, &["hosts", "gitlab.keyring.io", "token"]), None);
+        assert_eq!(yaml_value(config, &["gitlab.com", "token"]), None);
+    }
+}
@@ -1,125 +1,3558 @@
-/// Filter list by query case insensitively.
+//! Filtering of items by a human-typed query
+//!
+//! Queries are split on whitespace into terms which all have to match, the score of an
+//! item being the sum of its term scores. A [`Matcher`] compiles a query once to match
+//! single texts, the filter functions for lists build on it.
+//!
+//! # Query Syntax
+//!
+//! - `api` - matches according to the [`MatchMode`]
+//! - `-fork` or `!fork` - excludes items containing `fork`
+//! - `rust|go` - matches either alternative, `|` binds tighter than whitespace so
+//!   `api rust|go` means `api` and either `rust` or `go`
+//! - `'cli` - matches `cli` as a substring even in fuzzy mode, `'cli'` only at word
+//!   boundaries
+//! - `^repo` and `rs$` - the match has to begin at the start or finish at the end of the
+//!   text
+//! - `name:api`, `owner:dima` and `desc:deprecated` (or `description:`) - only match the
+//!   given field of a [`Candidate`], other prefixes are matched literally
+//! - `group/api` or `group/` - a term containing `/` also matches the `owner/name` path
+//!   of a repository
+//! - `is:fork`, `is:private`, `is:internal`, `is:archived`, `is:mirror`, `is:starred`
+//!   and `source:github` (or `gh`, `gitlab`, `gl`, `gitea`, `gt`, `bitbucket`, `bb`) -
+//!   filter on the [`Metadata`] of a candidate, unknown qualifiers are matched literally
+//!
+//! Anchors apply to the whole keyed text, so for formatted repository lines a suffix
+//! anchor is checked against the end of the line rather than the end of the name.
+//!
+//! # Match Modes
+//!
+//! - [`MatchMode::Fuzzy`] - characters of a term must appear in order, results are
+//!   ranked by how well they match (consecutive characters and word starts score higher)
+//! - [`MatchMode::Exact`] - terms must appear as substrings, input order is kept
+//! - [`MatchMode::Regex`] - the whole query is a regular expression, input order is kept
+//!
+//! # Ordering
+//!
+//! Filtering is deterministic, the same items and query always give the same results in
+//! the same order. Items with equal scores keep their input order unless a [`TieBreak`]
+//! orders them, and then still keep it where the tie-break is equal as well.
+//!
+//! # Case Modes
+//!
+//! - [`CaseMode::Smart`] - case insensitive unless the query contains an uppercase character
+//! - [`CaseMode::Insensitive`] - always case insensitive
+//! - [`CaseMode::Sensitive`] - always case sensitive
+//!
+//! Both query and items are decomposed (NFKD) and stripped of diacritics before
+//! matching unless [`FilterOptions::normalize`] is off, reported positions still refer
+//! to characters of the original text. Case insensitive matching uses Unicode case
+//! folding, so `ß` matches `ss`, a final `ς` matches `σ` and the Turkish `ı` and `İ`
+//! match `i`.
+
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use regex::{Regex, RegexBuilder};
+use crate::formatter::{RepoSource, Visibility};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Score of a fuzzy match, higher is better
+pub type Score = i32;
+
+/// How the terms of a query are matched against items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// Characters must appear in order, results are sorted by score
+    #[default]
+    Fuzzy,
+    /// Terms must appear as substrings, results keep input order
+    Exact,
+    /// The query is a regular expression without any term syntax, results keep input order
+    Regex,
+}
+
+/// How letter case is compared between the query and items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseMode {
+    /// Case sensitive only when the query contains an uppercase character
+    #[default]
+    Smart,
+    /// Case is ignored
+    Insensitive,
+    /// Case has to match
+    Sensitive,
+}
+
+impl CaseMode {
+    /// Every case mode, in the order a UI toggle cycles through them
+    pub const ALL: [CaseMode; 3] = [CaseMode::Smart, CaseMode::Insensitive, CaseMode::Sensitive];
+
+    /// Short label for display in the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            CaseMode::Smart => "smart-case",
+            CaseMode::Insensitive => "ignore-case",
+            CaseMode::Sensitive => "match-case",
+        }
+    }
+
+    /// The mode following this one when cycling through [`CaseMode::ALL`]
+    pub fn next(self) -> CaseMode {
+        let index = CaseMode::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        CaseMode::ALL[(index + 1) % CaseMode::ALL.len()]
+    }
+
+    /// Whether the given query is compared case sensitively in this mode
+    pub fn is_sensitive(self, query: &str) -> bool {
+        match self {
+            CaseMode::Smart => query.chars().any(char::is_uppercase),
+            CaseMode::Insensitive => false,
+            CaseMode::Sensitive => true,
+        }
+    }
+}
+
+/// How items with equal scores are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// Keep the order the items were given in
+    #[default]
+    Input,
+    /// Alphabetically by the mapped text, ignoring case
+    Alphabetical,
+    /// Shortest mapped text first, as short queries usually target short names
+    Shortest,
+}
+
+impl TieBreak {
+    /// Compares the mapped texts of two items with equal scores, `Equal` keeping their
+    /// input order
+    pub fn compare(self, a: &str, b: &str) -> std::cmp::Ordering {
+        match self {
+            TieBreak::Input => std::cmp::Ordering::Equal,
+            TieBreak::Alphabetical => a.to_lowercase().cmp(&b.to_lowercase()),
+            TieBreak::Shortest => a.chars().count().cmp(&b.chars().count()),
+        }
+    }
+}
+
+/// Weight of a repository name field
+pub const NAME_WEIGHT: Score = 3;
+/// Weight of a repository owner field
+pub const OWNER_WEIGHT: Score = 2;
+/// Weight of a repository description field
+pub const DESCRIPTION_WEIGHT: Score = 1;
+
+/// What a [`Field`] of a candidate holds, query terms can be scoped to one kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Repository name, scoped with `name:`
+    Name,
+    /// Owner or namespace, scoped with `owner:`
+    Owner,
+    /// Description, scoped with `desc:` or `description:`
+    Description,
+    /// `owner/name` of a repository, only matched by unscoped terms containing a `/`
+    Path,
+    /// Any other text, only matched by unscoped terms
+    Text,
+}
+
+impl FieldKind {
+    /// Looks up the kind a query scope prefix like `name` refers to
+    fn from_scope(scope: &str) -> Option<FieldKind> {
+        match scope {
+            "name" => Some(FieldKind::Name),
+            "owner" => Some(FieldKind::Owner),
+            "desc" | "description" => Some(FieldKind::Description),
+            _ => None,
+        }
+    }
+}
+
+/// A text field of a [`Candidate`], its match scores are multiplied by the weight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub kind: FieldKind,
+    pub text: String,
+    pub weight: Score,
+}
+
+/// Repository properties that `is:` and `source:` qualifiers filter on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub fork: bool,
+    pub visibility: Visibility,
+    pub archived: bool,
+    pub mirror: bool,
+    /// Starred by the user rather than one of theirs
+    pub starred: bool,
+    pub source: RepoSource,
+}
+
+/// A query qualifier filtering on [`Metadata`] instead of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Qualifier {
+    Fork,
+    Private,
+    Internal,
+    Archived,
+    Mirror,
+    Starred,
+    Source(RepoSource),
+}
+
+impl Qualifier {
+    /// Recognizes qualifiers like `is:fork` or `source:gh`
+    fn parse(text: &str) -> Option<Qualifier> {
+        match text.split_once(':')? {
+            ("is", "fork") => Some(Qualifier::Fork),
+            ("is", "private") => Some(Qualifier::Private),
+            ("is", "internal") => Some(Qualifier::Internal),
+            ("is", "archived") => Some(Qualifier::Archived),
+            ("is", "mirror") => Some(Qualifier::Mirror),
+            ("is", "starred") => Some(Qualifier::Starred),
+            ("source", "github" | "gh") => Some(Qualifier::Source(RepoSource::GitHub)),
+            ("source", "gitlab" | "gl") => Some(Qualifier::Source(RepoSource::GitLab)),
+            ("source", "gitea" | "gt") => Some(Qualifier::Source(RepoSource::Gitea)),
+            ("source", "bitbucket" | "bb") => Some(Qualifier::Source(RepoSource::Bitbucket)),
+            _ => None,
+        }
+    }
+
+    fn holds(self, metadata: &Metadata) -> bool {
+        match self {
+            Qualifier::Fork => metadata.fork,
+            Qualifier::Private => metadata.visibility == Visibility::Private,
+            Qualifier::Internal => metadata.visibility == Visibility::Internal,
+            Qualifier::Archived => metadata.archived,
+            Qualifier::Mirror => metadata.mirror,
+            Qualifier::Starred => metadata.starred,
+            Qualifier::Source(source) => metadata.source == source,
+        }
+    }
+}
+
+/// An item made of several weighted fields, matched by [`filter_candidates`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Candidate {
+    pub fields: Vec<Field>,
+    /// Properties for qualifiers, without them qualifiers are matched as text
+    pub metadata: Option<Metadata>,
+    /// Caller supplied weight like recency or how often the item was selected, added
+    /// to the score of a match scaled by [`FilterOptions::prior_weight`]
+    pub prior: f32,
+}
+
+impl Candidate {
+    /// Creates a candidate without fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a candidate with a single text field of weight 1
+    pub fn from_text(text: impl Into<String>) -> Self {
+        Self::new().field(FieldKind::Text, text, 1)
+    }
+
+    /// Creates a candidate for a repository, weighting the name highest
+    ///
+    /// The owner may be a nested namespace like `group/subgroup`, a term like
+    /// `group/project` matches it together with the name.
+    pub fn repository(name: &str, owner: &str, description: &str) -> Self {
+        let path = if owner.is_empty() {
+            String::new()
+        } else {
+            format!("{}/{}", owner, name)
+        };
+        Self::new()
+            .field(FieldKind::Name, name, NAME_WEIGHT)
+            .field(FieldKind::Owner, owner, OWNER_WEIGHT)
+            .field(FieldKind::Description, description, DESCRIPTION_WEIGHT)
+            .field(FieldKind::Path, path, NAME_WEIGHT)
+    }
+
+    /// Adds a field, empty texts are skipped
+    pub fn field(mut self, kind: FieldKind, text: impl Into<String>, weight: Score) -> Self {
+        let text = text.into();
+        if !text.is_empty() {
+            self.fields.push(Field { kind, text, weight });
+        }
+        self
+    }
+
+    /// Sets the properties `is:` and `source:` qualifiers filter on
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Sets the weight added to the score of every match, see [`Candidate::prior`]
+    pub fn prior(mut self, prior: f32) -> Self {
+        self.prior = prior;
+        self
+    }
+
+    /// The prior as it is added to the text score
+    fn prior_score(&self, weight: f32) -> Score {
+        (self.prior * weight).round() as Score
+    }
+
+    /// Text of the first field, used to break ties
+    pub fn primary_text(&self) -> &str {
+        self.fields.first().map_or("", |field| field.text.as_str())
+    }
+}
+
+/// Options controlling how [`filter_scored`] matches a query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterOptions {
+    pub match_mode: MatchMode,
+    pub case_mode: CaseMode,
+    pub tie_break: TieBreak,
+    /// Decompose characters (NFKD) and ignore diacritics, so `cafe` matches `café`;
+    /// not applied in [`MatchMode::Regex`]
+    pub normalize: bool,
+    /// When nothing matches, retry fuzzy terms allowing one typo each, see
+    /// [`IncrementalFilter::typo_fallback`]
+    pub typo_tolerance: bool,
+    /// Only return the best this many matches, without sorting the rest
+    pub limit: Option<usize>,
+    /// Scales [`Candidate::prior`] before it is added to the text score, so a prior
+    /// decides between similar matches without outranking much better ones
+    pub prior_weight: f32,
+}
+
+impl FilterOptions {
+    fn limit_removed(self) -> Self {
+        Self { limit: None, ..self }
+    }
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        Self {
+            match_mode: MatchMode::default(),
+            case_mode: CaseMode::default(),
+            tie_break: TieBreak::default(),
+            normalize: true,
+            typo_tolerance: false,
+            limit: None,
+            prior_weight: 1.0,
+        }
+    }
+}
+
+/// Candidate counts from which matching is spread over threads, smaller lists are
+/// faster on one
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+/// Subtracted from a term matched with a typo, so it ranks below any exact match of it
+const TYPO_PENALTY: Score = 48;
+/// Shorter patterns are never matched with typos, dropping a character would leave
+/// them matching nearly everything
+const TYPO_MIN_PATTERN_LEN: usize = 4;
+/// Longer texts are skipped when matching typos, keeping the retry cheap
+const TYPO_MAX_TEXT_LEN: usize = 256;
+
+const SCORE_MATCH: Score = 16;
+const SCORE_GAP_START: Score = -3;
+const SCORE_GAP_EXTENSION: Score = -1;
+const BONUS_START: Score = 10;
+/// Bonus for any other word start, after a separator, on a camelCase hump or at a
+/// switch between letters and digits
+const BONUS_BOUNDARY: Score = 8;
+const BONUS_CONSECUTIVE: Score = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: Score = 2;
+/// Bonus per character when every character of a pattern lands on a word start
+const BONUS_ACRONYM: Score = 8;
+
+/// Bonus for matching the character at `index`, based on the character before it
+fn position_bonus(chars: &[char], index: usize) -> Score {
+    let Some(&previous) = index.checked_sub(1).and_then(|i| chars.get(i)) else {
+        // The start of the text is the strongest word start
+        return BONUS_START;
+    };
+    let current = chars[index];
+    let next = chars.get(index + 1);
+
+    let word_start = (!previous.is_alphanumeric() && current.is_alphanumeric())
+        // Humps like in `MyAwesomeProject`, and the last capital of an acronym like the
+        // `P` in `XMLParser`
+        || (previous.is_lowercase() && current.is_uppercase())
+        || (previous.is_uppercase()
+            && current.is_uppercase()
+            && next.is_some_and(|next| next.is_lowercase()))
+        // Switches between letters and digits, like in `v2` or `2fa`
+        || (previous.is_alphabetic() && current.is_numeric())
+        || (previous.is_numeric() && current.is_alphabetic());
+
+    if word_start {
+        BONUS_BOUNDARY
+    } else {
+        0
+    }
+}
+
+/// Finds the best alignment of `pattern` as a subsequence of `text`, comparing case
+/// insensitively
+///
+/// Returns the score and the character indices of the matched characters in `text`.
+pub fn fuzzy_match(text: &str, pattern: &str) -> Option<(Score, Vec<usize>)> {
+    let folding = Folding {
+        case_sensitive: false,
+        normalize: true,
+    };
+    let pattern = Prepared::new(pattern, folding).folded;
+    fuzzy_match_with(text, &pattern, folding, Anchors::default())
+}
+
+/// How text is folded before comparing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Folding {
+    case_sensitive: bool,
+    /// Decompose characters and strip diacritics, so `café` matches `cafe`
+    normalize: bool,
+}
+
+/// Text split into characters for matching
+#[derive(Default)]
+struct Prepared {
+    /// Characters with diacritics stripped when normalizing, used for word boundaries,
+    /// repeated where folding expands them
+    chars: Vec<char>,
+    /// `chars` folded for comparison, `ß` becomes `ss`
+    folded: Vec<char>,
+    /// Index of the original character each character came from
+    origins: Vec<usize>,
+}
+
+impl Prepared {
+    fn new(text: &str, folding: Folding) -> Prepared {
+        let mut prepared = Prepared::default();
+        prepared.fill(text, folding);
+        prepared
+    }
+
+    /// Prepares `text`, reusing the buffers of the previously prepared text
+    fn fill(&mut self, text: &str, folding: Folding) {
+        let Prepared {
+            chars,
+            folded,
+            origins,
+        } = self;
+        chars.clear();
+        folded.clear();
+        origins.clear();
+
+        for (index, c) in text.chars().enumerate() {
+            for_each_folded(c, folding, |c, fold| {
+                chars.push(c);
+                folded.push(fold);
+                origins.push(index);
+            });
+        }
+    }
+
+    /// Maps positions in the prepared characters back to character indices of the
+    /// original text, merging characters which came from the same original one
+    fn original_positions(&self, positions: Vec<usize>) -> Vec<usize> {
+        let mut mapped: Vec<usize> = positions.into_iter().map(|j| self.origins[j]).collect();
+        mapped.dedup();
+        mapped
+    }
+}
+
+/// A text patterns are matched against, prepared for each match or ahead of time
+trait Subject {
+    /// Number of characters of the original text
+    fn char_count(&self) -> usize;
+
+    /// Positions of the first occurrence of the folded `pattern`
+    fn exact_match(&self, pattern: &[char], anchors: Anchors) -> Option<Vec<usize>>;
+
+    /// Score and positions of the best fuzzy alignment of the folded `pattern`
+    fn fuzzy_match(&self, pattern: &[char], anchors: Anchors) -> Option<(Score, Vec<usize>)>;
+}
+
+/// A text prepared on every match, positions refer to its characters
+struct RawText<'a> {
+    text: &'a str,
+    folding: Folding,
+}
+
+impl Subject for RawText<'_> {
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn exact_match(&self, pattern: &[char], anchors: Anchors) -> Option<Vec<usize>> {
+        exact_match(self.text, pattern, self.folding, anchors)
+    }
+
+    fn fuzzy_match(&self, pattern: &[char], anchors: Anchors) -> Option<(Score, Vec<usize>)> {
+        fuzzy_match_with(self.text, pattern, self.folding, anchors)
+    }
+}
+
+/// A text prepared once for case insensitive matching, kept by [`PreparedCandidates`]
+///
+/// Positions it reports refer to the folded characters, which only scoring uses.
+#[derive(Debug, Clone)]
+struct PreparedText {
+    /// The text folded like [`Prepared::folded`]
+    folded: Box<[char]>,
+    /// Bit `j % 64` of word `j / 64` is set where `folded[j]` starts a word
+    word_starts: Box<[u64]>,
+    char_count: usize,
+}
+
+impl PreparedText {
+    fn new(text: &str, folding: Folding) -> Self {
+        let prepared = Prepared::new(text, folding);
+        let mut word_starts = vec![0; prepared.chars.len().div_ceil(64)];
+        for j in 0..prepared.chars.len() {
+            if is_word_start(position_bonus(&prepared.chars, j)) {
+                word_starts[j / 64] |= 1 << (j % 64);
+            }
+        }
+
+        Self {
+            folded: prepared.folded.into_boxed_slice(),
+            word_starts: word_starts.into_boxed_slice(),
+            char_count: text.chars().count(),
+        }
+    }
+
+    /// The [`position_bonus`] of the character at `j`
+    fn bonus(&self, j: usize) -> Score {
+        if j == 0 {
+            BONUS_START
+        } else if self.word_starts[j / 64] & (1 << (j % 64)) != 0 {
+            BONUS_BOUNDARY
+        } else {
+            0
+        }
+    }
+
+    /// Whether the folded `pattern` appears in order in the text
+    fn contains_subsequence(&self, pattern: &[char]) -> bool {
+        let mut remaining = pattern.iter().peekable();
+        for c in self.folded.iter() {
+            if remaining.next_if_eq(&c).is_some() && remaining.peek().is_none() {
+                break;
+            }
+        }
+        remaining.peek().is_none()
+    }
+}
+
+impl Subject for PreparedText {
+    fn char_count(&self) -> usize {
+        self.char_count
+    }
+
+    fn exact_match(&self, pattern: &[char], anchors: Anchors) -> Option<Vec<usize>> {
+        if pattern.is_empty() {
+            return Some(Vec::new());
+        }
+        let start = exact_start(&self.folded, pattern, anchors)?;
+        Some((start..start + pattern.len()).collect())
+    }
+
+    fn fuzzy_match(&self, pattern: &[char], anchors: Anchors) -> Option<(Score, Vec<usize>)> {
+        if pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+        if !self.contains_subsequence(pattern) {
+            return None;
+        }
+
+        SCRATCH.with(|scratch| {
+            let Scratch { bonuses, cells, .. } = &mut *scratch.borrow_mut();
+            bonuses.clear();
+            bonuses.extend((0..self.folded.len()).map(|j| self.bonus(j)));
+            align(cells, &self.folded, bonuses, pattern, anchors)
+        })
+    }
+}
+
+/// The fields of every candidate prepared for case insensitive matching, so that the
+/// texts are not folded again on every keystroke
+#[derive(Debug, Clone, Default)]
+struct PreparedCandidates {
+    /// How the texts were folded, `None` before anything was prepared
+    folding: Option<Folding>,
+    /// The prepared texts of each candidate, in the order of its fields
+    candidates: Vec<Box<[PreparedText]>>,
+}
+
+impl PreparedCandidates {
+    /// Prepares the candidates appended since the last call, starting over when the
+    /// folding changed or candidates were removed
+    fn update(&mut self, candidates: &[Candidate], folding: Folding) {
+        if self.folding != Some(folding) || self.candidates.len() > candidates.len() {
+            self.clear();
+            self.folding = Some(folding);
+        }
+
+        let prepare = |candidate: &Candidate| -> Box<[PreparedText]> {
+            candidate.fields.iter().map(|field| PreparedText::new(&field.text, folding)).collect()
+        };
+        let added = &candidates[self.candidates.len()..];
+        if added.len() >= PARALLEL_THRESHOLD {
+            let prepared: Vec<_> = added.par_iter().map(prepare).collect();
+            self.candidates.extend(prepared);
+        } else {
+            self.candidates.extend(added.iter().map(prepare));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.folding = None;
+        self.candidates.clear();
+    }
+}
+
+/// Where a pattern has to be matched within the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Anchors {
+    /// The match has to begin at the start of the text, set by a leading `^`
+    start: bool,
+    /// The match has to finish at the end of the text, set by a trailing `$`
+    end: bool,
+    /// The match has to begin and finish at word boundaries, only used for exact matches
+    word_boundary: bool,
+}
+
+/// Whether the folded `pattern` appears in order in `text`, checked without allocating
+/// so most items can be rejected before they are prepared for matching
+fn contains_subsequence(text: &str, pattern: &[char], folding: Folding) -> bool {
+    let mut next = 0;
+
+    for c in text.chars() {
+        if next == pattern.len() {
+            break;
+        }
+        // ASCII is folded on its own, skipping the decomposition for the common case
+        if c.is_ascii() {
+            let fold = if folding.case_sensitive { c } else { c.to_ascii_lowercase() };
+            if fold == pattern[next] {
+                next += 1;
+            }
+            continue;
+        }
+        for_each_folded(c, folding, |_, fold| {
+            if next < pattern.len() && fold == pattern[next] {
+                next += 1;
+            }
+        });
+    }
+    next == pattern.len()
+}
+
+/// Calls `f` with each character `c` is prepared into, together with its folded form
+///
+/// Decomposing may split `c` into several characters, and folding may expand one, like
+/// `ß` into `ss`.
+fn for_each_folded(c: char, folding: Folding, mut f: impl FnMut(char, char)) {
+    let mut fold = |c: char| {
+        if folding.case_sensitive {
+            f(c, c);
+        } else {
+            fold_char(c, |fold| f(c, fold));
+        }
+    };
+
+    // ASCII never decomposes, skipping the lookup for the common case
+    if folding.normalize && !c.is_ascii() {
+        c.nfkd().filter(|&d| !is_combining_mark(d)).for_each(&mut fold);
+    } else {
+        fold(c);
+    }
+}
+
+/// Matches an already folded pattern, see [`Prepared`]
+fn fuzzy_match_with(
+    text: &str,
+    pattern: &[char],
+    folding: Folding,
+    anchors: Anchors,
+) -> Option<(Score, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    if !contains_subsequence(text, pattern, folding) {
+        return None;
+    }
+
+    SCRATCH.with(|scratch| {
+        let Scratch {
+            prepared,
+            bonuses,
+            cells,
+        } = &mut *scratch.borrow_mut();
+        prepared.fill(text, folding);
+        bonuses.clear();
+        bonuses.extend((0..prepared.chars.len()).map(|j| position_bonus(&prepared.chars, j)));
+
+        let (score, positions) = align(cells, &prepared.folded, bonuses, pattern, anchors)?;
+        Some((score, prepared.original_positions(positions)))
+    })
+}
+
+/// Buffers reused by every match on a thread, so scoring an item does not allocate
+/// beyond the returned positions
+#[derive(Default)]
+struct Scratch {
+    prepared: Prepared,
+    bonuses: Vec<Score>,
+    cells: Cells,
+}
+
+/// The score matrices of [`align`]
+#[derive(Default)]
+struct Cells {
+    scores: Vec<Option<Score>>,
+    previous: Vec<usize>,
+    chunks: Vec<Score>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+/// Finds the best alignment of `pattern` in the folded text, returning its score and
+/// the matched positions within `folded`
+///
+/// `bonuses` holds the [`position_bonus`] of every character, the pattern has to be a
+/// subsequence of the text already.
+fn align(
+    cells: &mut Cells,
+    folded: &[char],
+    bonuses: &[Score],
+    pattern: &[char],
+    anchors: Anchors,
+) -> Option<(Score, Vec<usize>)> {
+    let Cells {
+        scores,
+        previous,
+        chunks,
+    } = cells;
+    let n = folded.len();
+
+    // scores[i * n + j] is the best score with pattern[i] matched at text[j],
+    // previous[i * n + j] the position pattern[i - 1] was matched at for that score and
+    // chunks[i * n + j] the bonus of the consecutive run ending there, which later
+    // characters of the run inherit; each is a single buffer for all rows. Only cells
+    // with a score are ever read from the other two, so they keep stale values
+    let cells = n * pattern.len();
+    scores.clear();
+    scores.resize(cells, None);
+    previous.resize(previous.len().max(cells), 0);
+    chunks.resize(chunks.len().max(cells), 0);
+    let (scores, previous, chunks) =
+        (&mut scores[..], &mut previous[..cells], &mut chunks[..cells]);
+
+    // pattern[i] can only be matched where the rest of the pattern still fits after it,
+    // the subsequence check above made sure the whole pattern fits into the text
+    let last_position = |i: usize| n - (pattern.len() - i);
+
+    // An anchored start only allows the first character to match at the beginning
+    let first_positions = if anchors.start { 1 } else { last_position(0) + 1 };
+    for (j, &c) in folded.iter().enumerate().take(first_positions) {
+        if c == pattern[0] {
+            scores[j] = Some(SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+            chunks[j] = bonuses[j];
+        }
+    }
+
+    for (i, &wanted) in pattern.iter().enumerate().skip(1) {
+        let (row, above) = (i * n, (i - 1) * n);
+        // Best score of an earlier match followed by a gap, with its position
+        let mut gapped: Option<(Score, usize)> = None;
+
+        for j in i..=last_position(i) {
+            if j >= 2 {
+                let extended = gapped.map(|(score, k)| (score + SCORE_GAP_EXTENSION, k));
+                let started = scores[above + j - 2].map(|score| (score + SCORE_GAP_START, j - 2));
+                gapped = match (extended, started) {
+                    (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                };
+            }
+
+            if folded[j] != wanted {
+                continue;
+            }
+
+            let chunk = chunks[above + j - 1].max(bonuses[j]);
+            let consecutive = scores[above + j - 1]
+                .map(|score| (score + SCORE_MATCH + chunk.max(BONUS_CONSECUTIVE), j - 1));
+            let after_gap = gapped.map(|(score, k)| (score + SCORE_MATCH + bonuses[j], k));
+
+            let best = match (consecutive, after_gap) {
+                (Some(a), Some(b)) if b.0 > a.0 => Some((b, bonuses[j])),
+                (Some(a), _) => Some((a, chunk)),
+                (None, b) => b.map(|b| (b, bonuses[j])),
+            };
+            if let Some(((score, k), chunk)) = best {
+                scores[row + j] = Some(score);
+                previous[row + j] = k;
+                chunks[row + j] = chunk;
+            }
+        }
+    }
+
+    // Pick the best end position, the earliest one on ties
+    let last = pattern.len() - 1;
+    let (mut j, score) = scores[last * n..]
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !anchors.end || *j == n - 1)
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .fold(None, |best: Option<(usize, Score)>, (j, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((j, score)),
+        })?;
+
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        if i > 0 {
+            j = previous[i * n + j];
+        }
+    }
+
+    let mut best = (score + acronym_bonus(bonuses, &positions), positions);
+
+    // Word initials are worth more than the best plain alignment, which may not use them
+    if let Some(initials) = initials_alignment(folded, pattern, bonuses, anchors) {
+        let score = score_positions(bonuses, &initials) + acronym_bonus(bonuses, &initials);
+        if score > best.0 {
+            best = (score, initials);
+        }
+    }
+
+    Some(best)
+}
+
+/// Whether matching the character with this bonus starts a word
+fn is_word_start(bonus: Score) -> bool {
+    bonus >= BONUS_BOUNDARY
+}
+
+/// Bonus for alignments of several characters which all start words, like `rsg` for
+/// `repo-searcher-github`
+///
+/// Characters continuing a run from a word start count as well, so whole words like
+/// `api` in `api-tools` are not outranked by the initials in `a-p-i-tools`.
+fn acronym_bonus(bonuses: &[Score], positions: &[usize]) -> Score {
+    let on_words = positions.iter().enumerate().all(|(i, &j)| {
+        is_word_start(bonuses[j]) || (i > 0 && positions[i - 1] + 1 == j)
+    });
+
+    if positions.len() >= 2 && on_words {
+        BONUS_ACRONYM * positions.len() as Score
+    } else {
+        0
+    }
+}
+
+/// Finds the earliest alignment of `pattern` using only characters which start words
+fn initials_alignment(
+    folded: &[char],
+    pattern: &[char],
+    bonuses: &[Score],
+    anchors: Anchors,
+) -> Option<Vec<usize>> {
+    if pattern.len() < 2 {
+        return None;
+    }
+
+    let mut remaining = pattern.iter().peekable();
+    let mut positions = Vec::with_capacity(pattern.len());
+    for (j, c) in folded.iter().enumerate() {
+        if remaining.peek() == Some(&c) && is_word_start(bonuses[j]) {
+            positions.push(j);
+            remaining.next();
+        }
+    }
+
+    let complete = remaining.peek().is_none();
+    let anchored = (!anchors.start || positions.first() == Some(&0))
+        && (!anchors.end || positions.last() == Some(&(folded.len() - 1)));
+    (complete && anchored).then_some(positions)
+}
+
+/// Scores a given alignment with the same rules the matcher uses
+fn score_positions(bonuses: &[Score], positions: &[usize]) -> Score {
+    let mut score = 0;
+    let mut chunk = 0;
+
+    for (i, &j) in positions.iter().enumerate() {
+        if i == 0 {
+            score += SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+            chunk = bonuses[j];
+            continue;
+        }
+
+        let gap = j - positions[i - 1] - 1;
+        if gap == 0 {
+            chunk = chunk.max(bonuses[j]);
+            score += SCORE_MATCH + chunk.max(BONUS_CONSECUTIVE);
+        } else {
+            chunk = bonuses[j];
+            let penalty = SCORE_GAP_START + (gap as Score - 1) * SCORE_GAP_EXTENSION;
+            score += penalty + SCORE_MATCH + bonuses[j];
+        }
+    }
+
+    score
+}
+
+/// Folds a character for case insensitive comparison, calling `f` with each character
+/// it folds into
+///
+/// Lowercasing alone keeps `ß` apart from `ss` and the final `ς` apart from `σ`, and
+/// leaves the Turkish dotless `ı` unmatched by `i`, so these are folded explicitly.
+fn fold_char(c: char, mut f: impl FnMut(char)) {
+    if c.is_ascii() {
+        return f(c.to_ascii_lowercase());
+    }
+    match c {
+        'ß' | 'ẞ' => {
+            f('s');
+            f('s');
+        }
+        'ς' => f('σ'),
+        'ı' | 'İ' => f('i'),
+        'ſ' => f('s'),
+        'µ' => f('μ'),
+        _ => f(c.to_lowercase().next().unwrap_or(c)),
+    }
+}
+
+/// Filter list by query with smart-case, ranking fuzzy matches by score.
 pub fn filter_human<T, F>(items: &[T], query: &str, mapper: F) -> Vec<T>
 where
     T: Clone,
     F: Fn(&T) -> String,
 {
-    if items.is_empty() {
-        return Vec::new();
+    filter_with_mode(items, query, MatchMode::Fuzzy, mapper)
+}
+
+/// Filter list by query with smart-case and the given match mode.
+pub fn filter_with_mode<T, F>(items: &[T], query: &str, mode: MatchMode, mapper: F) -> Vec<T>
+where
+    T: Clone,
+    F: Fn(&T) -> String,
+{
+    let options = FilterOptions {
+        match_mode: mode,
+        ..FilterOptions::default()
+    };
+    filter_scored(items, query, options, mapper)
+        .into_iter()
+        .map(|(item, _)| item)
+        .collect()
+}
+
+/// Filter list by query, returning each match with its score.
+///
+/// Results are sorted by score descending, equal scores keep their input order. An
+/// invalid regular expression in [`MatchMode::Regex`] matches nothing, use
+/// [`try_filter_scored`] to report it instead.
+pub fn filter_scored<T, F>(
+    items: &[T],
+    query: &str,
+    options: FilterOptions,
+    mapper: F,
+) -> Vec<(T, Score)>
+where
+    T: Clone,
+    F: Fn(&T) -> String,
+{
+    try_filter_scored(items, query, options, mapper).unwrap_or_default()
+}
+
+/// Filter list by query like [`filter_scored`], failing when the query is not a valid
+/// regular expression in [`MatchMode::Regex`].
+pub fn try_filter_scored<T, F>(
+    items: &[T],
+    query: &str,
+    options: FilterOptions,
+    mapper: F,
+) -> Result<Vec<(T, Score)>, regex::Error>
+where
+    T: Clone,
+    F: Fn(&T) -> String,
+{
+    let matches = try_filter_matches(items, query, options, mapper)?;
+    Ok(matches
+        .into_iter()
+        .map(|matched| (items[matched.index].clone(), matched.score))
+        .collect())
+}
+
+/// Filter list by query like [`filter_scored`], returning the indices of the matches
+/// into `items` with their scores instead of clones of the items.
+///
+/// The key borrows the text to match from an item, so no strings are allocated for the
+/// items or the results.
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::filter::{self, FilterOptions};
+///
+/// let items = vec!["web".to_string(), "api-tools".to_string()];
+/// let matches = filter::filter_indices(&items, "api", FilterOptions::default(), |s| s);
+/// assert_eq!(matches[0].0, 1);
+/// ```
+pub fn filter_indices<T, F>(
+    items: &[T],
+    query: &str,
+    options: FilterOptions,
+    key: F,
+) -> Vec<(usize, Score)>
+where
+    F: Fn(&T) -> &str,
+{
+    try_filter_indices(items, query, options, key).unwrap_or_default()
+}
+
+/// Filter list by query like [`filter_indices`], failing when the query is not a valid
+/// regular expression in [`MatchMode::Regex`].
+pub fn try_filter_indices<T, F>(
+    items: &[T],
+    query: &str,
+    options: FilterOptions,
+    key: F,
+) -> Result<Vec<(usize, Score)>, regex::Error>
+where
+    F: Fn(&T) -> &str,
+{
+    let matches = try_filter_matches(items, query, options, key)?;
+    Ok(matches
+        .into_iter()
+        .map(|matched| (matched.index, matched.score))
+        .collect())
+}
+
+/// Filter list by query like [`filter_human`], returning each match with the
+/// character indices of the matched characters in the mapped string.
+///
+/// Positions are character indices, not byte offsets, sorted and without duplicates
+/// when several terms match the same characters.
+pub fn filter_with_positions<T, F>(items: &[T], query: &str, mapper: F) -> Vec<(T, Vec<usize>)>
+where
+    T: Clone,
+    F: Fn(&T) -> String,
+{
+    try_filter_matches(items, query, FilterOptions::default(), mapper)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|matched| (items[matched.index].clone(), matched.positions))
+        .collect()
+}
+
+/// An item which passed the filter, by its index
+struct Matched {
+    index: usize,
+    score: Score,
+    positions: Vec<usize>,
+}
+
+/// Finds the first occurrence of `pattern` in `text` satisfying the anchors
+///
+/// Returns the character indices of the occurrence in `text`.
+fn exact_match(
+    text: &str,
+    pattern: &[char],
+    folding: Folding,
+    anchors: Anchors,
+) -> Option<Vec<usize>> {
+    if pattern.is_empty() {
+        return Some(Vec::new());
+    }
+    // A substring is a subsequence as well, which is cheaper to rule out
+    if !contains_subsequence(text, pattern, folding) {
+        return None;
+    }
+
+    let prepared = Prepared::new(text, folding);
+    let start = exact_start(&prepared.folded, pattern, anchors)?;
+    Some(prepared.original_positions((start..start + pattern.len()).collect()))
+}
+
+/// Where the folded `pattern` first appears in the folded text within the anchors
+fn exact_start(folded: &[char], pattern: &[char], anchors: Anchors) -> Option<usize> {
+    let is_word = |index: Option<usize>| {
+        index
+            .and_then(|i| folded.get(i))
+            .is_some_and(|c| c.is_alphanumeric())
+    };
+    let accepts = |start: usize| {
+        let end = start + pattern.len();
+        (!anchors.start || start == 0)
+            && (!anchors.end || end == folded.len())
+            && (!anchors.word_boundary || (!is_word(start.checked_sub(1)) && !is_word(Some(end))))
+    };
+    folded
+        .windows(pattern.len())
+        .enumerate()
+        .position(|(start, window)| window == pattern && accepts(start))
+}
+
+/// A single pattern of a query term
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern {
+    text: String,
+    /// Items matching an excluded pattern are removed
+    excluded: bool,
+    /// Matched as a substring even in fuzzy mode, set by a leading `'`
+    exact: bool,
+    anchors: Anchors,
+    /// Only fields of this kind are matched, set by a prefix like `name:`
+    scope: Option<FieldKind>,
+    /// Filters on candidate metadata instead, `text` is kept for candidates without it
+    qualifier: Option<Qualifier>,
+    /// A variant of a typed pattern with one typo undone, scored with [`TYPO_PENALTY`]
+    typo: bool,
+    /// `text` folded for matching, prepared once per query by [`Pattern::prepare`]
+    folded: Option<Vec<char>>,
+}
+
+impl Pattern {
+    fn parse(part: &str) -> Pattern {
+        Pattern::parse_checked(part, &mut Vec::new())
+    }
+
+    /// Parses like [`Pattern::parse`], adding a warning for syntax which is matched
+    /// literally although it looks like it was meant otherwise
+    fn parse_checked(part: &str, warnings: &mut Vec<QueryWarning>) -> Pattern {
+        let (text, excluded) = match part.strip_prefix('-').or_else(|| part.strip_prefix('!')) {
+            // Check for a remainder, so a single minus or bang is still matched
+            Some(rest) if !rest.is_empty() => (rest, true),
+            _ => (part, false),
+        };
+
+        if let Some(qualifier) = Qualifier::parse(text) {
+            return Pattern {
+                text: text.to_string(),
+                excluded,
+                exact: false,
+                anchors: Anchors::default(),
+                scope: None,
+                qualifier: Some(qualifier),
+                typo: false,
+                folded: None,
+            };
+        }
+
+        // Unknown scopes are matched literally, like a URL with `https:`
+        let (text, scope) = match text.split_once(':') {
+            Some((prefix, rest)) => match FieldKind::from_scope(prefix) {
+                Some(kind) => (rest, Some(kind)),
+                None => {
+                    if let Some(warning) = QueryWarning::unknown_prefix(text, prefix, rest) {
+                        warnings.push(warning);
+                    }
+                    (text, None)
+                }
+            },
+            None => (text, None),
+        };
+
+        let unpaired_quote =
+            text == "'" || (text.len() > 1 && text.ends_with('\'') && !text.starts_with('\''));
+        if unpaired_quote {
+            warnings.push(QueryWarning::UnterminatedQuote {
+                term: part.to_string(),
+            });
+        }
+
+        let mut anchors = Anchors::default();
+        let (text, exact) = match text.strip_prefix('\'') {
+            // A lone quote is matched literally
+            Some("") | None => (text, false),
+            Some(rest) => match rest.strip_suffix('\'') {
+                Some(word) if !word.is_empty() => {
+                    anchors.word_boundary = true;
+                    (word, true)
+                }
+                _ => (rest, true),
+            },
+        };
+
+        // Lone anchors are matched literally as well
+        let text = match text.strip_prefix('^') {
+            Some(rest) if !rest.is_empty() => {
+                anchors.start = true;
+                rest
+            }
+            _ => text,
+        };
+        let text = match text.strip_suffix('$') {
+            Some(rest) if !rest.is_empty() => {
+                anchors.end = true;
+                rest
+            }
+            _ => text,
+        };
+
+        Pattern {
+            text: text.to_string(),
+            excluded,
+            exact,
+            anchors,
+            scope,
+            qualifier: None,
+            typo: false,
+            folded: None,
+        }
+    }
+
+    /// Folds the text once, instead of for every item it is matched against
+    fn prepare(&mut self, folding: Folding) {
+        self.folded = Some(Prepared::new(&self.text, folding).folded);
+    }
+
+    /// The folded text, prepared on the fly when [`Pattern::prepare`] was not called
+    fn folded(&self, folding: Folding) -> Cow<'_, [char]> {
+        match &self.folded {
+            Some(folded) => Cow::Borrowed(folded),
+            None => Cow::Owned(Prepared::new(&self.text, folding).folded),
+        }
+    }
+
+    /// Variants of the pattern with one typo undone: a character dropped, which also
+    /// covers a mistyped one as the rest is matched as a subsequence, or two neighbouring
+    /// characters swapped
+    fn typo_variants(&self) -> Vec<Pattern> {
+        let chars: Vec<char> = self.text.chars().collect();
+        let typed = !self.excluded && !self.exact && self.qualifier.is_none();
+        if !typed || chars.len() < TYPO_MIN_PATTERN_LEN {
+            return Vec::new();
+        }
+
+        let mut texts: Vec<String> = Vec::new();
+        for index in 0..chars.len() {
+            let mut dropped = chars.clone();
+            dropped.remove(index);
+            texts.push(dropped.into_iter().collect());
+        }
+        for index in 1..chars.len() {
+            if chars[index - 1] != chars[index] {
+                let mut swapped = chars.clone();
+                swapped.swap(index - 1, index);
+                texts.push(swapped.into_iter().collect());
+            }
+        }
+        texts.sort_unstable();
+        texts.dedup();
+
+        texts
+            .into_iter()
+            .map(|text| Pattern {
+                text,
+                typo: true,
+                folded: None,
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Matches the pattern against `text`, returning the score and matched positions
+    fn matches(&self, text: &str, mode: MatchMode, folding: Folding) -> Option<(Score, Vec<usize>)> {
+        self.matches_subject(&RawText { text, folding }, mode, folding)
+    }
+
+    /// Matches like [`Pattern::matches`], with positions as the subject reports them
+    fn matches_subject(
+        &self,
+        subject: &impl Subject,
+        mode: MatchMode,
+        folding: Folding,
+    ) -> Option<(Score, Vec<usize>)> {
+        let pattern = self.folded(folding);
+        let exact = || subject.exact_match(&pattern, self.anchors);
+
+        if self.typo {
+            if subject.char_count() > TYPO_MAX_TEXT_LEN {
+                return None;
+            }
+            return subject
+                .fuzzy_match(&pattern, self.anchors)
+                .map(|(score, positions)| (score - TYPO_PENALTY, positions));
+        }
+
+        if self.excluded {
+            // Exclusions always match exactly
+            return match exact() {
+                Some(_) => None,
+                None => Some((0, Vec::new())),
+            };
+        }
+
+        // Substring matches are not scored, so they keep the order of the other terms
+        if self.exact || mode == MatchMode::Exact {
+            return exact().map(|found| (0, found));
+        }
+
+        subject.fuzzy_match(&pattern, self.anchors)
+    }
+
+    /// Matches the fields of a candidate, using their `prepared` texts when given
+    fn matches_candidate(
+        &self,
+        candidate: &Candidate,
+        prepared: Option<&[PreparedText]>,
+        mode: MatchMode,
+        folding: Folding,
+    ) -> Option<Score> {
+        // A scope without text yet, like `name:` while typing, keeps every item
+        if self.text.is_empty() {
+            return Some(0);
+        }
+
+        if let (Some(qualifier), Some(metadata)) = (self.qualifier, &candidate.metadata) {
+            return (qualifier.holds(metadata) != self.excluded).then_some(0);
+        }
+
+        let matches = |index: usize, field: &Field| {
+            let found = match prepared {
+                Some(prepared) => self.matches_subject(&prepared[index], mode, folding),
+                None => self.matches(&field.text, mode, folding),
+            };
+            found.map(|(score, _)| score)
+        };
+
+        // The path is only matched when the term looks like one
+        let mut fields = candidate.fields.iter().enumerate().filter(|(_, field)| match self.scope {
+            Some(kind) => field.kind == kind,
+            None => field.kind != FieldKind::Path || self.text.contains('/'),
+        });
+
+        if self.excluded {
+            // Excluded patterns have to be absent from every field in scope
+            let absent = fields.all(|(index, field)| matches(index, field).is_some());
+            return absent.then_some(0);
+        }
+
+        fields
+            .filter_map(|(index, field)| Some(matches(index, field)? * field.weight))
+            .max()
+    }
+}
+
+/// A whitespace-separated term of a query, matching when any of its `|`-separated
+/// alternatives match
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    alternatives: Vec<Pattern>,
+}
+
+impl Term {
+    fn parse(part: &str, warnings: &mut Vec<QueryWarning>) -> Term {
+        let mut alternatives: Vec<Pattern> = part
+            .split('|')
+            .filter(|alternative| !alternative.is_empty())
+            .map(|alternative| Pattern::parse_checked(alternative, warnings))
+            .collect();
+
+        // A bare separator like `|` or `||` is matched literally
+        if alternatives.is_empty() {
+            alternatives.push(Pattern::parse_checked(part, warnings));
+        } else if part.split('|').any(str::is_empty) {
+            warnings.push(QueryWarning::EmptyAlternative {
+                term: part.to_string(),
+            });
+        }
+
+        Term { alternatives }
+    }
+
+    /// The term with typo variants added to its fuzzy alternatives, which are kept
+    /// first so a match without a typo still wins
+    fn with_typos(&self, mode: MatchMode) -> Term {
+        let mut alternatives = self.alternatives.clone();
+        if mode == MatchMode::Fuzzy {
+            alternatives.extend(self.alternatives.iter().flat_map(Pattern::typo_variants));
+        }
+        Term { alternatives }
+    }
+
+    fn prepare(&mut self, folding: Folding) {
+        for pattern in &mut self.alternatives {
+            pattern.prepare(folding);
+        }
+    }
+
+    /// Whether the term can only remove items, never score them
+    fn is_exclusion(&self) -> bool {
+        self.alternatives.iter().all(|pattern| pattern.excluded)
+    }
+
+    /// Matches the best alternative against `text`, earlier alternatives winning ties
+    fn matches(&self, text: &str, mode: MatchMode, folding: Folding) -> Option<(Score, Vec<usize>)> {
+        self.alternatives
+            .iter()
+            .filter_map(|pattern| pattern.matches(text, mode, folding))
+            .fold(None, |best: Option<(Score, Vec<usize>)>, found| match best {
+                Some(best) if best.0 >= found.0 => Some(best),
+                _ => Some(found),
+            })
+    }
+
+    /// Matches the best alternative against the fields of a candidate
+    fn matches_candidate(
+        &self,
+        candidate: &Candidate,
+        prepared: Option<&[PreparedText]>,
+        mode: MatchMode,
+        folding: Folding,
+    ) -> Option<Score> {
+        self.alternatives
+            .iter()
+            .filter_map(|pattern| pattern.matches_candidate(candidate, prepared, mode, folding))
+            .max()
+    }
+}
+
+/// Part of a query which is matched differently than it likely was meant, reported by
+/// [`query_warnings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryWarning {
+    /// A `|` without an alternative on one side, like in `api|`, which is ignored
+    EmptyAlternative { term: String },
+    /// A prefix like `nmae:` which is no field scope, so the term is matched literally
+    UnknownScope { scope: String },
+    /// An `is:` or `source:` qualifier with an unknown value, matched literally
+    UnknownQualifier { qualifier: String },
+    /// A quote which does not open an exact term, like a lone `'` or the one in `cli'`,
+    /// matched literally
+    UnterminatedQuote { term: String },
+}
+
+impl QueryWarning {
+    /// The warning for a `prefix:rest` term whose prefix is not a field scope, if it
+    /// looks like one was meant
+    fn unknown_prefix(text: &str, prefix: &str, rest: &str) -> Option<QueryWarning> {
+        if matches!(prefix, "is" | "source") {
+            return Some(QueryWarning::UnknownQualifier {
+                qualifier: text.to_string(),
+            });
+        }
+
+        // Texts like `https://` or `c++:` are no attempt at a scope
+        let scope_like = !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_alphabetic());
+        (scope_like && !rest.is_empty() && !rest.starts_with('/')).then(|| {
+            QueryWarning::UnknownScope {
+                scope: prefix.to_string(),
+            }
+        })
+    }
+}
+
+impl fmt::Display for QueryWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryWarning::EmptyAlternative { term } => {
+                write!(f, "empty alternative in '{}' is ignored", term)
+            }
+            QueryWarning::UnknownScope { scope } => {
+                write!(f, "unknown field '{}:' is matched literally", scope)
+            }
+            QueryWarning::UnknownQualifier { qualifier } => {
+                write!(f, "unknown qualifier '{}' is matched literally", qualifier)
+            }
+            QueryWarning::UnterminatedQuote { term } => {
+                write!(f, "unterminated quote in '{}' is matched literally", term)
+            }
+        }
+    }
+}
+
+/// A query split into terms, with warnings about its syntax
+struct ParsedQuery {
+    terms: Vec<Term>,
+    warnings: Vec<QueryWarning>,
+}
+
+/// Splits a query on whitespace into terms, with exclusions first so items can be
+/// rejected before any scoring is done
+///
+/// `|` binds tighter than whitespace, so `api rust|go` means `api` and either `rust`
+/// or `go`.
+fn parse_query(query: &str) -> ParsedQuery {
+    let mut warnings = Vec::new();
+    let mut terms: Vec<Term> = query
+        .split_whitespace()
+        .map(|part| Term::parse(part, &mut warnings))
+        .collect();
+    terms.sort_by_key(|term| !term.is_exclusion());
+    ParsedQuery { terms, warnings }
+}
+
+/// Warnings about the syntax of the query in the order of its terms, like an unknown
+/// field scope or an empty `|` alternative
+///
+/// Warnings never keep a query from being filtered, the affected terms are matched on a
+/// best-effort basis. [`MatchMode::Regex`] has no term syntax and never warns.
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::filter::{self, MatchMode, QueryWarning};
+///
+/// let warnings = filter::query_warnings("api|", MatchMode::Fuzzy);
+/// assert_eq!(warnings, vec![QueryWarning::EmptyAlternative { term: "api|".to_string() }]);
+/// ```
+pub fn query_warnings(query: &str, mode: MatchMode) -> Vec<QueryWarning> {
+    if mode == MatchMode::Regex {
+        return Vec::new();
+    }
+    parse_query(query).warnings
+}
+
+fn prepare_terms(terms: &mut [Term], folding: Folding) {
+    for term in terms {
+        term.prepare(folding);
+    }
+}
+
+/// A query compiled once for matching any number of texts, the matching behind every
+/// filter function of this module
+///
+/// Only the match mode, case mode and normalization of the options apply to single
+/// texts, [`FilterOptions::typo_tolerance`] needs a list to find out that nothing
+/// matched.
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::filter::{FilterOptions, Matcher};
+///
+/// let matcher = Matcher::new("feat !wip", FilterOptions::default()).unwrap();
+/// let branches = ["main", "feature/login", "feature/search-wip", "fix/typo"];
+/// let kept: Vec<&str> = branches.into_iter().filter(|branch| matcher.matches(branch)).collect();
+/// assert_eq!(kept, vec!["feature/login"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    query: String,
+    options: FilterOptions,
+    compiled: Compiled,
+    warnings: Vec<QueryWarning>,
+}
+
+/// How a [`Matcher`] matches texts
+#[derive(Debug, Clone)]
+enum Compiled {
+    /// An empty query, which keeps every text unscored
+    Everything,
+    Regex(Regex),
+    Terms { terms: Vec<Term>, folding: Folding },
+}
+
+impl Matcher {
+    /// Compiles the query, failing when it is not a valid regular expression in
+    /// [`MatchMode::Regex`]
+    ///
+    /// Syntax which is still matched on a best-effort basis is reported by
+    /// [`Matcher::warnings`].
+    pub fn new(query: &str, options: FilterOptions) -> Result<Self, regex::Error> {
+        let trimmed = query.trim();
+        let mut warnings = Vec::new();
+        let compiled = if trimmed.is_empty() {
+            Compiled::Everything
+        } else if options.match_mode == MatchMode::Regex {
+            Compiled::Regex(compile_regex(trimmed, options.case_mode)?)
+        } else {
+            let folding = Folding {
+                case_sensitive: options.case_mode.is_sensitive(trimmed),
+                normalize: options.normalize,
+            };
+            let parsed = parse_query(trimmed);
+            let mut terms = parsed.terms;
+            prepare_terms(&mut terms, folding);
+            warnings = parsed.warnings;
+            Compiled::Terms { terms, folding }
+        };
+
+        Ok(Self {
+            query: query.to_string(),
+            options,
+            compiled,
+            warnings,
+        })
+    }
+
+    /// Whether the text matches the query
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::filter::{FilterOptions, Matcher};
+    ///
+    /// let matcher = Matcher::new("rsg", FilterOptions::default()).unwrap();
+    /// assert!(matcher.matches("repo-searcher-github"));
+    /// assert!(!matcher.matches("gitlab"));
+    /// ```
+    pub fn matches(&self, text: &str) -> bool {
+        self.score(text).is_some()
+    }
+
+    /// Score of the text, higher for better fuzzy matches, or `None` if it does not
+    /// match
+    ///
+    /// Exact and regex matches are not scored and always score 0.
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::filter::{FilterOptions, Matcher};
+    ///
+    /// let matcher = Matcher::new("api", FilterOptions::default()).unwrap();
+    /// let prefix = matcher.score("api-server").unwrap();
+    /// let scattered = matcher.score("a-pretty-icon").unwrap();
+    /// assert!(prefix > scattered);
+    /// assert_eq!(matcher.score("web"), None);
+    /// ```
+    pub fn score(&self, text: &str) -> Option<Score> {
+        self.match_text(text).map(|(score, _)| score)
+    }
+
+    /// Character indices of the matched characters in the text, sorted and without
+    /// duplicates, or `None` if it does not match
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::filter::{FilterOptions, Matcher};
+    ///
+    /// let matcher = Matcher::new("rs", FilterOptions::default()).unwrap();
+    /// assert_eq!(matcher.indices("repo-searcher"), Some(vec![0, 5]));
+    /// assert_eq!(matcher.indices("web"), None);
+    /// ```
+    pub fn indices(&self, text: &str) -> Option<Vec<usize>> {
+        self.match_text(text).map(|(_, positions)| positions)
+    }
+
+    /// Warnings about the syntax of the query, see [`query_warnings`]
+    pub fn warnings(&self) -> &[QueryWarning] {
+        &self.warnings
+    }
+
+    fn match_text(&self, text: &str) -> Option<(Score, Vec<usize>)> {
+        match &self.compiled {
+            Compiled::Everything => Some((0, Vec::new())),
+            Compiled::Regex(regex) => {
+                let found = regex.find(text)?;
+
+                // Convert the byte range of the match to character indices
+                let start = text[..found.start()].chars().count();
+                let length = found.as_str().chars().count();
+                Some((0, (start..start + length).collect()))
+            }
+            Compiled::Terms { terms, folding } => {
+                let mut score = 0;
+                let mut positions = Vec::new();
+                for term in terms {
+                    let (term_score, term_positions) =
+                        term.matches(text, self.options.match_mode, *folding)?;
+                    score += term_score;
+                    positions.extend(term_positions);
+                }
+
+                // A single term already yields sorted, unique positions
+                if terms.len() > 1 {
+                    positions.sort_unstable();
+                    positions.dedup();
+                }
+                Some((score, positions))
+            }
+        }
+    }
+
+    /// Score of a candidate, its best weighted field for every term plus its prior,
+    /// using the `prepared` texts of its fields when given
+    fn score_candidate(
+        &self,
+        candidate: &Candidate,
+        prepared: Option<&[PreparedText]>,
+    ) -> Option<Score> {
+        match &self.compiled {
+            Compiled::Everything => Some(0),
+            Compiled::Regex(regex) => {
+                candidate.fields.iter().any(|field| regex.is_match(&field.text)).then_some(0)
+            }
+            Compiled::Terms { terms, folding } => {
+                let mode = self.options.match_mode;
+                let mut score = candidate.prior_score(self.options.prior_weight);
+                for term in terms {
+                    score += term.matches_candidate(candidate, prepared, mode, *folding)?;
+                }
+                Some(score)
+            }
+        }
+    }
+
+    /// The folding of texts the terms are matched against, `None` for an empty query or
+    /// a regular expression
+    fn folding(&self) -> Option<Folding> {
+        match &self.compiled {
+            Compiled::Terms { folding, .. } => Some(*folding),
+            _ => None,
+        }
+    }
+
+    /// The matcher to retry with when nothing matched, if typos are tolerated
+    fn with_typos(&self) -> Option<Matcher> {
+        let Compiled::Terms { terms, folding } = &self.compiled else {
+            return None;
+        };
+        if !self.options.typo_tolerance {
+            return None;
+        }
+
+        let mut terms: Vec<Term> =
+            terms.iter().map(|term| term.with_typos(self.options.match_mode)).collect();
+        prepare_terms(&mut terms, *folding);
+        Some(Matcher {
+            compiled: Compiled::Terms {
+                terms,
+                folding: *folding,
+            },
+            ..self.clone()
+        })
+    }
+}
+
+/// Compiles the query as a regular expression, with case sensitivity following the
+/// case mode
+fn compile_regex(query: &str, case_mode: CaseMode) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(query)
+        .case_insensitive(!case_mode.is_sensitive(query))
+        .build()
+}
+
+fn try_filter_matches<'a, T, S, F>(
+    items: &'a [T],
+    query: &str,
+    options: FilterOptions,
+    key: F,
+) -> Result<Vec<Matched>, regex::Error>
+where
+    S: AsRef<str>,
+    F: Fn(&'a T) -> S,
+{
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let matcher = Matcher::new(query, options)?;
+    let mut result = matcher_matches(items, &matcher, &key);
+    if result.is_empty() {
+        if let Some(matcher) = matcher.with_typos() {
+            result = matcher_matches(items, &matcher, &key);
+        }
+    }
+
+    rank(
+        &mut result,
+        options.tie_break,
+        |matched| matched.score,
+        |matched| key(&items[matched.index]),
+    );
+    if let Some(limit) = options.limit {
+        result.truncate(limit);
+    }
+    Ok(result)
+}
+
+/// Sorts matches by score descending, ordering equal scores by the tie-break
+fn rank<M, S: AsRef<str>>(
+    matches: &mut [M],
+    tie_break: TieBreak,
+    score: impl Fn(&M) -> Score,
+    text: impl Fn(&M) -> S,
+) {
+    // Stable sorts, so items equal in score and tie-break keep their input order
+    match tie_break {
+        TieBreak::Input => matches.sort_by_key(|matched| std::cmp::Reverse(score(matched))),
+        TieBreak::Alphabetical => matches.sort_by_cached_key(|matched| {
+            (std::cmp::Reverse(score(matched)), text(matched).as_ref().to_lowercase())
+        }),
+        TieBreak::Shortest => matches.sort_by_cached_key(|matched| {
+            (std::cmp::Reverse(score(matched)), text(matched).as_ref().chars().count())
+        }),
+    }
+}
+
+/// Filter candidates by query, scoring each term by its best weighted field.
+///
+/// Returns the indices of the matching candidates with their scores, ranked like
+/// [`filter_scored`] with the first field used for tie-breaks. A term has to match
+/// any field, an excluded term must not be contained in any field.
+pub fn filter_candidates(
+    candidates: &[Candidate],
+    query: &str,
+    options: FilterOptions,
+) -> Vec<(usize, Score)> {
+    try_filter_candidates(candidates, query, options).unwrap_or_default()
+}
+
+/// Filter candidates like [`filter_candidates`], failing when the query is not a valid
+/// regular expression in [`MatchMode::Regex`].
+pub fn try_filter_candidates(
+    candidates: &[Candidate],
+    query: &str,
+    options: FilterOptions,
+) -> Result<Vec<(usize, Score)>, regex::Error> {
+    let matcher = Matcher::new(query, options)?;
+    let indices = (0..candidates.len()).collect();
+    let (mut result, _) = candidate_matches(candidates, None, indices, &matcher);
+    rank_candidates(candidates, &mut result, options.tie_break, options.limit);
+    Ok(result)
+}
+
+/// Matches the candidates at `indices` in that order, unranked, and whether the matches
+/// needed typo tolerance
+///
+/// The `prepared` texts are used when they were folded the way the query is matched.
+fn candidate_matches(
+    candidates: &[Candidate],
+    prepared: Option<&PreparedCandidates>,
+    indices: Vec<usize>,
+    matcher: &Matcher,
+) -> (Vec<(usize, Score)>, bool) {
+    candidate_matches_with(candidates, prepared, indices, matcher, PARALLEL_THRESHOLD)
+}
+
+fn candidate_matches_with(
+    candidates: &[Candidate],
+    prepared: Option<&PreparedCandidates>,
+    indices: Vec<usize>,
+    matcher: &Matcher,
+    parallel_threshold: usize,
+) -> (Vec<(usize, Score)>, bool) {
+    if let Compiled::Everything = matcher.compiled {
+        return (indices.into_iter().map(|index| (index, 0)).collect(), false);
+    }
+
+    let prepared = prepared.filter(|prepared| {
+        prepared.folding.is_some() && prepared.folding == matcher.folding()
+    });
+    let score = |matcher: &Matcher, indices: &[usize]| {
+        select_candidates(candidates, indices, parallel_threshold, |index, candidate| {
+            let fields = prepared.map(|prepared| &prepared.candidates[index][..]);
+            matcher.score_candidate(candidate, fields)
+        })
+    };
+
+    let result = score(matcher, &indices);
+    if !result.is_empty() {
+        return (result, false);
+    }
+    let Some(matcher) = matcher.with_typos() else {
+        return (result, false);
+    };
+
+    // Every candidate is retried, as the indices may only hold the strict matches of a
+    // shorter query
+    let all: Vec<usize> = (0..candidates.len()).collect();
+    let result = score(&matcher, &all);
+    let typo_fallback = !result.is_empty();
+    (result, typo_fallback)
+}
+
+/// Scores the candidates at `indices`, spread over threads for long lists
+fn select_candidates<F>(
+    candidates: &[Candidate],
+    indices: &[usize],
+    parallel_threshold: usize,
+    score: F,
+) -> Vec<(usize, Score)>
+where
+    F: Fn(usize, &Candidate) -> Option<Score> + Sync,
+{
+    // Both collect in index order, so ranking the results gives the same order
+    let matched = |&index: &usize| Some((index, score(index, &candidates[index])?));
+    if indices.len() >= parallel_threshold {
+        indices.par_iter().filter_map(matched).collect()
+    } else {
+        indices.iter().filter_map(matched).collect()
+    }
+}
+
+/// Ranks candidate matches like [`rank`], keeping only the best `limit` of them
+fn rank_candidates(
+    candidates: &[Candidate],
+    matches: &mut Vec<(usize, Score)>,
+    tie_break: TieBreak,
+    limit: Option<usize>,
+) {
+    if let Some(limit) = limit.filter(|&limit| limit < matches.len()) {
+        // Matches are in index order, so comparing the indices last orders them like
+        // the stable sort and makes the selection agree with it
+        let compare = |a: &(usize, Score), b: &(usize, Score)| {
+            b.1.cmp(&a.1)
+                .then_with(|| {
+                    let primary_text = |index: usize| candidates[index].primary_text();
+                    tie_break.compare(primary_text(a.0), primary_text(b.0))
+                })
+                .then(a.0.cmp(&b.0))
+        };
+        if limit > 0 {
+            matches.select_nth_unstable_by(limit - 1, compare);
+        }
+        matches.truncate(limit);
+        matches.sort_unstable_by(compare);
+        return;
+    }
+
+    rank(
+        matches,
+        tie_break,
+        |&(_, score)| score,
+        |&(index, _)| candidates[index].primary_text(),
+    );
+}
+
+/// Filters candidates like [`try_filter_candidates`], remembering the last query so
+/// that extending it, as happens while typing, only re-checks the previous matches.
+///
+/// Any other change, like deleting characters, editing a negated or `|` term or
+/// switching options, falls back to a full scan. The candidate texts are folded once
+/// and kept for case insensitive queries, so call [`IncrementalFilter::reset`] whenever
+/// the candidates change, or [`IncrementalFilter::candidates_appended`] when they were
+/// only added to.
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalFilter {
+    query: String,
+    options: FilterOptions,
+    /// Ascending indices of the candidates matched by `query`, `None` before a scan
+    matched: Option<Vec<usize>>,
+    candidate_count: usize,
+    typo_fallback: bool,
+    match_count: usize,
+    prepared: PreparedCandidates,
+}
+
+impl IncrementalFilter {
+    /// A filter without a previous query, so its first call scans every candidate
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the previous matches and prepared texts, so the next call scans every
+    /// candidate
+    pub fn reset(&mut self) {
+        self.matched = None;
+        self.prepared.clear();
+    }
+
+    /// Forgets the previous matches after candidates were added to the end, keeping the
+    /// prepared texts of the existing ones
+    pub fn candidates_appended(&mut self) {
+        self.matched = None;
+    }
+
+    /// Whether the last results only matched with typos, because nothing matched the
+    /// query as typed with [`FilterOptions::typo_tolerance`] enabled
+    pub fn typo_fallback(&self) -> bool {
+        self.typo_fallback
+    }
+
+    /// Number of candidates the last query matched, including those cut off by
+    /// [`FilterOptions::limit`]
+    pub fn match_count(&self) -> usize {
+        self.match_count
+    }
+
+    /// Filters candidates like [`try_filter_candidates`], returning the indices of the
+    /// matches with their scores, best first and cut off at [`FilterOptions::limit`]
+    ///
+    /// Only the previous matches are checked again when the query extends the previous
+    /// one so that it can't match anything more, with the same candidates, the same
+    /// options apart from the limit and previous results that weren't typo matches.
+    /// Fails when the query is not a valid regular expression in [`MatchMode::Regex`].
+    pub fn filter(
+        &mut self,
+        candidates: &[Candidate],
+        query: &str,
+        options: FilterOptions,
+    ) -> Result<Vec<(usize, Score)>, regex::Error> {
+        let matcher = Matcher::new(query, options)?;
+        Ok(self.filter_with(candidates, &matcher))
+    }
+
+    /// Filters like [`IncrementalFilter::filter`] with an already compiled query
+    pub fn filter_with(
+        &mut self,
+        candidates: &[Candidate],
+        matcher: &Matcher,
+    ) -> Vec<(usize, Score)> {
+        let (query, options) = (matcher.query.as_str(), matcher.options);
+
+        // Typo matches of a shorter query can miss typo matches of a longer one
+        let previous = self.matched.take().filter(|_| {
+            // The limit only cuts off the ranking, all matches are remembered
+            !self.typo_fallback
+                && self.options.limit_removed() == options.limit_removed()
+                && self.candidate_count == candidates.len()
+                && narrows(&self.query, query, options.match_mode)
+        });
+
+        // Case sensitive queries are rare, their texts are still folded on every call
+        let folding = matcher.folding().filter(|folding| !folding.case_sensitive);
+        let prepared = folding.map(|folding| {
+            self.prepared.update(candidates, folding);
+            &self.prepared
+        });
+
+        let indices = previous.unwrap_or_else(|| (0..candidates.len()).collect());
+        let (mut result, typo_fallback) = candidate_matches(candidates, prepared, indices, matcher);
+
+        let mut matched: Vec<usize> = result.iter().map(|&(index, _)| index).collect();
+        matched.sort_unstable();
+        self.matched = Some(matched);
+        self.query = query.to_string();
+        self.options = options;
+        self.candidate_count = candidates.len();
+        self.typo_fallback = typo_fallback;
+        self.match_count = result.len();
+
+        rank_candidates(candidates, &mut result, options.tie_break, options.limit);
+        result
+    }
+}
+
+/// Whether every item matching `query` also matches `previous`, so only the previous
+/// matches have to be checked again
+fn narrows(previous: &str, query: &str, mode: MatchMode) -> bool {
+    // Regular expressions can match more when extended, like `a` to `a|b`
+    if mode == MatchMode::Regex || previous.trim().is_empty() {
+        return false;
+    }
+    let Some(added) = query.strip_prefix(previous) else {
+        return false;
+    };
+
+    // Further terms only remove items, as every term has to match
+    if previous.ends_with(char::is_whitespace) {
+        return true;
+    }
+
+    // Otherwise the last term grows and has to stay the same kind of pattern
+    let last = previous.split_whitespace().next_back().unwrap_or_default();
+    let grown = format!("{}{}", last, added.split_whitespace().next().unwrap_or_default());
+    if last.contains('|') || grown.contains('|') {
+        return false;
+    }
+    let (before, after) = (Pattern::parse(last), Pattern::parse(&grown));
+    !before.excluded
+        && !after.excluded
+        && before.qualifier.is_none()
+        && after.qualifier.is_none()
+        && before.scope == after.scope
+        && before.exact == after.exact
+        && after.text.starts_with(&before.text)
+        && (!before.anchors.start || after.anchors.start)
+        && (!before.anchors.end || after.anchors.end)
+        && (!before.anchors.word_boundary || after.anchors.word_boundary)
+}
+
+/// Keeps the items the matcher matches
+fn matcher_matches<'a, T, S, F>(items: &'a [T], matcher: &Matcher, key: F) -> Vec<Matched>
+where
+    S: AsRef<str>,
+    F: Fn(&'a T) -> S,
+{
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let (score, positions) = matcher.match_text(key(item).as_ref())?;
+            Some(Matched {
+                index,
+                score,
+                positions,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_empty_list() {
+        let items: Vec<String> = vec![];
+        let result = filter_human(&items, "query", |s| s.clone());
+        assert_eq!(result.len(), 0);
+    }
+
+    #[test]
+    fn test_empty_query() {
+        let items = vec!["apple", "banana", "cherry"];
+        let result = filter_human(&items, "", |s| s.to_string());
+        assert_eq!(result, items);
+    }
+
+    #[test]
+    fn test_simple_filter() {
+        let items = vec!["apple", "banana", "cherry"];
+        let result = filter_human(&items, "a", |s| s.to_string());
+        assert_eq!(result, vec!["apple", "banana"]);
+    }
+
+    #[test]
+    fn test_multiple_terms() {
+        let items = vec!["apple pie", "banana split", "cherry pie"];
+        let result = filter_human(&items, "pie a", |s| s.to_string());
+        assert_eq!(result, vec!["apple pie"]);
+    }
+
+    #[test]
+    fn test_exclusion() {
+        let items = vec!["apple pie", "banana split", "cherry pie"];
+        let result = filter_human(&items, "pie -cherry", |s| s.to_string());
+        assert_eq!(result, vec!["apple pie"]);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let items = vec!["Apple", "Banana", "Cherry"];
+        let result = filter_human(&items, "apple", |s| s.to_string());
+        assert_eq!(result, vec!["Apple"]);
+    }
+
+    #[test]
+    fn test_medical_medium_exclusion() {
+        let items = vec![
+            "medicalmedium-instagram (git@github.com:Dima-369/medicalmedium-instagram.git)",
+            "medical-medium-text-files (git@github.com:Dima-369/medical-medium-text-files.git)",
+            "medical-medium-demon-podcast-notes (git@github.com:Dima-369/medical-medium-demon-podcast-notes.git)"
+        ];
+
+        // Test with "medical -demon" to exclude demon podcast notes
+        let result = filter_human(&items, "medical -demon", |s| s.to_string());
+        assert_eq!(result, vec![
+            "medicalmedium-instagram (git@github.com:Dima-369/medicalmedium-instagram.git)",
+            "medical-medium-text-files (git@github.com:Dima-369/medical-medium-text-files.git)"
+        ]);
+    }
+
+    #[test]
+    fn test_exact_mode_keeps_substring_behavior() {
+        let items = vec!["repo-searcher", "rsrch"];
+        let result = filter_with_mode(&items, "rsrch", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["rsrch"]);
+    }
+
+    #[test]
+    fn test_fuzzy_subsequence() {
+        let items = vec!["repo-searcher", "go-microservices", "rust-web-server"];
+        let result = filter_human(&items, "rsrch", |s| s.to_string());
+        assert_eq!(result, vec!["repo-searcher"]);
+    }
+
+    #[test]
+    fn test_fuzzy_requires_order() {
+        let items = vec!["abc"];
+        assert!(filter_human(&items, "cba", |s| s.to_string()).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_word_boundary_ranks_higher() {
+        let items = vec!["formatter-internal", "fuzzy_finder"];
+        let result = filter_human(&items, "fin", |s| s.to_string());
+        assert_eq!(result, vec!["fuzzy_finder", "formatter-internal"]);
+    }
+
+    #[test]
+    fn test_fuzzy_consecutive_ranks_higher() {
+        let items = vec!["a-p-i-tools", "api-tools"];
+        let result = filter_human(&items, "api", |s| s.to_string());
+        assert_eq!(result, vec!["api-tools", "a-p-i-tools"]);
+    }
+
+    #[test]
+    fn test_fuzzy_gaps_are_penalized() {
+        let items = vec!["axxxxxxb", "axb"];
+        let result = filter_human(&items, "ab", |s| s.to_string());
+        assert_eq!(result, vec!["axb", "axxxxxxb"]);
+    }
+
+    #[test]
+    fn test_fuzzy_camel_case_boundary() {
+        let items = vec!["homepage", "HomePage"];
+        let result = filter_human(&items, "hp", |s| s.to_string());
+        assert_eq!(result, vec!["HomePage", "homepage"]);
+    }
+
+    #[test]
+    fn test_fuzzy_prefix_ranks_above_middle() {
+        let items = vec!["my-api", "api-server"];
+        let result = filter_human(&items, "api", |s| s.to_string());
+        assert_eq!(result, vec!["api-server", "my-api"]);
+    }
+
+    #[test]
+    fn test_fuzzy_equal_scores_keep_input_order() {
+        let items = vec!["web-one", "web-two", "web-six"];
+        let result = filter_human(&items, "web", |s| s.to_string());
+        assert_eq!(result, items);
+    }
+
+    #[test]
+    fn test_fuzzy_match_positions() {
+        let (_, positions) = fuzzy_match("fuzzy_finder", "fin").unwrap();
+        assert_eq!(positions, vec![6, 7, 8]);
+    }
+
+    #[test]
+    fn test_fuzzy_exclusion_is_exact() {
+        let items = vec!["demo-app", "d-e-m-o"];
+        let result = filter_human(&items, "d -demo", |s| s.to_string());
+        assert_eq!(result, vec!["d-e-m-o"]);
+    }
+
+    #[test]
+    fn test_positions_repeated_characters() {
+        let items = vec!["foo-tool"];
+        let result = filter_with_positions(&items, "oo", |s| s.to_string());
+        assert_eq!(result, vec![("foo-tool", vec![1, 2])]);
+
+        let result = filter_with_positions(&items, "too", |s| s.to_string());
+        assert_eq!(result, vec![("foo-tool", vec![4, 5, 6])]);
+
+        let result = filter_with_positions(&items, "ol", |s| s.to_string());
+        assert_eq!(result, vec![("foo-tool", vec![6, 7])]);
+    }
+
+    #[test]
+    fn test_positions_overlapping_terms() {
+        let items = vec!["foo-tool"];
+        let result = filter_with_positions(&items, "foo oo", |s| s.to_string());
+        assert_eq!(result, vec![("foo-tool", vec![0, 1, 2])]);
+    }
+
+    #[test]
+    fn test_positions_are_character_indices() {
+        let items = vec!["日本語-tool"];
+        let result = filter_with_positions(&items, "tl", |s| s.to_string());
+        assert_eq!(result, vec![("日本語-tool", vec![4, 7])]);
+    }
+
+    #[test]
+    fn test_positions_empty_query() {
+        let items = vec!["foo"];
+        let result = filter_with_positions(&items, "", |s| s.to_string());
+        assert_eq!(result, vec![("foo", Vec::new())]);
+    }
+
+    #[test]
+    fn test_smart_case_lowercase_query_ignores_case() {
+        let items = vec!["MyProject", "myproject", "other"];
+        let result = filter_human(&items, "myproject", |s| s.to_string());
+        assert_eq!(result, vec!["MyProject", "myproject"]);
+    }
+
+    #[test]
+    fn test_smart_case_uppercase_query_matches_case() {
+        let items = vec!["MyProject", "myproject"];
+        let result = filter_human(&items, "MyP", |s| s.to_string());
+        assert_eq!(result, vec!["MyProject"]);
+    }
+
+    #[test]
+    fn test_case_modes() {
+        let items = vec!["MyProject", "myproject"];
+        let with_case = |case_mode| {
+            let options = FilterOptions {
+                case_mode,
+                ..FilterOptions::default()
+            };
+            filter_scored(&items, "MyP", options, |s| s.to_string())
+                .into_iter()
+                .map(|(item, _)| item)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(with_case(CaseMode::Insensitive), vec!["MyProject", "myproject"]);
+        assert_eq!(with_case(CaseMode::Sensitive), vec!["MyProject"]);
+
+        let options = FilterOptions {
+            case_mode: CaseMode::Sensitive,
+            ..FilterOptions::default()
+        };
+        assert!(filter_scored(&items, "myp", options, |s| s.to_string()).len() == 1);
+    }
+
+    #[test]
+    fn test_smart_case_non_ascii() {
+        let items = vec!["Élan", "élan"];
+        assert_eq!(filter_human(&items, "élan", |s| s.to_string()), vec!["Élan", "élan"]);
+        assert_eq!(filter_human(&items, "Él", |s| s.to_string()), vec!["Élan"]);
+        assert_eq!(filter_with_mode(&items, "É", MatchMode::Exact, |s| s.to_string()), vec!["Élan"]);
+    }
+
+    #[test]
+    fn test_smart_case_applies_to_exclusions() {
+        let items = vec!["Demo-App", "demo-lib"];
+        let result = filter_human(&items, "De -App", |s| s.to_string());
+        assert!(result.is_empty());
+        let result = filter_human(&items, "d -app", |s| s.to_string());
+        assert_eq!(result, vec!["demo-lib"]);
+    }
+
+    #[test]
+    fn test_terms_match_in_any_order() {
+        let items = vec!["rust-api", "api-in-rust", "rust-cli", "go-api"];
+        let result = filter_human(&items, "api rust", |s| s.to_string());
+        assert_eq!(result.len(), 2);
+        assert!(result.contains(&"rust-api") && result.contains(&"api-in-rust"));
+    }
+
+    #[test]
+    fn test_terms_ignore_repeated_whitespace() {
+        let items = vec!["rust-api", "go-api"];
+        let result = filter_with_mode(&items, "  api \t  rust ", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["rust-api"]);
+    }
+
+    #[test]
+    fn test_term_scores_are_summed() {
+        let items = vec!["rust-api"];
+        let single = filter_scored(&items, "rust", FilterOptions::default(), |s| s.to_string());
+        let both = filter_scored(&items, "rust api", FilterOptions::default(), |s| s.to_string());
+        let (api, _) = fuzzy_match("rust-api", "api").unwrap();
+        assert_eq!(both[0].1, single[0].1 + api);
+    }
+
+    #[test]
+    fn test_parse_query_puts_exclusions_first() {
+        let terms = parse_query("api -fork  -").terms;
+        let texts: Vec<(&str, bool)> = terms
+            .iter()
+            .map(|term| (term.alternatives[0].text.as_str(), term.alternatives[0].excluded))
+            .collect();
+        assert_eq!(texts, vec![("fork", true), ("api", false), ("-", false)]);
+    }
+
+    #[test]
+    fn test_matcher_agrees_with_list_filters() {
+        let items = vec!["api-server", "web-api", "rapid", "web", "API"];
+        for query in ["api", "ap !serv", "web|rap", "'api'", "API", ""] {
+            let matcher = Matcher::new(query, FilterOptions::default()).unwrap();
+            let scored = filter_scored(&items, query, FilterOptions::default(), |s| s.to_string());
+            let positioned = filter_with_positions(&items, query, |s| s.to_string());
+
+            for (item, positions) in positioned {
+                assert_eq!(matcher.indices(item), Some(positions), "query {:?}", query);
+            }
+            for item in &items {
+                let score = scored.iter().find(|(scored, _)| scored == item).map(|m| m.1);
+                assert_eq!(matcher.score(item), score, "query {:?} on {:?}", query, item);
+                assert_eq!(matcher.matches(item), score.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn test_matcher_modes() {
+        let exact = FilterOptions {
+            match_mode: MatchMode::Exact,
+            ..FilterOptions::default()
+        };
+        let matcher = Matcher::new("api", exact).unwrap();
+        assert_eq!(matcher.indices("web-api"), Some(vec![4, 5, 6]));
+        assert_eq!(matcher.score("a-p-i"), None);
+
+        let matcher = Matcher::new(r"v\d+", regex_options()).unwrap();
+        assert_eq!(matcher.indices("café-v12"), Some(vec![5, 6, 7]));
+        assert!(matcher.warnings().is_empty());
+        assert!(Matcher::new("(", regex_options()).is_err());
+
+        let matcher = Matcher::new("api|", FilterOptions::default()).unwrap();
+        assert!(matcher.matches("api"));
+        assert_eq!(matcher.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_query_warnings() {
+        let warnings = |query: &str| query_warnings(query, MatchMode::Fuzzy);
+        assert_eq!(warnings("api owner:dima 'cli rs$ ^web is:fork"), vec![]);
+        assert_eq!(
+            warnings("rust| |go"),
+            vec![
+                QueryWarning::EmptyAlternative { term: "rust|".to_string() },
+                QueryWarning::EmptyAlternative { term: "|go".to_string() },
+            ]
+        );
+        assert_eq!(
+            warnings("nmae:api -is:forked"),
+            vec![
+                QueryWarning::UnknownScope { scope: "nmae".to_string() },
+                QueryWarning::UnknownQualifier { qualifier: "is:forked".to_string() },
+            ]
+        );
+        assert_eq!(
+            warnings("' cli'"),
+            vec![
+                QueryWarning::UnterminatedQuote { term: "'".to_string() },
+                QueryWarning::UnterminatedQuote { term: "cli'".to_string() },
+            ]
+        );
+        // Literal texts which only look like syntax
+        assert_eq!(warnings("https://gitlab.com c++: | don't"), vec![]);
+        assert_eq!(query_warnings("api|", MatchMode::Regex), vec![]);
+    }
+
+    #[test]
+    fn test_warned_queries_still_filter() {
+        let items = vec!["api", "web", "nmae:api"];
+        assert_eq!(filter_human(&items, "api|", |s| s.to_string()), vec!["api", "nmae:api"]);
+        assert_eq!(filter_human(&items, "nmae:", |s| s.to_string()), vec!["nmae:api"]);
+    }
+
+    #[test]
+    fn test_bang_negation() {
+        let items = vec!["api-client", "api-client-fork", "web-fork"];
+        let result = filter_human(&items, "api !fork", |s| s.to_string());
+        assert_eq!(result, vec!["api-client"]);
+
+        let result = filter_human(&items, "!fork", |s| s.to_string());
+        assert_eq!(result, vec!["api-client"]);
+    }
+
+    #[test]
+    fn test_bare_bang_is_literal() {
+        let items = vec!["wow!", "plain"];
+        let result = filter_human(&items, "!", |s| s.to_string());
+        assert_eq!(result, vec!["wow!"]);
+    }
+
+    #[test]
+    fn test_bang_and_minus_negation_combined() {
+        let items = vec!["rust-api", "rust-api-demo", "rust-api-fork", "go-api"];
+        let result = filter_human(&items, "api !fork -demo rust", |s| s.to_string());
+        assert_eq!(result, vec!["rust-api"]);
+    }
+
+    #[test]
+    fn test_or_group() {
+        let items = vec!["tool [GH]", "tool [GL]", "tool [BB]"];
+        let result = filter_with_mode(&items, "gh|gl", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["tool [GH]", "tool [GL]"]);
+    }
+
+    #[test]
+    fn test_or_group_with_and_terms() {
+        let items = vec!["api-rust", "api-go", "api-java", "rust-cli"];
+        let result = filter_with_mode(&items, "api rust|go", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["api-rust", "api-go"]);
+    }
+
+    #[test]
+    fn test_or_group_scores_best_branch() {
+        let items = vec!["xgxo", "go-tool"];
+        let result = filter_scored(&items, "zzz|go", FilterOptions::default(), |s| s.to_string());
+        let (go, _) = fuzzy_match("go-tool", "go").unwrap();
+        assert_eq!(result[0], ("go-tool", go));
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_or_group_with_negation() {
+        let items = vec!["api-rust", "api-rust-fork", "api-go-fork", "api-java"];
+        let result = filter_with_mode(&items, "api rust|!fork", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["api-rust", "api-rust-fork", "api-java"]);
+
+        // Only items containing both negated patterns fail the group
+        let result = filter_with_mode(&items, "api !go|!fork", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["api-rust", "api-rust-fork", "api-java"]);
+    }
+
+    #[test]
+    fn test_bare_or_separator_is_literal() {
+        let items = vec!["a|b", "ab"];
+        let result = filter_with_mode(&items, "|", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["a|b"]);
+    }
+
+    #[test]
+    fn test_quoted_term_is_exact() {
+        let items = vec!["c-l-i", "clippy-config", "my-cli-tool"];
+        let result = filter_human(&items, "'cli", |s| s.to_string());
+        assert_eq!(result, vec!["clippy-config", "my-cli-tool"]);
+    }
+
+    #[test]
+    fn test_quoted_term_word_boundary() {
+        let items = vec!["clippy-config", "my-cli-tool", "cli"];
+        let result = filter_human(&items, "'cli'", |s| s.to_string());
+        assert_eq!(result, vec!["my-cli-tool", "cli"]);
+    }
+
+    #[test]
+    fn test_lone_quote_is_literal() {
+        let items = vec!["it's", "its"];
+        let result = filter_human(&items, "'", |s| s.to_string());
+        assert_eq!(result, vec!["it's"]);
+    }
+
+    #[test]
+    fn test_quoted_terms_with_or_and_negation() {
+        let items = vec!["rust-cli", "rust-clippy", "go-cli-fork", "go-cli", "c-l-i-go"];
+        let result = filter_human(&items, "'cli' rust|go !fork", |s| s.to_string());
+        assert_eq!(result, vec!["rust-cli", "go-cli"]);
+
+        let result = filter_human(&items, "'clippy|'cli' !'fork", |s| s.to_string());
+        assert_eq!(result, vec!["rust-cli", "rust-clippy", "go-cli"]);
+    }
+
+    #[test]
+    fn test_prefix_anchor() {
+        let items = vec!["repo-searcher", "my-repo", "r-e-p-o-tool"];
+        let result = filter_with_mode(&items, "^repo", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["repo-searcher"]);
+
+        // Fuzzy anchored terms only need their first character at the start
+        let result = filter_human(&items, "^repo", |s| s.to_string());
+        assert_eq!(result, vec!["repo-searcher", "r-e-p-o-tool"]);
+    }
+
+    #[test]
+    fn test_suffix_anchor() {
+        let items = vec!["tool.rs", "rs-tool", "rust-bindings"];
+        let result = filter_with_mode(&items, "rs$", MatchMode::Exact, |s| s.to_string());
+        assert_eq!(result, vec!["tool.rs"]);
+
+        let result = filter_human(&items, "rs$", |s| s.to_string());
+        assert_eq!(result, vec!["tool.rs", "rust-bindings"]);
+    }
+
+    #[test]
+    fn test_anchors_on_quoted_term() {
+        let items = vec!["c-l-i", "cli-tool", "my-cli"];
+        assert_eq!(filter_human(&items, "'^cli", |s| s.to_string()), vec!["cli-tool"]);
+        assert_eq!(filter_human(&items, "'cli$", |s| s.to_string()), vec!["my-cli"]);
+        assert_eq!(filter_human(&items, "^cli$", |s| s.to_string()), vec!["c-l-i"]);
+    }
+
+    #[test]
+    fn test_negated_anchor() {
+        let items = vec!["fork-of-api", "api-fork-notes", "api"];
+        let result = filter_human(&items, "api !^fork-", |s| s.to_string());
+        assert_eq!(result.len(), 2);
+        assert!(!result.contains(&"fork-of-api"));
+
+        let result = filter_human(&items, "!notes$ !^fork", |s| s.to_string());
+        assert_eq!(result, vec!["api"]);
+    }
+
+    #[test]
+    fn test_lone_anchors_are_literal() {
+        let items = vec!["a^b", "cost$", "plain"];
+        assert_eq!(filter_human(&items, "^", |s| s.to_string()), vec!["a^b"]);
+        assert_eq!(filter_human(&items, "$", |s| s.to_string()), vec!["cost$"]);
+    }
+
+    fn regex_options() -> FilterOptions {
+        FilterOptions {
+            match_mode: MatchMode::Regex,
+            ..FilterOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_regex_mode() {
+        let items = vec!["api-v1", "api-v22", "web"];
+        let result = try_filter_scored(&items, r"v\d$", regex_options(), |s| s.to_string()).unwrap();
+        assert_eq!(result, vec![("api-v1", 0)]);
+
+        let result = try_filter_scored(&items, "^(api|web)", regex_options(), |s| s.to_string()).unwrap();
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn test_regex_invalid_pattern() {
+        let items = vec!["api"];
+        assert!(try_filter_scored(&items, "api(", regex_options(), |s| s.to_string()).is_err());
+        assert!(filter_scored(&items, "[a-", regex_options(), |s| s.to_string()).is_empty());
+    }
+
+    #[test]
+    fn test_regex_follows_case_mode() {
+        let items = vec!["MyProject", "myproject"];
+        let result = filter_scored(&items, "^my", regex_options(), |s| s.to_string());
+        assert_eq!(result.len(), 2);
+        let result = filter_scored(&items, "^My", regex_options(), |s| s.to_string());
+        assert_eq!(result, vec![("MyProject", 0)]);
+    }
+
+    #[test]
+    fn test_regex_unicode_classes() {
+        let items = vec!["λ-calculus", "lambda", "日本語-notes"];
+        let result = filter_scored(&items, r"^\p{Greek}", regex_options(), |s| s.to_string());
+        assert_eq!(result, vec![("λ-calculus", 0)]);
+        let result = filter_scored(&items, r"\p{Han}+", regex_options(), |s| s.to_string());
+        assert_eq!(result, vec![("日本語-notes", 0)]);
+    }
+
+    #[test]
+    fn test_regex_positions_are_character_indices() {
+        let items = vec!["日本語-notes"];
+        let matches = try_filter_matches(&items, "no", regex_options(), |s| s.to_string()).unwrap();
+        assert_eq!(matches[0].positions, vec![4, 5]);
+    }
+
+    fn with_tie_break(items: &[&'static str], query: &str, tie_break: TieBreak) -> Vec<&'static str> {
+        let options = FilterOptions {
+            tie_break,
+            ..FilterOptions::default()
+        };
+        filter_scored(items, query, options, |s| s.to_string())
+            .into_iter()
+            .map(|(item, _)| item)
+            .collect()
+    }
+
+    #[test]
+    fn test_tie_break_input_order_is_stable() {
+        let items = ["web-c", "web-a", "web-b", "web-a"];
+        assert_eq!(with_tie_break(&items, "web", TieBreak::Input), items);
+    }
+
+    #[test]
+    fn test_tie_break_alphabetical() {
+        let items = ["web-c", "Web-a", "web-b"];
+        assert_eq!(with_tie_break(&items, "web", TieBreak::Alphabetical), ["Web-a", "web-b", "web-c"]);
+    }
+
+    #[test]
+    fn test_tie_break_shortest() {
+        // Equal lengths keep their input order
+        let items = ["web-long", "web-b", "web-a"];
+        assert_eq!(with_tie_break(&items, "web", TieBreak::Shortest), ["web-b", "web-a", "web-long"]);
+    }
+
+    #[test]
+    fn test_tie_break_only_applies_to_equal_scores() {
+        let items = ["a-web", "web-zzz"];
+        assert_eq!(with_tie_break(&items, "web", TieBreak::Alphabetical), ["web-zzz", "a-web"]);
+    }
+
+    #[test]
+    fn test_tie_break_compare() {
+        assert_eq!(TieBreak::Input.compare("b", "a"), std::cmp::Ordering::Equal);
+        assert_eq!(TieBreak::Alphabetical.compare("B", "a"), std::cmp::Ordering::Greater);
+        assert_eq!(TieBreak::Shortest.compare("ab", "c"), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn test_score_positions_agrees_with_matcher() {
+        for (text, pattern) in [("fuzzy_finder", "fin"), ("axxxxxxb", "ab"), ("api-tools", "apt")] {
+            let chars: Vec<char> = text.chars().collect();
+            let bonuses: Vec<Score> = (0..chars.len()).map(|j| position_bonus(&chars, j)).collect();
+            let (score, positions) = fuzzy_match(text, pattern).unwrap();
+            assert_eq!(score, score_positions(&bonuses, &positions) + acronym_bonus(&bonuses, &positions));
+        }
+    }
+
+    #[test]
+    fn test_initials_outrank_contiguous_mid_word() {
+        let items = vec!["mirsgate", "repo-searcher-github"];
+        let result = filter_human(&items, "rsg", |s| s.to_string());
+        assert_eq!(result, vec!["repo-searcher-github", "mirsgate"]);
     }
 
-    let trimmed = query.trim();
-    if trimmed.is_empty() {
-        return items.to_vec();
+    #[test]
+    fn test_initials_outrank_scattered_match() {
+        let items = vec!["rustysigning", "repo-searcher-github"];
+        let result = filter_human(&items, "rsg", |s| s.to_string());
+        assert_eq!(result, vec!["repo-searcher-github", "rustysigning"]);
     }
 
-    let mut result = Vec::new();
-    let query_parts: Vec<String> = trimmed
-        .to_lowercase()
-        .split(' ')
-        .filter(|part| !part.is_empty())
-        .map(|part| part.to_string())
-        .collect();
+    #[test]
+    fn test_initials_camel_case() {
+        let items = vec!["rustsigning", "RepoSearcherGithub"];
+        let result = filter_human(&items, "rsg", |s| s.to_string());
+        assert_eq!(result, vec!["RepoSearcherGithub", "rustsigning"]);
+
+        let (_, positions) = fuzzy_match("RepoSearcherGithub", "rsg").unwrap();
+        assert_eq!(positions, vec![0, 4, 12]);
+    }
+
+    #[test]
+    fn test_initials_preferred_over_better_plain_alignment() {
+        // The plain alignment of `gs` picks the consecutive `gs` in the middle
+        let (_, positions) = fuzzy_match("go-bagsx-services", "gs").unwrap();
+        assert_eq!(positions, vec![0, 9]);
+    }
+
+    #[test]
+    fn test_diacritics_are_ignored() {
+        let items = vec!["café-api", "cafe-cli", "größe"];
+        assert_eq!(filter_human(&items, "cafe", |s| s.to_string()), vec!["café-api", "cafe-cli"]);
+        assert_eq!(filter_human(&items, "café", |s| s.to_string()), vec!["café-api", "cafe-cli"]);
+        assert_eq!(filter_human(&items, "grosse", |s| s.to_string()), vec!["größe"]);
+        assert_eq!(filter_human(&items, "große", |s| s.to_string()), vec!["größe"]);
+    }
+
+    #[test]
+    fn test_sharp_s_folds_to_ss() {
+        let items = vec!["Straße", "strasse", "STRASSE", "strase"];
+        let exact =
+            |query: &str| filter_with_mode(&items, query, MatchMode::Exact, |s| s.to_string());
+        assert_eq!(exact("strasse"), vec!["Straße", "strasse", "STRASSE"]);
+        assert_eq!(exact("straße"), vec!["Straße", "strasse", "STRASSE"]);
+
+        // Both halves of the expanded `ß` highlight the one original character
+        let (_, positions) = fuzzy_match("Straße-api", "asse").unwrap();
+        assert_eq!(positions, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_greek_final_sigma_folds_to_sigma() {
+        let items = vec!["οδός", "ΟΔΟΣ-tools"];
+        let exact =
+            |query: &str| filter_with_mode(&items, query, MatchMode::Exact, |s| s.to_string());
+        assert_eq!(exact("οδοσ"), items);
+        assert_eq!(exact("οδος"), items);
+
+        let (_, positions) = fuzzy_match("ΟΔΟΣ-tools", "δος").unwrap();
+        assert_eq!(positions, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_turkish_dotted_and_dotless_i_match_i() {
+        let items = vec!["İstanbul", "ılık-su", "istanbul"];
+        let human = |query: &str| filter_human(&items, query, |s| s.to_string());
+        assert_eq!(human("istanbul"), vec!["İstanbul", "istanbul"]);
+        assert_eq!(human("ilik"), vec!["ılık-su"]);
+        assert_eq!(human("ılık"), vec!["ılık-su"]);
+
+        let (_, positions) = fuzzy_match("İstanbul", "ist").unwrap();
+        assert_eq!(positions, vec![0, 1, 2]);
+
+        // Without normalizing, the dot above is not decomposed from the `İ`
+        let options = FilterOptions {
+            normalize: false,
+            ..FilterOptions::default()
+        };
+        let result = filter_scored(&items, "ist", options, |s| s.to_string());
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_precomposed_and_decomposed_match() {
+        let precomposed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let items = vec![precomposed, decomposed];
+        assert_eq!(filter_human(&items, precomposed, |s| s.to_string()), items);
+        assert_eq!(filter_human(&items, decomposed, |s| s.to_string()), items);
+        assert_eq!(filter_with_mode(&items, "cafe", MatchMode::Exact, |s| s.to_string()), items);
+    }
+
+    #[test]
+    fn test_normalized_positions_refer_to_original_text() {
+        // The decomposed accent is its own character in the original string
+        let items = vec!["cafe\u{301}-api", "café-api"];
+        let result = filter_with_positions(&items, "e-a", |s| s.to_string());
+        assert_eq!(result[0].1, vec![3, 5, 6]);
+        assert_eq!(result[1].1, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_normalization_with_mixed_scripts() {
+        let items = vec!["Ελληνικά-notes", "日本語-café", "ﬁle-tools"];
+        assert_eq!(filter_human(&items, "ελληνικα", |s| s.to_string()), vec!["Ελληνικά-notes"]);
+        assert_eq!(filter_human(&items, "日本語 cafe", |s| s.to_string()), vec!["日本語-café"]);
+
+        // Compatibility decomposition splits the ligature, both letters map to it
+        let result = filter_with_positions(&items, "file", |s| s.to_string());
+        assert_eq!(result, vec![("ﬁle-tools", vec![0, 1, 2])]);
+    }
+
+    #[test]
+    fn test_normalization_can_be_disabled() {
+        let items = vec!["café"];
+        let options = FilterOptions {
+            normalize: false,
+            ..FilterOptions::default()
+        };
+        assert!(filter_scored(&items, "cafe", options, |s| s.to_string()).is_empty());
+        assert_eq!(filter_scored(&items, "café", options, |s| s.to_string()).len(), 1);
+    }
+
+    fn ranked_names(candidates: &[Candidate], query: &str) -> Vec<String> {
+        filter_candidates(candidates, query, FilterOptions::default())
+            .into_iter()
+            .map(|(index, _)| candidates[index].primary_text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_name_outranks_description() {
+        let mut candidates: Vec<Candidate> = (0..20)
+            .map(|i| Candidate::repository(&format!("tool-{}", i), "dima", "Nice docs included"))
+            .collect();
+        candidates.push(Candidate::repository("docs", "dima", "Personal notes"));
+
+        let ranked = ranked_names(&candidates, "docs");
+        assert_eq!(ranked.len(), 21);
+        assert_eq!(ranked[0], "docs");
+    }
+
+    #[test]
+    fn test_owner_outranks_description() {
+        let candidates = vec![
+            Candidate::repository("notes", "someone", "Written by acme"),
+            Candidate::repository("tools", "acme", "Shared tools"),
+        ];
+        assert_eq!(ranked_names(&candidates, "acme"), vec!["tools", "notes"]);
+    }
+
+    #[test]
+    fn test_field_weights_multiply_scores() {
+        let candidates = vec![Candidate::repository("docs", "", ""), Candidate::from_text("docs")];
+        let result = filter_candidates(&candidates, "docs", FilterOptions::default());
+        assert_eq!(result[0].1, result[1].1 * NAME_WEIGHT);
+    }
+
+    #[test]
+    fn test_terms_may_match_different_fields() {
+        let candidates = vec![
+            Candidate::repository("api", "acme", "Rust bindings"),
+            Candidate::repository("api", "acme", "Go bindings"),
+        ];
+        assert_eq!(ranked_names(&candidates, "api rust"), vec!["api"]);
+        let result = filter_candidates(&candidates, "api rust", FilterOptions::default());
+        assert_eq!(result[0].0, 0);
+    }
+
+    #[test]
+    fn test_candidate_exclusion_checks_every_field() {
+        let candidates = vec![
+            Candidate::repository("api", "acme", "Fork of the old api"),
+            Candidate::repository("api-v2", "acme", "Current api"),
+        ];
+        assert_eq!(ranked_names(&candidates, "api !fork"), vec!["api-v2"]);
+    }
+
+    #[test]
+    fn test_candidate_anchors_apply_per_field() {
+        let candidates = vec![
+            Candidate::repository("tool.rs", "acme", "A tool"),
+            Candidate::repository("other", "acme", "Written in rs"),
+            Candidate::repository("rust-tool", "acme", "Nothing"),
+        ];
+        let options = FilterOptions {
+            match_mode: MatchMode::Exact,
+            ..FilterOptions::default()
+        };
+        let result = filter_candidates(&candidates, "rs$", options);
+        assert_eq!(result, vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_candidate_regex_matches_any_field() {
+        let candidates = vec![
+            Candidate::repository("api", "acme", "v2 release"),
+            Candidate::repository("web", "acme", ""),
+        ];
+        let options = FilterOptions {
+            match_mode: MatchMode::Regex,
+            ..FilterOptions::default()
+        };
+        assert_eq!(filter_candidates(&candidates, r"v\d", options), vec![(0, 0)]);
+        assert!(try_filter_candidates(&candidates, "(", options).is_err());
+    }
+
+    #[test]
+    fn test_prior_outranks_slightly_better_match() {
+        let candidates = vec![Candidate::from_text("aXpXi"), Candidate::from_text("webapi")];
+        assert_eq!(ranked_names(&candidates, "api"), vec!["aXpXi", "webapi"]);
+
+        let candidates = vec![candidates[0].clone(), candidates[1].clone().prior(10.0)];
+        assert_eq!(ranked_names(&candidates, "api"), vec!["webapi", "aXpXi"]);
+    }
+
+    #[test]
+    fn test_prior_does_not_outrank_much_better_match() {
+        let candidates =
+            vec![Candidate::from_text("api"), Candidate::from_text("webapi").prior(10.0)];
+        assert_eq!(ranked_names(&candidates, "api"), vec!["api", "webapi"]);
+    }
+
+    #[test]
+    fn test_prior_weight_scales_prior() {
+        let candidates =
+            vec![Candidate::from_text("api"), Candidate::from_text("webapi").prior(10.0)];
+        let options = FilterOptions {
+            prior_weight: 10.0,
+            ..FilterOptions::default()
+        };
+        let result = filter_candidates(&candidates, "api", options);
+        assert_eq!(result[0].0, 1);
+
+        let options = FilterOptions {
+            prior_weight: 0.0,
+            ..FilterOptions::default()
+        };
+        let without = filter_candidates(&candidates, "api", options);
+        assert_eq!(without[1], (1, result[0].1 - 100));
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        let pattern = |query: &str| Pattern::parse(query);
+        assert_eq!(pattern("name:api").scope, Some(FieldKind::Name));
+        assert_eq!(pattern("name:api").text, "api");
+        assert_eq!(pattern("owner:dima").scope, Some(FieldKind::Owner));
+        assert_eq!(pattern("desc:old").scope, Some(FieldKind::Description));
+        assert_eq!(pattern("description:old").scope, Some(FieldKind::Description));
+
+        let negated = pattern("!name:^fork");
+        assert!(negated.excluded && negated.anchors.start);
+        assert_eq!((negated.scope, negated.text.as_str()), (Some(FieldKind::Name), "fork"));
+
+        let unknown = pattern("https://host");
+        assert_eq!((unknown.scope, unknown.text.as_str()), (None, "https://host"));
+    }
+
+    fn scoped_candidates() -> Vec<Candidate> {
+        vec![
+            Candidate::repository("api", "dima", "Deprecated client"),
+            Candidate::repository("client", "api-team", "Uses the api"),
+            Candidate::repository("notes", "dima", "Nothing deprecated"),
+        ]
+    }
+
+    #[test]
+    fn test_scoped_terms() {
+        let candidates = scoped_candidates();
+        assert_eq!(ranked_names(&candidates, "name:api"), vec!["api"]);
+        assert_eq!(ranked_names(&candidates, "owner:api"), vec!["client"]);
+        assert_eq!(ranked_names(&candidates, "desc:deprecated"), vec!["api", "notes"]);
+        assert_eq!(ranked_names(&candidates, "owner:dima !name:notes"), vec!["api"]);
+    }
+
+    #[test]
+    fn test_unscoped_terms_match_all_fields() {
+        let candidates = scoped_candidates();
+        assert_eq!(ranked_names(&candidates, "api"), vec!["api", "client"]);
+    }
+
+    #[test]
+    fn test_empty_and_unknown_scopes() {
+        let candidates = scoped_candidates();
+        assert_eq!(ranked_names(&candidates, "name:").len(), 3);
+        assert!(ranked_names(&candidates, "lang:rust").is_empty());
+
+        let candidates = vec![Candidate::from_text("see lang:rust")];
+        assert_eq!(ranked_names(&candidates, "lang:rust"), vec!["see lang:rust"]);
+    }
+
+    #[test]
+    fn test_scopes_with_alternatives() {
+        let candidates = scoped_candidates();
+        // The name is weighted higher than the owner
+        assert_eq!(ranked_names(&candidates, "name:notes|owner:api"), vec!["notes", "client"]);
+    }
+
+    fn qualified_candidates() -> Vec<Candidate> {
+        let metadata = |fork, visibility, source| Metadata {
+            fork,
+            visibility,
+            archived: false,
+            mirror: source == RepoSource::GitLab && fork,
+            starred: false,
+            source,
+        };
+        vec![
+            Candidate::repository("api", "dima", "")
+                .metadata(metadata(false, Visibility::Public, RepoSource::GitHub)),
+            Candidate::repository("api-fork", "dima", "")
+                .metadata(metadata(true, Visibility::Internal, RepoSource::GitHub)),
+            Candidate::repository("apis", "dima", "")
+                .metadata(metadata(false, Visibility::Private, RepoSource::GitLab)),
+            Candidate::repository("web", "dima", "")
+                .metadata(metadata(true, Visibility::Private, RepoSource::GitLab)),
+        ]
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        let candidates = qualified_candidates();
+        assert_eq!(ranked_names(&candidates, "is:fork"), vec!["api-fork", "web"]);
+        assert_eq!(ranked_names(&candidates, "is:private"), vec!["apis", "web"]);
+        assert_eq!(ranked_names(&candidates, "is:internal"), vec!["api-fork"]);
+        assert!(ranked_names(&candidates, "is:archived").is_empty());
+        assert_eq!(ranked_names(&candidates, "is:mirror"), vec!["web"]);
+        assert_eq!(ranked_names(&candidates, "is:fork !is:mirror"), vec!["api-fork"]);
+        assert_eq!(ranked_names(&candidates, "source:github"), vec!["api", "api-fork"]);
+        assert_eq!(ranked_names(&candidates, "source:gl"), vec!["apis", "web"]);
+        assert!(ranked_names(&candidates, "source:gitea").is_empty());
+        assert!(ranked_names(&candidates, "source:bb").is_empty());
+        assert_eq!(ranked_names(&candidates, "!is:fork !source:gh"), vec!["apis"]);
+    }
+
+    #[test]
+    fn test_qualifiers_with_fuzzy_terms() {
+        let candidates = qualified_candidates();
+        assert_eq!(ranked_names(&candidates, "api is:fork"), vec!["api-fork"]);
+        assert_eq!(ranked_names(&candidates, "ap !is:private"), vec!["api", "api-fork"]);
+        assert_eq!(
+            ranked_names(&candidates, "source:gh|is:private api"),
+            vec!["api", "api-fork", "apis"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_qualifiers_are_literal() {
+        let candidates = vec![
+            Candidate::from_text("is:cool tool"),
+            Candidate::from_text("plain"),
+        ];
+        assert_eq!(ranked_names(&candidates, "is:cool"), vec!["is:cool tool"]);
+        assert!(ranked_names(&qualified_candidates(), "source:bitbucket").is_empty());
+    }
+
+    #[test]
+    fn test_qualifiers_without_metadata_match_text() {
+        let items = vec!["tool (is:fork)", "tool"];
+        assert_eq!(filter_human(&items, "is:fork", |s| s.to_string()), vec!["tool (is:fork)"]);
+    }
+
+    fn incremental_candidates() -> Vec<Candidate> {
+        [
+            "api-tools",
+            "api",
+            "rapid",
+            "web-app",
+            "name:tool",
+            "cli-api",
+            "rust|go",
+            "old-api-fork",
+        ]
+        .iter()
+        .map(|&text| Candidate::from_text(text))
+        .collect()
+    }
+
+    #[test]
+    fn test_narrows_only_for_extensions() {
+        let fuzzy = MatchMode::Fuzzy;
+        assert!(narrows("ap", "api", fuzzy));
+        assert!(narrows("api ", "api web", fuzzy));
+        assert!(narrows("api", "api$", fuzzy));
+        assert!(narrows("'cl", "'cli'", fuzzy));
+        assert!(!narrows("api", "ap", fuzzy));
+        assert!(!narrows("", "api", fuzzy));
+        assert!(!narrows("api", "api", MatchMode::Regex));
+        assert!(!narrows("-f", "-fo", fuzzy));
+        assert!(!narrows("-", "-f", fuzzy));
+        assert!(!narrows("rust", "rust|go", fuzzy));
+        assert!(!narrows("name", "name:", fuzzy));
+        assert!(!narrows("is", "is:fork", fuzzy));
+        assert!(!narrows("api$", "api$x", fuzzy));
+        assert!(!narrows("^", "^a", fuzzy));
+    }
+
+    #[test]
+    fn test_incremental_filter_matches_full_scan() {
+        let candidates = incremental_candidates();
+        let mut incremental = IncrementalFilter::new();
+        let queries = [
+            "a", "ap", "api", "api ", "api -", "api -f", "api -fo", "api", "a", "n", "na",
+            "name:", "name:t", "r", "ru", "rust|", "rust|g", "'", "'ap", "'api'", "API", "",
+        ];
+
+        for query in queries {
+            let options = FilterOptions::default();
+            assert_eq!(
+                incremental.filter(&candidates, query, options).unwrap(),
+                filter_candidates(&candidates, query, options),
+                "query {:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_incremental_filter_rescans_after_reset() {
+        let mut candidates = incremental_candidates();
+        let mut incremental = IncrementalFilter::new();
+        let options = FilterOptions::default();
+        incremental.filter(&candidates, "web", options).unwrap();
+
+        candidates.push(Candidate::from_text("web-api"));
+        incremental.reset();
+        let result = incremental.filter(&candidates, "web-", options).unwrap();
+        assert_eq!(result, filter_candidates(&candidates, "web-", options));
+        assert_eq!(result.len(), 2);
+    }
 
-    // Sort query parts to handle exclusions first
-    let query_parts = {
-        let mut parts = query_parts;
-        parts.sort_by(|a, b| {
-            if a.starts_with('-') && !b.starts_with('-') {
-                std::cmp::Ordering::Less
-            } else if !a.starts_with('-') && b.starts_with('-') {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Equal
+    #[test]
+    fn test_incremental_filter_prepares_replaced_candidates() {
+        let mut candidates = vec![Candidate::from_text("web"), Candidate::from_text("api")];
+        let mut incremental = IncrementalFilter::new();
+        let options = FilterOptions::default();
+        assert_eq!(incremental.filter(&candidates, "web", options).unwrap().len(), 1);
+
+        // Same count of candidates, only the texts changed
+        candidates[1] = Candidate::from_text("web-api");
+        incremental.reset();
+        let result = incremental.filter(&candidates, "web", options).unwrap();
+        assert_eq!(result, filter_candidates(&candidates, "web", options));
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_incremental_filter_prepares_appended_candidates() {
+        let mut candidates = vec![Candidate::from_text("web"), Candidate::from_text("api")];
+        let mut incremental = IncrementalFilter::new();
+        let options = FilterOptions::default();
+        incremental.filter(&candidates, "api", options).unwrap();
+
+        candidates.push(Candidate::from_text("api-v2"));
+        incremental.candidates_appended();
+        let result = incremental.filter(&candidates, "api", options).unwrap();
+        assert_eq!(result, filter_candidates(&candidates, "api", options));
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_incremental_filter_refolds_when_normalizing_changes() {
+        let candidates = vec![Candidate::from_text("café")];
+        let mut incremental = IncrementalFilter::new();
+        let options = FilterOptions::default();
+        assert_eq!(incremental.filter(&candidates, "cafe", options).unwrap().len(), 1);
+
+        let options = FilterOptions {
+            normalize: false,
+            ..options
+        };
+        assert!(incremental.filter(&candidates, "cafe", options).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prepared_texts_match_like_raw_texts() {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = move |bound: usize| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (state >> 33) as usize % bound
+        };
+        let words = ["api", "Straße", "café", "RepoSearch", "v2", "XMLParser", "go", "ΟΔΟΣ"];
+        let candidates: Vec<Candidate> = (0..500)
+            .map(|_| {
+                let name = (0..1 + next(3))
+                    .map(|_| words[next(words.len())])
+                    .collect::<Vec<_>>()
+                    .join(if next(2) == 0 { "-" } else { "_" });
+                Candidate::repository(&name, "group/dima", words[next(words.len())])
+            })
+            .collect();
+
+        let queries = [
+            "a", "api", "strasse", "cafe", "rs", "xmlp", "'v2", "^go", "ος$", "'api'", "dima/",
+            "reposerach", "name:st !go", "api|caf",
+        ];
+        for normalize in [true, false] {
+            let options = FilterOptions {
+                normalize,
+                typo_tolerance: true,
+                ..FilterOptions::default()
+            };
+            let folding = Folding {
+                case_sensitive: false,
+                normalize,
+            };
+            let mut prepared = PreparedCandidates::default();
+            prepared.update(&candidates, folding);
+
+            for query in queries {
+                let matcher = Matcher::new(query, options).unwrap();
+                let [raw, cached] = [None, Some(&prepared)].map(|prepared| {
+                    let indices = (0..candidates.len()).collect();
+                    candidate_matches(&candidates, prepared, indices, &matcher)
+                });
+                assert_eq!(raw, cached, "query {:?}", query);
+                assert!(!raw.0.is_empty(), "query {:?}", query);
             }
-        });
-        parts
-    };
+        }
+    }
 
-    for item in items {
-        let mapped = mapper(item).to_lowercase();
-        let mut pass = true;
+    #[test]
+    fn test_parallel_matches_serial() {
+        // Small linear congruential generator, so the data is random but reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move |bound: usize| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (state >> 33) as usize % bound
+        };
+        let words = ["api", "web", "cli", "Rust", "go", "tool", "app", "server", "docs", "kit"];
+        let candidates: Vec<Candidate> = (0..2_000)
+            .map(|_| {
+                let name = (0..1 + next(3))
+                    .map(|_| words[next(words.len())])
+                    .collect::<Vec<_>>()
+                    .join(if next(2) == 0 { "-" } else { "_" });
+                let description = words[next(words.len())];
+                Candidate::repository(&name, "dima", description)
+            })
+            .collect();
 
-        for query_part in &query_parts {
-            // Check length, so a single minus is still matched
-            if query_part.len() >= 2 && query_part.starts_with('-') {
-                if mapped.contains(&query_part[1..]) {
-                    pass = false;
-                    break;
-                }
-            } else if !mapped.contains(query_part) {
-                pass = false;
-                break;
+        for query in ["a", "api", "ap to", "rust|go !kit", "'cli", "name:ser", "T"] {
+            for tie_break in [TieBreak::Input, TieBreak::Alphabetical, TieBreak::Shortest] {
+                let options = FilterOptions {
+                    tie_break,
+                    ..FilterOptions::default()
+                };
+                let matcher = Matcher::new(query, options).unwrap();
+                let [mut serial, mut parallel] = [usize::MAX, 0].map(|threshold| {
+                    let indices = (0..candidates.len()).collect();
+                    candidate_matches_with(&candidates, None, indices, &matcher, threshold).0
+                });
+                rank_candidates(&candidates, &mut serial, tie_break, None);
+                rank_candidates(&candidates, &mut parallel, tie_break, None);
+                assert_eq!(serial, parallel, "query {:?}", query);
             }
         }
+    }
 
-        if pass {
-            result.push(item.clone());
+    fn typo_options() -> FilterOptions {
+        FilterOptions {
+            typo_tolerance: true,
+            ..FilterOptions::default()
         }
     }
 
-    result
-}
+    #[test]
+    fn test_typo_tolerance_is_opt_in() {
+        let items = vec!["search-tool", "web"];
+        assert!(filter_human(&items, "serach", |s| s.to_string()).is_empty());
+        let matched = filter_scored(&items, "serach", typo_options(), |s| s.to_string());
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].0, "search-tool");
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_typo_variants() {
+        let items = vec!["search", "rust-lang", "web"];
+        let typo = |query| filter_scored(&items, query, typo_options(), |s| s.to_string());
+        // Swapped, mistyped and extra characters
+        assert_eq!(typo("saerch")[0].0, "search");
+        assert_eq!(typo("seerch")[0].0, "search");
+        assert_eq!(typo("searchh")[0].0, "search");
+        // A second typo is not tolerated, and neither are typos in short terms
+        assert!(typo("saercj").is_empty());
+        assert!(typo("wxb").is_empty());
+    }
 
     #[test]
-    fn test_empty_list() {
-        let items: Vec<String> = vec![];
-        let result = filter_human(&items, "query", |s| s.clone());
-        assert_eq!(result.len(), 0);
+    fn test_typo_matches_are_down_ranked() {
+        let strict = filter_scored(&["search"], "search", FilterOptions::default(), |s| {
+            s.to_string()
+        });
+        let typo = filter_scored(&["search"], "seacrh", typo_options(), |s| s.to_string());
+        assert!(typo[0].1 < strict[0].1);
+
+        // Only terms that need it are matched with a typo
+        let items = vec!["api-search", "search"];
+        let matched = filter_scored(&items, "api saerch", typo_options(), |s| s.to_string());
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].0, "api-search");
     }
 
     #[test]
-    fn test_empty_query() {
-        let items = vec!["apple", "banana", "cherry"];
-        let result = filter_human(&items, "", |s| s.to_string());
-        assert_eq!(result, items);
+    fn test_typo_fallback_is_reported() {
+        let candidates = vec![
+            Candidate::from_text("search-tool"),
+            Candidate::from_text("searcher"),
+            Candidate::from_text("web"),
+        ];
+        let mut incremental = IncrementalFilter::new();
+
+        assert_eq!(incremental.filter(&candidates, "sear", typo_options()).unwrap().len(), 2);
+        assert!(!incremental.typo_fallback());
+
+        let matched = incremental.filter(&candidates, "seardh", typo_options()).unwrap();
+        assert_eq!(matched.len(), 2);
+        assert!(incremental.typo_fallback());
+
+        assert!(incremental.filter(&candidates, "seardh", FilterOptions::default()).unwrap().is_empty());
+        assert!(!incremental.typo_fallback());
     }
 
     #[test]
-    fn test_simple_filter() {
-        let items = vec!["apple", "banana", "cherry"];
-        let result = filter_human(&items, "a", |s| s.to_string());
-        assert_eq!(result, vec!["apple", "banana"]);
+    fn test_typos_skip_long_texts() {
+        let long = format!("search {}", "x".repeat(TYPO_MAX_TEXT_LEN));
+        let items = vec![long.as_str()];
+        assert!(filter_scored(&items, "saerch", typo_options(), |s| s.to_string()).is_empty());
+    }
+
+    fn namespaced_candidates() -> Vec<Candidate> {
+        vec![
+            Candidate::repository("project", "group/subgroup", ""),
+            Candidate::repository("api", "group", "Client/server API"),
+            Candidate::repository("subgroup-tools", "other", ""),
+            Candidate::repository("web", "other/group", ""),
+        ]
     }
 
     #[test]
-    fn test_multiple_terms() {
-        let items = vec!["apple pie", "banana split", "cherry pie"];
-        let result = filter_human(&items, "pie a", |s| s.to_string());
-        assert_eq!(result, vec!["apple pie"]);
+    fn test_namespace_prefix_narrows() {
+        let candidates = namespaced_candidates();
+        assert_eq!(ranked_names(&candidates, "group/"), vec!["project", "api", "web"]);
+        assert_eq!(ranked_names(&candidates, "group/subgroup/"), vec!["project"]);
+        assert_eq!(ranked_names(&candidates, "subgroup/project"), vec!["project"]);
+        assert_eq!(ranked_names(&candidates, "other/"), vec!["subgroup-tools", "web"]);
+        assert_eq!(ranked_names(&candidates, "!group/ other"), vec!["subgroup-tools"]);
     }
 
     #[test]
-    fn test_exclusion() {
-        let items = vec!["apple pie", "banana split", "cherry pie"];
-        let result = filter_human(&items, "pie -cherry", |s| s.to_string());
-        assert_eq!(result, vec!["apple pie"]);
+    fn test_paths_need_a_slash() {
+        let candidates = namespaced_candidates();
+        // Without a slash, the path does not add a match in the owner and name at once
+        assert!(ranked_names(&candidates, "subgroupproject").is_empty());
+        // A slash in other fields still matches
+        assert_eq!(ranked_names(&candidates, "client/server"), vec!["api"]);
+        assert!(ranked_names(&candidates, "owner:group/project").is_empty());
     }
 
     #[test]
-    fn test_case_insensitive() {
-        let items = vec!["Apple", "Banana", "Cherry"];
-        let result = filter_human(&items, "apple", |s| s.to_string());
-        assert_eq!(result, vec!["Apple"]);
+    fn test_bare_namespace_matches_segment_with_boundary_bonus() {
+        let candidates = namespaced_candidates();
+        let matches = filter_candidates(&candidates, "subgroup", FilterOptions::default());
+        let names: Vec<&str> =
+            matches.iter().map(|&(index, _)| candidates[index].primary_text()).collect();
+        assert_eq!(names, vec!["subgroup-tools", "project"]);
+
+        // The segment after the slash starts a word, scoring like a match at the start
+        let (nested, _) = fuzzy_match("group/subgroup", "subgroup").unwrap();
+        let (inner, _) = fuzzy_match("groupsubgroup", "subgroup").unwrap();
+        assert!(nested > inner);
     }
 
     #[test]
-    fn test_medical_medium_exclusion() {
-        let items = vec![
-            "medicalmedium-instagram (git@github.com:Dima-369/medicalmedium-instagram.git)",
-            "medical-medium-text-files (git@github.com:Dima-369/medical-medium-text-files.git)",
-            "medical-medium-demon-podcast-notes (git@github.com:Dima-369/medical-medium-demon-podcast-notes.git)"
-        ];
+    fn test_word_starts_score_alike() {
+        let bonus = |text: &str, index| position_bonus(&text.chars().collect::<Vec<_>>(), index);
+        assert_eq!(bonus("myProject", 2), bonus("my project", 3));
+        assert_eq!(bonus("my_project", 3), bonus("my project", 3));
+        assert_eq!(bonus("my.project", 3), bonus("my-project", 3));
+        assert_eq!(bonus("v2", 1), BONUS_BOUNDARY);
+        assert_eq!(bonus("2fa", 1), BONUS_BOUNDARY);
+        assert_eq!(bonus("XMLParser", 3), BONUS_BOUNDARY);
+        assert_eq!(bonus("XMLParser", 1), 0);
+        assert_eq!(bonus("project", 3), 0);
+    }
 
-        // Test with "medical -demon" to exclude demon podcast notes
-        let result = filter_human(&items, "medical -demon", |s| s.to_string());
-        assert_eq!(result, vec![
-            "medicalmedium-instagram (git@github.com:Dima-369/medicalmedium-instagram.git)",
-            "medical-medium-text-files (git@github.com:Dima-369/medical-medium-text-files.git)"
-        ]);
+    #[test]
+    fn test_camel_case_humps_rank_above_subsequences() {
+        let items = vec!["maximumpower", "MyAwesomeProject"];
+        let result = filter_human(&items, "map", |s| s.to_string());
+        assert_eq!(result, vec!["MyAwesomeProject", "maximumpower"]);
+
+        let (_, positions) = fuzzy_match("XMLParser", "xp").unwrap();
+        assert_eq!(positions, vec![0, 3]);
+    }
+
+    #[test]
+    fn test_snake_case_humps_rank_above_subsequences() {
+        let items = vec!["deep_value", "data_pipeline_v2"];
+        let result = filter_human(&items, "dpv", |s| s.to_string());
+        assert_eq!(result, vec!["data_pipeline_v2", "deep_value"]);
+
+        let items = vec!["dotprivate", "dot.private"];
+        let result = filter_human(&items, "dp", |s| s.to_string());
+        assert_eq!(result, vec!["dot.private", "dotprivate"]);
+    }
+
+    #[test]
+    fn test_digit_boundaries_rank_above_subsequences() {
+        let items = vec!["dumpy2000", "data_pipeline2"];
+        let result = filter_human(&items, "dp2", |s| s.to_string());
+        assert_eq!(result, vec!["data_pipeline2", "dumpy2000"]);
+
+        let items = vec!["oauth-storage", "oauth2store"];
+        let result = filter_human(&items, "o2s", |s| s.to_string());
+        assert_eq!(result, vec!["oauth2store"]);
+        let (_, positions) = fuzzy_match("oauth2store", "o2s").unwrap();
+        assert_eq!(positions, vec![0, 5, 6]);
+    }
+
+    #[test]
+    fn test_limit_keeps_the_best_matches_in_order() {
+        let words = ["api", "apis", "rapid", "map-it", "a-p-i", "apple-pie", "web"];
+        let candidates: Vec<Candidate> = (0..300)
+            .map(|i| Candidate::from_text(format!("{}-{}", words[i % words.len()], i % 13)))
+            .collect();
+
+        for tie_break in [TieBreak::Input, TieBreak::Alphabetical, TieBreak::Shortest] {
+            let options = FilterOptions {
+                tie_break,
+                ..FilterOptions::default()
+            };
+            let full = filter_candidates(&candidates, "ap", options);
+            for limit in [0, 1, 7, 50, full.len(), full.len() + 10] {
+                let limited = FilterOptions {
+                    limit: Some(limit),
+                    ..options
+                };
+                let expected = &full[..limit.min(full.len())];
+                assert_eq!(filter_candidates(&candidates, "ap", limited), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_limit_reports_the_total_match_count() {
+        let candidates: Vec<Candidate> =
+            (0..100).map(|i| Candidate::from_text(format!("repo-{}", i))).collect();
+        let options = FilterOptions {
+            limit: Some(5),
+            ..FilterOptions::default()
+        };
+        let mut incremental = IncrementalFilter::new();
+
+        assert_eq!(incremental.filter(&candidates, "r", options).unwrap().len(), 5);
+        assert_eq!(incremental.match_count(), 100);
+
+        // Narrowing still sees the matches beyond the limit
+        let matched = incremental.filter(&candidates, "repo-9", options).unwrap();
+        assert_eq!(matched.len(), 5);
+        assert_eq!(incremental.match_count(), 19);
+        assert_eq!(matched, filter_candidates(&candidates, "repo-9", options));
+    }
+
+    #[test]
+    fn test_limit_truncates_scored_items() {
+        let items = vec!["api", "api-tools", "rapid"];
+        let options = FilterOptions {
+            limit: Some(2),
+            ..FilterOptions::default()
+        };
+        let matched = filter_scored(&items, "api", options, |s| s.to_string());
+        assert_eq!(matched.len(), 2);
+        assert_eq!(matched[0].0, "api");
+    }
+
+    #[test]
+    fn test_filter_indices_match_filter_scored() {
+        let items = vec!["web", "api-tools", "rapid", "my-api", "API"];
+        for query in ["api", "ap !tools", "rapid|web", ""] {
+            let options = FilterOptions::default();
+            let indices = filter_indices(&items, query, options, |s| s);
+            let scored = filter_scored(&items, query, options, |s| s.to_string());
+            let indexed: Vec<(&str, Score)> =
+                indices.iter().map(|&(index, score)| (items[index], score)).collect();
+            assert_eq!(indexed, scored, "query {:?}", query);
+        }
+    }
+
+    #[test]
+    fn test_filter_indices_borrow_fields() {
+        struct Repo {
+            name: String,
+        }
+        let repos = vec![Repo { name: "web".to_string() }, Repo { name: "api".to_string() }];
+        let matches = filter_indices(&repos, "api", FilterOptions::default(), |repo| &repo.name);
+        assert_eq!(matches.iter().map(|&(index, _)| index).collect::<Vec<_>>(), vec![1]);
+
+        let options = regex_options();
+        assert!(try_filter_indices(&repos, "(", options, |repo| &repo.name).is_err());
+    }
+
+    proptest! {
+        // Small alphabets, so that random queries regularly match random items
+        #[test]
+        fn prop_results_are_matching_input_items(
+            items in prop::collection::vec("[a-cA-C_ -]{0,12}", 0..24),
+            query in "[a-cA-C]{1,4}",
+        ) {
+            let result = filter_indices(&items, &query, FilterOptions::default(), |s| s);
+            for &(index, score) in &result {
+                prop_assert!(index < items.len());
+                let item = &items[index];
+
+                // Smart case folds lowercase queries only, the characters appear in order
+                let folded = |text: &str| {
+                    if query.chars().any(char::is_uppercase) {
+                        text.to_string()
+                    } else {
+                        text.to_lowercase()
+                    }
+                };
+                let text = folded(item);
+                let mut rest = text.chars();
+                prop_assert!(folded(&query).chars().all(|c| rest.any(|t| t == c)));
+
+                let options = FilterOptions::default();
+                let alone = filter_indices(std::slice::from_ref(item), &query, options, |s| s);
+                prop_assert_eq!(alone, vec![(0, score)]);
+            }
+
+            let mut indices: Vec<usize> = result.iter().map(|&(index, _)| index).collect();
+            indices.sort_unstable();
+            indices.dedup();
+            prop_assert_eq!(indices.len(), result.len());
+        }
+
+        #[test]
+        fn prop_extending_a_query_never_adds_items(
+            items in prop::collection::vec("[a-cA-C_ -]{0,12}", 0..24),
+            query in "[a-c]{1,4}",
+            extension in "[a-c]",
+        ) {
+            let indices = |query: &str| {
+                let result = filter_indices(&items, query, FilterOptions::default(), |s| s);
+                let mut indices: Vec<usize> =
+                    result.into_iter().map(|(index, _)| index).collect();
+                indices.sort_unstable();
+                indices
+            };
+            let shorter = indices(&query);
+            let longer = indices(&format!("{}{}", query, extension));
+            let subset = longer.iter().all(|index| shorter.binary_search(index).is_ok());
+            prop_assert!(subset, "{:?} is not within {:?}", longer, shorter);
+        }
+
+        #[test]
+        fn prop_equal_scores_keep_input_order(
+            items in prop::collection::vec("[a-cA-C_ -]{0,12}", 0..24),
+            query in "[a-c]{1,4}",
+        ) {
+            let result = filter_indices(&items, &query, FilterOptions::default(), |s| s);
+            for pair in result.windows(2) {
+                prop_assert!(pair[0].1 > pair[1].1 || pair[0].0 < pair[1].0);
+            }
+        }
+
+        #[test]
+        fn prop_repeated_filtering_is_identical(
+            items in prop::collection::vec("[a-cA-C_ -]{0,12}", 0..24),
+            query in "[a-c!|' ]{0,6}",
+        ) {
+            let candidates: Vec<Candidate> = items.iter().map(Candidate::from_text).collect();
+            let first = filter_candidates(&candidates, &query, FilterOptions::default());
+            let again = filter_candidates(&candidates, &query, FilterOptions::default());
+            prop_assert_eq!(&first, &again);
+
+            // Spreading over threads and narrowing previous matches change nothing either
+            let matcher = Matcher::new(&query, FilterOptions::default()).unwrap();
+            let indices = (0..candidates.len()).collect();
+            let (mut parallel, _) = candidate_matches_with(&candidates, None, indices, &matcher, 0);
+            rank_candidates(&candidates, &mut parallel, TieBreak::Input, None);
+            prop_assert_eq!(&first, &parallel);
+
+            let mut incremental = IncrementalFilter::new();
+            let options = FilterOptions::default();
+            for end in 0..query.len() {
+                incremental.filter(&candidates, &query[..end], options).unwrap();
+            }
+            let narrowed = incremental.filter(&candidates, &query, options).unwrap();
+            prop_assert_eq!(&first, &narrowed);
+        }
     }
 }
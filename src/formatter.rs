@@ -9,129 +9,2362 @@
 //!
 //! - (fork) or (fork: description) - Fork of another repository
 //! - 🔒 - Private repository
+//! - 🏢 - Internal repository, visible to everyone signed in to the GitLab instance
+//! - 📦 - Archived repository, which is read-only
+//!
+//! ## Badges
+//!
+//! - [Rust] - Primary language, right after the source indicator
+//! - #cli #terminal +2 - Topics, the first few of them
+//! - ★ 1.2k - Star count, abbreviated with k/M and left out when there are none
+//! - · 3d ago - Time since the last push, can be turned off with [`FormatOptions`]
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::repo_info::RepoInfo;
+use crate::styled::{Style, StyledText};
+use crate::template::{Fields, Template};
+
+/// The widest the name column of aligned entries gets, longer names push the rest of
+/// their row to the right instead of being cut
+pub const MAX_NAME_COLUMN_WIDTH: usize = 40;
+
+/// How many topics an entry shows, the rest are counted in a `+2` marker
+pub const MAX_SHOWN_TOPICS: usize = 3;
+
+/// How many characters of a description entries show unless another cap is configured
+pub const DEFAULT_MAX_DESCRIPTION_LEN: usize = 80;
+
+/// Repositories smaller than this many kilobytes get no size badge unless another
+/// threshold is configured
+pub const DEFAULT_SIZE_THRESHOLD_KB: u64 = 50 * 1024;
+
+/// Space between the columns of aligned entries
+const COLUMN_GAP: &str = "  ";
+
+/// Repository source (GitHub, GitLab, a Gitea or Forgejo instance or Bitbucket Cloud)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepoSource {
+    GitHub,
+    GitLab,
+    Gitea,
+    Bitbucket,
+}
+
+impl RepoSource {
+    /// Every source, in the order their repositories are listed
+    pub const ALL: [RepoSource; 4] =
+        [RepoSource::GitHub, RepoSource::GitLab, RepoSource::Gitea, RepoSource::Bitbucket];
+
+    /// The name of the provider, like `GitHub`
+    pub fn name(self) -> &'static str {
+        match self {
+            RepoSource::GitHub => "GitHub",
+            RepoSource::GitLab => "GitLab",
+            RepoSource::Gitea => "Gitea",
+            RepoSource::Bitbucket => "Bitbucket",
+        }
+    }
+}
+
+/// Who can see a repository, GitLab has internal projects in between public and private
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    /// Visible to every signed in user of the instance
+    Internal,
+    Private,
+}
+
+impl Visibility {
+    /// GitHub only reports whether a repository is private
+    pub fn from_private(is_private: bool) -> Visibility {
+        if is_private {
+            Visibility::Private
+        } else {
+            Visibility::Public
+        }
+    }
+
+    /// Looks up a visibility by its name in the GitLab API, like `internal`
+    pub fn from_name(name: &str) -> Option<Visibility> {
+        match name {
+            "public" => Some(Visibility::Public),
+            "internal" => Some(Visibility::Internal),
+            "private" => Some(Visibility::Private),
+            _ => None,
+        }
+    }
+
+    /// The name of the visibility, like `internal`
+    pub fn name(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Internal => "internal",
+            Visibility::Private => "private",
+        }
+    }
+
+    /// The indicator for this visibility, empty for public repositories
+    pub fn icon(self, icons: IconSet) -> &'static str {
+        match self {
+            Visibility::Public => "",
+            Visibility::Internal => icons.internal(),
+            Visibility::Private => icons.private(),
+        }
+    }
+}
+
+/// The characters indicators are drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconSet {
+    /// Emoji and symbols like `🔒` and `★`
+    #[default]
+    Unicode,
+    /// Plain text for terminals and fonts without those symbols, like `[private]` and `*`
+    Ascii,
+}
+
+impl IconSet {
+    /// Marks private repositories
+    pub fn private(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "🔒",
+            IconSet::Ascii => "[private]",
+        }
+    }
+
+    /// Marks internal repositories
+    pub fn internal(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "🏢",
+            IconSet::Ascii => "[internal]",
+        }
+    }
+
+    /// Marks archived repositories
+    pub fn archived(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "📦",
+            IconSet::Ascii => "[archived]",
+        }
+    }
+
+    /// Marks mirrors of repositories elsewhere
+    pub fn mirror(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "⇄",
+            IconSet::Ascii => "[mirror]",
+        }
+    }
+
+    /// Marks template repositories
+    pub fn template(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "▤",
+            IconSet::Ascii => "[template]",
+        }
+    }
+
+    /// Marks repositories without any commits
+    pub fn empty(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "∅",
+            IconSet::Ascii => "[empty]",
+        }
+    }
+
+    /// Marks starred repositories of others
+    pub fn starred(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "⭐",
+            IconSet::Ascii => "[starred]",
+        }
+    }
+
+    /// Comes before the star count
+    pub fn star(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "★",
+            IconSet::Ascii => "*",
+        }
+    }
+
+    /// Comes before the open issue count
+    pub fn issues(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "⊙",
+            IconSet::Ascii => "issues",
+        }
+    }
+
+    /// Comes before the open pull or merge request count
+    pub fn merge_requests(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "⇵",
+            IconSet::Ascii => "requests",
+        }
+    }
+
+    /// Comes before the time since the last push
+    pub fn separator(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "·",
+            IconSet::Ascii => "-",
+        }
+    }
+
+    /// Go around license badges, like `‹MIT›`
+    pub fn license_quotes(self) -> (&'static str, &'static str) {
+        match self {
+            IconSet::Unicode => ("‹", "›"),
+            IconSet::Ascii => ("<", ">"),
+        }
+    }
+
+    /// Marks where text was cut
+    pub fn ellipsis(self) -> &'static str {
+        match self {
+            IconSet::Unicode => "…",
+            IconSet::Ascii => "...",
+        }
+    }
+}
+
+/// The text of every indicator, the icons of an [`IconSet`] unless the config file
+/// replaces some of them
+///
+/// An empty icon leaves its indicator out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icons {
+    pub private: String,
+    pub internal: String,
+    pub archived: String,
+    pub mirror: String,
+    pub template: String,
+    pub empty: String,
+    pub starred: String,
+    /// Starts the fork status, like `fork of rust-lang/cargo`
+    pub fork: String,
+    pub star: String,
+    pub issues: String,
+    pub merge_requests: String,
+    /// Comes before the time since the last push
+    pub separator: String,
+    pub license_start: String,
+    pub license_end: String,
+    pub ellipsis: String,
+    /// Put a Nerd Font glyph in front of languages, see [`language_glyph`]
+    pub language_glyphs: bool,
+}
+
+impl Icons {
+    /// The indicator for a visibility, empty for public repositories
+    pub fn visibility(&self, visibility: Visibility) -> &str {
+        match visibility {
+            Visibility::Public => "",
+            Visibility::Internal => &self.internal,
+            Visibility::Private => &self.private,
+        }
+    }
+
+    /// The indicators that can come right after a name, which selections are cut at
+    pub fn markers(&self) -> Vec<&str> {
+        [
+            &self.private,
+            &self.internal,
+            &self.archived,
+            &self.mirror,
+            &self.template,
+            &self.empty,
+            &self.starred,
+        ]
+        .into_iter()
+        .map(String::as_str)
+        .filter(|icon| !icon.trim().is_empty())
+        .collect()
+    }
+}
+
+impl From<IconSet> for Icons {
+    fn from(icons: IconSet) -> Self {
+        let (license_start, license_end) = icons.license_quotes();
+        Icons {
+            private: icons.private().to_string(),
+            internal: icons.internal().to_string(),
+            archived: icons.archived().to_string(),
+            mirror: icons.mirror().to_string(),
+            template: icons.template().to_string(),
+            empty: icons.empty().to_string(),
+            starred: icons.starred().to_string(),
+            fork: "fork".to_string(),
+            star: icons.star().to_string(),
+            issues: icons.issues().to_string(),
+            merge_requests: icons.merge_requests().to_string(),
+            separator: icons.separator().to_string(),
+            license_start: license_start.to_string(),
+            license_end: license_end.to_string(),
+            ellipsis: icons.ellipsis().to_string(),
+            language_glyphs: false,
+        }
+    }
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Icons::from(IconSet::default())
+    }
+}
+
+/// The label in the GitHub source tag unless another one is configured
+pub const DEFAULT_GITHUB_LABEL: &str = "GH";
+/// The label in the GitLab source tag unless another one is configured
+pub const DEFAULT_GITLAB_LABEL: &str = "GL";
+/// The label in the Gitea source tag unless another one is configured
+pub const DEFAULT_GITEA_LABEL: &str = "GT";
+/// The label in the Bitbucket source tag unless another one is configured
+pub const DEFAULT_BITBUCKET_LABEL: &str = "BB";
+
+/// The source tags, like `[GH]` or `[GL:work]`, telling instances apart
+///
+/// Selections are looked up by them, together with the indicators that can follow a
+/// name when the config file replaces the default ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLabels {
+    github: String,
+    gitlab: String,
+    gitea: String,
+    bitbucket: String,
+    markers: Vec<String>,
+}
+
+impl SourceLabels {
+    /// Checks that the labels can be told apart in formatted entries
+    pub fn new(
+        github: &str,
+        gitlab: &str,
+        gitea: &str,
+        bitbucket: &str,
+    ) -> Result<SourceLabels, String> {
+        let labels = [github, gitlab, gitea, bitbucket];
+        for label in labels {
+            if label.trim().is_empty() || label.contains(['[', ']']) {
+                return Err(format!(
+                    "Invalid source label '{}', labels can't be empty or contain brackets",
+                    label
+                ));
+            }
+        }
+        for (i, j) in distinct_pairs(labels.len()) {
+            if labels[i] == labels[j] {
+                return Err(format!(
+                    "The {} and {} labels are both '{}'",
+                    RepoSource::ALL[i].name(),
+                    RepoSource::ALL[j].name(),
+                    labels[i]
+                ));
+            }
+        }
+        Ok(SourceLabels {
+            github: format!("[{}]", github),
+            gitlab: format!("[{}]", gitlab),
+            gitea: format!("[{}]", gitea),
+            bitbucket: format!("[{}]", bitbucket),
+            markers: Vec::new(),
+        })
+    }
+
+    /// Uses whole tags instead of labels in brackets, like a Nerd Font glyph
+    ///
+    /// No tag may be part of another, the first one in an entry tells its source.
+    pub fn with_tags(
+        github: &str,
+        gitlab: &str,
+        gitea: &str,
+        bitbucket: &str,
+    ) -> Result<SourceLabels, String> {
+        let tags = [github, gitlab, gitea, bitbucket];
+        if tags.iter().any(|tag| tag.trim().is_empty()) {
+            return Err("Source tags can't be empty, selections are looked up by them".to_string());
+        }
+        for (i, j) in distinct_pairs(tags.len()) {
+            if tags[i].contains(tags[j]) || tags[j].contains(tags[i]) {
+                return Err(format!(
+                    "The {} tag '{}' and the {} tag '{}' can't be told apart",
+                    RepoSource::ALL[i].name(),
+                    tags[i],
+                    RepoSource::ALL[j].name(),
+                    tags[j]
+                ));
+            }
+        }
+        Ok(SourceLabels {
+            github: github.to_string(),
+            gitlab: gitlab.to_string(),
+            gitea: gitea.to_string(),
+            bitbucket: bitbucket.to_string(),
+            markers: Vec::new(),
+        })
+    }
+
+    /// Cuts selected names at these indicators as well, see [`Icons::markers`]
+    ///
+    /// Indicators follow the name after a space, so one made of letters doesn't cut
+    /// names that contain them.
+    pub fn markers(mut self, icons: &Icons) -> Self {
+        self.markers = icons.markers().into_iter().map(|icon| format!(" {}", icon)).collect();
+        self
+    }
+
+    /// The source tag of a source, like `[GH]`
+    pub fn tag(&self, source: RepoSource) -> &str {
+        match source {
+            RepoSource::GitHub => &self.github,
+            RepoSource::GitLab => &self.gitlab,
+            RepoSource::Gitea => &self.gitea,
+            RepoSource::Bitbucket => &self.bitbucket,
+        }
+    }
+
+    /// The repository name in a formatted entry from a source, see [`selected_name`]
+    pub fn selected_name<'a>(&self, selection: &'a str, source: RepoSource) -> &'a str {
+        let name = selected_name(selection, self.tag(source));
+        let end = self
+            .markers
+            .iter()
+            .filter_map(|marker| name.find(marker.as_str()))
+            .min()
+            .unwrap_or(name.len());
+        name[..end].trim()
+    }
+
+    /// Finds which source a formatted entry is from by the tag that comes first in it
+    pub fn source_of(&self, selection: &str) -> Option<RepoSource> {
+        RepoSource::ALL
+            .into_iter()
+            .filter_map(|source| selection.find(self.tag(source)).map(|index| (index, source)))
+            .min_by_key(|(index, _)| *index)
+            .map(|(_, source)| source)
+    }
+}
+
+impl Default for SourceLabels {
+    fn default() -> Self {
+        SourceLabels::new(
+            DEFAULT_GITHUB_LABEL,
+            DEFAULT_GITLAB_LABEL,
+            DEFAULT_GITEA_LABEL,
+            DEFAULT_BITBUCKET_LABEL,
+        )
+        .expect("the default labels differ")
+    }
+}
+
+// The index pairs of count sources that have to be told apart, every pair once
+fn distinct_pairs(count: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..count).flat_map(move |i| (i + 1..count).map(move |j| (i, j)))
+}
+
+/// When repository names are prefixed with their owner or namespace, like `myorg/api`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OwnerPrefix {
+    /// Prefix every name
+    Always,
+    /// Show bare names
+    #[default]
+    Never,
+    /// Prefix only names that appear more than once in the list, ignoring case
+    Auto,
+}
+
+impl OwnerPrefix {
+    /// Looks up a mode by its name as given on the command line
+    pub fn from_name(name: &str) -> Option<OwnerPrefix> {
+        match name {
+            "always" => Some(OwnerPrefix::Always),
+            "never" => Some(OwnerPrefix::Never),
+            "auto" => Some(OwnerPrefix::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Stands in for licenses GitHub or GitLab could not identify in badges
+pub const UNKNOWN_LICENSE: &str = "?";
+
+/// Whether a license is one the provider identified, rather than `NOASSERTION` or
+/// GitLab's `other`
+pub fn is_known_license(license: &str) -> bool {
+    let license = license.trim();
+    !license.is_empty()
+        && !license.eq_ignore_ascii_case("NOASSERTION")
+        && !license.eq_ignore_ascii_case("other")
+}
+
+/// Which repositories get a license badge, like `‹MIT›`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LicenseBadges {
+    /// No license badges
+    #[default]
+    Never,
+    /// Badges for identified licenses, unknown ones get none
+    Known,
+    /// Badges for every license, unknown ones shown as `‹?›`
+    All,
+}
+
+impl LicenseBadges {
+    /// Looks up a mode by its name as given on the command line
+    pub fn from_name(name: &str) -> Option<LicenseBadges> {
+        match name {
+            "never" => Some(LicenseBadges::Never),
+            "known" => Some(LicenseBadges::Known),
+            "all" => Some(LicenseBadges::All),
+            _ => None,
+        }
+    }
+
+    /// The text of the badge of a license, repositories without one never get a badge
+    pub fn badge(self, license: Option<&str>) -> Option<&str> {
+        let license = license?;
+        match self {
+            LicenseBadges::Never => None,
+            _ if is_known_license(license) => Some(license.trim()),
+            LicenseBadges::Known => None,
+            LicenseBadges::All => Some(UNKNOWN_LICENSE),
+        }
+    }
+}
+
+/// Options for how repositories are displayed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Append the time since the last push, like `· 3d ago`
+    pub pushed_time: bool,
+    /// Which names get an owner prefix
+    pub owner_prefix: OwnerPrefix,
+    /// The layout of each entry
+    pub template: Template,
+    /// Align entries in columns fitting this width instead of using the template
+    pub aligned_width: Option<usize>,
+    /// The text indicators are drawn with in aligned entries, templates have their own
+    pub icons: Icons,
+    /// The labels in the source tags
+    pub source_labels: SourceLabels,
+    /// Append the default branch, like `(main)`
+    pub default_branch: bool,
+    /// Shorten descriptions to this many characters before the entry is laid out,
+    /// `None` keeps them whole
+    pub max_description_len: Option<usize>,
+    /// Which repositories get a license badge
+    pub licenses: LicenseBadges,
+    /// Show the size of repositories of at least this many kilobytes
+    pub size_threshold_kb: u64,
+    /// Mark popular repositories by these tiers of their star count, off with `None`
+    pub popularity: Option<Tiers>,
+    /// Move the stars, language and push time to the right edge of the row, see
+    /// [`right_badges`]
+    pub right_aligned: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            pushed_time: true,
+            owner_prefix: OwnerPrefix::default(),
+            template: Template::default(),
+            aligned_width: None,
+            icons: Icons::default(),
+            source_labels: SourceLabels::default(),
+            default_branch: false,
+            max_description_len: Some(DEFAULT_MAX_DESCRIPTION_LEN),
+            licenses: LicenseBadges::default(),
+            size_threshold_kb: DEFAULT_SIZE_THRESHOLD_KB,
+            popularity: None,
+            right_aligned: false,
+        }
+    }
+}
+
+/// Finds the names that appear more than once, compared case-insensitively
+///
+/// The returned names are lowercase, this is the pre-pass [`OwnerPrefix::Auto`] needs
+/// to know which names are ambiguous in a list.
+pub fn duplicate_names<'a>(names: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        *counts.entry(name.to_lowercase()).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Finds for every repository the other sources with a repository of the same owner
+/// and name, compared case-insensitively, like a project mirrored to GitHub and GitLab
+///
+/// The sources of each repository are in the order they first appear in the list.
+pub fn other_sources(repos: &[RepoInfo]) -> Vec<Vec<RepoSource>> {
+    let key = |repo: &RepoInfo| (repo.owner.to_lowercase(), repo.name.to_lowercase());
+    let mut sources: HashMap<(String, String), Vec<RepoSource>> = HashMap::new();
+    for repo in repos {
+        let found = sources.entry(key(repo)).or_default();
+        if !found.contains(&repo.source) {
+            found.push(repo.source);
+        }
+    }
+    repos
+        .iter()
+        .map(|repo| {
+            sources[&key(repo)].iter().copied().filter(|&source| source != repo.source).collect()
+        })
+        .collect()
+}
+
+/// Appends `(also on GitLab)` to the entries of repositories that exist on other
+/// sources too, so mirrored projects don't only differ in their source tag
+///
+/// `displays` are the formatted entries of `repos`, in the same order.
+pub fn mark_cross_source_duplicates(repos: &[RepoInfo], displays: &mut [StyledText]) {
+    for (display, sources) in displays.iter_mut().zip(other_sources(repos)) {
+        if sources.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = sources.iter().map(|source| source.name()).collect();
+        display.push(&format!(" (also on {})", names.join(", ")), Style::Plain);
+    }
+}
+
+/// Returns the name to display, prefixed with the owner when the mode asks for it
+///
+/// `duplicates` are the lowercase names from [`duplicate_names`], only used in
+/// [`OwnerPrefix::Auto`] mode. Names without an owner are never prefixed.
+pub fn display_name(
+    name: &str,
+    owner: &str,
+    prefix: OwnerPrefix,
+    duplicates: &HashSet<String>,
+) -> String {
+    let prefixed = match prefix {
+        OwnerPrefix::Always => true,
+        OwnerPrefix::Never => false,
+        OwnerPrefix::Auto => duplicates.contains(&name.to_lowercase()),
+    };
+    if prefixed && !owner.is_empty() {
+        format!("{}/{}", owner, name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Finds the repository name in a display string that contains the source tag
+///
+/// The name comes first, so it ends at the tag or at the first indicator or badge
+/// before it. Owner prefixes are kept, like `myorg/api`.
+pub fn selected_name<'a>(selection: &'a str, source_tag: &str) -> &'a str {
+    let before_tag = selection.split_once(source_tag).map_or(selection, |(name, _)| name);
+    let end = ["🔒", "🏢", "📦", "⇄", "▤", "∅", "⭐", " [", " (", " ★", " ·"]
+        .iter()
+        .filter_map(|marker| before_tag.find(marker))
+        .min()
+        .unwrap_or(before_tag.len());
+    before_tag[..end].trim()
+}
+
+/// Formats a repository name with visibility and archived status indicators and source
+pub fn format_repo_name(
+    name: &str,
+    _is_fork: bool,
+    visibility: Visibility,
+    is_archived: bool,
+    source: RepoSource,
+) -> String {
+    // Add source, visibility and archived icons
+    let private_icon = match visibility {
+        Visibility::Public => String::new(),
+        _ => format!(" {}", visibility.icon(IconSet::Unicode)),
+    };
+    let archived_icon = if is_archived { " 📦" } else { "" };
+    let source_icon = match source {
+        RepoSource::GitHub => " [GH]",
+        RepoSource::GitLab => " [GL]",
+        RepoSource::Gitea => " [GT]",
+        RepoSource::Bitbucket => " [BB]",
+    };
+
+    format!("{}{}{}{}", name, private_icon, archived_icon, source_icon)
+}
+
+
+
+/// Formats the first [`MAX_SHOWN_TOPICS`] topics as hashtags, like `#cli #terminal +2`
+pub fn format_topics(topics: &[String]) -> String {
+    let mut shown: Vec<String> = topics
+        .iter()
+        .take(MAX_SHOWN_TOPICS)
+        .map(|topic| format!("#{}", topic))
+        .collect();
+    if topics.len() > MAX_SHOWN_TOPICS {
+        shown.push(format!("+{}", topics.len() - MAX_SHOWN_TOPICS));
+    }
+    shown.join(" ")
+}
+
+/// Formats the open issue and pull or merge request counts, like `⊙ 12 ⇵ 3`, leaving
+/// out counts that are zero or unknown
+pub fn format_open_counts(
+    open_issues: Option<u32>,
+    open_merge_requests: Option<u32>,
+    icons: &Icons,
+) -> String {
+    [(open_issues, &icons.issues), (open_merge_requests, &icons.merge_requests)]
+        .into_iter()
+        .filter_map(|(count, icon)| match count {
+            Some(count) if count > 0 => Some(format!("{} {}", icon, format_star_count(count))),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Abbreviates a star count to at most one decimal, like `999`, `1.2k` or `3M`
+///
+/// Counts are rounded to the nearest tenth of their unit, switching to the next unit
+/// when that rounds up to a thousand, so 999 950 becomes `1M` rather than `1000k`.
+pub fn format_star_count(count: u32) -> String {
+    let count = u64::from(count);
+    if count < 1000 {
+        return count.to_string();
+    }
+
+    let mut tenths = (count + 50) / 100;
+    let mut unit = 'k';
+    if tenths >= 10_000 {
+        tenths = (count + 50_000) / 100_000;
+        unit = 'M';
+    }
+
+    if tenths % 10 == 0 {
+        format!("{}{}", tenths / 10, unit)
+    } else {
+        format!("{}.{}{}", tenths / 10, tenths % 10, unit)
+    }
+}
+
+/// Formats a size in kilobytes with binary units and at most one decimal, like `512 KB`,
+/// `1.4 MB` or `2 GB`
+///
+/// Like star counts, sizes switch to the next unit when they round up to 1024 of the
+/// current one.
+pub fn format_size(size_kb: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size_kb < 1024 {
+        return format!("{} KB", size_kb);
+    }
+
+    let mut exponent = 1;
+    let tenths = loop {
+        let divisor = 1024_u64.pow(exponent as u32);
+        let tenths = (size_kb * 10 + divisor / 2) / divisor;
+        if tenths < 10_240 || exponent == UNITS.len() - 1 {
+            break tenths;
+        }
+        exponent += 1;
+    };
+
+    if tenths % 10 == 0 {
+        format!("{} {}", tenths / 10, UNITS[exponent])
+    } else {
+        format!("{}.{} {}", tenths / 10, tenths % 10, UNITS[exponent])
+    }
+}
+
+/// Formats how long ago a time was in its largest whole unit, like `5m ago` or `2y ago`
+pub fn format_relative_time(time: DateTime<Utc>) -> String {
+    format_relative_time_since(time, Utc::now())
+}
+
+/// Formats how long before `now` a time was, times in the future count as just now
+fn format_relative_time_since(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(time);
+    let days = elapsed.num_days();
+
+    if elapsed.num_minutes() < 1 {
+        "just now".to_string()
+    } else if elapsed.num_hours() < 1 {
+        format!("{}m ago", elapsed.num_minutes())
+    } else if days < 1 {
+        format!("{}h ago", elapsed.num_hours())
+    } else if days < 30 {
+        format!("{}d ago", days)
+    } else if days < 365 {
+        format!("{}mo ago", days / 30)
+    } else {
+        format!("{}y ago", days / 365)
+    }
+}
+
+/// Formats a complete repository display string with name, language and description,
+/// followed by a star badge when the repository has any stars and the time since the
+/// last push when it is known
+///
+/// This is the [default template](crate::template::DEFAULT_TEMPLATE) with descriptions cut
+/// to [`DEFAULT_MAX_DESCRIPTION_LEN`], use a [`Template`] for other layouts.
+pub fn format_repo_info(repo: &RepoInfo) -> String {
+    static TEMPLATE: OnceLock<Template> = OnceLock::new();
+    let description = truncate_description(
+        &sanitize_text(&repo.description),
+        DEFAULT_MAX_DESCRIPTION_LEN,
+        IconSet::Unicode.ellipsis(),
+    );
+    TEMPLATE.get_or_init(Template::default).render(&Fields {
+        description: &description,
+        ..Fields::from(repo)
+    })
+}
+
+/// [`format_repo_info`] for a repository given field by field
+#[allow(clippy::too_many_arguments)]
+pub fn format_repository(
+    name: &str,
+    description: &str,
+    is_fork: bool,
+    visibility: Visibility,
+    is_archived: bool,
+    source: RepoSource,
+    language: Option<&str>,
+    stars: Option<u32>,
+    pushed_at: Option<DateTime<Utc>>,
+) -> String {
+    format_repo_info(&RepoInfo {
+        description: description.to_string(),
+        is_fork,
+        fork_parent: None,
+        visibility,
+        is_archived,
+        language: language.map(str::to_string),
+        stars,
+        pushed_at,
+        ..RepoInfo::new(name, "", source)
+    })
+}
+
+/// [`format_repository`] with a private flag, public repositories are the rest
+#[deprecated(since = "0.1.0", note = "pass a `Visibility` to `format_repository`")]
+#[allow(clippy::too_many_arguments)]
+pub fn format_repository_with_private_flag(
+    name: &str,
+    description: &str,
+    is_fork: bool,
+    is_private: bool,
+    is_archived: bool,
+    source: RepoSource,
+    language: Option<&str>,
+    stars: Option<u32>,
+    pushed_at: Option<DateTime<Utc>>,
+) -> String {
+    format_repository(
+        name,
+        description,
+        is_fork,
+        Visibility::from_private(is_private),
+        is_archived,
+        source,
+        language,
+        stars,
+        pushed_at,
+    )
+}
+
+/// Formats repositories as a table: name, source, status icons, language, stars and
+/// push time each get a column as wide as its widest entry, followed by the details
+///
+/// Widths are display widths, so emoji and CJK text line up. The details are cut to
+/// fit into `max_width`, columns that are empty for every repository are left out and
+/// names are padded to at most [`MAX_NAME_COLUMN_WIDTH`].
+pub fn format_repositories_aligned(
+    repos: &[Fields],
+    max_width: usize,
+    icons: &Icons,
+) -> Vec<String> {
+    format_repositories_aligned_styled(repos, max_width, icons)
+        .iter()
+        .map(StyledText::to_plain_string)
+        .collect()
+}
+
+/// [`format_repositories_aligned`] with the styles of the default template, widths are
+/// those of the plain text
+pub fn format_repositories_aligned_styled(
+    repos: &[Fields],
+    max_width: usize,
+    icons: &Icons,
+) -> Vec<StyledText> {
+    let rows: Vec<[StyledText; 10]> =
+        repos.iter().map(|repo| aligned_columns(repo, icons)).collect();
+
+    let mut widths = [0; 10];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.to_plain_string().width());
+        }
+    }
+    widths[0] = widths[0].min(MAX_NAME_COLUMN_WIDTH);
+
+    rows.iter()
+        .zip(repos)
+        .map(|(row, repo)| {
+            let mut line = StyledText::new();
+            let mut used = 0;
+            for (cell, &width) in row.iter().zip(&widths) {
+                if width == 0 {
+                    continue;
+                }
+                if used > 0 {
+                    line.push(COLUMN_GAP, Style::Plain);
+                    used += COLUMN_GAP.len();
+                }
+                let cell_width = cell.to_plain_string().width();
+                line.append(cell);
+                line.push(&" ".repeat(width.saturating_sub(cell_width)), Style::Plain);
+                used += width.max(cell_width);
+            }
+
+            let details = [repo.details(icons), format_topics(repo.topics)]
+                .into_iter()
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let used = used + COLUMN_GAP.len();
+            if !details.is_empty() && used < max_width {
+                line.push(COLUMN_GAP, Style::Plain);
+                line.push(
+                    &truncate_to_width(&details, max_width - used, &icons.ellipsis),
+                    Style::Gray,
+                );
+            }
+            line.trim_end();
+            line
+        })
+        .collect()
+}
+
+/// The cells of an aligned entry before the details
+fn aligned_columns(repo: &Fields, icons: &Icons) -> [StyledText; 10] {
+    let mut status = StyledText::new();
+    let private = if repo.visibility == Visibility::Private { Style::Red } else { Style::Plain };
+    let flags = [
+        (true, icons.visibility(repo.visibility), private),
+        (repo.is_archived, icons.archived.as_str(), Style::Plain),
+        (repo.is_mirror, icons.mirror.as_str(), Style::Plain),
+        (repo.is_template, icons.template.as_str(), Style::Plain),
+        (repo.is_empty, icons.empty.as_str(), Style::Plain),
+        (repo.is_starred, icons.starred.as_str(), Style::Plain),
+    ];
+    let shown = flags.iter().filter(|(set, icon, _)| *set && !icon.is_empty());
+    for (i, (_, icon, style)) in shown.enumerate() {
+        if i > 0 {
+            status.push(" ", Style::Plain);
+        }
+        status.push(icon, *style);
+    }
+    let stars = match repo.stars {
+        Some(count) if count > 0 => {
+            let stars = format!("{} {}", icons.star, format_star_count(count));
+            match repo.popularity {
+                Some(marker) => format!("{} {}", stars, marker),
+                None => stars,
+            }
+        }
+        _ => String::new(),
+    };
+    let mut source = StyledText::new();
+    source.push(&repo.tag(), Style::Dim);
+    let mut pushed = StyledText::new();
+    pushed.push(&repo.pushed_at.map(format_relative_time).unwrap_or_default(), Style::Dim);
+    let mut license = StyledText::new();
+    if let Some(id) = repo.license {
+        license.push(&format!("{}{}{}", icons.license_start, id, icons.license_end), Style::Dim);
+    }
+    let mut branch = StyledText::new();
+    if let Some(default_branch) = repo.default_branch {
+        branch.push(&format!("({})", default_branch), Style::Dim);
+    }
+    [
+        StyledText::from(repo.name),
+        source,
+        status,
+        StyledText::from(format_language(repo.language.unwrap_or_default(), icons)),
+        license,
+        StyledText::from(stars),
+        StyledText::from(format_open_counts(repo.open_issues, repo.open_merge_requests, icons)),
+        StyledText::from(repo.size_kb.map(format_size).unwrap_or_default()),
+        pushed,
+        branch,
+    ]
+}
+
+/// Nerd Font glyphs of the most common languages on GitHub, by their name there
+const LANGUAGE_GLYPHS: [(&str, &str); 41] = [
+    ("Rust", "\u{e7a8}"),
+    ("Python", "\u{e73c}"),
+    ("JavaScript", "\u{e74e}"),
+    ("TypeScript", "\u{e628}"),
+    ("Go", "\u{e627}"),
+    ("Java", "\u{e738}"),
+    ("C", "\u{e61e}"),
+    ("C++", "\u{e61d}"),
+    ("C#", "\u{f031b}"),
+    ("Ruby", "\u{e739}"),
+    ("PHP", "\u{e73d}"),
+    ("Swift", "\u{e755}"),
+    ("Kotlin", "\u{e634}"),
+    ("Scala", "\u{e737}"),
+    ("Haskell", "\u{e777}"),
+    ("Clojure", "\u{e768}"),
+    ("Elixir", "\u{e62d}"),
+    ("Erlang", "\u{e7b1}"),
+    ("Lua", "\u{e620}"),
+    ("Perl", "\u{e769}"),
+    ("R", "\u{f07d4}"),
+    ("Shell", "\u{e795}"),
+    ("Dart", "\u{e798}"),
+    ("HTML", "\u{e736}"),
+    ("CSS", "\u{e749}"),
+    ("SCSS", "\u{e74b}"),
+    ("Vue", "\u{f0844}"),
+    ("Vim Script", "\u{e7c5}"),
+    ("Emacs Lisp", "\u{e632}"),
+    ("Elm", "\u{e62c}"),
+    ("Julia", "\u{e624}"),
+    ("OCaml", "\u{e67a}"),
+    ("Nix", "\u{f313}"),
+    ("Dockerfile", "\u{e7b0}"),
+    ("Markdown", "\u{e73e}"),
+    ("PowerShell", "\u{f0a0a}"),
+    ("Objective-C", "\u{e711}"),
+    ("Groovy", "\u{e775}"),
+    ("Zig", "\u{e6a9}"),
+    ("Crystal", "\u{e62f}"),
+    ("F#", "\u{e7a7}"),
+];
+
+/// The glyph of languages without one of their own, a pair of angle brackets
+const GENERIC_LANGUAGE_GLYPH: &str = "\u{f121}";
+
+/// The Nerd Font glyph of a language, ignoring case, or a generic code glyph
+///
+/// The glyphs are in the private use area, which terminals draw a single column wide.
+pub fn language_glyph(language: &str) -> &'static str {
+    LANGUAGE_GLYPHS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(language.trim()))
+        .map_or(GENERIC_LANGUAGE_GLYPH, |(_, glyph)| glyph)
+}
+
+/// The trimmed language, after its glyph when the icons have language glyphs
+pub fn format_language(language: &str, icons: &Icons) -> String {
+    let language = language.trim();
+    if icons.language_glyphs && !language.is_empty() {
+        format!("{} {}", language_glyph(language), language)
+    } else {
+        language.to_string()
+    }
+}
+
+/// Star counts from which repositories get a popularity marker, like `🔥` from 5k on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tiers {
+    /// The least stars of each tier and its marker, by ascending star count
+    tiers: Vec<(u32, String)>,
+}
+
+impl Tiers {
+    /// Creates tiers from their least star counts and markers, in any order
+    pub fn new(mut tiers: Vec<(u32, String)>) -> Result<Tiers, String> {
+        tiers.sort_by_key(|(stars, _)| *stars);
+        if let Some(pair) = tiers.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("Two popularity tiers start at {} stars", pair[0].0));
+        }
+        if tiers.iter().any(|(_, marker)| marker.trim().is_empty()) {
+            return Err("Popularity tiers need a marker".to_string());
+        }
+        Ok(Tiers { tiers })
+    }
+}
+
+impl Default for Tiers {
+    /// `☆` from 50 stars, `★` from 500 and `🔥` above 5k
+    fn default() -> Self {
+        Tiers::new(vec![
+            (50, "☆".to_string()),
+            (500, "★".to_string()),
+            (5_001, "🔥".to_string()),
+        ])
+        .expect("the default tiers are valid")
+    }
+}
+
+/// The marker of the highest tier a star count reaches, `None` below the first one
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::formatter::{popularity_marker, Tiers};
+///
+/// assert_eq!(popularity_marker(49, &Tiers::default()), None);
+/// assert_eq!(popularity_marker(1_200, &Tiers::default()), Some("★"));
+/// ```
+pub fn popularity_marker(stars: u32, tiers: &Tiers) -> Option<&str> {
+    tiers
+        .tiers
+        .iter()
+        .rev()
+        .find(|(least, _)| stars >= *least)
+        .map(|(_, marker)| marker.as_str())
+}
+
+/// The badges drawn at the right edge of the row, like `★ 321 · Rust · 3d ago`
+///
+/// The template is rendered without them, so they are not shown twice.
+pub fn right_badges(repo: &Fields, icons: &Icons) -> StyledText {
+    let badges = [
+        repo.stars.filter(|&stars| stars > 0).map(|stars| {
+            (format!("{} {}", icons.star, format_star_count(stars)), Style::Plain)
+        }),
+        repo.language
+            .map(str::trim)
+            .filter(|language| !language.is_empty())
+            .map(|language| (format_language(language, icons), Style::Plain)),
+        repo.pushed_at.map(|time| (format_relative_time(time), Style::Dim)),
+    ];
+    let mut text = StyledText::new();
+    for (i, (badge, style)) in badges.into_iter().flatten().enumerate() {
+        if i > 0 {
+            text.push(&format!(" {} ", icons.separator), Style::Dim);
+        }
+        text.push(&badge, style);
+    }
+    text
+}
+
+/// Formats repositories as newline-delimited JSON, one object per line with every field
+/// of [`RepoInfo`] under its field name
+///
+/// Each line can be read back on its own, so tools can consume the list as it streams.
+pub fn format_repositories_json(repos: &[RepoInfo]) -> String {
+    repos
+        .iter()
+        .map(|repo| serde_json::to_string(repo).expect("repositories serialize to JSON"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A column of the tab-separated output, named like the JSON fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    /// The user, organization or GitLab namespace
+    Owner,
+    Source,
+    Visibility,
+    Private,
+    Fork,
+    Archived,
+    Description,
+    Language,
+    License,
+    Topics,
+    Stars,
+    /// The page in the browser
+    Url,
+    SshUrl,
+    HttpsUrl,
+    PushedAt,
+    CreatedAt,
+}
+
+impl Column {
+    const ALL: [(&'static str, Column); 17] = [
+        ("name", Column::Name),
+        ("owner", Column::Owner),
+        ("source", Column::Source),
+        ("visibility", Column::Visibility),
+        ("private", Column::Private),
+        ("fork", Column::Fork),
+        ("archived", Column::Archived),
+        ("description", Column::Description),
+        ("language", Column::Language),
+        ("license", Column::License),
+        ("topics", Column::Topics),
+        ("stars", Column::Stars),
+        ("url", Column::Url),
+        ("ssh_url", Column::SshUrl),
+        ("https_url", Column::HttpsUrl),
+        ("pushed_at", Column::PushedAt),
+        ("created_at", Column::CreatedAt),
+    ];
+
+    /// The columns shown unless others are picked
+    pub const DEFAULT: [Column; 4] = [Column::Name, Column::Owner, Column::Source, Column::Url];
+
+    /// The name of the column in the header row and on the command line
+    pub fn name(self) -> &'static str {
+        Column::ALL
+            .iter()
+            .find(|(_, column)| *column == self)
+            .map(|(name, _)| *name)
+            .expect("every column has a name")
+    }
+
+    /// Parses a comma-separated list of column names, like `name,url,stars`
+    pub fn parse_list(list: &str) -> Result<Vec<Column>, String> {
+        list.split(',')
+            .map(|name| {
+                let name = name.trim();
+                Column::ALL
+                    .iter()
+                    .find(|(known, _)| *known == name)
+                    .map(|(_, column)| *column)
+                    .ok_or_else(|| {
+                        let known: Vec<&str> = Column::ALL.iter().map(|(name, _)| *name).collect();
+                        format!("Unknown column '{}', valid columns are {}", name, known.join(", "))
+                    })
+            })
+            .collect()
+    }
+
+    /// The value of the column for a repository, empty when it is unknown
+    fn value(self, repo: &RepoInfo) -> String {
+        let time = |time: Option<DateTime<Utc>>| {
+            time.map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+                .unwrap_or_default()
+        };
+        match self {
+            Column::Name => repo.name.clone(),
+            Column::Owner => repo.owner.clone(),
+            Column::Source => repo.source.name().to_string(),
+            Column::Visibility => repo.visibility.name().to_string(),
+            Column::Private => (repo.visibility == Visibility::Private).to_string(),
+            Column::Fork => repo.is_fork.to_string(),
+            Column::Archived => repo.is_archived.to_string(),
+            Column::Description => repo.description.trim().to_string(),
+            Column::Language => repo.language.clone().unwrap_or_default(),
+            Column::License => repo.license.clone().unwrap_or_default(),
+            Column::Topics => repo.topics.join(","),
+            Column::Stars => repo.stars.map(|stars| stars.to_string()).unwrap_or_default(),
+            Column::Url => repo.web_url.clone(),
+            Column::SshUrl => repo.ssh_url.clone(),
+            Column::HttpsUrl => repo.https_url.clone(),
+            Column::PushedAt => time(repo.pushed_at),
+            Column::CreatedAt => time(repo.created_at),
+        }
+    }
+}
+
+/// Formats repositories as tab-separated values with a header row, for `cut` and `awk`
+///
+/// Tabs and line breaks inside values are replaced by spaces so every repository stays
+/// on one row.
+pub fn format_repositories_tsv(repos: &[RepoInfo], columns: &[Column]) -> String {
+    let header = columns.iter().map(|column| column.name()).collect::<Vec<_>>().join("\t");
+    let rows = repos.iter().map(|repo| {
+        columns
+            .iter()
+            .map(|column| column.value(repo).replace(['\t', '\n', '\r'], " "))
+            .collect::<Vec<_>>()
+            .join("\t")
+    });
+    std::iter::once(header).chain(rows).collect::<Vec<_>>().join("\n")
+}
+
+/// Makes text from an API safe to show on one row: line breaks and tabs become spaces,
+/// ANSI escape sequences and other control characters are removed and runs of
+/// whitespace are collapsed into one space
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::formatter::sanitize_text;
+///
+/// assert_eq!(sanitize_text("\x1b[31mRed\x1b[0m\r\n  alert\x07"), "Red alert");
+/// ```
+pub fn sanitize_text(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                Some('[') => skip_control_sequence(&mut chars),
+                // Operating system commands and other strings end at BEL or ESC \
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Intermediate bytes, like the `(` of `\x1b(B`, come before the final one
+                Some(' '..='/') => {
+                    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                    chars.next();
+                }
+                // Otherwise the sequence is just the escape and one character
+                _ => {}
+            },
+            '\u{9b}' => skip_control_sequence(&mut chars),
+            c if c.is_whitespace() => cleaned.push(' '),
+            c if c.is_control() => {}
+            c => cleaned.push(c),
+        }
+    }
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Skips the parameters of a control sequence like `\x1b[1;31m` up to its final byte
+fn skip_control_sequence(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    for c in chars.by_ref() {
+        if ('@'..='~').contains(&c) {
+            break;
+        }
+    }
+}
+
+/// Shortens a trimmed description to at most `max_len` characters, ellipsis included,
+/// cutting after the last whole word that fits
+///
+/// A first word that is longer than the cap is cut inside the word instead.
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::formatter::truncate_description;
+///
+/// assert_eq!(truncate_description(" A fast finder ", 20, "…"), "A fast finder");
+/// let description = "A fast finder for repositories";
+/// assert_eq!(truncate_description(description, 20, "…"), "A fast finder for…");
+/// ```
+pub fn truncate_description(description: &str, max_len: usize, ellipsis: &str) -> String {
+    let description = description.trim();
+    if description.chars().count() <= max_len {
+        return description.to_string();
+    }
+
+    let kept_len = max_len.saturating_sub(ellipsis.chars().count());
+    let end = description
+        .char_indices()
+        .nth(kept_len)
+        .map_or(description.len(), |(index, _)| index);
+    let (kept, rest) = description.split_at(end);
+    // Cutting right before a space keeps the last word whole
+    let kept = if rest.starts_with(char::is_whitespace) {
+        kept
+    } else {
+        kept.rfind(char::is_whitespace).map_or(kept, |space| &kept[..space])
+    };
+    let kept = kept.trim_end_matches(|c: char| c.is_whitespace() || ",;:".contains(c));
+    format!("{}{}", kept, ellipsis)
+}
+
+/// Cuts a text to a display width, ending it with the ellipsis when anything was cut
+fn truncate_to_width(text: &str, width: usize, ellipsis: &str) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + ellipsis.width() > width {
+            break;
+        }
+        truncated.push(c);
+        used += char_width;
+    }
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_repo_name() {
+        // Regular repository (GitHub)
+        assert_eq!(format_repo_name("normal-repo", false, Visibility::Public, false, RepoSource::GitHub), "normal-repo [GH]");
+
+        // Regular repository (GitLab)
+        assert_eq!(format_repo_name("normal-repo", false, Visibility::Public, false, RepoSource::GitLab), "normal-repo [GL]");
+
+        // Forked repository - fork status is now handled in format_repository
+        assert_eq!(format_repo_name("forked-repo", true, Visibility::Public, false, RepoSource::GitHub), "forked-repo [GH]");
+
+        // Private repository
+        assert_eq!(format_repo_name("private-repo", false, Visibility::Private, false, RepoSource::GitHub), "private-repo 🔒 [GH]");
+
+        // Both forked and private - fork status is now handled in format_repository
+        assert_eq!(format_repo_name("private-fork", true, Visibility::Private, false, RepoSource::GitLab), "private-fork 🔒 [GL]");
+    }
+
+
+
+    #[test]
+    fn test_format_repo_info() {
+        let repo = RepoInfo {
+            description: "iOS client".to_string(),
+            visibility: Visibility::Private,
+            language: Some("Swift".to_string()),
+            stars: Some(42),
+            ..RepoInfo::new("mobile-app", "dima", RepoSource::GitHub)
+        };
+        assert_eq!(format_repo_info(&repo), "mobile-app 🔒 [GH] [Swift] (iOS client) ★ 42");
+        assert_eq!(
+            format_repo_info(&repo),
+            format_repository("mobile-app", "iOS client", false, Visibility::Private, false, RepoSource::GitHub, Some("Swift"), Some(42), None)
+        );
+    }
+
+    #[test]
+    fn test_format_repository() {
+        // Repository with description (GitHub)
+        assert_eq!(
+            format_repository("web-app", "Frontend application", false, Visibility::Public, false, RepoSource::GitHub, None, None, None),
+            "web-app [GH] (Frontend application)"
+        );
+
+        // Repository with description (GitLab)
+        assert_eq!(
+            format_repository("web-app", "Frontend application", false, Visibility::Public, false, RepoSource::GitLab, None, None, None),
+            "web-app [GL] (Frontend application)"
+        );
+
+        // Repository with description and fork status
+        assert_eq!(
+            format_repository("forked-api", "Backend service", true, Visibility::Public, false, RepoSource::GitHub, None, None, None),
+            "forked-api [GH] (fork: Backend service)"
+        );
+
+        // Repository with description and private status
+        assert_eq!(
+            format_repository("mobile-app", "iOS client", false, Visibility::Private, false, RepoSource::GitHub, None, None, None),
+            "mobile-app 🔒 [GH] (iOS client)"
+        );
+
+        // Repository with description, fork and private status
+        assert_eq!(
+            format_repository("game-demo", "Unity project", true, Visibility::Private, false, RepoSource::GitLab, None, None, None),
+            "game-demo 🔒 [GL] (fork: Unity project)"
+        );
+
+        // Repository with no description
+        assert_eq!(
+            format_repository("test-framework", "", false, Visibility::Public, false, RepoSource::GitHub, None, None, None),
+            "test-framework [GH]"
+        );
+
+        // Repository with no description but with fork and private status
+        assert_eq!(
+            format_repository("private-fork", "", true, Visibility::Private, false, RepoSource::GitLab, None, None, None),
+            "private-fork 🔒 [GL] (fork)"
+        );
+
+        // Repository with description containing extra whitespace
+        assert_eq!(
+            format_repository("whitespace-test", "  Description with extra spaces  ", false, Visibility::Public, false, RepoSource::GitHub, None, None, None),
+            "whitespace-test [GH] (Description with extra spaces)"
+        );
 
-use serde::{Deserialize, Serialize};
+        // Forked repository with no description
+        assert_eq!(
+            format_repository("just-fork", "", true, Visibility::Public, false, RepoSource::GitLab, None, None, None),
+            "just-fork [GL] (fork)"
+        );
+    }
 
-/// Repository source (GitHub or GitLab)
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub enum RepoSource {
-    GitHub,
-    GitLab,
-}
+    #[test]
+    fn test_duplicate_names() {
+        let duplicates = duplicate_names(["api", "docs", "web", "API", "Docs", "docs", "cli"]);
+        let expected: HashSet<String> = ["api", "docs"].iter().map(|name| name.to_string()).collect();
+        assert_eq!(duplicates, expected);
 
-/// Formats a repository name with private status indicator and source
-pub fn format_repo_name(name: &str, _is_fork: bool, is_private: bool, source: RepoSource) -> String {
-    // Add source and private icons
-    let private_icon = if is_private { " 🔒" } else { "" };
-    let source_icon = match source {
-        RepoSource::GitHub => " [GH]",
-        RepoSource::GitLab => " [GL]",
-    };
+        assert!(duplicate_names(["api", "web"]).is_empty());
+        assert!(duplicate_names([]).is_empty());
+    }
 
-    format!("{}{}{}", name, private_icon, source_icon)
-}
+    #[test]
+    fn test_display_name() {
+        let duplicates = duplicate_names(["api", "Api", "web"]);
 
+        assert_eq!(display_name("api", "myorg", OwnerPrefix::Always, &duplicates), "myorg/api");
+        assert_eq!(display_name("web", "group/subgroup", OwnerPrefix::Always, &duplicates), "group/subgroup/web");
+        assert_eq!(display_name("api", "myorg", OwnerPrefix::Never, &duplicates), "api");
 
+        // Auto only prefixes names that collide, in any case
+        assert_eq!(display_name("Api", "other", OwnerPrefix::Auto, &duplicates), "other/Api");
+        assert_eq!(display_name("web", "myorg", OwnerPrefix::Auto, &duplicates), "web");
 
-/// Formats a complete repository display string with name and description
-pub fn format_repository(name: &str, description: &str, is_fork: bool, is_private: bool, source: RepoSource) -> String {
-    let formatted_name = format_repo_name(name, is_fork, is_private, source);
+        // Nothing to prefix without an owner
+        assert_eq!(display_name("api", "", OwnerPrefix::Always, &duplicates), "api");
+    }
 
-    if is_fork {
-        if description.is_empty() {
-            format!("{} (fork)", formatted_name)
-        } else {
-            // Trim the description before formatting
-            let trimmed_description = description.trim();
-            format!("{} (fork: {})", formatted_name, trimmed_description)
+    #[test]
+    fn test_aligned_styles() {
+        let repo = Fields {
+            visibility: Visibility::Private,
+            is_archived: true,
+            ..fields("api", "Service")
+        };
+        let lines = format_repositories_aligned_styled(&[repo], 60, &IconSet::Unicode.into());
+        assert_eq!(lines[0].to_plain_string(), "api  [GH]  🔒 📦  Service");
+        let styles: Vec<(&str, Style)> = lines[0]
+            .segments()
+            .iter()
+            .map(|segment| (segment.text.as_str(), segment.style))
+            .collect();
+        assert_eq!(
+            styles,
+            vec![
+                ("api  ", Style::Plain),
+                ("[GH]", Style::Dim),
+                ("  ", Style::Plain),
+                ("🔒", Style::Red),
+                (" 📦  ", Style::Plain),
+                ("Service", Style::Gray),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_and_empty_indicators() {
+        let template = Fields {
+            is_template: true,
+            ..fields("starter", "Project skeleton")
+        };
+        let empty = Fields {
+            is_empty: true,
+            visibility: Visibility::Private,
+            ..fields("scratch", "")
+        };
+        let both = Fields {
+            is_empty: true,
+            ..template
+        };
+        let unicode = Template::default();
+        assert_eq!(unicode.render(&template), "starter ▤ [GH] (Project skeleton)");
+        assert_eq!(unicode.render(&empty), "scratch 🔒 ∅ [GH]");
+        assert_eq!(unicode.render(&both), "starter ▤ ∅ [GH] (Project skeleton)");
+
+        let ascii = Template::default_for(IconSet::Ascii);
+        assert_eq!(ascii.render(&template), "starter [template] [GH] (Project skeleton)");
+        assert_eq!(ascii.render(&empty), "scratch [private] [empty] [GH]");
+
+        let lines = format_repositories_aligned(&[template, empty], 80, &IconSet::Unicode.into());
+        assert_eq!(lines, ["starter  [GH]  ▤     Project skeleton", "scratch  [GH]  🔒 ∅"]);
+
+        for display in [unicode.render(&both), ascii.render(&empty)] {
+            assert!(["starter", "scratch"].contains(&selected_name(&display, "[GH]")));
         }
-    } else if description.is_empty() {
-        formatted_name
-    } else {
-        // Trim the description before formatting
-        let trimmed_description = description.trim();
-        format!("{} ({})", formatted_name, trimmed_description)
+        assert_eq!(selected_name("starter▤ [GH]", "[GH]"), "starter");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_cross_source_duplicates() {
+        let repo = |name: &str, owner: &str, source| RepoInfo::new(name, owner, source);
+        let repos = [
+            repo("api", "dima", RepoSource::GitHub),
+            repo("notes", "dima", RepoSource::GitHub),
+            repo("api", "dima", RepoSource::GitLab),
+            // Another owner's repository with the same name is not the same project
+            repo("notes", "team", RepoSource::GitLab),
+        ];
+        assert_eq!(
+            other_sources(&repos),
+            vec![vec![RepoSource::GitLab], vec![], vec![RepoSource::GitHub], vec![]]
+        );
+
+        let mut displays: Vec<StyledText> =
+            repos.iter().map(|repo| StyledText::from(format_repo_info(repo))).collect();
+        mark_cross_source_duplicates(&repos, &mut displays);
+        let displays: Vec<String> = displays.iter().map(StyledText::to_plain_string).collect();
+        assert_eq!(
+            displays,
+            vec![
+                "api [GH] (also on GitLab)",
+                "notes [GH]",
+                "api [GL] (also on GitHub)",
+                "notes [GL]",
+            ]
+        );
+        assert_eq!(selected_name(&displays[0], "[GH]"), "api");
+
+        // Three entries of one project, two of them on the same source and spelled
+        // differently, only point to the other source
+        let repos = [
+            repo("API", "Dima", RepoSource::GitHub),
+            repo("api", "dima", RepoSource::GitLab),
+            repo("Api", "dima", RepoSource::GitLab),
+        ];
+        assert_eq!(
+            other_sources(&repos),
+            vec![vec![RepoSource::GitLab], vec![RepoSource::GitHub], vec![RepoSource::GitHub]]
+        );
+    }
 
     #[test]
-    fn test_format_repo_name() {
-        // Regular repository (GitHub)
-        assert_eq!(format_repo_name("normal-repo", false, false, RepoSource::GitHub), "normal-repo [GH]");
+    fn test_format_internal_repository() {
+        assert_eq!(
+            format_repo_name("handbook", false, Visibility::Internal, false, RepoSource::GitLab),
+            "handbook 🏢 [GL]"
+        );
+        assert_eq!(
+            format_repository("handbook", "Team docs", false, Visibility::Internal, false, RepoSource::GitLab, None, None, None),
+            "handbook 🏢 [GL] (Team docs)"
+        );
+        assert_eq!(
+            ascii_repository("handbook", "", false, Visibility::Internal, false, RepoSource::GitLab, None, None),
+            "handbook [internal] [GL]"
+        );
+        assert_eq!(selected_name("handbook 🏢 [GL] (Team docs)", "[GL]"), "handbook");
 
-        // Regular repository (GitLab)
-        assert_eq!(format_repo_name("normal-repo", false, false, RepoSource::GitLab), "normal-repo [GL]");
+        assert_eq!(Visibility::from_name("internal"), Some(Visibility::Internal));
+        assert_eq!(Visibility::from_name("secret"), None);
+        assert_eq!(Visibility::from_private(true), Visibility::Private);
+        assert_eq!(Visibility::Public.icon(IconSet::Unicode), "");
 
-        // Forked repository - fork status is now handled in format_repository
-        assert_eq!(format_repo_name("forked-repo", true, false, RepoSource::GitHub), "forked-repo [GH]");
+        let repo = Fields {
+            visibility: Visibility::Internal,
+            ..fields("handbook", "")
+        };
+        assert_eq!(
+            format_repositories_aligned(&[repo], 40, &IconSet::Unicode.into()),
+            vec!["handbook  [GH]  🏢"]
+        );
+    }
 
-        // Private repository
-        assert_eq!(format_repo_name("private-repo", false, true, RepoSource::GitHub), "private-repo 🔒 [GH]");
+    #[test]
+    #[allow(deprecated)]
+    fn test_format_repository_with_private_flag() {
+        assert_eq!(
+            format_repository_with_private_flag("mobile-app", "iOS client", false, true, false, RepoSource::GitHub, None, None, None),
+            format_repository("mobile-app", "iOS client", false, Visibility::Private, false, RepoSource::GitHub, None, None, None)
+        );
+    }
 
-        // Both forked and private - fork status is now handled in format_repository
-        assert_eq!(format_repo_name("private-fork", true, true, RepoSource::GitLab), "private-fork 🔒 [GL]");
+    #[test]
+    fn test_source_labels() {
+        let labels = SourceLabels::default();
+        assert_eq!(labels.tag(RepoSource::GitHub), "[GH]");
+        assert_eq!(labels.tag(RepoSource::GitLab), "[GL]");
+
+        assert_eq!(labels.tag(RepoSource::Gitea), "[GT]");
+        assert_eq!(labels.source_of("tea [GT]"), Some(RepoSource::Gitea));
+        assert_eq!(labels.source_of("shop [BB] (GT [GL])"), Some(RepoSource::Bitbucket));
+
+        let labels = SourceLabels::new("GHE", "GL:work", "CB", "BB").unwrap();
+        assert_eq!(labels.tag(RepoSource::GitHub), "[GHE]");
+        assert_eq!(labels.source_of("api [GL:work] (About [GHE])"), Some(RepoSource::GitLab));
+        assert_eq!(labels.source_of("api [GHE]"), Some(RepoSource::GitHub));
+        assert_eq!(labels.source_of("api [GH]"), None);
+
+        assert!(SourceLabels::new("GH", "GH", "GT", "BB").is_err());
+        assert_eq!(
+            SourceLabels::new("GH", "GL", "GL", "BB"),
+            Err("The GitLab and Gitea labels are both 'GL'".to_string())
+        );
+        assert!(SourceLabels::new("", "GL", "GT", "BB").is_err());
+        assert!(SourceLabels::new("GH", "[GL]", "GT", "BB").is_err());
+        assert!(SourceLabels::with_tags("gh", "gl", "gt", "g").is_err());
+
+        let repo = Fields {
+            source_tag: Some("[GHE]"),
+            ..fields("api", "Service")
+        };
+        assert_eq!(Template::default().render(&repo), "api [GHE] (Service)");
+        assert_eq!(
+            format_repositories_aligned(&[repo], 40, &IconSet::Unicode.into()),
+            vec!["api  [GHE]  Service"]
+        );
     }
 
+    #[test]
+    fn test_selected_name() {
+        assert_eq!(selected_name("api 🔒 📦 [GH] [Rust] (fork: Service) ★ 3", "[GH]"), "api");
+        assert_eq!(selected_name("myorg/api [GH]", "[GH]"), "myorg/api");
+        assert_eq!(selected_name("My Project [GL] (A (nested) description)", "[GL]"), "My Project");
 
+        // Layouts from other templates
+        assert_eq!(selected_name("api🔒[GH]", "[GH]"), "api");
+        assert_eq!(selected_name("api [Rust] (Service) [GH]", "[GH]"), "api");
+        assert_eq!(selected_name("api ★ 12 · 3d ago [GH]", "[GH]"), "api");
+    }
 
     #[test]
-    fn test_format_repository() {
+    fn test_format_archived_repository() {
+        assert_eq!(format_repo_name("old-repo", false, Visibility::Public, true, RepoSource::GitHub), "old-repo 📦 [GH]");
+
+        assert_eq!(
+            format_repository("old-api", "Legacy service", false, Visibility::Public, true, RepoSource::GitHub, None, None, None),
+            "old-api 📦 [GH] (Legacy service)"
+        );
+
+        // Archived fork
+        assert_eq!(
+            format_repository("old-fork", "", true, Visibility::Public, true, RepoSource::GitLab, None, None, None),
+            "old-fork 📦 [GL] (fork)"
+        );
+
+        // Archived private repository
+        assert_eq!(
+            format_repository("old-secret", "Keys", false, Visibility::Private, true, RepoSource::GitHub, None, None, None),
+            "old-secret 🔒 📦 [GH] (Keys)"
+        );
+
+        // Archived private fork
+        assert_eq!(
+            format_repository("old-private-fork", "Game", true, Visibility::Private, true, RepoSource::GitLab, None, None, None),
+            "old-private-fork 🔒 📦 [GL] (fork: Game)"
+        );
+    }
+
+    #[test]
+    fn test_format_repository_with_language() {
+        assert_eq!(
+            format_repository("repo-searcher", "Fuzzy finder", false, Visibility::Public, false, RepoSource::GitHub, Some("Rust"), None, None),
+            "repo-searcher [GH] [Rust] (Fuzzy finder)"
+        );
+
+        // Languages with spaces stay in one badge
+        assert_eq!(
+            format_repository("notebooks", "", true, Visibility::Private, false, RepoSource::GitLab, Some("Jupyter Notebook"), Some(3), None),
+            "notebooks 🔒 [GL] [Jupyter Notebook] (fork) ★ 3"
+        );
+
+        // Empty languages add nothing
+        assert_eq!(
+            format_repository("test-framework", "", false, Visibility::Public, false, RepoSource::GitHub, Some(""), None, None),
+            "test-framework [GH]"
+        );
+    }
+
+    #[test]
+    fn test_format_relative_time() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let ago = |seconds: i64| format_relative_time_since(now - chrono::Duration::seconds(seconds), now);
+
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(59), "just now");
+        assert_eq!(ago(60), "1m ago");
+        assert_eq!(ago(59 * 60 + 59), "59m ago");
+        assert_eq!(ago(60 * 60), "1h ago");
+        assert_eq!(ago(24 * 60 * 60 - 1), "23h ago");
+        assert_eq!(ago(24 * 60 * 60), "1d ago");
+        assert_eq!(ago(29 * 24 * 60 * 60), "29d ago");
+        assert_eq!(ago(30 * 24 * 60 * 60), "1mo ago");
+        assert_eq!(ago(364 * 24 * 60 * 60), "12mo ago");
+        assert_eq!(ago(365 * 24 * 60 * 60), "1y ago");
+        assert_eq!(ago(3 * 365 * 24 * 60 * 60), "3y ago");
+
+        // Clock differences can put the push slightly into the future
+        assert_eq!(ago(-30), "just now");
+    }
+
+    #[test]
+    fn test_format_repository_with_pushed_time() {
+        let pushed_at = Utc::now() - chrono::Duration::days(3);
+        assert_eq!(
+            format_repository("web-app", "", false, Visibility::Public, false, RepoSource::GitHub, None, Some(1000), Some(pushed_at)),
+            "web-app [GH] ★ 1k · 3d ago"
+        );
+    }
+
+    #[test]
+    fn test_format_star_count() {
+        assert_eq!(format_star_count(0), "0");
+        assert_eq!(format_star_count(999), "999");
+        assert_eq!(format_star_count(1000), "1k");
+        assert_eq!(format_star_count(1234), "1.2k");
+        assert_eq!(format_star_count(10_050), "10.1k");
+        assert_eq!(format_star_count(999_949), "999.9k");
+        assert_eq!(format_star_count(999_950), "1M");
+        assert_eq!(format_star_count(1_000_000), "1M");
+        assert_eq!(format_star_count(2_450_000), "2.5M");
+        assert_eq!(format_star_count(u32::MAX), "4295M");
+    }
+
+    #[test]
+    fn test_format_repository_with_stars() {
+        assert_eq!(
+            format_repository("web-app", "Frontend application", false, Visibility::Public, false, RepoSource::GitHub, None, Some(1234), None),
+            "web-app [GH] (Frontend application) ★ 1.2k"
+        );
+
+        assert_eq!(
+            format_repository("just-fork", "", true, Visibility::Public, false, RepoSource::GitLab, None, Some(7), None),
+            "just-fork [GL] (fork) ★ 7"
+        );
+
+        // No badge without stars
+        assert_eq!(
+            format_repository("test-framework", "", false, Visibility::Public, false, RepoSource::GitHub, None, Some(0), None),
+            "test-framework [GH]"
+        );
+    }
+
+    fn fields<'a>(name: &'a str, description: &'a str) -> Fields<'a> {
+        Fields {
+            name,
+            owner: "dima",
+            description,
+            is_fork: false,
+            fork_parent: None,
+            visibility: Visibility::Public,
+            is_archived: false,
+            is_mirror: false,
+            is_template: false,
+            is_empty: false,
+            is_starred: false,
+            source: RepoSource::GitHub,
+            source_tag: None,
+            language: None,
+            license: None,
+            topics: &[],
+            stars: None,
+            popularity: None,
+            size_kb: None,
+            open_issues: None,
+            open_merge_requests: None,
+            pushed_at: None,
+            default_branch: None,
+        }
+    }
+
+    #[test]
+    fn test_format_repositories_aligned() {
+        let repos = [
+            Fields {
+                visibility: Visibility::Private,
+                language: Some("Rust"),
+                stars: Some(1234),
+                ..fields("api", "Backend service")
+            },
+            Fields {
+                source: RepoSource::GitLab,
+                language: Some("Jupyter Notebook"),
+                ..fields("notebooks", "")
+            },
+            Fields {
+                is_fork: true,
+                is_archived: true,
+                visibility: Visibility::Private,
+                ..fields("web", "Frontend")
+            },
+        ];
+        assert_eq!(
+            format_repositories_aligned(&repos, 100, &IconSet::Unicode.into()),
+            vec![
+                "api        [GH]  🔒     Rust              ★ 1.2k  Backend service",
+                "notebooks  [GL]         Jupyter Notebook",
+                "web        [GH]  🔒 📦                            fork: Frontend",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aligned_columns_use_display_width() {
+        let repos = [fields("日本語", "説明"), fields("abcdef", "plain")];
+        let lines = format_repositories_aligned(&repos, 100, &IconSet::Unicode.into());
+        assert_eq!(lines[0], "日本語  [GH]  説明");
+        assert_eq!(lines[1], "abcdef  [GH]  plain");
+        assert_eq!(lines[0].width(), lines[1].width() - 1);
+    }
+
+    #[test]
+    fn test_aligned_descriptions_are_cut_to_the_width() {
+        let repos = [fields("api", "A rather long description")];
+        assert_eq!(format_repositories_aligned(&repos, 20, &IconSet::Unicode.into()), vec!["api  [GH]  A rather…"]);
+        assert_eq!(
+            format_repositories_aligned(&repos, 100, &IconSet::Unicode.into()),
+            vec!["api  [GH]  A rather long description"]
+        );
+
+        // Wide characters are never split
+        let repos = [fields("api", "日本語の説明")];
+        assert_eq!(format_repositories_aligned(&repos, 17, &IconSet::Unicode.into()), vec!["api  [GH]  日本…"]);
+
+        // No room for the description at all
+        assert_eq!(format_repositories_aligned(&repos, 10, &IconSet::Unicode.into()), vec!["api  [GH]"]);
+    }
+
+    #[test]
+    fn test_aligned_name_column_is_capped() {
+        let long_name = "x".repeat(MAX_NAME_COLUMN_WIDTH + 10);
+        let repos = [fields(&long_name, ""), fields("api", "Service")];
+        let lines = format_repositories_aligned(&repos, 200, &IconSet::Unicode.into());
+        assert_eq!(lines[0], format!("{}  [GH]", long_name));
+        let padding = " ".repeat(MAX_NAME_COLUMN_WIDTH - 3);
+        assert_eq!(lines[1], format!("api{}  [GH]  Service", padding));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn ascii_repository(
+        name: &str,
+        description: &str,
+        is_fork: bool,
+        visibility: Visibility,
+        is_archived: bool,
+        source: RepoSource,
+        language: Option<&str>,
+        stars: Option<u32>,
+    ) -> String {
+        Template::default_for(IconSet::Ascii).render(&Fields {
+            name,
+            owner: "",
+            description,
+            is_fork,
+            fork_parent: None,
+            visibility,
+            is_archived,
+            is_mirror: false,
+            is_template: false,
+            is_empty: false,
+            is_starred: false,
+            source,
+            source_tag: None,
+            language,
+            license: None,
+            topics: &[],
+            stars,
+            popularity: None,
+            size_kb: None,
+            open_issues: None,
+            open_merge_requests: None,
+            pushed_at: None,
+            default_branch: None,
+        })
+    }
+
+    #[test]
+    fn test_format_repository_ascii() {
         // Repository with description (GitHub)
         assert_eq!(
-            format_repository("web-app", "Frontend application", false, false, RepoSource::GitHub),
+            ascii_repository("web-app", "Frontend application", false, Visibility::Public, false, RepoSource::GitHub, None, None),
             "web-app [GH] (Frontend application)"
         );
 
         // Repository with description (GitLab)
         assert_eq!(
-            format_repository("web-app", "Frontend application", false, false, RepoSource::GitLab),
+            ascii_repository("web-app", "Frontend application", false, Visibility::Public, false, RepoSource::GitLab, None, None),
             "web-app [GL] (Frontend application)"
         );
 
         // Repository with description and fork status
         assert_eq!(
-            format_repository("forked-api", "Backend service", true, false, RepoSource::GitHub),
+            ascii_repository("forked-api", "Backend service", true, Visibility::Public, false, RepoSource::GitHub, None, None),
             "forked-api [GH] (fork: Backend service)"
         );
 
         // Repository with description and private status
         assert_eq!(
-            format_repository("mobile-app", "iOS client", false, true, RepoSource::GitHub),
-            "mobile-app 🔒 [GH] (iOS client)"
+            ascii_repository("mobile-app", "iOS client", false, Visibility::Private, false, RepoSource::GitHub, None, None),
+            "mobile-app [private] [GH] (iOS client)"
         );
 
         // Repository with description, fork and private status
         assert_eq!(
-            format_repository("game-demo", "Unity project", true, true, RepoSource::GitLab),
-            "game-demo 🔒 [GL] (fork: Unity project)"
+            ascii_repository("game-demo", "Unity project", true, Visibility::Private, false, RepoSource::GitLab, None, None),
+            "game-demo [private] [GL] (fork: Unity project)"
         );
 
         // Repository with no description
         assert_eq!(
-            format_repository("test-framework", "", false, false, RepoSource::GitHub),
+            ascii_repository("test-framework", "", false, Visibility::Public, false, RepoSource::GitHub, None, None),
             "test-framework [GH]"
         );
 
         // Repository with no description but with fork and private status
         assert_eq!(
-            format_repository("private-fork", "", true, true, RepoSource::GitLab),
-            "private-fork 🔒 [GL] (fork)"
+            ascii_repository("private-fork", "", true, Visibility::Private, false, RepoSource::GitLab, None, None),
+            "private-fork [private] [GL] (fork)"
         );
 
         // Repository with description containing extra whitespace
         assert_eq!(
-            format_repository("whitespace-test", "  Description with extra spaces  ", false, false, RepoSource::GitHub),
+            ascii_repository("whitespace-test", "  Description with extra spaces  ", false, Visibility::Public, false, RepoSource::GitHub, None, None),
             "whitespace-test [GH] (Description with extra spaces)"
         );
 
         // Forked repository with no description
         assert_eq!(
-            format_repository("just-fork", "", true, false, RepoSource::GitLab),
+            ascii_repository("just-fork", "", true, Visibility::Public, false, RepoSource::GitLab, None, None),
             "just-fork [GL] (fork)"
         );
     }
+
+    #[test]
+    fn test_format_archived_repository_ascii() {
+        assert_eq!(
+            ascii_repository("old-api", "Legacy service", false, Visibility::Public, true, RepoSource::GitHub, None, None),
+            "old-api [archived] [GH] (Legacy service)"
+        );
+        assert_eq!(
+            ascii_repository("old-private-fork", "Game", true, Visibility::Private, true, RepoSource::GitLab, None, None),
+            "old-private-fork [private] [archived] [GL] (fork: Game)"
+        );
+    }
+
+    #[test]
+    fn test_format_badges_ascii() {
+        assert_eq!(
+            ascii_repository("notebooks", "", true, Visibility::Private, false, RepoSource::GitLab, Some("Jupyter Notebook"), Some(1234)),
+            "notebooks [private] [GL] [Jupyter Notebook] (fork) * 1.2k"
+        );
+
+        let pushed_at = Utc::now() - chrono::Duration::days(3);
+        let template = Template::default_for(IconSet::Ascii);
+        let repo = Fields {
+            pushed_at: Some(pushed_at),
+            ..fields("web-app", "")
+        };
+        assert_eq!(template.render(&repo), "web-app [GH] - 3d ago");
+        assert!(template.render(&repo).is_ascii());
+    }
+
+    #[test]
+    fn test_format_repositories_aligned_ascii() {
+        let repos = [
+            Fields {
+                visibility: Visibility::Private,
+                stars: Some(1234),
+                ..fields("api", "A rather long description")
+            },
+            Fields {
+                is_archived: true,
+                ..fields("web", "Frontend")
+            },
+        ];
+        assert_eq!(
+            format_repositories_aligned(&repos, 40, &IconSet::Ascii.into()),
+            vec![
+                "api  [GH]  [private]   * 1.2k  A rath...",
+                "web  [GH]  [archived]          Frontend",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_mirrors() {
+        let template = Template::default();
+        let mirror = Fields {
+            is_mirror: true,
+            ..fields("upstream", "Copy of a project")
+        };
+        assert_eq!(template.render(&mirror), "upstream ⇄ [GH] (Copy of a project)");
+
+        // Mirrored fork
+        let mirrored_fork = Fields {
+            is_fork: true,
+            visibility: Visibility::Private,
+            ..mirror
+        };
+        assert_eq!(template.render(&mirrored_fork), "upstream 🔒 ⇄ [GH] (fork: Copy of a project)");
+        assert_eq!(
+            Template::default_for(IconSet::Ascii).render(&mirrored_fork),
+            "upstream [private] [mirror] [GH] (fork: Copy of a project)"
+        );
+        assert_eq!(selected_name(&template.render(&mirrored_fork), "[GH]"), "upstream");
+
+        assert_eq!(
+            format_repositories_aligned(&[mirrored_fork, fields("api", "")], 80, &IconSet::Unicode.into()),
+            vec!["upstream  [GH]  🔒 ⇄  fork: Copy of a project", "api       [GH]"]
+        );
+    }
+
+    #[test]
+    fn test_truncate_description() {
+        // Short descriptions are only trimmed
+        assert_eq!(truncate_description("  Finder  ", 10, "…"), "Finder");
+        assert_eq!(truncate_description("exactly 10", 10, "…"), "exactly 10");
+
+        // The cut goes after the last word that fits, commas before it are dropped
+        assert_eq!(truncate_description("Fast, small and simple", 12, "…"), "Fast, small…");
+        assert_eq!(truncate_description("Fast, small and simple", 11, "…"), "Fast…");
+        assert_eq!(truncate_description("one two three", 9, "..."), "one...");
+
+        // A single word longer than the cap is cut inside the word
+        assert_eq!(truncate_description("Supercalifragilistic", 8, "…"), "Superca…");
+
+        // Multibyte characters count once and are never split
+        assert_eq!(truncate_description("Über schön 日本語のテキスト", 12, "…"), "Über schön…");
+        assert_eq!(truncate_description("日本語のテキストです", 5, "…"), "日本語の…");
+
+        let repo = RepoInfo {
+            description: "word ".repeat(30),
+            ..RepoInfo::new("api", "dima", RepoSource::GitHub)
+        };
+        let details = format!("api [GH] ({}…)", "word ".repeat(16).trim_end());
+        assert_eq!(format_repo_info(&repo), details);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 KB");
+        assert_eq!(format_size(1023), "1023 KB");
+        assert_eq!(format_size(1024), "1 MB");
+        assert_eq!(format_size(1434), "1.4 MB");
+        // 1023.96 MB rounds up to the next unit rather than to 1024 MB
+        assert_eq!(format_size(1024 * 1024 - 40), "1 GB");
+        assert_eq!(format_size(1024 * 1024 - 60), "1023.9 MB");
+        assert_eq!(format_size(2_202_010), "2.1 GB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3 TB");
+        assert_eq!(format_size(5000 * 1024 * 1024 * 1024), "5000 TB");
+    }
+
+    #[test]
+    fn test_license_badges() {
+        assert_eq!(LicenseBadges::Known.badge(Some("MIT")), Some("MIT"));
+        assert_eq!(LicenseBadges::All.badge(Some("MIT")), Some("MIT"));
+        assert_eq!(LicenseBadges::Never.badge(Some("MIT")), None);
+
+        // Unrecognized licenses are marked or left out, no license is never marked
+        assert_eq!(LicenseBadges::Known.badge(Some("NOASSERTION")), None);
+        assert_eq!(LicenseBadges::All.badge(Some("NOASSERTION")), Some(UNKNOWN_LICENSE));
+        assert_eq!(LicenseBadges::All.badge(Some("other")), Some(UNKNOWN_LICENSE));
+        assert_eq!(LicenseBadges::All.badge(None), None);
+
+        let repo = Fields {
+            language: Some("Rust"),
+            license: Some("Apache-2.0"),
+            ..fields("api", "Service")
+        };
+        assert_eq!(Template::default().render(&repo), "api [GH] [Rust] ‹Apache-2.0› (Service)");
+        let ascii = Template::default_for(IconSet::Ascii).render(&repo);
+        assert_eq!(ascii, "api [GH] [Rust] <Apache-2.0> (Service)");
+        let lines = format_repositories_aligned_styled(&[repo], 80, &IconSet::Unicode.into());
+        assert_eq!(lines[0].to_plain_string(), "api  [GH]  Rust  ‹Apache-2.0›  Service");
+        assert!(lines[0].segments().contains(&crate::styled::Segment {
+            text: "‹Apache-2.0›".to_string(),
+            style: Style::Dim
+        }));
+    }
+
+    #[test]
+    fn test_format_open_counts() {
+        assert_eq!(format_open_counts(Some(12), Some(3), &IconSet::Unicode.into()), "⊙ 12 ⇵ 3");
+        assert_eq!(format_open_counts(Some(0), Some(3), &IconSet::Unicode.into()), "⇵ 3");
+        assert_eq!(format_open_counts(Some(1500), None, &IconSet::Ascii.into()), "issues 1.5k");
+        assert_eq!(format_open_counts(Some(0), None, &IconSet::Unicode.into()), "");
+
+        let repo = Fields {
+            open_issues: Some(12),
+            open_merge_requests: Some(0),
+            ..fields("repo-searcher", "Finder")
+        };
+        let zero = Fields { open_issues: Some(0), ..repo };
+        let lines = format_repositories_aligned(&[repo, zero], 80, &IconSet::Unicode.into());
+        assert_eq!(
+            lines,
+            ["repo-searcher  [GH]  ⊙ 12  Finder", "repo-searcher  [GH]        Finder"]
+        );
+    }
+
+    #[test]
+    fn test_format_topics() {
+        let topics: Vec<String> = ["cli", "terminal", "rust", "fuzzy", "search"]
+            .iter()
+            .map(|topic| topic.to_string())
+            .collect();
+        assert_eq!(format_topics(&topics[..1]), "#cli");
+        assert_eq!(format_topics(&topics[..3]), "#cli #terminal #rust");
+        assert_eq!(format_topics(&topics[..4]), "#cli #terminal #rust +1");
+        assert_eq!(format_topics(&topics), "#cli #terminal #rust +2");
+        assert_eq!(format_topics(&[]), "");
+
+        let repo = Fields {
+            topics: &topics,
+            stars: Some(5),
+            ..fields("repo-searcher", "Finder")
+        };
+        assert_eq!(
+            Template::default().render(&repo),
+            "repo-searcher [GH] (Finder) #cli #terminal #rust +2 ★ 5"
+        );
+        assert_eq!(
+            format_repositories_aligned(&[repo], 60, &IconSet::Unicode.into()),
+            vec!["repo-searcher  [GH]  ★ 5  Finder #cli #terminal #rust +2"]
+        );
+    }
+
+    #[test]
+    fn test_selected_name_ascii() {
+        assert_eq!(selected_name("api [private] [archived] [GH] (fork) * 3 - 3d ago", "[GH]"), "api");
+    }
+
+    #[test]
+    fn test_json_output_round_trips() {
+        let mut api = RepoInfo {
+            description: "Line \"one\"\nline two".to_string(),
+            visibility: Visibility::Internal,
+            stars: Some(12),
+            license: Some("MIT".to_string()),
+            pushed_at: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+            ..RepoInfo::new("api", "group/sub", RepoSource::GitLab)
+        };
+        api.topics = vec!["cli".to_string()];
+        let repos = vec![api, RepoInfo::new("notes", "dima", RepoSource::GitHub)];
+
+        let json = format_repositories_json(&repos);
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"{"name":"api","ssh_url":"#));
+        assert!(lines[0].contains(r#""visibility":"internal""#));
+        assert!(lines[0].contains(r#""pushed_at":"2024-05-01T10:00:00Z""#));
+        assert!(lines[1].contains(r#""stars":null"#));
+        let read: Vec<RepoInfo> =
+            lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(read, repos);
+        assert_eq!(format_repositories_json(&[]), "");
+    }
+
+    #[test]
+    fn test_tsv_output() {
+        let api = RepoInfo {
+            description: "Line one\r\nline\ttwo".to_string(),
+            visibility: Visibility::Private,
+            stars: Some(12),
+            web_url: "https://gitlab.com/group/api".to_string(),
+            pushed_at: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+            ..RepoInfo::new("api", "group", RepoSource::GitLab)
+        };
+        let notes = RepoInfo::new("notes", "dima", RepoSource::GitHub);
+        let columns = Column::parse_list("name, private,description,stars,pushed_at").unwrap();
+        assert_eq!(
+            format_repositories_tsv(&[api, notes], &columns),
+            "name\tprivate\tdescription\tstars\tpushed_at\n\
+             api\ttrue\tLine one  line two\t12\t2024-05-01T10:00:00Z\n\
+             notes\tfalse\t\t\t"
+        );
+        assert_eq!(format_repositories_tsv(&[], &Column::DEFAULT), "name\towner\tsource\turl");
+    }
+
+    #[test]
+    fn test_unknown_columns_list_the_valid_ones() {
+        let error = Column::parse_list("name,stargazers").unwrap_err();
+        assert!(error.starts_with("Unknown column 'stargazers'"));
+        assert!(error.contains("name, owner, source"));
+        assert!(Column::parse_list("").is_err());
+    }
+
+    #[test]
+    fn test_sanitize_text() {
+        assert_eq!(sanitize_text("\x1b[31mError\x1b[0m: build failed"), "Error: build failed");
+        assert_eq!(sanitize_text("First line\r\nSecond\tline\r\n"), "First line Second line");
+        assert_eq!(sanitize_text("\x1b[1;38;5;208mBold\x1b[m  and   wide"), "Bold and wide");
+        // Hyperlinks keep their text, C1 controls and stray escapes disappear
+        assert_eq!(
+            sanitize_text("\x1b]8;;https://example.com\x1b\\Docs\x1b]8;;\x07 here"),
+            "Docs here"
+        );
+        assert_eq!(sanitize_text("a\u{9b}2Jb\u{85}c\x1b(Bd\x00"), "ab cd");
+        assert_eq!(sanitize_text("日本語 ★ fine"), "日本語 ★ fine");
+    }
+
+    #[test]
+    fn test_control_characters_do_not_break_up_entries() {
+        let display = format_repository(
+            "api",
+            "\x1b[31mService\x1b[0m\r\nfor things",
+            false,
+            Visibility::Public,
+            false,
+            RepoSource::GitLab,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(display, "api [GL] (Service for things)");
+    }
+
+    #[test]
+    fn test_popularity_marker_boundaries() {
+        let tiers = Tiers::default();
+        let expected = [
+            (0, None),
+            (49, None),
+            (50, Some("☆")),
+            (499, Some("☆")),
+            (500, Some("★")),
+            (5_000, Some("★")),
+            (5_001, Some("🔥")),
+            (u32::MAX, Some("🔥")),
+        ];
+        for (stars, marker) in expected {
+            assert_eq!(popularity_marker(stars, &tiers), marker, "{} stars", stars);
+        }
+
+        // Tiers can be given in any order, but not twice for the same count
+        let tiers = Tiers::new(vec![(10, "b".to_string()), (1, "a".to_string())]).unwrap();
+        assert_eq!(popularity_marker(0, &tiers), None);
+        assert_eq!(popularity_marker(1, &tiers), Some("a"));
+        assert_eq!(popularity_marker(10, &tiers), Some("b"));
+        assert!(Tiers::new(vec![(1, "a".to_string()), (1, "b".to_string())]).is_err());
+        assert!(Tiers::new(vec![(1, String::new())]).is_err());
+        assert_eq!(popularity_marker(100, &Tiers::new(Vec::new()).unwrap()), None);
+    }
+
+    #[test]
+    fn test_popularity_is_off_by_default() {
+        let repo = RepoInfo {
+            stars: Some(12_000),
+            ..RepoInfo::new("api", "dima", RepoSource::GitHub)
+        };
+        assert_eq!(format_repo_info(&repo), "api [GH] ★ 12k");
+        let fields = Fields {
+            popularity: Some("🔥"),
+            ..Fields::from(&repo)
+        };
+        assert_eq!(Template::default().render(&fields), "api [GH] 🔥 ★ 12k");
+        assert_eq!(
+            format_repositories_aligned(&[fields], 80, &IconSet::Unicode.into()),
+            vec!["api  [GH]  ★ 12k 🔥"]
+        );
+    }
+
+    #[test]
+    fn test_language_glyphs() {
+        assert_eq!(language_glyph("Rust"), "\u{e7a8}");
+        assert_eq!(language_glyph(" vim script "), "\u{e7c5}");
+        // Unknown and empty languages fall back to the generic glyph
+        assert_eq!(language_glyph("Brainfuck"), GENERIC_LANGUAGE_GLYPH);
+        assert_eq!(language_glyph(""), GENERIC_LANGUAGE_GLYPH);
+
+        // Every glyph takes one column, so aligned columns and cut rows stay in place
+        for (_, glyph) in LANGUAGE_GLYPHS.iter().chain([&("", GENERIC_LANGUAGE_GLYPH)]) {
+            assert_eq!(glyph.width(), 1, "{:?}", glyph);
+            assert_eq!(glyph.chars().count(), 1);
+        }
+
+        let icons = Icons {
+            language_glyphs: true,
+            ..Icons::default()
+        };
+        assert_eq!(format_language("Rust ", &icons), "\u{e7a8} Rust");
+        assert_eq!(format_language("Brainfuck", &icons), "\u{f121} Brainfuck");
+        assert_eq!(format_language(" ", &icons), "");
+        assert_eq!(format_language("Rust", &Icons::default()), "Rust");
+
+        let repo = RepoInfo {
+            language: Some("Rust".to_string()),
+            ..RepoInfo::new("api", "dima", RepoSource::GitHub)
+        };
+        assert_eq!(
+            Template::default_with(&icons).render(&Fields::from(&repo)),
+            "api [GH] [\u{e7a8} Rust]"
+        );
+        let lines = format_repositories_aligned(&[Fields::from(&repo)], 80, &icons);
+        assert_eq!(lines, vec!["api  [GH]  \u{e7a8} Rust"]);
+    }
 }
@@ -1,30 +1,212 @@
-use std::io::{self, stdin, stdout, Write};
-use std::process;
+use std::fs::OpenOptions;
+use std::io::{self, stdout, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use termion::clear;
 use termion::color;
 use termion::cursor;
-use termion::event::Key;
-use termion::input::TermRead;
+use termion::event::{Event, Key};
+use termion::input::{Events, TermRead};
 use termion::raw::IntoRawMode;
 use termion::screen::IntoAlternateScreen;
 use termion::style;
-use termion as terminal;
+use termion::AsyncReader;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::filter;
+use crate::clipboard;
+use crate::styled::{Style, StyledText};
+use crate::filter::{
+    self, Candidate, CaseMode, FilterOptions, MatchMode, Matcher, Score, TieBreak,
+};
 
 // Custom UI for displaying and filtering repositories
 pub struct FuzzyFinder {
     items: Vec<String>,
-    filtered_items: Vec<String>,
+    /// How each of `items` is drawn, `None` for plain items
+    styled_items: Vec<Option<StyledText>>,
+    /// What the filter matches for each of `items`
+    candidates: Vec<Candidate>,
+    /// Indices into `items` and scores of the matches in display order, the scores are
+    /// used to merge appended matches into the ranking
+    filtered_matches: Vec<(usize, Score)>,
+    /// Lets a query extended by typing only re-check the previous matches
+    incremental: filter::IncrementalFilter,
+    /// Number of matches including those not loaded into `filtered_matches`
+    match_count: usize,
+    /// How many of the best matches are loaded, grown when scrolling to the end
+    result_limit: usize,
+    match_mode: MatchMode,
+    case_mode: CaseMode,
+    tie_break: TieBreak,
+    typo_tolerance: bool,
+    prior_weight: f32,
     query: String,
+    /// Position of the cursor in the query, in characters rather than bytes
     cursor_pos: usize,
     selected_index: usize,
     max_display: usize,
+    render_interval: Duration,
+    status_area_height: usize,
+    prompt: String,
+    accept_query_key: Key,
+    placeholder: String,
+    no_matches_text: String,
+    no_items_text: String,
+    loading_text: String,
+    ellipsis: String,
+    track_best_match: Option<bool>,
+    best_match_suspended: bool,
+    preview_visible: bool,
+    on_preview_toggle: Option<PreviewToggleCallback>,
+    ctrl_c_copies_query: bool,
+    debounce_threshold: Duration,
+    debounce_delay: Duration,
+    last_filter_duration: Duration,
+    pending_filter_since: Option<Instant>,
+    debug_input_log: Option<PathBuf>,
+    detail_provider: Option<DetailProvider>,
+    intents: Vec<(Key, String)>,
+    view_stack: Vec<ViewState>,
     scroll_offset: usize,
     status_message: Option<String>,
     error_message: Option<String>,
+    /// Error of the current query, such as an invalid regex, shown instead of `error_message`
+    filter_error: Option<String>,
+    /// First syntax warning of the current query, shown in yellow instead of the status
+    query_warning: Option<String>,
+    events: Option<Events<AsyncReader>>,
+    /// Changes sent while the finder runs, see [`FuzzyFinder::set_update_receiver`]
+    updates: Option<Receiver<FinderUpdate>>,
+    /// Whether more items are still being sent
+    loading: bool,
+    last_frame: Frame,
+    last_size: (u16, u16),
+    last_scroll_offset: usize,
+    action_menu: Option<usize>,
+    horizontal_offset: usize,
+}
+
+/// Number of columns Alt+Left/Alt+Right scroll the selected row by
+const HORIZONTAL_SCROLL_STEP: isize = 10;
+
+/// How many screens of the best matches are loaded at first, sorting all matches
+/// of a short query would be wasted on a list only a screen of it is shown from
+const RESULT_LIMIT_SCREENS: usize = 5;
+
+/// Terminals narrower than this never show the preview pane
+const MIN_PREVIEW_TERMINAL_WIDTH: usize = 40;
+
+/// Maps the selected item to the details shown in the footer
+type DetailProvider = Box<dyn Fn(&str) -> Option<String>>;
+
+/// Callback invoked with the new visibility whenever the preview pane is toggled
+pub type PreviewToggleCallback = Arc<dyn Fn(bool) + Send + Sync>;
+
+/// Actions offered by the Alt+Enter menu for the selected item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    OpenInBrowser,
+    CopyUrl,
+    Clone,
+    ShowDetails,
+}
+
+impl Action {
+    /// All actions in the order they are listed in the menu
+    pub const ALL: [Action; 4] = [
+        Action::OpenInBrowser,
+        Action::CopyUrl,
+        Action::Clone,
+        Action::ShowDetails,
+    ];
+
+    /// Label shown in the action menu
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::OpenInBrowser => "Open in browser",
+            Action::CopyUrl => "Copy URL",
+            Action::Clone => "Clone",
+            Action::ShowDetails => "Show details",
+        }
+    }
+}
+
+/// Outcome of a finder run, each variant carries the query at accept time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinderResult {
+    /// An item was accepted with Enter
+    Selected { item: String, query: String },
+    /// An action was chosen for an item from the Alt+Enter menu
+    Action {
+        item: String,
+        action: Action,
+        query: String,
+    },
+    /// An item was accepted with a key bound to an intent
+    Intent {
+        item: String,
+        intent: String,
+        query: String,
+    },
+    /// The literal query was accepted, regardless of any matches
+    Query(String),
+    /// The user asked to export the currently filtered items, in display order
+    ExportList(Vec<String>),
+    /// The user left the finder with Esc
+    Cancelled,
+    /// The user interrupted the finder with Ctrl+C
+    Interrupted,
+}
+
+impl FinderResult {
+    /// Returns the query that was typed when the result was accepted, empty when aborted
+    pub fn query(&self) -> &str {
+        match self {
+            FinderResult::Selected { query, .. } => query,
+            FinderResult::Action { query, .. } => query,
+            FinderResult::Intent { query, .. } => query,
+            FinderResult::Query(query) => query,
+            FinderResult::ExportList(_) | FinderResult::Cancelled | FinderResult::Interrupted => "",
+        }
+    }
+}
+
+/// A change sent to a running finder from another thread, see
+/// [`FuzzyFinder::set_update_receiver`]
+pub enum FinderUpdate {
+    /// Items drawn in styles added to the end of the list
+    Append(Vec<(StyledText, Candidate)>),
+    /// Items drawn in styles that replace the whole list
+    Replace(Vec<(StyledText, Candidate)>),
+    Status(Option<String>),
+    Error(Option<String>),
+    /// Every item was sent
+    LoadingComplete,
+}
+
+/// View saved before a mode change
+struct ViewState {
+    query: String,
+    selected_item: Option<String>,
+    scroll_offset: usize,
+}
+
+/// Screen content of a single render pass
+#[derive(Default)]
+struct Frame {
+    rows: Vec<String>,
+    cursor: (u16, u16),
+}
+
+/// Result of handling a single key press
+enum KeyOutcome {
+    /// Keep reading input
+    Continue,
+    /// The finder finished with the given result
+    Accept(FinderResult),
 }
 
 impl FuzzyFinder {
@@ -34,35 +216,256 @@ impl FuzzyFinder {
         screen.flush().unwrap();
     }
 
-    // Helper method to exit the program
-    fn exit_program<W: Write>(screen: &mut W, message: &str) -> ! {
-        Self::cleanup_terminal(screen);
-        let _ = screen; // Mark screen as used without trying to drop the reference
-        println!("{}", message);
-        process::exit(0);
+    /// Creates a finder over the given items with the default configuration
+    pub fn new(items: Vec<String>) -> Self {
+        FuzzyFinderBuilder::new().build(items)
     }
 
-    pub fn new(items: Vec<String>) -> Self {
-        let filtered_items = items.clone();
-        let max_display = 10; // Number of items to display at once
+    /// Returns a builder to configure a finder before creating it
+    pub fn builder() -> FuzzyFinderBuilder {
+        FuzzyFinderBuilder::new()
+    }
 
-        Self {
-            items,
-            filtered_items,
-            query: String::new(),
-            cursor_pos: 0,
-            selected_index: 0,
-            max_display,
-            scroll_offset: 0,
-            status_message: None,
-            error_message: None,
-        }
+    /// Returns the current query
+    pub fn query(&self) -> &str {
+        &self.query
     }
 
     /// Updates the items list and refreshes the display
     pub fn update_items(&mut self, new_items: Vec<String>) {
-        self.items = new_items;
+        self.update_candidates(text_candidates(new_items));
+    }
+
+    /// Updates the items list with the fields the filter matches for each item, while
+    /// the items themselves stay what is displayed
+    pub fn update_candidates(&mut self, new_items: Vec<(String, Candidate)>) {
+        (self.items, self.candidates) = new_items.into_iter().unzip();
+        self.styled_items = vec![None; self.items.len()];
+        self.incremental.reset();
+        self.update_filter();
+    }
+
+    /// Updates the items list with items drawn in styles, the filter, the preview and
+    /// the result only see their plain text
+    pub fn update_styled_candidates(&mut self, new_items: Vec<(StyledText, Candidate)>) {
+        let (styled, candidates): (Vec<StyledText>, Vec<Candidate>) =
+            new_items.into_iter().unzip();
+        let plain = styled.iter().map(StyledText::to_plain_string);
+        self.update_candidates(plain.zip(candidates).collect());
+        self.styled_items = styled.into_iter().map(Some).collect();
+    }
+
+    /// Appends items to the list, only filtering the new entries against the current query
+    pub fn append_items(&mut self, new_items: Vec<String>) {
+        self.append_candidates(text_candidates(new_items));
+    }
+
+    /// Appends items with the fields the filter matches for them, only filtering the
+    /// new entries against the current query
+    pub fn append_candidates(&mut self, new_items: Vec<(String, Candidate)>) {
+        let offset = self.items.len();
+        let (new_items, new_candidates): (Vec<String>, Vec<Candidate>) =
+            new_items.into_iter().unzip();
+        self.styled_items.resize(offset + new_items.len(), None);
+        self.items.extend(new_items);
+        self.candidates.extend(new_candidates);
+        self.incremental.candidates_appended();
+
+        // An invalid query keeps the previous results, which the new items are not part of
+        let options = FilterOptions {
+            limit: None,
+            ..self.filter_options()
+        };
+        let new_candidates = &self.candidates[offset..];
+        let matches = match filter::try_filter_candidates(new_candidates, &self.query, options) {
+            Ok(matches) if self.filter_error.is_none() => matches,
+            _ => Vec::new(),
+        };
+        let loaded = self.filtered_matches.len();
+        let cut_off = loaded < self.match_count;
+        self.match_count += matches.len();
+        let matches = matches.into_iter().map(|(index, score)| (offset + index, score));
+
+        // Both lists are ranked, so merge them and let existing items win full ties,
+        // the same order a full filter pass would produce
+        let mut existing = std::mem::take(&mut self.filtered_matches).into_iter().peekable();
+        let mut matches = matches.peekable();
+        let mut merged = Vec::with_capacity(existing.len() + matches.len());
+
+        while let Some(&(new_index, new_score)) = matches.peek() {
+            let keeps_existing = existing.peek().is_some_and(|&(index, score)| {
+                let tie = self.tie_break.compare(
+                    self.candidates[index].primary_text(),
+                    self.candidates[new_index].primary_text(),
+                );
+                score > new_score || (score == new_score && tie != std::cmp::Ordering::Greater)
+            });
+            merged.push(if keeps_existing {
+                existing.next().unwrap()
+            } else {
+                matches.next().unwrap()
+            });
+        }
+        merged.extend(existing);
+
+        // Matches that were not loaded could rank above the new ones past this point
+        if cut_off {
+            merged.truncate(loaded);
+        }
+        self.set_filtered(merged);
+    }
+
+    /// Appends items drawn in styles, only filtering the new entries against the
+    /// current query
+    pub fn append_styled_candidates(&mut self, new_items: Vec<(StyledText, Candidate)>) {
+        let offset = self.items.len();
+        let (styled, candidates): (Vec<StyledText>, Vec<Candidate>) =
+            new_items.into_iter().unzip();
+        let plain = styled.iter().map(StyledText::to_plain_string);
+        self.append_candidates(plain.zip(candidates).collect());
+        self.styled_items.truncate(offset);
+        self.styled_items.extend(styled.into_iter().map(Some));
+    }
+
+    /// Replaces the filtered list with the given ranked matches
+    fn set_filtered(&mut self, matches: Vec<(usize, Score)>) {
+        self.filtered_matches = matches;
+    }
+
+    /// Registers a function mapping an item to details shown in a footer while it is
+    /// selected, for example its URL; returning `None` hides the footer
+    pub fn set_detail_provider(&mut self, provider: impl Fn(&str) -> Option<String> + 'static) {
+        self.detail_provider = Some(Box::new(provider));
+    }
+
+    /// Lets another thread change the items and messages while the finder runs, like
+    /// a fetch sending pages as they arrive
+    ///
+    /// The finder counts as loading until [`FinderUpdate::LoadingComplete`] is sent or
+    /// the sender is dropped. The selected item stays selected as items come in, unless
+    /// the selection follows the best match.
+    pub fn set_update_receiver(&mut self, updates: Receiver<FinderUpdate>) {
+        self.updates = Some(updates);
+        self.loading = true;
+    }
+
+    /// Whether the thread sending updates has not sent every item yet
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    /// Applies the updates sent so far, returns whether there were any
+    fn apply_updates(&mut self) -> bool {
+        let mut applied = false;
+        while let Some(updates) = &self.updates {
+            let update = match updates.try_recv() {
+                Ok(update) => update,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.updates = None;
+                    self.loading = false;
+                    break;
+                }
+            };
+            match update {
+                FinderUpdate::Append(items) => {
+                    self.keep_selection(|finder| finder.append_styled_candidates(items))
+                }
+                FinderUpdate::Replace(items) => {
+                    self.keep_selection(|finder| finder.update_styled_candidates(items))
+                }
+                FinderUpdate::Status(message) => self.set_status_message(message),
+                FinderUpdate::Error(message) => self.set_error_message(message),
+                FinderUpdate::LoadingComplete => self.loading = false,
+            }
+            applied = true;
+        }
+        applied
+    }
+
+    /// Changes the items while keeping the selected item selected, wherever it moved to
+    fn keep_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        let selected = self.filtered_item(self.selected_index).cloned();
+        change(self);
+        if self.tracks_best_match() && !self.best_match_suspended {
+            return;
+        }
+        let position = selected.and_then(|selected| {
+            let mut matches = self.filtered_matches.iter();
+            matches.position(|&(index, _)| self.items[index] == selected)
+        });
+        if let Some(index) = position {
+            self.selected_index = index;
+            self.scroll_offset = self
+                .scroll_offset
+                .min(index)
+                .max((index + 1).saturating_sub(self.max_display));
+        }
+    }
+
+    /// Remembers the current view before a mode change, such as toggling a filter or
+    /// the sort order, so [`FuzzyFinder::restore_view`] can return to it
+    pub fn save_view(&mut self) {
+        self.view_stack.push(ViewState {
+            query: self.query.clone(),
+            selected_item: self.filtered_item(self.selected_index).cloned(),
+            scroll_offset: self.scroll_offset,
+        });
+    }
+
+    /// Returns to the most recently saved view when the mode is toggled back
+    ///
+    /// The view is only restored if its selected item still exists, returns whether
+    /// it was restored.
+    pub fn restore_view(&mut self) -> bool {
+        let Some(view) = self.view_stack.pop() else {
+            return false;
+        };
+
+        let Some(selected_item) = view.selected_item else {
+            return false;
+        };
+        if !self.items.contains(&selected_item) {
+            return false;
+        }
+
+        self.cursor_pos = view.query.chars().count();
+        self.query = view.query;
         self.update_filter();
+
+        // The selection may rank below the loaded matches
+        let position = |finder: &Self| {
+            let mut matches = finder.filtered_matches.iter();
+            matches.position(|&(index, _)| finder.items[index] == selected_item)
+        };
+        if position(self).is_none() {
+            self.load_all_matches();
+        }
+        let Some(index) = position(self) else {
+            return false;
+        };
+        self.selected_index = index;
+        self.horizontal_offset = 0;
+
+        // Keep the old scroll position as long as the selection stays visible
+        self.scroll_offset = view
+            .scroll_offset
+            .min(index)
+            .max((index + 1).saturating_sub(self.max_display));
+        true
+    }
+
+    /// Returns the loaded items matching the current query, in display order
+    ///
+    /// With many matches only the best few screens are loaded, more are loaded when
+    /// scrolling to the end.
+    pub fn filtered_items(&self) -> Vec<&str> {
+        self.filtered_matches.iter().map(|&(index, _)| self.items[index].as_str()).collect()
+    }
+
+    /// The item shown at `position` of the filtered list
+    fn filtered_item(&self, position: usize) -> Option<&String> {
+        self.filtered_matches.get(position).map(|&(index, _)| &self.items[index])
     }
 
     /// Sets a status message to be displayed in the UI
@@ -75,16 +478,126 @@ impl FuzzyFinder {
         self.error_message = message;
     }
 
+    /// Options passed to the filter for the current modes
+    fn filter_options(&self) -> FilterOptions {
+        FilterOptions {
+            match_mode: self.match_mode,
+            case_mode: self.case_mode,
+            tie_break: self.tie_break,
+            typo_tolerance: self.typo_tolerance,
+            limit: Some(self.result_limit),
+            prior_weight: self.prior_weight,
+            ..FilterOptions::default()
+        }
+    }
+
+    /// Loads twice as many of the best matches as before, keeping the selection
+    fn load_more_matches(&mut self) {
+        self.load_matches(self.result_limit.saturating_mul(2));
+    }
+
+    /// Loads every match, for when the whole list is needed rather than its top
+    fn load_all_matches(&mut self) {
+        self.load_matches(usize::MAX);
+    }
+
+    fn load_matches(&mut self, limit: usize) {
+        if self.filtered_matches.len() >= self.match_count || self.filter_error.is_some() {
+            return;
+        }
+        self.result_limit = limit;
+        let options = self.filter_options();
+        if let Ok(matches) = self.incremental.filter(&self.candidates, &self.query, options) {
+            self.set_filtered(matches);
+        }
+    }
+
+    /// Switches to the next case mode and refilters
+    fn cycle_case_mode(&mut self) {
+        self.case_mode = self.case_mode.next();
+        self.update_filter();
+    }
+
+    /// Whether the selection should snap to the top-ranked match after filtering
+    fn tracks_best_match(&self) -> bool {
+        // Only a ranking filter has a best match worth following by default
+        self.track_best_match
+            .unwrap_or(self.match_mode == MatchMode::Fuzzy)
+    }
+
+    /// Byte offset of the cursor in the query, for editing it
+    fn cursor_byte(&self) -> usize {
+        self.query
+            .char_indices()
+            .nth(self.cursor_pos)
+            .map_or(self.query.len(), |(index, _)| index)
+    }
+
+    /// Re-filters after the query was edited, resuming best match tracking
+    ///
+    /// When the previous filter pass was slow, filtering is deferred until typing
+    /// pauses so the query itself stays responsive.
+    fn query_changed(&mut self) {
+        self.best_match_suspended = false;
+        if self.last_filter_duration > self.debounce_threshold {
+            self.pending_filter_since = Some(Instant::now());
+        } else {
+            self.update_filter();
+        }
+    }
+
+    /// Runs a deferred filter pass once no key arrived for the debounce delay
+    fn apply_debounced_filter(&mut self) -> bool {
+        match self.pending_filter_since {
+            Some(since) if since.elapsed() >= self.debounce_delay => {
+                self.update_filter();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs a deferred filter pass immediately, before the filtered list is used
+    fn flush_pending_filter(&mut self) {
+        if self.pending_filter_since.is_some() {
+            self.update_filter();
+        }
+    }
+
     fn update_filter(&mut self) {
-        // Use the filter_human function to filter items based on query
-        self.filtered_items = filter::filter_human(&self.items, &self.query, |s| s.clone());
+        let started = Instant::now();
+
+        self.result_limit = RESULT_LIMIT_SCREENS * self.max_display;
+        match Matcher::new(&self.query, self.filter_options()) {
+            Ok(matcher) => {
+                let matches = self.incremental.filter_with(&self.candidates, &matcher);
+                self.set_filtered(matches);
+                self.match_count = self.incremental.match_count();
+                self.filter_error = None;
+                // Warnings only explain how the query was read, the results still apply
+                self.query_warning = matcher.warnings().first().map(ToString::to_string);
+            }
+            // Keep showing the previous results while the query is not valid
+            Err(e) => {
+                self.filter_error = Some(format!("Invalid regex: {}", e));
+                self.query_warning = None;
+            }
+        }
+        self.last_filter_duration = started.elapsed();
+        self.pending_filter_since = None;
+        self.horizontal_offset = 0;
+
+        // Snap to the best match unless the user moved the selection manually
+        if self.tracks_best_match() && !self.best_match_suspended {
+            self.selected_index = 0;
+        }
 
         // Reset selection if it's out of bounds
-        if self.selected_index >= self.filtered_items.len() {
-            self.selected_index = if self.filtered_items.is_empty() {
+        if self.selected_index >= self.filtered_matches.len() {
+            self.selected_index = if self.filtered_matches.is_empty() {
                 0
             } else {
-                self.filtered_items.len() - 1
+                self.filtered_matches.len() - 1
             };
         }
 
@@ -97,8 +610,9 @@ impl FuzzyFinder {
     }
 
     fn move_cursor_up(&mut self) {
-        if !self.filtered_items.is_empty() && self.selected_index > 0 {
+        if !self.filtered_matches.is_empty() && self.selected_index > 0 {
             self.selected_index -= 1;
+            self.horizontal_offset = 0;
 
             // Adjust scroll offset if needed
             if self.selected_index < self.scroll_offset {
@@ -107,9 +621,30 @@ impl FuzzyFinder {
         }
     }
 
+    /// Scrolls the text of the selected row horizontally by the given number of columns
+    fn scroll_selected_horizontally(&mut self, columns: isize) {
+        let Some(item) = self.filtered_item(self.selected_index) else {
+            return;
+        };
+
+        if columns < 0 {
+            self.horizontal_offset = self.horizontal_offset.saturating_sub(columns.unsigned_abs());
+        } else {
+            // Only scroll further while text is still cut off on the right
+            let window = item_text_width(self.list_width(self.last_size.0 as usize));
+            if self.horizontal_offset + window < item.width() {
+                self.horizontal_offset += columns as usize;
+            }
+        }
+    }
+
     fn move_cursor_down(&mut self) {
-        if !self.filtered_items.is_empty() && self.selected_index < self.filtered_items.len() - 1 {
+        if self.selected_index + 1 >= self.filtered_matches.len() {
+            self.load_more_matches();
+        }
+        if self.selected_index + 1 < self.filtered_matches.len() {
             self.selected_index += 1;
+            self.horizontal_offset = 0;
 
             // Adjust scroll offset if needed
             if self.selected_index >= self.scroll_offset + self.max_display {
@@ -118,279 +653,605 @@ impl FuzzyFinder {
         }
     }
 
-    fn render<W: Write>(&self, screen: &mut W) -> io::Result<()> {
-        // Get terminal size
-        let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+    /// Width of the list column, which shrinks while the preview pane is shown
+    fn list_width(&self, width: usize) -> usize {
+        if self.preview_visible && width >= MIN_PREVIEW_TERMINAL_WIDTH {
+            width / 2
+        } else {
+            width
+        }
+    }
 
-        // Clear screen
-        write!(screen, "{}{}", clear::All, cursor::Goto(1, 1))?;
+    /// Shows or hides the preview pane
+    fn toggle_preview(&mut self) {
+        self.preview_visible = !self.preview_visible;
 
-        // Calculate available space for items (accounting for prompt and status lines)
-        let available_lines = height as usize - 3; // Prompt line (with input) + status line + separator line
+        // The list width changes, so every row has to be truncated and drawn again
+        self.last_frame = Frame::default();
 
-        // Adjust max_display based on available space
-        let display_count = std::cmp::min(available_lines, self.filtered_items.len());
-        let end_idx = std::cmp::min(
-            self.scroll_offset + display_count,
-            self.filtered_items.len(),
-        );
+        if let Some(on_toggle) = &self.on_preview_toggle {
+            on_toggle(self.preview_visible);
+        }
+    }
+
+    /// Composes the complete screen content, one string per terminal row
+    fn compose(&self, width: u16, height: u16) -> Frame {
+        let width = width as usize;
+        let height = height as usize;
+
+        // Reserve space for status messages
+        let status_area_height = self.status_area_height;
+
+        // Ask the caller for details about the selected item only
+        let detail = self.detail_provider.as_ref().and_then(|provider| {
+            self.filtered_item(self.selected_index).and_then(|item| provider(item))
+        });
+        let footer_height = usize::from(detail.is_some());
+
+        // Calculate available space for items (accounting for the status area,
+        // the footer, the separator line and the prompt line)
+        let available_lines = height.saturating_sub(status_area_height + footer_height + 2);
+
+        let mut rows = Vec::with_capacity(height);
+        let mut row_widths = Vec::with_capacity(available_lines);
+        let list_width = self.list_width(width);
 
         // Display items
+        let end_idx = std::cmp::min(
+            self.scroll_offset + available_lines,
+            self.filtered_matches.len(),
+        );
         for i in self.scroll_offset..end_idx {
-            let item = &self.filtered_items[i];
-
-            // Calculate available width for text (accounting for the prefix)
-            let prefix_len = 2; // Both "> " and "  " are 2 characters
-            let available_width = width as usize - prefix_len - 5; // Extra buffer for emojis and safety
+            let index = self.filtered_matches[i].0;
+            let item = &self.items[index];
 
-            // Truncate item text if it's too long
-            let display_text = if item.chars().count() > available_width {
-                // Truncate and add ellipsis, being careful with multibyte characters like emojis
-                let mut truncated = String::new();
-                let mut char_count = 0;
+            let available_width = item_text_width(list_width);
 
-                for c in item.chars() {
-                    if char_count >= available_width - 1 {
-                        break;
-                    }
-                    truncated.push(c);
-                    char_count += 1;
-                }
+            // Right-aligned parts go to the edge of the list, unless it is too narrow for them
+            let laid_out = self.styled_items[index]
+                .as_ref()
+                .and_then(|styled| styled.layout_right(available_width, &self.ellipsis));
 
-                format!("{truncated}…")
+            // Truncate item text if it's too long, the selected row can be scrolled horizontally
+            let truncated = item.chars().count() > available_width;
+            let kept = available_width.saturating_sub(self.ellipsis.chars().count());
+            let display_text = if let Some(line) = laid_out
+                .as_ref()
+                .filter(|_| i != self.selected_index || self.horizontal_offset == 0)
+            {
+                line.to_plain_string()
+            } else if i == self.selected_index {
+                scroll_window(item, self.horizontal_offset, available_width, &self.ellipsis)
+            } else if truncated {
+                // Truncate and add ellipsis, being careful with multibyte characters like emojis
+                let truncated: String = item.chars().take(kept).collect();
+                format!("{truncated}{}", self.ellipsis)
             } else {
                 item.clone()
             };
 
-            // Highlight selected item
+            // Highlight selected item, the others are drawn in their styles if they have any
             if i == self.selected_index {
-                write!(
-                    screen,
+                rows.push(format!(
                     "{}{}> {}{}",
                     color::Fg(color::Green),
                     style::Bold,
                     display_text,
                     style::Reset
-                )?;
+                ));
+            } else if let Some(line) = &laid_out {
+                rows.push(format!("  {}", styled_row(line)));
+            } else if let Some(styled) = &self.styled_items[index] {
+                let styled = styled.joined();
+                let mut visible = if truncated { styled.take_chars(kept) } else { styled };
+                if truncated {
+                    visible.push(&self.ellipsis, Style::Plain);
+                }
+                rows.push(format!("  {}", styled_row(&visible)));
             } else {
-                write!(screen, "  {}", display_text)?;
+                rows.push(format!("  {}", display_text));
             }
-
-            write!(screen, "\r\n")?;
+            row_widths.push(display_text.width() + 2);
         }
 
-        // Reserve space for status messages (2 lines)
-        let status_area_height: u16 = 2;
+        // Fill any remaining list lines with empty space
+        rows.resize(available_lines, String::new());
+        row_widths.resize(available_lines, 0);
 
-        // Fill any remaining lines with empty space
-        let display_items_count = end_idx - self.scroll_offset;
-        let required_lines = 4 + status_area_height as usize + display_items_count;
-        let empty_lines = if height as usize > required_lines {
-            height as usize - required_lines
-        } else {
-            0 // No empty lines if we don't have enough space
-        };
+        // Show the full text of the selected item next to the list
+        if list_width < width {
+            let preview_width = width - list_width - 2;
+            let preview_lines = self
+                .filtered_item(self.selected_index)
+                .map(|item| wrap_text(item, preview_width))
+                .unwrap_or_default();
 
-        for _ in 0..empty_lines {
-            write!(screen, "\r\n")?;
+            for (i, row) in rows.iter_mut().enumerate() {
+                let padding = list_width.saturating_sub(row_widths[i]);
+                row.push_str(&" ".repeat(padding));
+                row.push_str(&format!("{}│{} ", color::Fg(color::Blue), style::Reset));
+                if let Some(line) = preview_lines.get(i) {
+                    row.push_str(line);
+                }
+            }
         }
 
-        // Calculate the position for the status area (safely)
-        let status_pos = if height > 3 + status_area_height {
-            height - 3 - status_area_height
-        } else {
-            1 // Fallback to top of screen if terminal is too small
-        };
+        // Explain an empty list with a centered hint
+        if self.filtered_matches.is_empty() && available_lines > 0 {
+            let hint = if self.items.is_empty() && self.loading {
+                &self.loading_text
+            } else if self.items.is_empty() {
+                &self.no_items_text
+            } else {
+                &self.no_matches_text
+            };
+            let padding = width.saturating_sub(hint.width()) / 2;
+            rows[available_lines / 2] = format!(
+                "{}{}{}{}",
+                " ".repeat(padding),
+                style::Faint,
+                hint,
+                style::Reset
+            );
+        }
 
-        // Position cursor for the status area
-        write!(screen, "{}", cursor::Goto(1, status_pos))?;
+        // Draw the action menu over the top of the list
+        if let Some(action_index) = self.action_menu {
+            let mut menu_rows = vec![format!(
+                "{}{} Actions (Enter to run, Esc to go back){}",
+                color::Fg(color::Cyan),
+                style::Bold,
+                style::Reset
+            )];
+            for (i, action) in Action::ALL.iter().enumerate() {
+                if i == action_index {
+                    menu_rows.push(format!(
+                        "{}{} > {}{}",
+                        color::Fg(color::Green),
+                        style::Bold,
+                        action.label(),
+                        style::Reset
+                    ));
+                } else {
+                    menu_rows.push(format!("   {}", action.label()));
+                }
+            }
 
-        // Clear the status area (2 lines)
-        for _ in 0..status_area_height {
-            write!(screen, "{}{}", terminal::clear::CurrentLine, "\r\n")?;
+            for (row, menu_row) in rows.iter_mut().zip(menu_rows) {
+                *row = menu_row;
+            }
         }
 
-        // Move back to the start of the status area
-        write!(screen, "{}", cursor::Goto(1, status_pos))?;
-
-        // Display error message if any (in red)
-        if let Some(error) = &self.error_message {
-            write!(
-                screen,
-                "{}>Error: {}{}",
+        // Messages are cut at the terminal width so they never wrap into the prompt
+        let error = self.filter_error.as_ref().or(self.error_message.as_ref());
+        let error_line = error.map(|error| {
+            format!(
+                "{}{}{}",
                 color::Fg(color::Red),
-                error,
+                scroll_window(&format!(">Error: {}", error), 0, width, &self.ellipsis),
+                style::Reset
+            )
+        });
+        let warning_line = self.query_warning.as_ref().map(|warning| {
+            format!(
+                "{}{}{}",
+                color::Fg(color::Yellow),
+                scroll_window(&format!(">Warning: {}", warning), 0, width, &self.ellipsis),
                 style::Reset
-            )?;
-        }
-        // Otherwise display status message if any (in green)
-        else if let Some(status) = &self.status_message {
-            write!(
-                screen,
-                "{}>{}{}",
-                color::Fg(color::Green),
-                status,
+            )
+        });
+        let status_line = warning_line.or_else(|| {
+            self.status_message.as_ref().map(|status| {
+                format!(
+                    "{}{}{}",
+                    color::Fg(color::Green),
+                    scroll_window(&format!(">{}", status), 0, width, &self.ellipsis),
+                    style::Reset
+                )
+            })
+        });
+
+        if status_area_height >= 2 {
+            // The error (in red) and the status (in green, or a query warning in yellow)
+            // each get their own line
+            rows.push(error_line.unwrap_or_default());
+            rows.push(status_line.unwrap_or_default());
+        } else {
+            // With a single line, an error takes precedence over the status
+            rows.push(error_line.or(status_line).unwrap_or_default());
+        }
+        rows.resize(available_lines + status_area_height, String::new());
+
+        // Display the selected item's details in a dim footer, cut at the terminal width
+        if let Some(detail) = detail {
+            rows.push(format!(
+                "{}{}{}",
+                style::Faint,
+                scroll_window(&detail, 0, width, &self.ellipsis),
                 style::Reset
-            )?;
+            ));
         }
-        write!(screen, "\r\n")?;
 
         // Create the status text with count
-        let count_text = format!("{}/{}", self.filtered_items.len(), self.items.len());
+        let mut count_text = format!("{}/{}", self.match_count, self.items.len());
+        if self.case_mode != CaseMode::Smart {
+            count_text.push_str(&format!(" {}", self.case_mode.label()));
+        }
+        if self.incremental.typo_fallback() {
+            count_text.push_str(&format!(" showing typo matches for '{}'", self.query.trim()));
+        }
+        if self.pending_filter_since.is_some() {
+            count_text.push_str(&format!(" filtering{}", self.ellipsis));
+        }
+
+        // List the registered intent keys after the count
+        let hints: Vec<String> = self
+            .intents
+            .iter()
+            .map(|(key, intent)| format!("{} {}", key_label(*key), intent))
+            .collect();
+        let hints_text = if hints.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", hints.join("  "))
+        };
 
-        // Display status line at the bottom (format: "12/12 ───────────────")
-        write!(
-            screen,
-            "{}{} {}{}",
+        // Display status line (format: "12/12 ^O open ───────────────")
+        rows.push(format!(
+            "{}{} {}{}{}{}",
             color::Fg(color::Yellow),
             count_text,
+            hints_text,
             color::Fg(color::Blue),
-            "─".repeat(width as usize - count_text.len() - 1)
-        )?;
-        write!(screen, "{}", style::Reset)?;
+            "─".repeat(width.saturating_sub(count_text.width() + hints_text.width() + 1)),
+            style::Reset
+        ));
 
         // Display prompt at the bottom with input text on the same line
-        write!(screen, "\r\n{}>{} ", color::Fg(color::Blue), style::Reset)?;
-
-        // Display the input text on the same line as the prompt
-        if !self.query.is_empty() {
-            // Truncate query if it's too long for the terminal width
-            // Account for the prompt (2 characters: '>' and space)
-            let available_width = width as usize - 2;
-            let display_query = if self.query.len() > available_width {
-                // Show the last part of the query that fits in the terminal
-                let start_pos = self.query.len() - available_width + 1;
-                format!("…{}", &self.query[start_pos..])
-            } else {
-                self.query.clone()
-            };
-            write!(screen, "{}", display_query)?;
-        }
+        // Account for the prompt and the space after it
+        let prompt_width = self.prompt.width() + 1;
+        let available_width = width.saturating_sub(prompt_width);
+        let query_chars = self.query.chars().count();
+        let display_query = if query_chars > available_width {
+            // Show the last part of the query that fits in the terminal
+            let start_pos =
+                (query_chars - available_width + self.ellipsis.width()).min(query_chars);
+            let visible: String = self.query.chars().skip(start_pos).collect();
+            format!("{}{}", self.ellipsis, visible)
+        } else {
+            self.query.clone()
+        };
+        let placeholder = if self.query.is_empty() {
+            format!("{}{}{}", style::Faint, self.placeholder, style::Reset)
+        } else {
+            String::new()
+        };
+        rows.push(format!(
+            "{}{}{} {}{}",
+            color::Fg(color::Blue),
+            self.prompt,
+            style::Reset,
+            display_query,
+            placeholder
+        ));
 
         // Position cursor at the right position in the input line
-        let available_width = width as usize - 2; // Account for '>' and space
-        if self.query.len() > available_width {
+        let cursor_row = rows.len() as u16;
+        let cursor_col = if query_chars > available_width {
             // If text is truncated, position cursor at the end of visible text
-            write!(screen, "{}", cursor::Goto(width, height))?;
+            width as u16
         } else {
             // Otherwise, position cursor at the current position (after the prompt)
-            write!(
-                screen,
-                "{}",
-                cursor::Goto(self.cursor_pos as u16 + 3, height)
-            )?;
+            (self.query[..self.cursor_byte()].width() + prompt_width + 1) as u16
+        };
+
+        Frame {
+            rows,
+            cursor: (cursor_col, cursor_row),
+        }
+    }
+
+    /// Renders the current state, only rewriting rows that changed since the last frame
+    fn render<W: Write>(&mut self, screen: &mut W) -> io::Result<()> {
+        // Get terminal size
+        let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+        self.render_sized(screen, width, height)
+    }
+
+    /// Renders for the given terminal size, writing the whole frame at once
+    fn render_sized<W: Write>(&mut self, screen: &mut W, width: u16, height: u16) -> io::Result<()> {
+        let frame = self.compose(width, height);
+
+        // Redraw everything after a resize, on the first frame, or when the list
+        // scrolled so far that hardly any row can be reused
+        let scroll_jump = self.scroll_offset.abs_diff(self.last_scroll_offset);
+        let full_redraw = self.last_size != (width, height)
+            || self.last_frame.rows.len() != frame.rows.len()
+            || scroll_jump > height as usize;
+
+        // Build the frame in memory, so it costs a single write instead of one per
+        // escape sequence, which matters over high-latency connections
+        let mut output = String::new();
+        if full_redraw {
+            output.push_str(clear::All.as_ref());
+        }
+
+        for (i, row) in frame.rows.iter().enumerate() {
+            if full_redraw || self.last_frame.rows.get(i) != Some(row) {
+                output.push_str(&format!(
+                    "{}{}{}",
+                    cursor::Goto(1, i as u16 + 1),
+                    clear::CurrentLine,
+                    row
+                ));
+            }
         }
 
+        let (cursor_col, cursor_row) = frame.cursor;
+        output.push_str(&cursor::Goto(cursor_col, cursor_row).to_string());
+
+        screen.write_all(output.as_bytes())?;
+
         // Ensure all output is flushed to the screen
         screen.flush()?;
+
+        self.last_size = (width, height);
+        self.last_scroll_offset = self.scroll_offset;
+        self.last_frame = frame;
         Ok(())
     }
 
-    /// Run the fuzzy finder with support for background updates
-    pub fn run(&mut self) -> Option<String> {
-        // Set up terminal
-        let mut screen = stdout()
-            .into_raw_mode()
-            .unwrap()
-            .into_alternate_screen()
-            .unwrap();
-
-        // Show cursor and perform initial render
-        write!(screen, "{}", cursor::Show).unwrap();
-        screen.flush().unwrap();
-        self.render(&mut screen).unwrap();
-
-        // Process input
-        let stdin = stdin();
-        let mut keys = stdin.keys();
+    /// Appends an unrecognized input sequence to the debug log, if one is configured
+    fn log_unrecognized_input(&mut self, bytes: &[u8]) {
+        let Some(path) = &self.debug_input_log else {
+            return;
+        };
 
-        // For non-blocking input
-        let mut last_render = std::time::Instant::now();
-        let render_interval = Duration::from_millis(100); // Refresh UI every 100ms
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "unrecognized input: {}", hex.join(" ")));
 
-        loop {
-            // Check if it's time to re-render (for status updates)
-            let now = std::time::Instant::now();
-            if now.duration_since(last_render) >= render_interval {
-                self.render(&mut screen).unwrap();
-                last_render = now;
-            }
+        if let Err(e) = result {
+            self.set_error_message(Some(format!("Failed to write input log: {}", e)));
+        }
+    }
 
-            // Process key input (non-blocking)
-            if let Some(Ok(key)) = keys.next() {
-                match key {
-                    Key::Char('\n') | Key::Char('\r') => {
-                        // Return selected item but don't exit the program
-                        if !self.filtered_items.is_empty() {
-                            // Store the selected item
-                            let selected = self.filtered_items[self.selected_index].clone();
+    /// Applies a single key press to the finder state
+    fn handle_key(&mut self, key: Key) -> KeyOutcome {
+        // Keys that act on the filtered list must never see a stale result
+        if matches!(
+            key,
+            Key::Char('\n') | Key::Char('\r') | Key::Alt('\n') | Key::Alt('\r') | Key::Ctrl('e')
+        ) {
+            self.flush_pending_filter();
+        }
 
-                            // Properly restore terminal state before returning
-                            Self::cleanup_terminal(&mut screen);
-                            let _ = screen; // Mark screen as used without trying to drop the reference
+        if let Some(action_index) = self.action_menu {
+            return self.handle_action_menu_key(key, action_index);
+        }
 
-                            // Return the selected item to be processed
-                            return Some(selected);
-                        }
-                    }
-                    Key::Char(c) => {
-                        // Add character to query at cursor position
-                        self.query.insert(self.cursor_pos, c);
-                        self.cursor_pos += 1;
-                        self.update_filter();
-                    }
-                    Key::Backspace => {
-                        // Remove character before cursor position
-                        if !self.query.is_empty() && self.cursor_pos > 0 {
-                            self.query.remove(self.cursor_pos - 1);
-                            self.cursor_pos -= 1;
-                            self.update_filter();
-                        }
-                    }
-                    Key::Up => {
-                        self.move_cursor_up();
-                    }
-                    Key::Down => {
-                        self.move_cursor_down();
-                    }
-                    Key::Left => {
-                        // Move cursor left if possible
-                        if self.cursor_pos > 0 {
-                            self.cursor_pos -= 1;
-                        }
-                    }
-                    Key::Right => {
-                        // Move cursor right if possible
-                        if self.cursor_pos < self.query.len() {
-                            self.cursor_pos += 1;
-                        }
-                    }
-                    Key::Delete => {
-                        // Remove character at cursor position
-                        if !self.query.is_empty() && self.cursor_pos < self.query.len() {
-                            self.query.remove(self.cursor_pos);
-                            self.update_filter();
-                        }
-                    }
-                    Key::Home => {
-                        // Move cursor to the beginning of the query
-                        self.cursor_pos = 0;
-                    }
-                    Key::End => {
-                        // Move cursor to the end of the query
-                        self.cursor_pos = self.query.len();
-                    }
-                    Key::Ctrl('c') => {
-                        Self::exit_program(&mut screen, "\nExiting...");
-                    }
-                    Key::Esc => {
-                        Self::exit_program(&mut screen, "\nExiting...");
-                    }
-                    _ => {}
-                }
+        if key == self.accept_query_key {
+            // Accept the literal query even when nothing matches
+            return KeyOutcome::Accept(FinderResult::Query(self.query.clone()));
+        }
 
-                // Re-render after each key press
-                self.render(&mut screen).unwrap();
+        if let Some((_, intent)) = self.intents.iter().find(|(bound, _)| *bound == key) {
+            if let Some(item) = self.filtered_item(self.selected_index) {
+                return KeyOutcome::Accept(FinderResult::Intent {
+                    item: item.clone(),
+                    intent: intent.clone(),
+                    query: self.query.clone(),
+                });
+            }
+            return KeyOutcome::Continue;
+        }
+
+        match key {
+            Key::Char('\n') | Key::Char('\r') if !self.filtered_matches.is_empty() => {
+                // Return selected item but don't exit the program
+                let item = self.items[self.filtered_matches[self.selected_index].0].clone();
+                let query = self.query.clone();
+                return KeyOutcome::Accept(FinderResult::Selected { item, query });
+            }
+            Key::Char('\n') | Key::Char('\r') => {}
+            Key::Alt('\n') | Key::Alt('\r') if !self.filtered_matches.is_empty() => {
+                // Open the action menu for the selected item
+                self.action_menu = Some(0);
+            }
+            Key::Char(c) => {
+                // Add character to query at cursor position
+                self.query.insert(self.cursor_byte(), c);
+                self.cursor_pos += 1;
+                self.query_changed();
+            }
+            Key::Backspace if !self.query.is_empty() && self.cursor_pos > 0 => {
+                // Remove character before cursor position
+                self.cursor_pos -= 1;
+                self.query.remove(self.cursor_byte());
+                self.query_changed();
+            }
+            Key::Up => {
+                self.best_match_suspended = true;
+                self.move_cursor_up();
+            }
+            Key::Down => {
+                self.best_match_suspended = true;
+                self.move_cursor_down();
+            }
+            Key::Left if self.cursor_pos > 0 => {
+                // Move cursor left if possible
+                self.cursor_pos -= 1;
+            }
+            Key::Right if self.cursor_pos < self.query.chars().count() => {
+                // Move cursor right if possible
+                self.cursor_pos += 1;
+            }
+            Key::Delete if self.cursor_pos < self.query.chars().count() => {
+                // Remove character at cursor position
+                self.query.remove(self.cursor_byte());
+                self.query_changed();
+            }
+            Key::AltLeft | Key::Alt('b') => {
+                self.scroll_selected_horizontally(-HORIZONTAL_SCROLL_STEP);
+            }
+            Key::AltRight | Key::Alt('f') => {
+                self.scroll_selected_horizontally(HORIZONTAL_SCROLL_STEP);
+            }
+            Key::Ctrl('t') => {
+                self.toggle_preview();
+            }
+            Key::Alt('c') => {
+                self.cycle_case_mode();
+            }
+            Key::Ctrl('e') => {
+                self.load_all_matches();
+                let items = self.filtered_items().into_iter().map(str::to_string).collect();
+                return KeyOutcome::Accept(FinderResult::ExportList(items));
+            }
+            Key::Home => {
+                // Move cursor to the beginning of the query
+                self.cursor_pos = 0;
+            }
+            Key::End => {
+                // Move cursor to the end of the query
+                self.cursor_pos = self.query.chars().count();
+            }
+            Key::Ctrl('c') if self.ctrl_c_copies_query && !self.query.is_empty() => {
+                match clipboard::copy_to_clipboard(&self.query) {
+                    Ok(()) => self.set_status_message(Some("Copied query to clipboard".to_string())),
+                    Err(e) => self.set_error_message(Some(format!("Failed to copy query: {}", e))),
+                }
+            }
+            Key::Ctrl('c') => {
+                return KeyOutcome::Accept(FinderResult::Interrupted);
+            }
+            Key::Null => {
+                // Sent by some terminals for unmapped keys, nothing to do
+            }
+            Key::Esc => {
+                return KeyOutcome::Accept(FinderResult::Cancelled);
+            }
+            _ => {}
+        }
+
+        KeyOutcome::Continue
+    }
+
+    /// Applies a key press while the action menu is open
+    fn handle_action_menu_key(&mut self, key: Key, action_index: usize) -> KeyOutcome {
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                self.action_menu = None;
+                let item = self.items[self.filtered_matches[self.selected_index].0].clone();
+                let action = Action::ALL[action_index];
+                let query = self.query.clone();
+                return KeyOutcome::Accept(FinderResult::Action {
+                    item,
+                    action,
+                    query,
+                });
+            }
+            Key::Up if action_index > 0 => {
+                self.action_menu = Some(action_index - 1);
+            }
+            Key::Down if action_index < Action::ALL.len() - 1 => {
+                self.action_menu = Some(action_index + 1);
+            }
+            Key::Esc => {
+                // Go back to the main list without touching query or selection
+                self.action_menu = None;
+            }
+            Key::Ctrl('c') => {
+                return KeyOutcome::Accept(FinderResult::Interrupted);
+            }
+            _ => {}
+        }
+
+        KeyOutcome::Continue
+    }
+
+    /// Run the fuzzy finder with support for background updates
+    ///
+    /// The terminal is restored before this returns, including when the user aborts
+    /// with Esc or Ctrl+C, so the caller decides how to exit.
+    pub fn run(&mut self) -> FinderResult {
+        // Set up terminal
+        let mut screen = stdout()
+            .into_raw_mode()
+            .unwrap()
+            .into_alternate_screen()
+            .unwrap();
+
+        // Show cursor and perform initial render on the fresh alternate screen
+        write!(screen, "{}", cursor::Show).unwrap();
+        screen.flush().unwrap();
+        self.last_frame = Frame::default();
+        self.apply_updates();
+        self.render(&mut screen).unwrap();
+
+        // Reuse the non-blocking input reader across runs, so no keys are lost
+        // to a stale reader thread after a selection
+        let mut events = self
+            .events
+            .take()
+            .unwrap_or_else(|| termion::async_stdin().events());
+
+        let mut last_render = std::time::Instant::now();
+        let render_interval = self.render_interval;
+
+        loop {
+            // Drain every key currently buffered on stdin before rendering once,
+            // so a burst of fast typing costs a single redraw
+            let mut dirty = false;
+            for event in events.by_ref() {
+                dirty = true;
+                let key = match event {
+                    Ok(Event::Key(key)) => key,
+                    Ok(Event::Unsupported(bytes)) => {
+                        // Swallow escape sequences termion does not know
+                        self.log_unrecognized_input(&bytes);
+                        continue;
+                    }
+                    Ok(Event::Mouse(_)) => continue,
+                    Err(e) => {
+                        self.set_error_message(Some(format!("Failed to read input: {}", e)));
+                        continue;
+                    }
+                };
+
+                match self.handle_key(key) {
+                    KeyOutcome::Continue => {}
+                    KeyOutcome::Accept(result) => {
+                        // Properly restore terminal state before returning, dropping the
+                        // screen leaves the alternate screen and raw mode
+                        self.events = Some(events);
+                        Self::cleanup_terminal(&mut screen);
+                        drop(screen);
+
+                        // Return the result to be processed
+                        return result;
+                    }
+                }
+            }
+
+            // Catch up with the query once typing paused
+            if self.apply_debounced_filter() {
+                dirty = true;
+            }
+
+            // Show items and messages sent from other threads
+            if self.apply_updates() {
+                dirty = true;
+            }
+
+            // Re-render after key presses, or periodically for status updates
+            let now = std::time::Instant::now();
+            if dirty || now.duration_since(last_render) >= render_interval {
+                self.render(&mut screen).unwrap();
+                last_render = now;
             }
 
             // Small sleep to prevent CPU hogging
@@ -398,3 +1259,1216 @@ impl FuzzyFinder {
         }
     }
 }
+
+/// Configures and creates a [`FuzzyFinder`]
+///
+/// ```
+/// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+///
+/// let finder = FuzzyFinderBuilder::new()
+///     .prompt("repo>")
+///     .initial_query("rust")
+///     .build(vec!["rust-web-server".to_string(), "go-microservices".to_string()]);
+/// assert_eq!(finder.query(), "rust");
+/// ```
+#[derive(Clone)]
+pub struct FuzzyFinderBuilder {
+    prompt: String,
+    initial_query: String,
+    max_display: usize,
+    render_interval: Duration,
+    status_area_height: usize,
+    accept_query_key: Key,
+    placeholder: String,
+    no_matches_text: String,
+    no_items_text: String,
+    loading_text: String,
+    ellipsis: String,
+    track_best_match: Option<bool>,
+    match_mode: MatchMode,
+    case_mode: CaseMode,
+    tie_break: TieBreak,
+    typo_tolerance: bool,
+    prior_weight: f32,
+    preview_visible: bool,
+    on_preview_toggle: Option<PreviewToggleCallback>,
+    ctrl_c_copies_query: bool,
+    debounce_threshold: Duration,
+    debounce_delay: Duration,
+    debug_input_log: Option<PathBuf>,
+    intents: Vec<(Key, String)>,
+}
+
+impl Default for FuzzyFinderBuilder {
+    fn default() -> Self {
+        Self {
+            prompt: ">".to_string(),
+            initial_query: String::new(),
+            max_display: 10,
+            render_interval: Duration::from_millis(100),
+            status_area_height: 2,
+            accept_query_key: Key::Ctrl('q'),
+            placeholder: "type to filter repositories…".to_string(),
+            no_matches_text: "no matches".to_string(),
+            no_items_text: "no repositories loaded".to_string(),
+            loading_text: "loading repositories…".to_string(),
+            ellipsis: "…".to_string(),
+            track_best_match: None,
+            match_mode: MatchMode::default(),
+            case_mode: CaseMode::default(),
+            tie_break: TieBreak::default(),
+            typo_tolerance: false,
+            prior_weight: FilterOptions::default().prior_weight,
+            preview_visible: false,
+            on_preview_toggle: None,
+            ctrl_c_copies_query: false,
+            debounce_threshold: Duration::from_millis(30),
+            debounce_delay: Duration::from_millis(80),
+            debug_input_log: None,
+            intents: Vec::new(),
+        }
+    }
+}
+
+impl FuzzyFinderBuilder {
+    /// Creates a builder with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prompt shown in front of the query, `>` by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().prompt("search:").build(Vec::new());
+    /// ```
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Sets the query the finder starts with, empty by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().initial_query("api").build(Vec::new());
+    /// assert_eq!(finder.query(), "api");
+    /// ```
+    pub fn initial_query(mut self, query: impl Into<String>) -> Self {
+        self.initial_query = query.into();
+        self
+    }
+
+    /// Sets how many rows the selection can move down before the list scrolls, 10 by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().max_display(20).build(Vec::new());
+    /// ```
+    pub fn max_display(mut self, max_display: usize) -> Self {
+        self.max_display = max_display.max(1);
+        self
+    }
+
+    /// Sets how often the UI refreshes to show status updates, 100ms by default
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .render_interval(Duration::from_millis(250))
+    ///     .build(Vec::new());
+    /// ```
+    pub fn render_interval(mut self, interval: Duration) -> Self {
+        self.render_interval = interval;
+        self
+    }
+
+    /// Sets how many lines are reserved for status and error messages, 2 by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().status_area_height(1).build(Vec::new());
+    /// ```
+    pub fn status_area_height(mut self, height: usize) -> Self {
+        self.status_area_height = height;
+        self
+    }
+
+    /// Sets the key that accepts the literal query instead of a match, Ctrl+Q by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    /// use termion::event::{Event, Key};
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .accept_query_key(Key::Alt('q'))
+    ///     .build(Vec::new());
+    /// ```
+    pub fn accept_query_key(mut self, key: Key) -> Self {
+        self.accept_query_key = key;
+        self
+    }
+
+    /// Sets the dim text shown after the prompt while the query is empty
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .placeholder("tippen zum Filtern…")
+    ///     .build(Vec::new());
+    /// ```
+    pub fn placeholder(mut self, text: impl Into<String>) -> Self {
+        self.placeholder = text.into();
+        self
+    }
+
+    /// Sets the text shown in the list area when no item matches the query
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().no_matches_text("keine Treffer").build(Vec::new());
+    /// ```
+    pub fn no_matches_text(mut self, text: impl Into<String>) -> Self {
+        self.no_matches_text = text.into();
+        self
+    }
+
+    /// Sets the text shown in the list area when there are no items at all
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .no_items_text("keine Repositories geladen")
+    ///     .build(Vec::new());
+    /// ```
+    pub fn no_items_text(mut self, text: impl Into<String>) -> Self {
+        self.no_items_text = text.into();
+        self
+    }
+
+    /// Sets the text shown in the list area while there are no items yet but more are
+    /// being sent, see [`FuzzyFinder::set_update_receiver`]
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .loading_text("Repositories werden geladen…")
+    ///     .build(Vec::new());
+    /// ```
+    pub fn loading_text(mut self, text: impl Into<String>) -> Self {
+        self.loading_text = text.into();
+        self
+    }
+
+    /// Sets the text marking where rows, messages and the query were cut, `…` by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().ellipsis("...").build(Vec::new());
+    /// ```
+    pub fn ellipsis(mut self, text: impl Into<String>) -> Self {
+        self.ellipsis = text.into();
+        self
+    }
+
+    /// Sets whether the selection snaps to the best match whenever the query changes
+    ///
+    /// Moving the selection with Up/Down suspends tracking until the query is edited
+    /// again. When not set, tracking follows whether the filter ranks its results.
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().track_best_match(true).build(Vec::new());
+    /// ```
+    pub fn track_best_match(mut self, track: bool) -> Self {
+        self.track_best_match = Some(track);
+        self
+    }
+
+    /// Sets how the query is matched against items, fuzzy and ranked by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::filter::MatchMode;
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().match_mode(MatchMode::Exact).build(Vec::new());
+    /// ```
+    pub fn match_mode(mut self, mode: MatchMode) -> Self {
+        self.match_mode = mode;
+        self
+    }
+
+    /// Sets how letter case is compared, smart-case by default
+    ///
+    /// Alt+C cycles through the case modes while the finder runs.
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::filter::CaseMode;
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().case_mode(CaseMode::Sensitive).build(Vec::new());
+    /// ```
+    pub fn case_mode(mut self, mode: CaseMode) -> Self {
+        self.case_mode = mode;
+        self
+    }
+
+    /// Sets how matches with equal scores are ordered, keeping their input order by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::filter::TieBreak;
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().tie_break(TieBreak::Shortest).build(Vec::new());
+    /// ```
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Sets whether a query without matches is retried allowing one typo per term,
+    /// which is off by default
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().typo_tolerance(true).build(Vec::new());
+    /// ```
+    pub fn typo_tolerance(mut self, enabled: bool) -> Self {
+        self.typo_tolerance = enabled;
+        self
+    }
+
+    /// Sets how strongly the priors of candidates, like recency, weigh against the text
+    /// score of a match, see [`Candidate::prior`]
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().prior_weight(2.0).build(Vec::new());
+    /// ```
+    pub fn prior_weight(mut self, weight: f32) -> Self {
+        self.prior_weight = weight;
+        self
+    }
+
+    /// Sets whether the preview pane with the selected item's full text starts visible
+    ///
+    /// Ctrl+T toggles the pane at runtime.
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().preview(true).build(Vec::new());
+    /// ```
+    pub fn preview(mut self, visible: bool) -> Self {
+        self.preview_visible = visible;
+        self
+    }
+
+    /// Sets a callback that receives the new visibility whenever the preview pane is
+    /// toggled, for example to remember the choice for the next run
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .on_preview_toggle(|visible| println!("preview visible: {}", visible))
+    ///     .build(Vec::new());
+    /// ```
+    pub fn on_preview_toggle(mut self, callback: impl Fn(bool) + Send + Sync + 'static) -> Self {
+        self.on_preview_toggle = Some(Arc::new(callback));
+        self
+    }
+
+    /// Makes Ctrl+C copy a non-empty query to the clipboard instead of interrupting,
+    /// disabled by default so Ctrl+C always returns [`FinderResult::Interrupted`]
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new().ctrl_c_copies_query(true).build(Vec::new());
+    /// ```
+    pub fn ctrl_c_copies_query(mut self, enabled: bool) -> Self {
+        self.ctrl_c_copies_query = enabled;
+        self
+    }
+
+    /// Sets when filtering is debounced: once a filter pass takes longer than
+    /// `threshold`, the next pass waits until no key arrived for `delay`
+    ///
+    /// Defaults to a 30ms threshold and an 80ms delay.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .debounce(Duration::from_millis(50), Duration::from_millis(120))
+    ///     .build(Vec::new());
+    /// ```
+    pub fn debounce(mut self, threshold: Duration, delay: Duration) -> Self {
+        self.debounce_threshold = threshold;
+        self.debounce_delay = delay;
+        self
+    }
+
+    /// Sets a file that unrecognized terminal input sequences are appended to as hex,
+    /// useful to report how a terminal encodes its keys
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .debug_input_log("/tmp/repo-searcher-input.log")
+    ///     .build(Vec::new());
+    /// ```
+    pub fn debug_input_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.debug_input_log = Some(path.into());
+        self
+    }
+
+    /// Binds a key that accepts the selected item together with an intent, returned
+    /// as [`FinderResult::Intent`] so the caller can act on it without a menu
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::fuzzy_finder::FuzzyFinderBuilder;
+    /// use termion::event::Key;
+    ///
+    /// let finder = FuzzyFinderBuilder::new()
+    ///     .bind_intent(Key::Ctrl('o'), "open")
+    ///     .bind_intent(Key::Ctrl('u'), "copy-url")
+    ///     .build(Vec::new());
+    /// ```
+    pub fn bind_intent(mut self, key: Key, intent: impl Into<String>) -> Self {
+        self.intents.retain(|(bound, _)| *bound != key);
+        self.intents.push((key, intent.into()));
+        self
+    }
+
+    /// Creates the finder over the given items
+    pub fn build(self, items: Vec<String>) -> FuzzyFinder {
+        let mut finder = FuzzyFinder {
+            candidates: items.iter().map(Candidate::from_text).collect(),
+            styled_items: vec![None; items.len()],
+            filtered_matches: (0..items.len()).map(|index| (index, 0)).collect(),
+            incremental: filter::IncrementalFilter::new(),
+            match_count: items.len(),
+            result_limit: RESULT_LIMIT_SCREENS * self.max_display,
+            match_mode: self.match_mode,
+            case_mode: self.case_mode,
+            tie_break: self.tie_break,
+            typo_tolerance: self.typo_tolerance,
+            prior_weight: self.prior_weight,
+            items,
+            query: String::new(),
+            cursor_pos: 0,
+            selected_index: 0,
+            max_display: self.max_display,
+            render_interval: self.render_interval,
+            status_area_height: self.status_area_height,
+            prompt: self.prompt,
+            accept_query_key: self.accept_query_key,
+            placeholder: self.placeholder,
+            no_matches_text: self.no_matches_text,
+            no_items_text: self.no_items_text,
+            loading_text: self.loading_text,
+            ellipsis: self.ellipsis,
+            track_best_match: self.track_best_match,
+            best_match_suspended: false,
+            preview_visible: self.preview_visible,
+            on_preview_toggle: self.on_preview_toggle,
+            ctrl_c_copies_query: self.ctrl_c_copies_query,
+            debounce_threshold: self.debounce_threshold,
+            debounce_delay: self.debounce_delay,
+            last_filter_duration: Duration::ZERO,
+            pending_filter_since: None,
+            debug_input_log: self.debug_input_log,
+            detail_provider: None,
+            intents: self.intents,
+            view_stack: Vec::new(),
+            scroll_offset: 0,
+            status_message: None,
+            error_message: None,
+            filter_error: None,
+            query_warning: None,
+            events: None,
+            updates: None,
+            loading: false,
+            last_frame: Frame::default(),
+            last_size: (0, 0),
+            last_scroll_offset: 0,
+            action_menu: None,
+            horizontal_offset: 0,
+        };
+
+        if !self.initial_query.is_empty() {
+            finder.cursor_pos = self.initial_query.chars().count();
+            finder.query = self.initial_query;
+            finder.update_filter();
+        } else if finder.tie_break != TieBreak::Input {
+            // Even without a query the list is ordered by the tie-break
+            finder.update_filter();
+        }
+
+        finder
+    }
+}
+
+/// Width available for an item's text in a terminal of the given width
+pub fn item_text_width(width: usize) -> usize {
+    let prefix_len = 2; // Both "> " and "  " are 2 characters
+    width.saturating_sub(prefix_len + 5).max(1) // Extra buffer for emojis and safety
+}
+
+/// Draws styled text with terminal escape codes, resetting after every styled segment
+fn styled_row(text: &StyledText) -> String {
+    let mut row = String::new();
+    for segment in text.segments() {
+        let start = match segment.style {
+            Style::Plain => {
+                row.push_str(&segment.text);
+                continue;
+            }
+            Style::Dim => style::Faint.to_string(),
+            Style::Gray => color::Fg(color::LightBlack).to_string(),
+            Style::Red => color::Fg(color::Red).to_string(),
+        };
+        row.push_str(&format!("{}{}{}", start, segment.text, style::Reset));
+    }
+    row
+}
+
+/// Pairs plain items with candidates matching their whole text
+fn text_candidates(items: Vec<String>) -> Vec<(String, Candidate)> {
+    items
+        .into_iter()
+        .map(|item| {
+            let candidate = Candidate::from_text(item.as_str());
+            (item, candidate)
+        })
+        .collect()
+}
+
+/// Short label for a key, as shown in the footer hints
+fn key_label(key: Key) -> String {
+    match key {
+        Key::Ctrl(c) => format!("^{}", c.to_ascii_uppercase()),
+        Key::Alt(c) => format!("M-{}", c),
+        Key::Char(c) => c.to_string(),
+        Key::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Splits text into lines of at most `width` columns
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if line_width + char_width > width && !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        line.push(c);
+        line_width += char_width;
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Returns the part of `text` visible in a window of `width` columns starting at
+/// column `offset`, marking text cut off on either side with an ellipsis
+fn scroll_window(text: &str, offset: usize, width: usize, ellipsis: &str) -> String {
+    let total = text.width();
+    if offset == 0 && total <= width {
+        return text.to_string();
+    }
+
+    let cut_left = offset > 0;
+    let cut_right = total > offset + width;
+    let mark_width = ellipsis.width();
+    let start = offset + if cut_left { mark_width } else { 0 };
+    let end = (offset + width).saturating_sub(if cut_right { mark_width } else { 0 });
+
+    let mut visible = String::new();
+    if cut_left {
+        visible.push_str(ellipsis);
+    }
+
+    // Keep only characters that fit completely into the window, so wide
+    // characters are never split
+    let mut column = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if column >= start && column + char_width <= end {
+            visible.push(c);
+        }
+        column += char_width;
+        if column >= end {
+            break;
+        }
+    }
+
+    if cut_right {
+        visible.push_str(ellipsis);
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_window_fits() {
+        assert_eq!(scroll_window("short", 0, 10, "…"), "short");
+    }
+
+    #[test]
+    fn test_scroll_window_truncates_right() {
+        assert_eq!(scroll_window("abcdefghij", 0, 5, "…"), "abcd…");
+    }
+
+    #[test]
+    fn test_scroll_window_both_ends() {
+        assert_eq!(scroll_window("abcdefghij", 2, 5, "…"), "…def…");
+    }
+
+    #[test]
+    fn test_scroll_window_end_of_text() {
+        assert_eq!(scroll_window("abcdefghij", 5, 5, "…"), "…ghij");
+    }
+
+    #[test]
+    fn test_scroll_window_ascii_ellipsis() {
+        assert_eq!(scroll_window("abcdefghij", 0, 5, "..."), "ab...");
+        assert_eq!(scroll_window("abcdefghijkl", 2, 8, "..."), "...fg...");
+    }
+
+    #[test]
+    fn test_scroll_window_wide_characters() {
+        // Each CJK character takes two columns and must never be split
+        assert_eq!(scroll_window("日本語テキスト", 0, 7, "…"), "日本語…");
+        assert_eq!(scroll_window("日本語テキスト", 2, 7, "…"), "…語テ…");
+        assert_eq!(scroll_window("日本語テキスト", 4, 7, "…"), "…テキ…");
+    }
+
+    #[test]
+    fn test_accept_returns_query() {
+        let mut finder = FuzzyFinder::builder()
+            .initial_query("web")
+            .build(vec!["rust-web-server".to_string(), "go-microservices".to_string()]);
+
+        match finder.handle_key(Key::Char('\n')) {
+            KeyOutcome::Accept(result) => assert_eq!(
+                result,
+                FinderResult::Selected {
+                    item: "rust-web-server".to_string(),
+                    query: "web".to_string(),
+                }
+            ),
+            _ => panic!("expected the selection to be accepted"),
+        }
+    }
+
+    #[test]
+    fn test_edit_query_with_multi_byte_characters() {
+        let mut finder =
+            FuzzyFinder::builder().initial_query("ß").build(vec!["straße".to_string()]);
+        for c in "ıσ".chars() {
+            finder.handle_key(Key::Char(c));
+        }
+        assert_eq!(finder.query(), "ßıσ");
+
+        finder.handle_key(Key::Left);
+        finder.handle_key(Key::Backspace);
+        assert_eq!(finder.query(), "ßσ");
+        finder.handle_key(Key::Char('e'));
+        assert_eq!(finder.query(), "ßeσ");
+
+        finder.handle_key(Key::Home);
+        finder.handle_key(Key::Delete);
+        finder.handle_key(Key::Right);
+        finder.handle_key(Key::Char('ü'));
+        assert_eq!(finder.query(), "eüσ");
+
+        finder.handle_key(Key::End);
+        finder.handle_key(Key::Right);
+        finder.handle_key(Key::Delete);
+        finder.handle_key(Key::Backspace);
+        assert_eq!(finder.query(), "eü");
+        assert_eq!(finder.cursor_pos, 2);
+    }
+
+    #[test]
+    fn test_accept_literal_query_without_matches() {
+        let mut finder = FuzzyFinder::builder()
+            .initial_query("new-repo")
+            .build(vec!["rust-web-server".to_string()]);
+        assert!(matches!(finder.handle_key(Key::Char('\n')), KeyOutcome::Continue));
+
+        match finder.handle_key(Key::Ctrl('q')) {
+            KeyOutcome::Accept(result) => {
+                assert_eq!(result, FinderResult::Query("new-repo".to_string()))
+            }
+            _ => panic!("expected the query to be accepted"),
+        }
+    }
+
+    #[test]
+    fn test_append_items_filters_new_entries() {
+        let mut finder = FuzzyFinder::builder()
+            .initial_query("api")
+            .build(vec!["api-one".to_string(), "web-one".to_string()]);
+
+        finder.append_items(vec!["web-two".to_string(), "api-two".to_string()]);
+
+        assert_eq!(finder.filtered_items(), ["api-one", "api-two"]);
+        assert_eq!(finder.items.len(), 4);
+    }
+
+    #[test]
+    fn test_count_reflects_negated_terms() {
+        let items = vec!["api".to_string(), "api-fork".to_string(), "web".to_string()];
+        let finder = FuzzyFinder::builder().initial_query("api !fork").build(items);
+        assert_eq!(finder.filtered_items(), ["api"]);
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("1/3")));
+    }
+
+    #[test]
+    fn test_invalid_regex_keeps_previous_results() {
+        let items = vec!["api-one".to_string(), "web".to_string()];
+        let mut finder = FuzzyFinder::builder()
+            .match_mode(MatchMode::Regex)
+            .initial_query("api")
+            .build(items);
+        assert_eq!(finder.filtered_items(), ["api-one"]);
+
+        finder.handle_key(Key::Char('('));
+        finder.flush_pending_filter();
+        assert_eq!(finder.filtered_items(), ["api-one"]);
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("Invalid regex")));
+
+        finder.handle_key(Key::Char(')'));
+        finder.flush_pending_filter();
+        assert_eq!(finder.filtered_items(), ["api-one"]);
+        assert!(!finder.compose(80, 24).rows.iter().any(|row| row.contains("Invalid regex")));
+    }
+
+    #[test]
+    fn test_typo_matches_are_announced() {
+        let items = vec!["search-tool".to_string(), "web".to_string()];
+        let finder = FuzzyFinder::builder().initial_query("serach").build(items.clone());
+        assert!(finder.filtered_items().is_empty());
+
+        let mut finder =
+            FuzzyFinder::builder().typo_tolerance(true).initial_query("serach").build(items);
+        assert_eq!(finder.filtered_items(), ["search-tool"]);
+        let announced = |finder: &FuzzyFinder| {
+            finder.compose(80, 24).rows.iter().any(|row| row.contains("typo matches for 'serach'"))
+        };
+        assert!(announced(&finder));
+
+        finder.handle_key(Key::Backspace);
+        finder.flush_pending_filter();
+        assert!(!announced(&finder));
+    }
+
+    #[test]
+    fn test_append_items_respects_tie_break() {
+        let mut finder = FuzzyFinder::builder()
+            .tie_break(TieBreak::Alphabetical)
+            .build(vec!["web-d".to_string(), "web-b".to_string()]);
+        assert_eq!(finder.filtered_items(), ["web-b", "web-d"]);
+
+        finder.append_items(vec!["web-c".to_string(), "web-a".to_string()]);
+        assert_eq!(finder.filtered_items(), ["web-a", "web-b", "web-c", "web-d"]);
+    }
+
+    #[test]
+    fn test_candidates_order_by_weighted_fields() {
+        let mut finder = FuzzyFinder::builder().initial_query("docs").build(Vec::new());
+        finder.update_candidates(vec![
+            ("site (has docs) [GH]".to_string(), Candidate::repository("site", "me", "has docs")),
+            ("docs [GH]".to_string(), Candidate::repository("docs", "me", "")),
+        ]);
+        assert_eq!(finder.filtered_items(), ["docs [GH]", "site (has docs) [GH]"]);
+
+        finder.append_candidates(vec![(
+            "wiki (docs) [GL]".to_string(),
+            Candidate::repository("wiki", "me", "docs"),
+        )]);
+        assert_eq!(finder.filtered_items()[0], "docs [GH]");
+        assert_eq!(finder.filtered_items().len(), 3);
+    }
+
+    #[test]
+    fn test_alt_c_cycles_case_mode() {
+        let mut finder = FuzzyFinder::builder()
+            .initial_query("myp")
+            .build(vec!["MyProject".to_string(), "myproject".to_string()]);
+        assert_eq!(finder.filtered_items().len(), 2);
+
+        finder.handle_key(Key::Alt('c'));
+        assert_eq!(finder.case_mode, CaseMode::Insensitive);
+        finder.handle_key(Key::Alt('c'));
+        assert_eq!(finder.case_mode, CaseMode::Sensitive);
+        assert_eq!(finder.filtered_items(), ["myproject"]);
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("match-case")));
+    }
+
+    #[test]
+    fn test_append_items_merges_by_score() {
+        let mut finder = FuzzyFinder::builder()
+            .initial_query("api")
+            .build(vec!["my-api".to_string(), "a-p-i".to_string()]);
+
+        finder.append_items(vec!["api-server".to_string(), "rapid".to_string()]);
+        let merged: Vec<String> = finder.filtered_items().iter().map(|s| s.to_string()).collect();
+
+        finder.update_items(finder.items.clone());
+        assert_eq!(merged, finder.filtered_items());
+        assert_eq!(merged[0], "api-server");
+    }
+
+    #[test]
+    fn test_empty_list_hints() {
+        let finder = FuzzyFinder::builder().no_items_text("nothing here").build(Vec::new());
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("nothing here")));
+
+        let finder = FuzzyFinder::builder()
+            .initial_query("zzz")
+            .no_matches_text("no hits")
+            .build(vec!["api".to_string()]);
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("no hits")));
+    }
+
+    #[test]
+    fn test_track_best_match_until_manual_movement() {
+        let items = vec!["api-one".to_string(), "api-two".to_string(), "api-three".to_string()];
+        let mut finder = FuzzyFinder::builder().track_best_match(true).build(items);
+
+        finder.handle_key(Key::Down);
+        finder.handle_key(Key::Down);
+        assert_eq!(finder.selected_index, 2);
+
+        // Refreshing the items keeps the manual selection
+        finder.update_items(finder.items.clone());
+        assert_eq!(finder.selected_index, 2);
+
+        // Editing the query snaps back to the best match
+        finder.handle_key(Key::Char('a'));
+        assert_eq!(finder.selected_index, 0);
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(wrap_text("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(wrap_text("日本語", 4), vec!["日本", "語"]);
+        assert!(wrap_text("", 3).is_empty());
+    }
+
+    #[test]
+    fn test_toggle_preview_reports_visibility() {
+        let toggled = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = toggled.clone();
+        let mut finder = FuzzyFinder::builder()
+            .on_preview_toggle(move |visible| recorder.lock().unwrap().push(visible))
+            .build(vec!["api".to_string()]);
+
+        finder.handle_key(Key::Ctrl('t'));
+        finder.handle_key(Key::Ctrl('t'));
+
+        assert_eq!(*toggled.lock().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_error_and_status_shown_together() {
+        let mut finder = FuzzyFinder::new(vec!["api".to_string()]);
+        finder.set_error_message(Some("GitLab failed: 401".to_string()));
+        finder.set_status_message(Some("Loaded 80 GitHub repos".to_string()));
+
+        let frame = finder.compose(80, 24);
+        let error_row = frame.rows.iter().position(|row| row.contains("GitLab failed"));
+        let status_row = frame.rows.iter().position(|row| row.contains("Loaded 80"));
+        assert_eq!(error_row, Some(20));
+        assert_eq!(status_row, Some(21));
+    }
+
+    #[test]
+    fn test_query_warning_replaces_status_while_filtering() {
+        let items = vec!["api".to_string(), "web".to_string()];
+        let mut finder = FuzzyFinder::builder().initial_query("api|").build(items);
+        finder.set_status_message(Some("Loaded 2 repos".to_string()));
+
+        let frame = finder.compose(80, 24);
+        let warning = format!("{}>Warning: empty alternative in 'api|'", color::Fg(color::Yellow));
+        assert!(frame.rows[21].starts_with(&warning), "{:?}", frame.rows[21]);
+        assert_eq!(finder.filtered_items(), vec!["api"]);
+
+        finder.handle_key(Key::Backspace);
+        let frame = finder.compose(80, 24);
+        assert!(frame.rows[21].contains("Loaded 2 repos"));
+    }
+
+    #[test]
+    fn test_long_status_is_truncated() {
+        let mut finder = FuzzyFinder::new(Vec::new());
+        finder.set_status_message(Some("x".repeat(200)));
+
+        let frame = finder.compose(40, 24);
+        let status_row = frame.rows.iter().find(|row| row.contains("xxx")).unwrap();
+        assert!(status_row.contains('…'));
+        assert!(status_row.matches('x').count() < 40);
+    }
+
+    #[test]
+    fn test_abort_keys_return_results() {
+        let mut finder = FuzzyFinder::new(vec!["api".to_string()]);
+        assert!(matches!(
+            finder.handle_key(Key::Ctrl('c')),
+            KeyOutcome::Accept(FinderResult::Interrupted)
+        ));
+        assert!(matches!(
+            finder.handle_key(Key::Esc),
+            KeyOutcome::Accept(FinderResult::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_export_list_contains_filtered_items() {
+        let mut finder = FuzzyFinder::builder()
+            .initial_query("api")
+            .build(vec!["api-one".to_string(), "web".to_string(), "api-two".to_string()]);
+
+        match finder.handle_key(Key::Ctrl('e')) {
+            KeyOutcome::Accept(FinderResult::ExportList(items)) => {
+                assert_eq!(items, vec!["api-one", "api-two"])
+            }
+            _ => panic!("expected the filtered list to be exported"),
+        }
+    }
+
+    #[test]
+    fn test_slow_filter_is_debounced() {
+        let mut finder = FuzzyFinder::builder()
+            .debounce(Duration::ZERO, Duration::from_secs(60))
+            .build(vec!["api".to_string(), "web".to_string()]);
+
+        // The first pass runs right away and counts as slow with a zero threshold
+        finder.handle_key(Key::Char('a'));
+        assert_eq!(finder.filtered_items(), ["api"]);
+
+        // The next pass is deferred, while the query updates immediately
+        finder.handle_key(Key::Char('x'));
+        assert_eq!(finder.query(), "ax");
+        assert_eq!(finder.filtered_items(), ["api"]);
+        assert!(!finder.apply_debounced_filter());
+
+        // Accepting flushes the pending pass first
+        assert!(matches!(finder.handle_key(Key::Char('\n')), KeyOutcome::Continue));
+        assert!(finder.filtered_items().is_empty());
+    }
+
+    #[test]
+    fn test_unrecognized_input_is_logged() {
+        let path = std::env::temp_dir().join(format!("repo-searcher-input-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut finder = FuzzyFinder::builder().debug_input_log(&path).build(Vec::new());
+
+        finder.log_unrecognized_input(b"\x1b[27u");
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(log, "unrecognized input: 1b 5b 32 37 75\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_styled_items_are_drawn_but_matched_as_plain_text() {
+        let styled = |name: &str, description: &str| {
+            let mut text = StyledText::from(name);
+            text.push(" [GH]", Style::Dim);
+            text.push(description, Style::Gray);
+            (text, Candidate::from_text(name))
+        };
+        let mut finder = FuzzyFinder::new(Vec::new());
+        let long = "x".repeat(100);
+        finder.update_styled_candidates(vec![styled("api", " (Service)"), styled("web", &long)]);
+        finder.append_styled_candidates(vec![styled("docs", "")]);
+        assert_eq!(
+            finder.filtered_items(),
+            vec!["api [GH] (Service)", &format!("web [GH]{}", long), "docs [GH]"]
+        );
+
+        // The selected row is drawn plain, the others in their styles and cut by their text
+        let frame = finder.compose(40, 24);
+        assert!(frame.rows[0].contains("> api [GH] (Service)"));
+        let web = &frame.rows[1];
+        assert!(web.contains(&format!("{} [GH]{}", style::Faint, style::Reset)));
+        assert!(web.contains(&color::Fg(color::LightBlack).to_string()));
+        assert!(web.ends_with('…'));
+        assert_eq!(web.matches('x').count(), item_text_width(40) - "web [GH]…".chars().count());
+
+        finder.query = "doc".to_string();
+        finder.update_filter();
+        assert_eq!(finder.filtered_items(), vec!["docs [GH]"]);
+    }
+
+    #[test]
+    fn test_right_aligned_parts_are_drawn_at_the_edge() {
+        let item = |name: &str| {
+            let mut text = StyledText::from(name);
+            text.align_right(StyledText::from("★ 3"));
+            (text, Candidate::from_text(name))
+        };
+        let mut finder = FuzzyFinder::new(Vec::new());
+        finder.update_styled_candidates(vec![item("api"), item(&"x".repeat(100))]);
+        assert_eq!(finder.filtered_items()[0], "api  ★ 3");
+
+        let frame = finder.compose(40, 24);
+        let width = item_text_width(40);
+        assert!(frame.rows[0].contains(&format!("> api{}★ 3", " ".repeat(width - 6))));
+        // Long text is cut before the right-aligned part instead of cutting it off
+        assert!(frame.rows[1].ends_with(&format!("{}…  ★ 3", "x".repeat(width - 6))));
+
+        // Scrolling the selected row shows its whole text instead
+        finder.horizontal_offset = 2;
+        let frame = finder.compose(40, 24);
+        assert!(frame.rows[0].contains("> …  ★ 3"));
+    }
+
+    #[test]
+    fn test_detail_footer_for_selected_item() {
+        let mut finder = FuzzyFinder::new(vec!["api".to_string(), "web".to_string()]);
+        finder.set_detail_provider(|item| (item == "api").then(|| "https://example.com/api".to_string()));
+
+        let frame = finder.compose(80, 24);
+        assert_eq!(frame.rows.len(), 24);
+        assert!(frame.rows[21].contains("https://example.com/api"));
+
+        // The footer disappears when the provider has nothing to show
+        finder.handle_key(Key::Down);
+        let frame = finder.compose(80, 24);
+        assert!(!frame.rows.iter().any(|row| row.contains("https://")));
+    }
+
+    #[test]
+    fn test_intent_binding() {
+        let mut finder = FuzzyFinder::builder()
+            .bind_intent(Key::Ctrl('o'), "open")
+            .build(vec!["api".to_string()]);
+
+        let frame = finder.compose(80, 24);
+        assert!(frame.rows[22].contains("^O open"));
+
+        match finder.handle_key(Key::Ctrl('o')) {
+            KeyOutcome::Accept(result) => assert_eq!(
+                result,
+                FinderResult::Intent {
+                    item: "api".to_string(),
+                    intent: "open".to_string(),
+                    query: String::new(),
+                }
+            ),
+            _ => panic!("expected the intent to be accepted"),
+        }
+
+        // Unregistered keys behave as before
+        assert!(matches!(finder.handle_key(Key::Ctrl('u')), KeyOutcome::Continue));
+    }
+
+    fn numbered_items(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("repo-{}", i)).collect()
+    }
+
+    #[test]
+    fn test_restore_view_after_mode_toggle() {
+        let mut finder = FuzzyFinder::new(numbered_items(30));
+        for _ in 0..15 {
+            finder.handle_key(Key::Down);
+        }
+        assert_eq!((finder.selected_index, finder.scroll_offset), (15, 6));
+
+        // Toggling a mode swaps the items, toggling back restores the view
+        finder.save_view();
+        finder.update_items(numbered_items(5));
+        finder.update_items(numbered_items(30));
+        assert!(finder.restore_view());
+
+        assert_eq!(finder.filtered_items()[finder.selected_index], "repo-15");
+        assert_eq!(finder.scroll_offset, 6);
+    }
+
+    #[test]
+    fn test_restore_view_restores_query() {
+        let mut finder = FuzzyFinder::builder().initial_query("repo-2").build(numbered_items(30));
+        finder.handle_key(Key::Down);
+
+        finder.save_view();
+        finder.handle_key(Key::Backspace);
+        assert!(finder.restore_view());
+
+        assert_eq!(finder.query(), "repo-2");
+        assert_eq!(finder.filtered_items()[finder.selected_index], "repo-20");
+    }
+
+    #[test]
+    fn test_restore_view_requires_selected_item() {
+        let mut finder = FuzzyFinder::new(numbered_items(30));
+        finder.handle_key(Key::Down);
+
+        finder.save_view();
+        finder.update_items(numbered_items(1));
+        assert!(!finder.restore_view());
+
+        // The stack is consumed either way
+        assert!(!finder.restore_view());
+    }
+
+    /// Writer that counts how often it is written to
+    #[derive(Default)]
+    struct CountingWriter {
+        writes: usize,
+        bytes: Vec<u8>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_render_writes_frame_at_once() {
+        let mut finder = FuzzyFinder::new(numbered_items(500));
+
+        let mut screen = CountingWriter::default();
+        finder.render_sized(&mut screen, 120, 200).unwrap();
+        assert_eq!(screen.writes, 1);
+        assert!(String::from_utf8(screen.bytes).unwrap().contains("repo-150"));
+
+        // Unchanged frames only move the cursor
+        let mut screen = CountingWriter::default();
+        finder.render_sized(&mut screen, 120, 200).unwrap();
+        assert_eq!(screen.writes, 1);
+        assert!(!String::from_utf8(screen.bytes).unwrap().contains("repo-"));
+    }
+
+    #[test]
+    fn test_result_limit_keeps_count_accurate() {
+        let finder = FuzzyFinder::builder()
+            .max_display(2)
+            .initial_query("repo")
+            .build(numbered_items(40));
+        assert_eq!(finder.filtered_items().len(), 2 * RESULT_LIMIT_SCREENS);
+        assert_eq!(finder.filtered_items()[0], "repo-0");
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("40/40")));
+    }
+
+    #[test]
+    fn test_scrolling_to_the_end_loads_more_matches() {
+        let mut finder = FuzzyFinder::builder()
+            .max_display(2)
+            .initial_query("repo")
+            .build(numbered_items(40));
+        let loaded = finder.filtered_items().len();
+
+        for _ in 0..39 {
+            finder.handle_key(Key::Down);
+        }
+        assert_eq!(finder.filtered_items().len(), 40);
+        assert_eq!(finder.filtered_items()[finder.selected_index], "repo-39");
+        assert!(loaded < 40);
+    }
+
+    #[test]
+    fn test_export_list_contains_matches_beyond_the_limit() {
+        let mut finder = FuzzyFinder::builder()
+            .max_display(1)
+            .initial_query("repo")
+            .build(numbered_items(20));
+
+        match finder.handle_key(Key::Ctrl('e')) {
+            KeyOutcome::Accept(FinderResult::ExportList(items)) => {
+                assert_eq!(items, numbered_items(20))
+            }
+            _ => panic!("expected the filtered list to be exported"),
+        }
+    }
+
+    #[test]
+    fn test_append_items_beyond_the_limit() {
+        let mut finder = FuzzyFinder::builder()
+            .max_display(1)
+            .initial_query("r1")
+            .build(numbered_items(20));
+        finder.append_items(vec!["r1".to_string(), "web".to_string()]);
+
+        // The exact match ranks first, the rest stays cut off at the loaded matches
+        assert_eq!(finder.filtered_items()[0], "r1");
+        assert_eq!(finder.filtered_items().len(), RESULT_LIMIT_SCREENS);
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("12/22")));
+    }
+
+    #[test]
+    fn test_updates_from_another_thread() {
+        let styled = |names: &[&str]| -> Vec<(StyledText, Candidate)> {
+            let names = names.iter();
+            names.map(|&name| (StyledText::from(name), Candidate::from_text(name))).collect()
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut finder = FuzzyFinder::builder().loading_text("fetching").build(Vec::new());
+        finder.set_update_receiver(rx);
+        assert!(finder.is_loading());
+        assert!(finder.compose(80, 24).rows.iter().any(|row| row.contains("fetching")));
+
+        // The selection stays on its item while pages come in
+        tx.send(FinderUpdate::Append(styled(&["web", "cli"]))).unwrap();
+        tx.send(FinderUpdate::Status(Some("Loaded 2/~4…".to_string()))).unwrap();
+        assert!(finder.apply_updates());
+        finder.handle_key(Key::Down);
+        assert_eq!(finder.filtered_item(finder.selected_index).unwrap(), "cli");
+        tx.send(FinderUpdate::Replace(styled(&["api", "cli", "web"]))).unwrap();
+        assert!(finder.apply_updates());
+        assert_eq!(finder.filtered_items(), ["api", "cli", "web"]);
+        assert_eq!(finder.filtered_item(finder.selected_index).unwrap(), "cli");
+        assert_eq!(finder.status_message.as_deref(), Some("Loaded 2/~4…"));
+        assert!(!finder.apply_updates());
+
+        tx.send(FinderUpdate::LoadingComplete).unwrap();
+        finder.apply_updates();
+        assert!(!finder.is_loading());
+
+        // A dropped sender ends loading as well
+        let (tx, rx) = std::sync::mpsc::channel();
+        finder.set_update_receiver(rx);
+        drop(tx);
+        finder.apply_updates();
+        assert!(!finder.is_loading());
+    }
+}
@@ -0,0 +1,514 @@
+//! Fetching repositories from Gitea and Forgejo, like Codeberg
+//!
+//! Their API is below `/api/v1` and close to GitHub's, the token goes in an
+//! `Authorization: token ...` header. Lists are paged with `page` and `limit`, and the
+//! `X-Total-Count` header tells how many items there are in total.
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::formatter::{self, RepoSource};
+use crate::hosts;
+use crate::pagination::PageCallback;
+use crate::repo_info::{Affiliation, FetchOptions, RepoInfo};
+use crate::retry::{self, RetryPolicy};
+
+/// Where the API of a Gitea or Forgejo instance is, below its web address
+pub const API_PATH: &str = "/api/v1";
+
+// The page size asked for, the most instances allow unless configured otherwise
+const LIMIT: usize = 50;
+
+// Gitea API response structures. Only the name is required, older instances leave out
+// what was added after their version, like licenses
+#[derive(Debug, Deserialize, Clone)]
+struct GiteaRepo {
+    name: String,
+    owner: Option<GiteaOwner>,
+    description: Option<String>,
+    #[serde(default)]
+    fork: bool,
+    parent: Option<GiteaParent>,
+    #[serde(default)]
+    private: bool,
+    // Visible to every signed in user of the instance
+    #[serde(default)]
+    internal: bool,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    mirror: bool,
+    #[serde(default)]
+    template: bool,
+    #[serde(default)]
+    empty: bool,
+    // In kilobytes
+    size: Option<u64>,
+    // Empty rather than missing for repositories without commits
+    default_branch: Option<String>,
+    language: Option<String>,
+    topics: Option<Vec<String>>,
+    // SPDX identifiers, since Gitea 1.22
+    licenses: Option<Vec<String>>,
+    stars_count: Option<u32>,
+    open_issues_count: Option<u32>,
+    open_pr_counter: Option<u32>,
+    // Gitea has no push time, a push updates the repository
+    updated_at: Option<DateTime<Utc>>,
+    created_at: Option<DateTime<Utc>>,
+    ssh_url: Option<String>,
+    clone_url: Option<String>,
+    html_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GiteaOwner {
+    login: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GiteaParent {
+    full_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GiteaOrg {
+    // Called username like the login of a user
+    username: String,
+}
+
+// Converts a repository from the API, owned by the user or organization it belongs to.
+// Pull requests are only counted with merge_request_counts like on the other providers,
+// even though Gitea reports them for free
+fn convert_repo(repo: GiteaRepo, username: &str, merge_request_counts: bool) -> RepoInfo {
+    let owner = repo.owner.map_or_else(|| username.to_string(), |owner| owner.login);
+    let visibility = match (repo.private, repo.internal) {
+        (true, _) => formatter::Visibility::Private,
+        (false, true) => formatter::Visibility::Internal,
+        (false, false) => formatter::Visibility::Public,
+    };
+    let default_branch = repo.default_branch.filter(|branch| !branch.is_empty());
+    RepoInfo {
+        ssh_url: repo.ssh_url.unwrap_or_default(),
+        description: formatter::sanitize_text(&repo.description.unwrap_or_default()),
+        is_fork: repo.fork,
+        fork_parent: repo.parent.and_then(|parent| parent.full_name),
+        visibility,
+        is_archived: repo.archived,
+        is_mirror: repo.mirror,
+        is_template: repo.template,
+        is_empty: repo.empty || repo.size == Some(0) || default_branch.is_none(),
+        language: repo.language.filter(|language| !language.is_empty()),
+        topics: repo.topics.unwrap_or_default(),
+        license: repo.licenses.and_then(|licenses| licenses.into_iter().next()),
+        default_branch,
+        stars: repo.stars_count,
+        size_kb: repo.size,
+        // Unlike on GitHub, pull requests are not counted as issues
+        open_issues: repo.open_issues_count,
+        open_merge_requests: repo.open_pr_counter.filter(|_| merge_request_counts),
+        pushed_at: repo.updated_at,
+        created_at: repo.created_at,
+        https_url: repo.clone_url.unwrap_or_default(),
+        web_url: repo.html_url.unwrap_or_default(),
+        ..RepoInfo::new(&repo.name, &owner, RepoSource::Gitea)
+    }
+}
+
+// Fetches every page of a list, handing each to on_items with the total the instance
+// reported. Pages are asked for until the total is reached, or without one until a
+// page comes back empty
+async fn fetch_pages<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    url: &str,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+    on_items: &mut dyn FnMut(Vec<T>, Option<usize>),
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut fetched = 0;
+    for page in 1.. {
+        let query = [("page", page.to_string()), ("limit", LIMIT.to_string())];
+        let request = client.get(url).query(&query).headers(headers.clone()).build()?;
+        let response = retry::send("Gitea", client, request, policy, on_status).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await?;
+            return Err(format!("Gitea API error: {} - {}", status, text).into());
+        }
+
+        let total = response
+            .headers()
+            .get("x-total-count")
+            .and_then(|total| total.to_str().ok()?.trim().parse::<usize>().ok());
+        let items = response.json::<Vec<T>>().await?;
+        if items.is_empty() {
+            break;
+        }
+        fetched += items.len();
+        on_items(items, total);
+        if total.is_some_and(|total| fetched >= total) {
+            break;
+        }
+    }
+    Ok(fetched)
+}
+
+// Fetches the user the token belongs to, which tells apart an instance that can't be
+// reached from a token it turns down
+async fn fetch_user(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    host: &str,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let request = client.get(format!("{}{}/user", host, API_PATH)).headers(headers.clone());
+    let response = retry::send("Gitea", client, request.build()?, policy, on_status)
+        .await
+        .map_err(|e| format!("Could not reach Gitea at {}: {}", host, e))?;
+
+    let status = response.status();
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err(format!(
+            "Gitea at {} rejected the token ({}), check that it is valid and can read \
+             repositories",
+            host, status
+        )
+        .into());
+    }
+    if !status.is_success() {
+        let text = response.text().await?;
+        return Err(format!("Gitea API error: {} - {}", status, text).into());
+    }
+    // Something else answering at the address, like a login page, sends no JSON
+    response
+        .json()
+        .await
+        .map_err(|_| format!("{} doesn't look like a Gitea or Forgejo instance", host).into())
+}
+
+/// Fetches the repositories of the user the token belongs to on a Gitea or Forgejo
+/// instance
+///
+/// `host` is the web address of the instance, like `https://codeberg.org`. Only those
+/// with the affiliations in `options` are kept, with `starred` the repositories the
+/// user starred are fetched too. `on_page` gets every page as it is fetched, `on_status`
+/// how long is left to wait for a rate limit.
+pub async fn fetch_repos(
+    token: &str,
+    host: &str,
+    options: &FetchOptions,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(String, Vec<RepoInfo>), Box<dyn std::error::Error>> {
+    let (affiliations, policy) = (&options.affiliations, &options.retry);
+    let merge_request_counts = options.merge_request_counts;
+    eprint!("Fetching Gitea user information... ");
+    std::io::stderr().flush().unwrap();
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(retry::CONNECT_TIMEOUT)
+        .timeout(retry::REQUEST_TIMEOUT)
+        .build()?;
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("token {}", token))?);
+
+    let user = fetch_user(&client, &headers, host, policy, on_status).await?;
+    let username = user["login"]
+        .as_str()
+        .ok_or("Failed to get the Gitea username. Please check your Gitea token.")?
+        .to_string();
+    let api = format!("{}{}", host, API_PATH);
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprint!("Fetching repositories for Gitea user {}... ", username);
+    std::io::stderr().flush().unwrap();
+
+    // The owner of a repository doesn't say whether it is an organization
+    let mut orgs = HashSet::new();
+    let orgs_url = format!("{}/user/orgs", api);
+    fetch_pages(&client, &headers, &orgs_url, policy, on_status, &mut |page, _| {
+        orgs.extend(page.into_iter().map(|org: GiteaOrg| org.username.to_lowercase()));
+    })
+    .await?;
+
+    let mut all_repos = Vec::new();
+    let mut seen = HashSet::new();
+    let repos_url = format!("{}/user/repos", api);
+    let mut on_repos = |page: Vec<GiteaRepo>, total: Option<usize>| {
+        let repos: Vec<RepoInfo> = page
+            .into_iter()
+            .map(|repo| convert_repo(repo, &username, merge_request_counts))
+            .filter_map(|repo| {
+                let owned = repo.owner.eq_ignore_ascii_case(&username);
+                let in_organization = orgs.contains(&repo.owner.to_lowercase());
+                let affiliation = Affiliation::infer(owned, in_organization, affiliations);
+                if !affiliations.contains(&affiliation) {
+                    return None;
+                }
+                seen.insert(repo.https_url.clone());
+                Some(RepoInfo { affiliation: Some(affiliation), ..repo })
+            })
+            .collect();
+        on_page(&username, &repos, total);
+        all_repos.extend(repos);
+    };
+    fetch_pages(&client, &headers, &repos_url, policy, on_status, &mut on_repos).await?;
+
+    if options.starred {
+        let starred_url = format!("{}/user/starred", api);
+        let mut on_starred = |page: Vec<GiteaRepo>, _| {
+            let repos: Vec<RepoInfo> = page
+                .into_iter()
+                .map(|repo| convert_repo(repo, &username, merge_request_counts))
+                .filter(|repo| seen.insert(repo.https_url.clone()))
+                .map(|repo| RepoInfo { is_starred: true, ..repo })
+                .collect();
+            on_page(&username, &repos, None);
+            all_repos.extend(repos);
+        };
+        fetch_pages(&client, &headers, &starred_url, policy, on_status, &mut on_starred).await?;
+    }
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} Gitea repositories", all_repos.len());
+    Ok((username, all_repos))
+}
+
+pub fn extract_repo_info(
+    selection: &str,
+    username: &str,
+    source_tag: &str,
+    host: &str,
+) -> Option<(String, String, Option<String>)> {
+    // Extract the repository name, everything after it is indicators, badges and the description
+    let repo_name = formatter::selected_name(selection, source_tag);
+
+    // A name prefixed with its owner, like myorg/api, belongs to that owner
+    let (owner, repo_name) = repo_name.rsplit_once('/').unwrap_or((username, repo_name));
+    let (url, browser_url) = repo_urls(owner, repo_name, host);
+
+    Some((repo_name.to_string(), url, Some(browser_url)))
+}
+
+/// The clone and browser URLs of the repository of an owner
+pub fn repo_urls(owner: &str, repo_name: &str, host: &str) -> (String, String) {
+    let url = format!("git@{}:{}/{}.git", hosts::ssh_host(host), owner, repo_name);
+    let browser_url = format!("{}/{}/{}", host, owner, repo_name);
+    (url, browser_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::NO_WAIT;
+    use crate::test_server::{Response, TestServer};
+
+    fn repository(name: &str, owner: &str) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "owner": { "login": owner },
+            "default_branch": "main",
+            "size": 120,
+            "clone_url": format!("https://codeberg.org/{}/{}.git", owner, name),
+        })
+    }
+
+    #[test]
+    fn test_convert_repo_from_api_payload() {
+        let payload = serde_json::json!({
+            "id": 7,
+            "name": "tea",
+            "full_name": "myorg/tea",
+            "owner": { "id": 3, "login": "myorg", "full_name": "My Org" },
+            "description": "The command line tool",
+            "fork": true,
+            "parent": { "name": "tea", "full_name": "gitea/tea" },
+            "private": false,
+            "internal": true,
+            "archived": true,
+            "mirror": false,
+            "template": false,
+            "empty": false,
+            "size": 2048,
+            "default_branch": "main",
+            "language": "Go",
+            "topics": ["cli"],
+            "licenses": ["MIT"],
+            "stars_count": 12,
+            "open_issues_count": 3,
+            "open_pr_counter": 2,
+            "updated_at": "2024-05-01T10:00:00Z",
+            "created_at": "2020-01-01T00:00:00Z",
+            "ssh_url": "git@codeberg.org:myorg/tea.git",
+            "clone_url": "https://codeberg.org/myorg/tea.git",
+            "html_url": "https://codeberg.org/myorg/tea"
+        });
+        let repo: GiteaRepo = serde_json::from_value(payload).unwrap();
+        let converted = convert_repo(repo.clone(), "dima", true);
+        assert_eq!(
+            converted,
+            RepoInfo {
+                ssh_url: "git@codeberg.org:myorg/tea.git".to_string(),
+                description: "The command line tool".to_string(),
+                is_fork: true,
+                fork_parent: Some("gitea/tea".to_string()),
+                visibility: formatter::Visibility::Internal,
+                is_archived: true,
+                language: Some("Go".to_string()),
+                topics: vec!["cli".to_string()],
+                license: Some("MIT".to_string()),
+                default_branch: Some("main".to_string()),
+                stars: Some(12),
+                size_kb: Some(2048),
+                open_issues: Some(3),
+                open_merge_requests: Some(2),
+                pushed_at: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+                created_at: Some("2020-01-01T00:00:00Z".parse().unwrap()),
+                https_url: "https://codeberg.org/myorg/tea.git".to_string(),
+                web_url: "https://codeberg.org/myorg/tea".to_string(),
+                ..RepoInfo::new("tea", "myorg", RepoSource::Gitea)
+            }
+        );
+        assert_eq!(convert_repo(repo, "dima", false).open_merge_requests, None);
+
+        // Repositories without commits have an empty default branch and no language
+        let empty: GiteaRepo = serde_json::from_value(serde_json::json!({
+            "name": "notes",
+            "default_branch": "",
+            "language": "",
+            "topics": null
+        }))
+        .unwrap();
+        let converted = convert_repo(empty, "dima", false);
+        assert_eq!(converted.owner, "dima");
+        assert!(converted.is_empty);
+        assert_eq!((converted.default_branch, converted.language), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_repos_pages_by_total_count() {
+        let server = TestServer::start(|request| match request.target.as_str() {
+            "/api/v1/user" => Response::json(r#"{"id": 1, "login": "dima"}"#),
+            "/api/v1/user/orgs?page=1&limit=50" => {
+                Response::json(r#"[{"id": 5, "username": "MyOrg"}]"#).header("x-total-count", "1")
+            }
+            "/api/v1/user/repos?page=1&limit=50" => {
+                let page: Vec<_> =
+                    (0..50).map(|i| repository(&format!("repo-{}", i), "dima")).collect();
+                Response::json(serde_json::Value::from(page)).header("x-total-count", "52")
+            }
+            "/api/v1/user/repos?page=2&limit=50" => Response::json(serde_json::json!([
+                repository("tea", "myorg"),
+                repository("shared", "friend"),
+            ]))
+            .header("x-total-count", "52"),
+            _ => Response::json("[]").status(404),
+        })
+        .await;
+
+        let mut pages = Vec::new();
+        let options = FetchOptions { retry: NO_WAIT, ..Default::default() };
+        let (username, repos) = fetch_repos(
+            "secret",
+            &server.url,
+            &options,
+            &mut |_, repos, total| pages.push((repos.len(), total)),
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(username, "dima");
+        assert_eq!(repos.len(), 52);
+        assert_eq!(pages, vec![(50, Some(52)), (2, Some(52))]);
+        let affiliations: Vec<_> = repos[49..].iter().map(|repo| repo.affiliation).collect();
+        assert_eq!(
+            affiliations,
+            [
+                Some(Affiliation::Owner),
+                Some(Affiliation::OrganizationMember),
+                Some(Affiliation::Collaborator),
+            ]
+        );
+
+        // The total count ends the paging, there is no request for an empty third page
+        let requests = server.requests();
+        let authorized = |request: &crate::test_server::Request| {
+            request.header("authorization") == Some("token secret")
+        };
+        assert!(requests.iter().all(authorized));
+        assert!(!requests.iter().any(|request| request.target.contains("page=3")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_repos_without_total_count_stops_at_an_empty_page() {
+        let server = TestServer::start(|request| match request.target.as_str() {
+            "/api/v1/user" => Response::json(r#"{"id": 1, "login": "dima"}"#),
+            "/api/v1/user/repos?page=1&limit=50" => {
+                Response::json(serde_json::json!([repository("api", "dima")]))
+            }
+            "/api/v1/user/starred?page=1&limit=50" => Response::json(serde_json::json!([
+                repository("api", "dima"),
+                repository("forgejo", "forgejo"),
+            ])),
+            _ => Response::json("[]"),
+        })
+        .await;
+
+        let options = FetchOptions {
+            affiliations: vec![Affiliation::Owner],
+            starred: true,
+            retry: NO_WAIT,
+            ..Default::default()
+        };
+        let (on_page, on_status) = (&mut |_: &str, _: &[RepoInfo], _| {}, &mut |_| {});
+        let fetched = fetch_repos("secret", &server.url, &options, on_page, on_status);
+        let (_, repos) = fetched.await.unwrap();
+
+        // Starred repositories of the user's own stay as they are
+        let names: Vec<_> =
+            repos.iter().map(|repo| (repo.name.as_str(), repo.is_starred)).collect();
+        assert_eq!(names, [("api", false), ("forgejo", true)]);
+        let targets: Vec<_> = server.requests().into_iter().map(|request| request.target).collect();
+        assert!(targets.contains(&"/api/v1/user/repos?page=2&limit=50".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_user_tells_rejected_tokens_apart() {
+        let rejected = |_: &_| Response::json(r#"{"message": "token is required"}"#).status(401);
+        let server = TestServer::start(rejected).await;
+        let client = reqwest::Client::new();
+        let error = fetch_user(&client, &HeaderMap::new(), &server.url, &NO_WAIT, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("rejected the token (401 Unauthorized)"), "{}", error);
+
+        let server = TestServer::start(|_| Response::json("<html>Sign in</html>")).await;
+        let error = fetch_user(&client, &HeaderMap::new(), &server.url, &NO_WAIT, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{} doesn't look like a Gitea or Forgejo instance", server.url)
+        );
+    }
+
+    #[test]
+    fn test_extract_repo_info() {
+        assert_eq!(
+            extract_repo_info("myorg/tea [GT] (About)", "dima", "[GT]", hosts::CODEBERG),
+            Some((
+                "tea".to_string(),
+                "git@codeberg.org:myorg/tea.git".to_string(),
+                Some("https://codeberg.org/myorg/tea".to_string())
+            ))
+        );
+    }
+}
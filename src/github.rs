@@ -1,132 +1,1293 @@
+use chrono::{DateTime, Utc};
 use octocrab::Octocrab;
-use octocrab::models::Repository as OctocrabRepo;
+use octocrab::service::middleware::retry::RetryConfig;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashSet;
 use std::io::Write;
 
-pub type Repository = (String, String, String, String, bool, bool); // (name, ssh_url, description, owner, is_fork, is_private)
+use crate::cache::{CachedPage, PageCache};
+use crate::formatter::{self, RepoSource};
+use crate::hosts;
+use crate::pagination::{self, PageCallback};
+use crate::rate_limit;
+use crate::retry::{self, Retries, RetryPolicy};
+use crate::repo_info::{Affiliation, FetchOptions, RepoInfo};
 
-// Helper function to convert GitHub API repository to our Repository type
-fn convert_repo(repo: OctocrabRepo, username: &str) -> Repository {
-    (
-        repo.name,
-        repo.ssh_url.unwrap_or_default(),
-        repo.description.unwrap_or_default(),
-        username.to_string(),
-        repo.fork.unwrap_or(false),
-        repo.private.unwrap_or(false)
-    )
+mod graphql;
+
+// How many fork parent requests run at the same time
+const FORK_PARENT_REQUESTS_AT_ONCE: usize = 10;
+
+// How many pull request count requests run at the same time
+const PULL_REQUEST_COUNTS_AT_ONCE: usize = 10;
+
+// The API of github.com
+const GITHUB_API: &str = "https://api.github.com";
+
+// Where the API of a GitHub Enterprise Server is, below its web address
+pub const API_PATH: &str = "/api/v3";
+
+// The repositories of the authenticated user, including those of their organizations
+// unless an affiliation says otherwise
+const REPOS_ROUTE: &str = "/user/repos?per_page=100";
+
+// The organizations the authenticated user belongs to
+const ORGS_ROUTE: &str = "/user/orgs?per_page=100";
+
+// The repositories the authenticated user starred
+const STARRED_ROUTE: &str = "/user/starred?per_page=100";
+
+// The page size asked for in REPOS_ROUTE and the organization repositories
+const REPOS_PER_PAGE: usize = 100;
+
+/// Which organizations' repositories are fetched next to the user's own
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OrgRepos {
+    /// Those of every organization the user belongs to
+    #[default]
+    All,
+    /// None, only the user's own and those they collaborate on
+    Exclude,
+    /// Those of the named organizations, ignoring case
+    Only(Vec<String>),
+}
+
+impl OrgRepos {
+    /// Whether the repositories of an organization are fetched
+    pub fn includes(&self, org: &str) -> bool {
+        match self {
+            OrgRepos::All => true,
+            OrgRepos::Exclude => false,
+            OrgRepos::Only(orgs) => orgs.iter().any(|name| name.eq_ignore_ascii_case(org)),
+        }
+    }
+}
+
+// GitHub API response structures. Only the name is required: GitHub Enterprise Server
+// leaves out what was added after its version, like topics, and what is missing is
+// unknown rather than failing the whole page
+#[derive(Debug, Deserialize, Clone)]
+struct GitHubRepo {
+    name: String,
+    // Like owner/name
+    full_name: Option<String>,
+    owner: Option<GitHubOwner>,
+    description: Option<String>,
+    fork: Option<bool>,
+    // Only reported by the detail endpoint
+    parent: Option<GitHubParent>,
+    private: Option<bool>,
+    archived: Option<bool>,
+    mirror_url: Option<String>,
+    is_template: Option<bool>,
+    // In kilobytes
+    size: Option<u32>,
+    default_branch: Option<String>,
+    language: Option<String>,
+    topics: Option<Vec<String>>,
+    license: Option<GitHubLicense>,
+    stargazers_count: Option<u32>,
+    open_issues_count: Option<u32>,
+    pushed_at: Option<DateTime<Utc>>,
+    created_at: Option<DateTime<Utc>>,
+    ssh_url: Option<String>,
+    clone_url: Option<String>,
+    html_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GitHubOwner {
+    login: String,
+    // User or Organization
+    #[serde(default)]
+    r#type: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GitHubParent {
+    full_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GitHubLicense {
+    // Null on older servers for licenses they could not identify
+    spdx_id: Option<String>,
+}
+
+// A list of repositories fetched page by page
+enum List<'a> {
+    // The user's own with these affiliations, like owner
+    Affiliated(Vec<&'static str>),
+    // Those of an organization
+    Org(&'a str),
+    Starred,
+}
+
+impl List<'_> {
+    // Where the REST API has the list
+    fn route(&self) -> String {
+        match self {
+            List::Affiliated(wanted) => format!("{}&affiliation={}", REPOS_ROUTE, wanted.join(",")),
+            List::Org(org) => format!("/orgs/{}/repos?per_page=100", org),
+            List::Starred => STARRED_ROUTE.to_string(),
+        }
+    }
+}
+
+// Which API the lists are fetched with. The GraphQL API has the parents of forks and
+// the pull request counts in the same response, which costs the REST API a request per
+// repository; those are only taken over when asked for
+#[derive(Clone, Copy)]
+enum Api<'a> {
+    Rest,
+    GraphQl(&'a graphql::Options<'a>),
 }
 
-// Helper function to update progress display
+// The API of a GitHub at a web address, github.com has its own host for it
+fn api_url(host: &str) -> String {
+    if host == hosts::GITHUB_COM {
+        GITHUB_API.to_string()
+    } else {
+        format!("{}{}", host, API_PATH)
+    }
+}
+
+// Helper function to convert GitHub API repository to our RepoInfo type, owned by the
+// user or organization it belongs to
+fn convert_repo(repo: GitHubRepo, username: &str) -> RepoInfo {
+    let owner = repo.owner.map_or_else(|| username.to_string(), |owner| owner.login);
+    RepoInfo {
+        ssh_url: repo.ssh_url.unwrap_or_default(),
+        description: formatter::sanitize_text(&repo.description.unwrap_or_default()),
+        is_fork: repo.fork.unwrap_or(false),
+        // Only the detail endpoint reports the parent, see fetch_fork_parents
+        fork_parent: repo.parent.and_then(|parent| parent.full_name),
+        visibility: formatter::Visibility::from_private(repo.private.unwrap_or(false)),
+        is_archived: repo.archived.unwrap_or(false),
+        is_mirror: repo.mirror_url.is_some(),
+        is_template: repo.is_template.unwrap_or(false),
+        // Empty repositories take no space and may not have a default branch yet
+        is_empty: repo.size == Some(0) || repo.default_branch.is_none(),
+        language: repo.language,
+        topics: repo.topics.unwrap_or_default(),
+        license: repo
+            .license
+            .map(|license| license.spdx_id.unwrap_or_else(|| "NOASSERTION".to_string())),
+        default_branch: repo.default_branch,
+        stars: repo.stargazers_count,
+        size_kb: repo.size.map(u64::from),
+        // Pull requests are counted as issues too, see fetch_pull_request_counts
+        open_issues: repo.open_issues_count,
+        pushed_at: repo.pushed_at,
+        created_at: repo.created_at,
+        https_url: repo.clone_url.unwrap_or_default(),
+        web_url: repo.html_url.unwrap_or_default(),
+        ..RepoInfo::new(&repo.name, &owner, RepoSource::GitHub)
+    }
+}
+
+// Looks up the parents of forks, a few forks at a time. The list endpoint leaves them
+// out, so this costs a request per fork; forks whose parent can't be fetched keep
+// showing a bare (fork)
+async fn fetch_fork_parents(octocrab: &Octocrab, repos: &mut [RepoInfo]) {
+    let mut forks: Vec<&mut RepoInfo> =
+        repos.iter_mut().filter(|repo| repo.is_fork && repo.fork_parent.is_none()).collect();
+
+    for chunk in forks.chunks_mut(FORK_PARENT_REQUESTS_AT_ONCE) {
+        let requests: Vec<_> = chunk
+            .iter()
+            .map(|repo| {
+                let octocrab = octocrab.clone();
+                let (owner, name) = (repo.owner.clone(), repo.name.clone());
+                tokio::spawn(async move {
+                    let details = octocrab.repos(owner, name).get().await.ok()?;
+                    details.parent.and_then(|parent| parent.full_name)
+                })
+            })
+            .collect();
+
+        for (repo, request) in chunk.iter_mut().zip(requests) {
+            repo.fork_parent = request.await.ok().flatten();
+        }
+    }
+}
+
+// Counts the open pull requests of each repository, a few repositories at a time.
+// With one pull request per page the number of the last page is the count. GitHub
+// counts pull requests as issues, so they are taken off the issue count as well;
+// repositories whose pull requests can't be counted keep their counts as they are
+async fn fetch_pull_request_counts(octocrab: &Octocrab, repos: &mut [RepoInfo]) {
+    for chunk in repos.chunks_mut(PULL_REQUEST_COUNTS_AT_ONCE) {
+        let requests: Vec<_> = chunk
+            .iter()
+            .map(|repo| {
+                let octocrab = octocrab.clone();
+                let (owner, name) = (repo.owner.clone(), repo.name.clone());
+                tokio::spawn(async move {
+                    let page = octocrab
+                        .pulls(owner, name)
+                        .list()
+                        .state(octocrab::params::State::Open)
+                        .per_page(1)
+                        .send()
+                        .await
+                        .ok()?;
+                    Some(page.number_of_pages().unwrap_or(page.items.len() as u32))
+                })
+            })
+            .collect();
+
+        for (repo, request) in chunk.iter_mut().zip(requests) {
+            if let Some(count) = request.await.ok().flatten() {
+                repo.open_merge_requests = Some(count);
+                repo.open_issues = repo.open_issues.map(|issues| issues.saturating_sub(count));
+            }
+        }
+    }
+}
+
+// Helper function to update progress display, on stderr as stdout is left to --output
 fn update_progress(page_count: usize, repos_count: usize) {
-    print!("\r                                                  "); // Clear the line
-    print!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
-    std::io::stdout().flush().unwrap();
+    eprint!("\r                                                  "); // Clear the line
+    eprint!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
+    std::io::stderr().flush().unwrap();
 }
 
-pub async fn fetch_repos(token: &str) -> octocrab::Result<(String, Vec<Repository>)> {
-    print!("Fetching user information... ");
-    std::io::stdout().flush().unwrap();
+// Builds the API client. Its own retries are turned off, they don't wait between
+// tries and would use up the retries of get
+fn client(base_uri: &str, token: &str) -> octocrab::Result<Octocrab> {
+    Octocrab::builder()
+        .base_uri(base_uri)?
+        .personal_token(token.to_string())
+        .add_retry_config(RetryConfig::None)
+        .set_connect_timeout(Some(retry::CONNECT_TIMEOUT))
+        .set_read_timeout(Some(retry::REQUEST_TIMEOUT))
+        .build()
+}
 
-    let octocrab = Octocrab::builder().personal_token(token.to_string()).build()?;
+// The API client with the pages of the last fetch, whose ETags are sent along, and how
+// its requests are retried
+struct Client<'a> {
+    octocrab: Octocrab,
+    pages: &'a PageCache,
+    policy: &'a RetryPolicy,
+}
 
-    // Get authenticated user information
-    let user = octocrab.current().user().await?;
-    let username = user.login;
-
-    println!("✓"); // Show checkmark on its own line
-    print!("Fetching repositories for {}... ", username);
-    std::io::stdout().flush().unwrap();
-
-    let mut page = octocrab
-        .current()
-        .list_repos_for_authenticated_user()
-        .per_page(100) // Maximum allowed per page
-        .send()
-        .await?;
+// Sends a GET request to the API, retrying failures that tend to go away and waiting
+// for a rate limit to reset when it resets soon enough. Returns the headers and the
+// body of a successful response. A page fetched before is only sent again when it
+// changed, GitHub doesn't count the requests that say it didn't against the rate limit
+async fn get(
+    client: &Client<'_>,
+    url: &str,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(HeaderMap, String), Box<dyn std::error::Error>> {
+    let previous = client.pages.previous(url);
+    let etag = previous.map(|page| page.etag.as_str());
+    let (status, headers, body) =
+        send(&client.octocrab, url, None, etag, client.policy, on_status).await?;
+    if let Some(page) = previous.filter(|_| status == StatusCode::NOT_MODIFIED) {
+        client.pages.insert(url, page.clone());
+        let mut headers = HeaderMap::new();
+        if let Some(link) = page.link.as_deref().and_then(|link| link.parse().ok()) {
+            headers.insert(header::LINK, link);
+        }
+        return Ok((headers, page.body.clone()));
+    }
 
-    let mut all_repos = Vec::new();
-    let mut page_count = 1;
+    let text = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok());
+    if let Some(etag) = text(header::ETAG) {
+        let link = text(header::LINK).map(str::to_string);
+        let page = CachedPage { etag: etag.to_string(), link, body: body.clone() };
+        client.pages.insert(url, page);
+    }
+    Ok((headers, body))
+}
 
-    // Add repos from the first page
-    all_repos.extend(
-        page.items
-            .into_iter()
-            .map(|repo| convert_repo(repo, &username))
-    );
+// Sends a GET request, or a POST with a body, like get. A GET with an ETag comes back
+// with 304 Not Modified and an empty body when the page didn't change
+async fn send(
+    octocrab: &Octocrab,
+    url: &str,
+    body: Option<&serde_json::Value>,
+    etag: Option<&str>,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(StatusCode, HeaderMap, String), Box<dyn std::error::Error>> {
+    let method = if body.is_some() { "POST" } else { "GET" };
+    let mut retries = Retries::new(policy, format!("{} {}", method, url));
+    let mut waits = 0;
+    let mut request_headers = HeaderMap::new();
+    if let Some(etag) = etag {
+        request_headers.insert(header::IF_NONE_MATCH, etag.parse()?);
+    }
+    loop {
+        let response = match body {
+            Some(body) => octocrab._post(url, Some(body)).await,
+            None => octocrab._get_with_headers(url, Some(request_headers.clone())).await,
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                retries.retry(e).await?;
+                continue;
+            }
+        };
+        let limited = rate_limit::limited_until(response.status(), response.headers(), Utc::now());
+        if let Some(until) = limited {
+            if waits == rate_limit::MAX_WAITS {
+                return Err(rate_limit::reset_message("GitHub", until, Utc::now()).into());
+            }
+            waits += 1;
+            let max_wait = policy.max_rate_limit_wait;
+            rate_limit::wait_for_reset("GitHub", until, max_wait, on_status).await?;
+            continue;
+        }
+        if retry::is_transient(response.status()) {
+            retries.retry(response.status()).await?;
+            continue;
+        }
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok((response.status(), response.headers().clone(), String::new()));
+        }
+        let response = octocrab::map_github_error(response).await?;
+        let headers = response.headers().clone();
+        return Ok((response.status(), headers, octocrab.body_to_string(response).await?));
+    }
+}
+
+// Fetches every page of a repository list, following the Link header of each page and
+// converting the repositories with convert, which leaves out those it returns None
+// for. Every page is handed to on_page, so it can be shown before the last page is
+// in, with the total estimated from the number of the last page. Returns the
+// repositories with the number of pages
+async fn fetch_repo_pages(
+    client: &Client<'_>,
+    route: &str,
+    username: &str,
+    convert: &mut dyn FnMut(GitHubRepo) -> Option<RepoInfo>,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(Vec<RepoInfo>, usize), Box<dyn std::error::Error>> {
+    let mut all_repos = Vec::new();
+    let mut expected = None;
+    let mut page_count = 0;
+    let mut next = Some(route.to_string());
 
-    update_progress(page_count, all_repos.len());
+    while let Some(url) = next {
+        if page_count > 0 {
+            // Add a small sleep to allow Ctrl+C to be processed
+            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        }
 
-    // Fetch all remaining pages
-    while let Some(next_page) = octocrab.get_page(&page.next).await? {
-        // Add a small sleep to allow Ctrl+C to be processed
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        let (headers, body) = get(client, &url, on_status).await?;
+        let link = headers.get("link").map(|link| {
+            link.to_str()
+                .map(str::to_string)
+                .map_err(|_| "Malformed Link header".to_string())
+        });
+        let page: Vec<GitHubRepo> = serde_json::from_str(&body)?;
 
         page_count += 1;
-        page = next_page;
+        let fetched = all_repos.len();
+        all_repos.extend(page.into_iter().filter_map(&mut *convert));
+        update_progress(page_count, all_repos.len());
+        // Every page but the last is full, and the last page only links back
+        if let Some(Ok(link)) = &link {
+            let last_page = pagination::last_page_number(link);
+            expected = last_page.map(|pages| pages as usize * REPOS_PER_PAGE).or(expected);
+        }
+        let expected_total = expected.map(|total| total.max(all_repos.len()));
+        on_page(username, &all_repos[fetched..], expected_total);
 
-        all_repos.extend(
-            page.items
-                .into_iter()
-                .map(|repo| convert_repo(repo, &username))
+        // The page is kept even when the link to the next one can't be read
+        next = match link {
+            Some(link) => pagination::next_page_url(&link?)?,
+            None => None,
+        };
+    }
+
+    Ok((all_repos, page_count))
+}
+
+// Fetches every page of a list with the given API, like fetch_repo_pages
+async fn fetch_list(
+    client: &Client<'_>,
+    api: Api<'_>,
+    list: &List<'_>,
+    username: &str,
+    convert: &mut dyn FnMut(GitHubRepo) -> Option<RepoInfo>,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(Vec<RepoInfo>, usize), Box<dyn std::error::Error>> {
+    match api {
+        Api::Rest => {
+            let route = list.route();
+            fetch_repo_pages(client, &route, username, convert, on_page, on_status).await
+        }
+        Api::GraphQl(options) => {
+            let policy = client.policy;
+            graphql::fetch_pages(options, list, username, convert, policy, on_page, on_status)
+                .await
+        }
+    }
+}
+
+// The logins of the organizations the user belongs to
+async fn fetch_org_logins(
+    client: &Client<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut logins = Vec::new();
+    let mut next = Some(ORGS_ROUTE.to_string());
+    while let Some(url) = next {
+        let (headers, body) = get(client, &url, on_status).await?;
+        let orgs: Vec<serde_json::Value> = serde_json::from_str(&body)?;
+        logins.extend(orgs.iter().filter_map(|org| Some(org["login"].as_str()?.to_string())));
+        next = match headers.get("link") {
+            Some(link) => {
+                pagination::next_page_url(link.to_str().map_err(|_| "Malformed Link header")?)?
+            }
+            None => None,
+        };
+    }
+    Ok(logins)
+}
+
+// Fetches the user's repositories with the affiliations asked for, those of the
+// organizations asked for and with starred the ones they starred. An organization's
+// repositories are fetched on their own as well, as the user's list only has those a
+// team gave them access to, but only for organization_member. Repositories are listed
+// once, by full name, so starring their own doesn't mark them
+async fn fetch_all_repos(
+    client: &Client<'_>,
+    api: Api<'_>,
+    username: &str,
+    options: &FetchOptions,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(Vec<RepoInfo>, usize), Box<dyn std::error::Error>> {
+    let (affiliations, orgs) = (&options.affiliations, &options.orgs);
+    // Starred repositories are kept whichever organization they belong to
+    let mut seen = HashSet::new();
+    let mut keep = |repo: &GitHubRepo, is_starred: bool| {
+        let owned_by_org = repo.owner.as_ref().filter(|owner| owner.r#type == "Organization");
+        if !is_starred && owned_by_org.is_some_and(|org| !orgs.includes(&org.login)) {
+            return false;
+        }
+        let full_name = repo.full_name.clone().unwrap_or_else(|| repo.name.clone());
+        seen.insert(full_name.to_lowercase())
+    };
+    let mut convert = |repo: GitHubRepo, is_starred: bool| {
+        if !keep(&repo, is_starred) {
+            return None;
+        }
+        let owner = repo.owner.as_ref();
+        let affiliation = Affiliation::infer(
+            owner.is_some_and(|owner| owner.login.eq_ignore_ascii_case(username)),
+            owner.is_some_and(|owner| owner.r#type == "Organization"),
+            affiliations,
         );
-        update_progress(page_count, all_repos.len());
+        let affiliation = (!is_starred).then_some(affiliation);
+        Some(RepoInfo { is_starred, affiliation, ..convert_repo(repo, username) })
+    };
+    let mut convert_own = |repo| convert(repo, false);
+
+    // Organization members are left out along with the organizations
+    let wanted: Vec<&str> = Affiliation::ALL
+        .into_iter()
+        .filter(|affiliation| affiliations.contains(affiliation))
+        .filter(|affiliation| {
+            *affiliation != Affiliation::OrganizationMember || *orgs != OrgRepos::Exclude
+        })
+        .map(Affiliation::name)
+        .collect();
+    let (mut all_repos, mut page_count) = if wanted.is_empty() {
+        (Vec::new(), 0)
+    } else {
+        let list = List::Affiliated(wanted);
+        fetch_list(client, api, &list, username, &mut convert_own, on_page, on_status).await?
+    };
+
+    let org_logins = match orgs {
+        _ if !affiliations.contains(&Affiliation::OrganizationMember) => Vec::new(),
+        OrgRepos::All => fetch_org_logins(client, on_status).await?,
+        OrgRepos::Exclude => Vec::new(),
+        OrgRepos::Only(orgs) => orgs.clone(),
+    };
+    for org in org_logins {
+        // The estimated totals count what was fetched before
+        let fetched = all_repos.len();
+        let mut on_org_page = |username: &str, repos: &[RepoInfo], expected: Option<usize>| {
+            on_page(username, repos, expected.map(|expected| expected + fetched))
+        };
+        let list = List::Org(&org);
+        let org_pages = fetch_list(
+            client,
+            api,
+            &list,
+            username,
+            &mut convert_own,
+            &mut on_org_page,
+            on_status,
+        );
+        // Organizations that restrict access, like with SAML, don't stop the others
+        match org_pages.await {
+            Ok((repos, pages)) => {
+                all_repos.extend(repos);
+                page_count += pages;
+            }
+            Err(e) => on_status(format!("Skipped the repositories of {}: {}", org, e)),
+        }
     }
 
-    println!("✓"); // Show checkmark on its own line
-    println!("Fetched {} repositories from {} pages", all_repos.len(), page_count);
+    if options.starred {
+        let fetched = all_repos.len();
+        let mut on_starred_page = |username: &str, repos: &[RepoInfo], expected: Option<usize>| {
+            on_page(username, repos, expected.map(|expected| expected + fetched))
+        };
+        let mut convert_starred = |repo| convert(repo, true);
+        let (repos, pages) = fetch_list(
+            client,
+            api,
+            &List::Starred,
+            username,
+            &mut convert_starred,
+            &mut on_starred_page,
+            on_status,
+        )
+        .await?;
+        all_repos.extend(repos);
+        page_count += pages;
+    }
+    Ok((all_repos, page_count))
+}
+
+/// Fetches all repositories of the user the token belongs to
+///
+/// `host` is the web address of GitHub, like `https://github.com` or that of a GitHub
+/// Enterprise Server. `options` says which of the repositories the user has access to
+/// are fetched and which organizations' repositories and whether the starred ones are
+/// fetched too. `on_page` gets every page as it is fetched, `on_status` how long is left
+/// to wait for a rate limit. The pages of the last fetch in `options`, which GitHub only
+/// sends again when they changed, get those of this one.
+///
+/// With `fork_parents` or `merge_request_counts` the lists are fetched with the GraphQL
+/// API, which has them without a request per repository. The REST API is used when it
+/// fails before the first page is in, like for a server without it.
+pub async fn fetch_repos(
+    token: &str,
+    host: &str,
+    options: &FetchOptions,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(String, Vec<RepoInfo>), Box<dyn std::error::Error>> {
+    eprint!("Fetching user information... ");
+    std::io::stderr().flush().unwrap();
+
+    let api_client = Client {
+        octocrab: client(&api_url(host), token)?,
+        pages: &options.pages,
+        policy: &options.retry,
+    };
+
+    // Get authenticated user information
+    let (_, user) = get(&api_client, "/user", on_status).await?;
+    let user: serde_json::Value = serde_json::from_str(&user)?;
+    let username = user["login"]
+        .as_str()
+        .ok_or("Failed to get GitHub username. Please check your GitHub token.")?
+        .to_string();
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprint!("Fetching repositories for {}... ", username);
+    std::io::stderr().flush().unwrap();
+
+    let (fork_parents, pull_request_counts) = (options.fork_parents, options.merge_request_counts);
+    let mut fetched = None;
+    if fork_parents || pull_request_counts {
+        let (base_uri, route) = graphql::endpoint(host);
+        let graphql_options = graphql::Options {
+            client: client(&base_uri, token)?,
+            route,
+            fork_parents,
+            pull_request_counts,
+        };
+        let mut shown = false;
+        let mut on_graphql_page = |username: &str, repos: &[RepoInfo], expected| {
+            shown = true;
+            on_page(username, repos, expected)
+        };
+        let all = fetch_all_repos(
+            &api_client,
+            Api::GraphQl(&graphql_options),
+            &username,
+            options,
+            &mut on_graphql_page,
+            on_status,
+        );
+        match all.await {
+            Ok(all) => fetched = Some(all),
+            Err(e) if !shown => on_status(format!("Using the GitHub REST API: {}", e)),
+            Err(e) => return Err(e),
+        }
+    }
+    let graphql = fetched.is_some();
+    let (mut all_repos, page_count) = match fetched {
+        Some(fetched) => fetched,
+        None => {
+            fetch_all_repos(&api_client, Api::Rest, &username, options, on_page, on_status).await?
+        }
+    };
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} repositories from {} pages", all_repos.len(), page_count);
+
+    // The GraphQL API had them already
+    if graphql {
+        return Ok((username, all_repos));
+    }
+    if fork_parents {
+        eprint!("Looking up the parents of forks... ");
+        std::io::stderr().flush().unwrap();
+        fetch_fork_parents(&api_client.octocrab, &mut all_repos).await;
+        eprintln!("✓");
+    }
+    if pull_request_counts {
+        eprint!("Counting open pull requests... ");
+        std::io::stderr().flush().unwrap();
+        fetch_pull_request_counts(&api_client.octocrab, &mut all_repos).await;
+        eprintln!("✓");
+    }
     Ok((username, all_repos))
 }
 
-pub fn generate_dummy_repos() -> (String, Vec<Repository>) {
-    println!("Using 100 dummy repositories for testing");
+pub fn generate_dummy_repos() -> (String, Vec<RepoInfo>) {
+    eprintln!("Using 100 dummy repositories for testing");
     let username = "dima-369".to_string();
 
     // Generate 100 dummy repositories with different names and categories
     let mut dummy_repos = Vec::with_capacity(100);
+    let dummy_repo = |name: &str, description: &str| RepoInfo {
+        ssh_url: format!("git@github.com:{}/{}.git", username, name),
+        description: description.to_string(),
+        https_url: format!("https://github.com/{}/{}.git", username, name),
+        web_url: format!("https://github.com/{}/{}", username, name),
+        ..RepoInfo::new(name, &username, RepoSource::GitHub)
+    };
+    let topics = |topics: &[&str]| topics.iter().map(|topic| topic.to_string()).collect();
 
     // Add some special repositories that are easy to find
-    dummy_repos.push(("clj-basic-image-cache-server".to_string(), "git@github.com:dima-369/clj-basic-image-cache-server.git".to_string(), "A basic image cache server written in Clojure".to_string(), username.clone(), true, false));
-    dummy_repos.push(("rust-web-server".to_string(), "git@github.com:dima-369/rust-web-server.git".to_string(), "A web server written in Rust".to_string(), username.clone(), false, true));
-    dummy_repos.push(("go-microservices".to_string(), "git@github.com:dima-369/go-microservices.git".to_string(), "Microservices examples in Go".to_string(), username.clone(), false, false));
+    dummy_repos.push(RepoInfo {
+        is_fork: true,
+        fork_parent: Some("ring-clojure/ring".to_string()),
+        is_archived: true,
+        language: Some("Clojure".to_string()),
+        topics: topics(&["clojure", "cache"]),
+        stars: Some(12),
+        pushed_at: Some(Utc::now() - chrono::Duration::days(800)),
+        ..dummy_repo(
+            "clj-basic-image-cache-server",
+            "A basic image cache server written in Clojure",
+        )
+    });
+    dummy_repos.push(RepoInfo {
+        visibility: formatter::Visibility::Private,
+        language: Some("Rust".to_string()),
+        topics: topics(&["rust", "http", "server", "async"]),
+        open_issues: Some(12),
+        open_merge_requests: Some(3),
+        pushed_at: Some(Utc::now() - chrono::Duration::hours(5)),
+        ..dummy_repo("rust-web-server", "A web server written in Rust")
+    });
+    dummy_repos.push(RepoInfo {
+        is_mirror: true,
+        language: Some("Go".to_string()),
+        stars: Some(1_480),
+        ..dummy_repo("go-microservices", "Microservices examples in Go")
+    });
 
     // Add repositories by category
     let categories = ["api", "web", "mobile", "backend", "frontend", "database", "utils", "tools", "docs", "test"];
+    let languages = [Some("Rust"), Some("TypeScript"), Some("Kotlin"), None, Some("Jupyter Notebook"), Some("Go")];
 
     for i in 1..=97 {
         let category = categories[i % categories.len()];
         let name = format!("{}-project-{}", category, i);
-        let url = format!("git@github.com:{}/{}.git", username, name);
         let description = format!("A {} project for {}", category, if i % 2 == 0 { "development" } else { "production" });
         // Make some repos forks and some private for variety
-        let is_fork = i % 5 == 0;  // Every 5th repo is a fork
-        let is_private = i % 7 == 0; // Every 7th repo is private
-        dummy_repos.push((name, url, description, username.clone(), is_fork, is_private));
+        dummy_repos.push(RepoInfo {
+            is_fork: i % 5 == 0, // Every 5th repo is a fork
+            // Every 7th repo is private
+            visibility: formatter::Visibility::from_private(i % 7 == 0),
+            is_archived: i % 11 == 0, // Every 11th repo is archived
+            is_mirror: i % 13 == 0, // Every 13th repo is a mirror
+            stars: Some((i * i * 37 % 2_000) as u32), // Some repos have no stars at all
+            open_issues: Some((i % 9) as u32), // Some repos have no open issues
+            language: languages[i % languages.len()].map(str::to_string),
+            topics: vec![category.to_string()],
+            pushed_at: Some(Utc::now() - chrono::Duration::days((i * i % 1_500) as i64)),
+            ..dummy_repo(&name, &description)
+        });
     }
 
     (username, dummy_repos)
 }
 
-pub fn extract_repo_info(selection: &str, username: &str) -> Option<(String, String, Option<String>)> {
-    // First, remove the GitHub indicator [GH] if present
-    let cleaned_selection = selection.replace(" [GH]", "");
+pub fn extract_repo_info(
+    selection: &str,
+    username: &str,
+    source_tag: &str,
+    host: &str,
+) -> Option<(String, String, Option<String>)> {
+    // Extract the repository name, everything after it is indicators, badges and the description
+    let repo_name = formatter::selected_name(selection, source_tag);
 
-    // Remove the private indicator if present
-    let cleaned_selection = cleaned_selection.replace(" 🔒", "");
+    // A name prefixed with its owner, like myorg/api, belongs to that owner
+    let (owner, repo_name) = repo_name.rsplit_once('/').unwrap_or((username, repo_name));
+    let (url, browser_url) = repo_urls(owner, repo_name, host);
 
-    // Extract repository name and description from selection
-    let repo_name = if let Some((name, _description_part)) = cleaned_selection.split_once(" (") {
-        // Selection has a description in parentheses
-        name.trim()
-    } else {
-        // Selection is just the repo name without description
-        cleaned_selection.trim()
-    };
+    Some((repo_name.to_string(), url, Some(browser_url)))
+}
 
-    // Construct a URL based on the repository name and username
-    let url = format!("git@github.com:{}/{}.git", username, repo_name);
+/// The clone and browser URLs of the repository of an owner
+pub fn repo_urls(owner: &str, repo_name: &str, host: &str) -> (String, String) {
+    // Construct a URL based on the repository name and owner
+    let url = format!("git@{}:{}/{}.git", hosts::ssh_host(host), owner, repo_name);
 
     // Extract GitHub repo path for browser URL
-    let browser_url = Some(format!("https://github.com/{}/{}", username, repo_name));
+    let browser_url = format!("{}/{}/{}", host, owner, repo_name);
+
+    (url, browser_url)
+}
 
-    Some((repo_name.to_string(), url, browser_url))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::NO_WAIT;
+    use crate::test_server::{Response, TestServer};
+
+    #[test]
+    fn test_convert_repo_from_api_payload() {
+        let user = "https://api.github.com/users/myorg";
+        let payload = serde_json::json!({
+            "id": 1296269,
+            "node_id": "MDEwOlJlcG9zaXRvcnkxMjk2MjY5",
+            "name": "api",
+            "full_name": "myorg/api",
+            "owner": {
+                "login": "myorg",
+                "id": 3,
+                "node_id": "MDQ6VXNlcjM=",
+                "avatar_url": "https://avatars.githubusercontent.com/u/3",
+                "gravatar_id": "",
+                "url": user,
+                "html_url": "https://github.com/myorg",
+                "followers_url": format!("{}/followers", user),
+                "following_url": format!("{}/following", user),
+                "gists_url": format!("{}/gists", user),
+                "starred_url": format!("{}/starred", user),
+                "subscriptions_url": format!("{}/subscriptions", user),
+                "organizations_url": format!("{}/orgs", user),
+                "repos_url": format!("{}/repos", user),
+                "events_url": format!("{}/events", user),
+                "received_events_url": format!("{}/received_events", user),
+                "type": "Organization",
+                "site_admin": false
+            },
+            "private": true,
+            "html_url": "https://github.com/myorg/api",
+            "description": "Backend service",
+            "fork": false,
+            "url": "https://api.github.com/repos/myorg/api",
+            "ssh_url": "git@github.com:myorg/api.git",
+            "clone_url": "https://github.com/myorg/api.git",
+            "mirror_url": null,
+            "language": "Rust",
+            "topics": ["http", "service"],
+            "license": {
+                "key": "mit",
+                "name": "MIT License",
+                "spdx_id": "MIT",
+                "url": "https://api.github.com/licenses/mit",
+                "node_id": "MDc6TGljZW5zZW1pdA=="
+            },
+            "default_branch": "main",
+            "is_template": true,
+            "stargazers_count": 1234,
+            "size": 108,
+            "open_issues_count": 8,
+            "archived": true,
+            "created_at": "2020-01-26T19:01:12Z",
+            "pushed_at": "2024-05-01T10:00:00Z"
+        });
+        let repo = convert_repo(serde_json::from_value(payload.clone()).unwrap(), "dima");
+
+        assert_eq!(
+            repo,
+            RepoInfo {
+                ssh_url: "git@github.com:myorg/api.git".to_string(),
+                description: "Backend service".to_string(),
+                visibility: formatter::Visibility::Private,
+                is_archived: true,
+                is_template: true,
+                language: Some("Rust".to_string()),
+                topics: vec!["http".to_string(), "service".to_string()],
+                license: Some("MIT".to_string()),
+                default_branch: Some("main".to_string()),
+                stars: Some(1234),
+                size_kb: Some(108),
+                open_issues: Some(8),
+                pushed_at: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+                created_at: Some("2020-01-26T19:01:12Z".parse().unwrap()),
+                https_url: "https://github.com/myorg/api.git".to_string(),
+                web_url: "https://github.com/myorg/api".to_string(),
+                ..RepoInfo::new("api", "myorg", RepoSource::GitHub)
+            }
+        );
+        let display = formatter::format_repo_info(&repo);
+        let badges = "[Rust] ‹MIT› (Backend service) #http #service ★ 1.2k ⊙ 8 · ";
+        assert!(display.starts_with(&format!("api 🔒 📦 ▤ [GH] {}", badges)));
+
+        // An empty repository has no branch yet, or at least takes no space
+        let mut empty = payload.clone();
+        empty["default_branch"] = serde_json::Value::Null;
+        let repo = convert_repo(serde_json::from_value(empty).unwrap(), "dima");
+        assert_eq!(repo.default_branch, None);
+        assert!(repo.is_empty);
+        let mut empty = payload;
+        empty["size"] = 0.into();
+        assert!(convert_repo(serde_json::from_value(empty).unwrap(), "dima").is_empty);
+    }
+
+    // Serves three pages of repositories, the second one with the given Link header
+    async fn paginated_server(second_link: Option<&'static str>) -> TestServer {
+        TestServer::start(move |request| {
+            let api_repo = |name: &str| {
+                serde_json::json!({
+                    "id": name.len(),
+                    "name": name,
+                    "url": format!("https://api.github.com/repos/dima/{}", name),
+                })
+            };
+            let next = |page: u32| {
+                let url = format!("{}/user/repos?per_page=100&page={}", request.base_url(), page);
+                format!(r#"<{}>; rel="next""#, url)
+            };
+            match request.target.as_str() {
+                REPOS_ROUTE => {
+                    let last = format!("{}/user/repos?per_page=100&page=3", request.base_url());
+                    let link = format!(r#"{}, <{}>; rel="last""#, next(2), last);
+                    Response::json(serde_json::json!([api_repo("api"), api_repo("cli")]))
+                        .header("link", &link)
+                }
+                "/user/repos?per_page=100&page=2" => {
+                    let link = second_link.map_or_else(|| next(3), str::to_string);
+                    Response::json(serde_json::json!([api_repo("web")])).header("link", &link)
+                }
+                "/user/repos?per_page=100&page=3" => {
+                    Response::json(serde_json::json!([api_repo("docs")]))
+                }
+                _ => Response::json(r#"{"message": "Not Found"}"#).status(404),
+            }
+        })
+        .await
+    }
+
+    fn test_client<'a>(
+        server: &TestServer,
+        pages: &'a PageCache,
+        policy: &'a RetryPolicy,
+    ) -> Client<'a> {
+        Client {
+            octocrab: client(&server.url, "token").unwrap(),
+            pages,
+            policy,
+        }
+    }
+
+    // Fetches the pages of REPOS_ROUTE for dima, keeping every repository
+    async fn fetch_user_pages(
+        client: &Client<'_>,
+        on_page: &mut PageCallback<'_>,
+        on_status: &mut dyn FnMut(String),
+    ) -> Result<Vec<RepoInfo>, Box<dyn std::error::Error>> {
+        let convert = &mut |repo| Some(convert_repo(repo, "dima"));
+        let pages = fetch_repo_pages(client, REPOS_ROUTE, "dima", convert, on_page, on_status);
+        Ok(pages.await?.0)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_every_page() {
+        let server = paginated_server(None).await;
+        let mut pages = Vec::new();
+        let mut on_page = |username: &str, repos: &[RepoInfo], expected: Option<usize>| {
+            assert_eq!(username, "dima");
+            pages.push((repos.len(), expected));
+        };
+        let cache = PageCache::default();
+        let client = test_client(&server, &cache, &NO_WAIT);
+        let repos = fetch_user_pages(&client, &mut on_page, &mut |_| {})
+            .await
+            .unwrap();
+
+        let names: Vec<_> = repos.iter().map(|repo| repo.name.as_str()).collect();
+        assert_eq!(names, ["api", "cli", "web", "docs"]);
+        assert_eq!(pages, [(2, Some(300)), (1, Some(300)), (1, Some(300))]);
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(server.requests()[0].header("authorization"), Some("Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stops_at_a_malformed_link_header() {
+        let link = r#"<http://localhost/user/repos?page=3; rel="next""#;
+        let server = paginated_server(Some(link)).await;
+        let mut pages = Vec::new();
+        let mut on_page = |_: &str, repos: &[RepoInfo], _| pages.push(repos.len());
+        let cache = PageCache::default();
+        let client = test_client(&server, &cache, &NO_WAIT);
+        let error = fetch_user_pages(&client, &mut on_page, &mut |_| {})
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().starts_with("Malformed Link header"));
+        // The pages before it were still handed out
+        assert_eq!(pages, [2, 1]);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_waits_for_a_rate_limit_that_resets_soon() {
+        // The second page is turned down once by a secondary rate limit
+        let limited = std::sync::atomic::AtomicBool::new(false);
+        let server = TestServer::start(move |request| {
+            let api_repo =
+                serde_json::json!({"id": 1, "name": "api", "url": "https://api.github.com"});
+            let next = format!(r#"<{}/user/repos?page=2>; rel="next""#, request.base_url());
+            match request.target.as_str() {
+                REPOS_ROUTE => Response::json(serde_json::json!([api_repo])).header("link", &next),
+                _ if !limited.swap(true, std::sync::atomic::Ordering::SeqCst) => {
+                    Response::json(r#"{"message": "You have exceeded a secondary rate limit"}"#)
+                        .status(403)
+                        .header("retry-after", "1")
+                }
+                _ => Response::json(serde_json::json!([api_repo])),
+            }
+        })
+        .await;
+
+        let mut statuses = Vec::new();
+        let cache = PageCache::default();
+        let policy = RetryPolicy::default();
+        let client = test_client(&server, &cache, &policy);
+        let repos = fetch_user_pages(&client, &mut |_, _, _| {}, &mut |status| {
+            statuses.push(status)
+        })
+        .await
+        .unwrap();
+        assert_eq!(repos.len(), 2);
+        assert_eq!(statuses, ["GitHub rate limit reached, retrying in 1s"]);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_reports_when_a_rate_limit_resets() {
+        let server = TestServer::start(|_| {
+            let reset = (Utc::now() + chrono::Duration::hours(1)).timestamp().to_string();
+            Response::json(r#"{"message": "API rate limit exceeded"}"#)
+                .status(403)
+                .header("x-ratelimit-remaining", "0")
+                .header("x-ratelimit-reset", &reset)
+        })
+        .await;
+
+        let cache = PageCache::default();
+        let policy = RetryPolicy::default();
+        let client = test_client(&server, &cache, &policy);
+        let error = fetch_user_pages(&client, &mut |_, _, _| {}, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("GitHub rate limit exceeded, it resets at "));
+        assert!(error.to_string().ends_with("(in 60 minutes)"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_server_errors() {
+        // The first page fails twice before it comes through
+        let failures = std::sync::atomic::AtomicUsize::new(0);
+        let server = TestServer::start(move |_| {
+            if failures.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+                Response::json(r#"{"message": "Server Error"}"#).status(502)
+            } else {
+                Response::json(r#"[{"id": 1, "name": "api", "url": "https://api.github.com"}]"#)
+            }
+        })
+        .await;
+
+        let cache = PageCache::default();
+        let client = test_client(&server, &cache, &NO_WAIT);
+        let repos = fetch_user_pages(&client, &mut |_, _, _| {}, &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(repos.len(), 1);
+        assert_eq!(server.requests().len(), 3);
+
+        // Without retries left the request that failed is named
+        let policy = RetryPolicy {
+            retries: 1,
+            ..NO_WAIT
+        };
+        let failing = TestServer::start(|_| Response::json("{}").status(503)).await;
+        let cache = PageCache::default();
+        let client = test_client(&failing, &cache, &policy);
+        let error = fetch_user_pages(&client, &mut |_, _, _| {}, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "GET /user/repos?per_page=100 failed after 2 attempts: 503 Service Unavailable"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sends_the_etags_of_the_last_fetch() {
+        // Only the second page changes after the first fetch
+        let changed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let server = TestServer::start({
+            let changed = changed.clone();
+            move |request| {
+                let repo = |name: &str| {
+                    serde_json::json!([{"id": 1, "name": name, "url": "https://api.github.com"}])
+                };
+                let next = |page| {
+                    format!(r#"<{}/user/repos?page={}>; rel="next""#, request.base_url(), page)
+                };
+                let (etag, page, link) = match request.target.as_str() {
+                    REPOS_ROUTE => (r#""a""#, repo("api"), Some(next(2))),
+                    "/user/repos?page=2" if changed.load(std::sync::atomic::Ordering::SeqCst) => {
+                        (r#""c""#, repo("web"), Some(next(3)))
+                    }
+                    "/user/repos?page=2" => (r#""b""#, repo("cli"), Some(next(3))),
+                    _ => (r#"W/"d""#, repo("docs"), None),
+                };
+                let response = match request.header("if-none-match") {
+                    Some(sent) if sent == etag => Response::json("").status(304),
+                    _ => Response::json(page),
+                };
+                let response = response.header("etag", etag);
+                match link {
+                    Some(link) => response.header("link", &link),
+                    None => response,
+                }
+            }
+        })
+        .await;
+        let fetch_names = async |cache: &PageCache| {
+            let client = test_client(&server, cache, &NO_WAIT);
+            let (on_page, on_status) = (&mut |_: &str, _: &[RepoInfo], _| {}, &mut |_| {});
+            let repos = fetch_user_pages(&client, on_page, on_status).await.unwrap();
+            repos.into_iter().map(|repo| repo.name).collect::<Vec<_>>()
+        };
+
+        let cache = PageCache::default();
+        assert_eq!(fetch_names(&cache).await, ["api", "cli", "docs"]);
+        let first = cache.into_fetched();
+        assert_eq!(first.len(), 3);
+        assert_eq!(first[REPOS_ROUTE].etag, r#""a""#);
+        assert!(first[REPOS_ROUTE].link.as_ref().unwrap().contains("page=2"));
+
+        changed.store(true, std::sync::atomic::Ordering::SeqCst);
+        let cache = PageCache::new(first.clone());
+        assert_eq!(fetch_names(&cache).await, ["api", "web", "docs"]);
+
+        // The pages that didn't change are kept as they were
+        let requests = server.requests();
+        let sent: Vec<_> = requests[3..].iter().map(|r| r.header("if-none-match")).collect();
+        assert_eq!(sent, [Some(r#""a""#), Some(r#""b""#), Some(r#"W/"d""#)]);
+        let second = cache.into_fetched();
+        let page_2 = server.url.clone() + "/user/repos?page=2";
+        assert_eq!(second[REPOS_ROUTE], first[REPOS_ROUTE]);
+        assert_eq!(second[&page_2].etag, r#""c""#);
+        assert!(second[&page_2].body.contains("web"));
+    }
+
+    // A repository as the lists have it, owned by a user or an organization
+    fn owned_repo(owner: &str, owner_type: &str, name: &str) -> serde_json::Value {
+        let user = format!("https://api.github.com/users/{}", owner);
+        serde_json::json!({
+            "id": name.len(),
+            "name": name,
+            "full_name": format!("{}/{}", owner, name),
+            "url": format!("https://api.github.com/repos/{}/{}", owner, name),
+            "owner": {
+                "login": owner,
+                "id": owner.len(),
+                "node_id": "MDQ6VXNlcjM=",
+                "avatar_url": "https://avatars.githubusercontent.com/u/3",
+                "gravatar_id": "",
+                "url": user,
+                "html_url": format!("https://github.com/{}", owner),
+                "followers_url": user,
+                "following_url": user,
+                "gists_url": user,
+                "starred_url": user,
+                "subscriptions_url": user,
+                "organizations_url": user,
+                "repos_url": user,
+                "events_url": user,
+                "received_events_url": user,
+                "type": owner_type,
+                "site_admin": false
+            }
+        })
+    }
+
+    async fn org_server() -> TestServer {
+        // Organization names are not case-sensitive
+        TestServer::start(|request| match request.target.to_lowercase().as_str() {
+            "/user/repos?per_page=100&affiliation=owner,collaborator,organization_member" => {
+                Response::json(serde_json::json!([
+                    owned_repo("dima", "User", "api"),
+                    owned_repo("acme", "Organization", "web"),
+                    owned_repo("other", "Organization", "lib"),
+                ]))
+            }
+            "/user/repos?per_page=100&affiliation=owner,collaborator" => {
+                Response::json(serde_json::json!([
+                    owned_repo("dima", "User", "api"),
+                    owned_repo("acme", "Organization", "web"),
+                ]))
+            }
+            ORGS_ROUTE => Response::json(r#"[{"login": "acme"}, {"login": "locked"}]"#),
+            "/orgs/acme/repos?per_page=100" => Response::json(serde_json::json!([
+                owned_repo("acme", "Organization", "web"),
+                owned_repo("acme", "Organization", "docs"),
+            ])),
+            STARRED_ROUTE => Response::json(serde_json::json!([
+                owned_repo("dima", "User", "api"),
+                owned_repo("rust-lang", "Organization", "rust"),
+            ])),
+            _ => Response::json(r#"{"message": "Resource protected by SAML enforcement"}"#)
+                .status(403),
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_fetch_organization_repositories() {
+        let server = org_server().await;
+        let cache = PageCache::default();
+        let client = test_client(&server, &cache, &NO_WAIT);
+        let fetch = async |orgs: OrgRepos, statuses: &mut Vec<String>| {
+            let on_status = &mut |status| statuses.push(status);
+            let on_page = &mut |_: &str, _: &[RepoInfo], _| {};
+            let options = FetchOptions { orgs, ..Default::default() };
+            let fetched = fetch_all_repos(&client, Api::Rest, "dima", &options, on_page, on_status);
+            let (repos, _) = fetched.await.unwrap();
+            let names = repos.into_iter().map(|repo| format!("{}/{}", repo.owner, repo.name));
+            names.collect::<Vec<_>>()
+        };
+
+        // Repositories in both lists are listed once, locked organizations are skipped
+        let mut statuses = Vec::new();
+        let all = fetch(OrgRepos::All, &mut statuses).await;
+        assert_eq!(all, ["dima/api", "acme/web", "other/lib", "acme/docs"]);
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].starts_with("Skipped the repositories of locked: "));
+
+        let only = fetch(OrgRepos::Only(vec!["ACME".to_string()]), &mut Vec::new()).await;
+        assert_eq!(only, ["dima/api", "acme/web", "acme/docs"]);
+        assert_eq!(fetch(OrgRepos::Exclude, &mut Vec::new()).await, ["dima/api"]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_starred_repositories() {
+        let server = org_server().await;
+        let cache = PageCache::default();
+        let client = test_client(&server, &cache, &NO_WAIT);
+        let on_page = &mut |_: &str, _: &[RepoInfo], _| {};
+        let on_status = &mut |_| {};
+        let options = FetchOptions {
+            orgs: OrgRepos::Exclude,
+            starred: true,
+            ..Default::default()
+        };
+        let fetched = fetch_all_repos(&client, Api::Rest, "dima", &options, on_page, on_status);
+        let (repos, _) = fetched.await.unwrap();
+
+        // The owned entry wins, starred repositories are kept whoever owns them
+        let names: Vec<_> = repos
+            .iter()
+            .map(|repo| (format!("{}/{}", repo.owner, repo.name), repo.is_starred))
+            .collect();
+        assert_eq!(names, [("dima/api".to_string(), false), ("rust-lang/rust".to_string(), true)]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_repositories_by_affiliation() {
+        let server = org_server().await;
+        let cache = PageCache::default();
+        let client = test_client(&server, &cache, &NO_WAIT);
+        let on_page = &mut |_: &str, _: &[RepoInfo], _| {};
+        let on_status = &mut |_| {};
+        let options = FetchOptions {
+            affiliations: vec![Affiliation::Collaborator, Affiliation::Owner],
+            ..Default::default()
+        };
+        let fetched = fetch_all_repos(&client, Api::Rest, "dima", &options, on_page, on_status);
+        let (repos, _) = fetched.await.unwrap();
+
+        // The organization's repository was shared with the user, the organizations are
+        // left alone
+        let tagged: Vec<_> =
+            repos.iter().map(|repo| (repo.name.as_str(), repo.affiliation)).collect();
+        assert_eq!(
+            tagged,
+            [("api", Some(Affiliation::Owner)), ("web", Some(Affiliation::Collaborator))]
+        );
+        let requests = server.requests();
+        assert!(!requests.iter().any(|request| request.target.starts_with("/user/orgs")));
+        assert!(!requests.iter().any(|request| request.target.starts_with("/orgs/")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_an_enterprise_server() {
+        // An older server below /api/v3 without topics, node IDs or identified licenses
+        // and with rate limiting turned off, so without its headers
+        let server = TestServer::start(|request| match request.target.as_str() {
+            target if target.starts_with("/api/v3/user/repos?per_page=100&") => {
+                Response::json(serde_json::json!([{
+                    "id": 7,
+                    "name": "billing",
+                    "full_name": "platform/billing",
+                    "owner": { "login": "platform", "id": 3, "type": "Organization" },
+                    "license": { "key": "other", "name": "Other", "spdx_id": null },
+                    "html_url": "https://github.mycorp.com/platform/billing",
+                    "language": null
+                }]))
+            }
+            _ => Response::json(r#"{"message": "Not Found"}"#).status(404),
+        })
+        .await;
+        let cache = PageCache::default();
+        let client = Client {
+            octocrab: client(&api_url(&server.url), "token").unwrap(),
+            pages: &cache,
+            policy: &NO_WAIT,
+        };
+        let orgs = OrgRepos::Only(vec!["platform".to_string()]);
+        let options = FetchOptions { orgs, ..Default::default() };
+        let on_page = &mut |_: &str, _: &[RepoInfo], _| {};
+        let on_status = &mut |_| {};
+        let fetched = fetch_all_repos(&client, Api::Rest, "dima", &options, on_page, on_status);
+        let (repos, _) = fetched.await.unwrap();
+
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos[0].owner, "platform");
+        assert_eq!(repos[0].topics, Vec::<String>::new());
+        assert_eq!(repos[0].license, Some("NOASSERTION".to_string()));
+        assert_eq!(repos[0].web_url, "https://github.mycorp.com/platform/billing");
+        assert_eq!(api_url(hosts::GITHUB_COM), GITHUB_API);
+    }
 }
@@ -0,0 +1,581 @@
+//! Fetching the repository lists with the GitHub GraphQL API
+//!
+//! A page of 100 repositories comes with the parents of forks and the open issue and pull
+//! request counts, which the REST API needs a request per repository for. Repositories
+//! are turned into the shape the REST API has, so both share `convert_repo`. Parts of a
+//! page GitHub can't deliver, like a repository of an organization with SAML
+//! enforcement, come as errors next to the rest of the page; the rest is kept and the
+//! errors are shown in the status line.
+
+use chrono::{DateTime, Utc};
+use octocrab::Octocrab;
+use serde::Deserialize;
+
+use super::{GitHubLicense, GitHubOwner, GitHubParent, GitHubRepo, List, REPOS_PER_PAGE};
+use crate::hosts;
+use crate::pagination::PageCallback;
+use crate::repo_info::RepoInfo;
+use crate::retry::RetryPolicy;
+
+// What is asked for of every repository, all of it is optional for older servers
+const REPOSITORY_FRAGMENT: &str = "
+fragment repository on Repository {
+  name
+  nameWithOwner
+  owner { login __typename }
+  description
+  isFork
+  parent { nameWithOwner }
+  isPrivate
+  isArchived
+  mirrorUrl
+  isTemplate
+  diskUsage
+  defaultBranchRef { name }
+  primaryLanguage { name }
+  repositoryTopics(first: 20) { nodes { topic { name } } }
+  licenseInfo { spdxId }
+  stargazerCount
+  issues(states: OPEN) { totalCount }
+  pullRequests(states: OPEN) { totalCount }
+  pushedAt
+  createdAt
+  sshUrl
+  url
+}";
+
+// What is asked for of every page
+const CONNECTION_FIELDS: &str =
+    "totalCount pageInfo { endCursor hasNextPage } nodes { ...repository }";
+
+/// The base URI of a client for the GraphQL API and its route. github.com has its own
+/// host for it, GitHub Enterprise Server has it at `/api/graphql` next to the REST API
+/// at `/api/v3`, which its REST client can't reach as it puts its base path in front
+pub fn endpoint(host: &str) -> (String, &'static str) {
+    if host == hosts::GITHUB_COM {
+        (super::GITHUB_API.to_string(), "/graphql")
+    } else {
+        (host.to_string(), "/api/graphql")
+    }
+}
+
+/// The client for the endpoint and what is taken over from its answers
+pub struct Options<'a> {
+    pub client: Octocrab,
+    pub route: &'a str,
+    pub fork_parents: bool,
+    pub pull_request_counts: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection {
+    total_count: Option<usize>,
+    page_info: PageInfo,
+    // Repositories GitHub couldn't deliver are null
+    #[serde(default)]
+    nodes: Vec<Option<Node>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    end_cursor: Option<String>,
+    has_next_page: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Node {
+    name: String,
+    name_with_owner: Option<String>,
+    owner: Option<Owner>,
+    description: Option<String>,
+    is_fork: Option<bool>,
+    parent: Option<NameWithOwner>,
+    is_private: Option<bool>,
+    is_archived: Option<bool>,
+    mirror_url: Option<String>,
+    is_template: Option<bool>,
+    // In kilobytes
+    disk_usage: Option<u32>,
+    default_branch_ref: Option<Name>,
+    primary_language: Option<Name>,
+    repository_topics: Option<Topics>,
+    license_info: Option<License>,
+    stargazer_count: Option<u32>,
+    issues: Option<Count>,
+    pull_requests: Option<Count>,
+    pushed_at: Option<DateTime<Utc>>,
+    created_at: Option<DateTime<Utc>>,
+    ssh_url: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Owner {
+    login: String,
+    // User or Organization
+    #[serde(rename = "__typename", default)]
+    typename: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NameWithOwner {
+    name_with_owner: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Name {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Topics {
+    nodes: Vec<TopicNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopicNode {
+    topic: Name,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct License {
+    spdx_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Count {
+    total_count: u32,
+}
+
+impl Node {
+    // The repository as the REST API has it, with its open pull requests. The REST API
+    // counts pull requests as issues, so they are counted in here as well
+    fn into_rest(self, fork_parents: bool) -> (GitHubRepo, Option<u32>) {
+        let pull_requests = self.pull_requests.map(|count| count.total_count);
+        let repo = GitHubRepo {
+            name: self.name,
+            full_name: self.name_with_owner,
+            owner: self.owner.map(|owner| GitHubOwner {
+                login: owner.login,
+                r#type: owner.typename,
+            }),
+            description: self.description,
+            fork: self.is_fork,
+            parent: self.parent.filter(|_| fork_parents).map(|parent| GitHubParent {
+                full_name: Some(parent.name_with_owner),
+            }),
+            private: self.is_private,
+            archived: self.is_archived,
+            mirror_url: self.mirror_url,
+            is_template: self.is_template,
+            size: self.disk_usage,
+            default_branch: self.default_branch_ref.map(|branch| branch.name),
+            language: self.primary_language.map(|language| language.name),
+            topics: self.repository_topics.map(|topics| {
+                topics.nodes.into_iter().map(|node| node.topic.name).collect()
+            }),
+            license: self.license_info.map(|license| GitHubLicense {
+                spdx_id: license.spdx_id,
+            }),
+            stargazers_count: self.stargazer_count,
+            open_issues_count: self
+                .issues
+                .map(|issues| issues.total_count + pull_requests.unwrap_or(0)),
+            pushed_at: self.pushed_at,
+            created_at: self.created_at,
+            ssh_url: self.ssh_url,
+            clone_url: self.url.as_ref().map(|url| format!("{}.git", url)),
+            html_url: self.url,
+        };
+        (repo, pull_requests)
+    }
+}
+
+// The query of a list, where its connection is in the response and the variables it
+// needs besides the cursor
+fn query(list: &List<'_>) -> (String, &'static str, serde_json::Value) {
+    let (arguments, selection, pointer, variables) = match list {
+        List::Affiliated(wanted) => {
+            let wanted = wanted.join(", ").to_uppercase();
+            let connection = format!(
+                "repositories(first: {}, after: $cursor, affiliations: [{}], \
+                 ownerAffiliations: [{}])",
+                REPOS_PER_PAGE, wanted, wanted
+            );
+            (
+                "",
+                format!("viewer {{ {} {{ {} }} }}", connection, CONNECTION_FIELDS),
+                "/viewer/repositories",
+                serde_json::json!({}),
+            )
+        }
+        List::Org(org) => (
+            ", $login: String!",
+            format!(
+                "organization(login: $login) {{ \
+                 repositories(first: {}, after: $cursor) {{ {} }} }}",
+                REPOS_PER_PAGE, CONNECTION_FIELDS
+            ),
+            "/organization/repositories",
+            serde_json::json!({ "login": org }),
+        ),
+        List::Starred => (
+            "",
+            format!(
+                "viewer {{ starredRepositories(first: {}, after: $cursor) {{ {} }} }}",
+                REPOS_PER_PAGE, CONNECTION_FIELDS
+            ),
+            "/viewer/starredRepositories",
+            serde_json::json!({}),
+        ),
+    };
+    let query = format!(
+        "query($cursor: String{}) {{ {} }}\n{}",
+        arguments, selection, REPOSITORY_FRAGMENT
+    );
+    (query, pointer, variables)
+}
+
+// The connection at pointer in a response and the errors next to it. Without the
+// connection the errors say why
+fn parse_page(body: &str, pointer: &str) -> Result<(Connection, Vec<String>), String> {
+    let response: Response = serde_json::from_str(body)
+        .map_err(|e| format!("Malformed GitHub GraphQL response: {}", e))?;
+    let errors: Vec<String> = response.errors.into_iter().map(|error| error.message).collect();
+    let connection = response
+        .data
+        .as_ref()
+        .and_then(|data| data.pointer(pointer))
+        .filter(|connection| !connection.is_null());
+    let Some(connection) = connection else {
+        return Err(match errors.is_empty() {
+            true => format!("The GitHub GraphQL response has no {}", pointer),
+            false => errors.join("; "),
+        });
+    };
+    let connection = serde_json::from_value(connection.clone())
+        .map_err(|e| format!("Malformed GitHub GraphQL response: {}", e))?;
+    Ok((connection, errors))
+}
+
+/// Fetches every page of a list, following the cursors, like `fetch_repo_pages` does
+/// with the REST API
+pub async fn fetch_pages(
+    options: &Options<'_>,
+    list: &List<'_>,
+    username: &str,
+    convert: &mut dyn FnMut(GitHubRepo) -> Option<RepoInfo>,
+    policy: &RetryPolicy,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(Vec<RepoInfo>, usize), Box<dyn std::error::Error>> {
+    let (query, pointer, variables) = query(list);
+    let mut all_repos = Vec::new();
+    let mut page_count = 0;
+    let mut cursor: Option<String> = None;
+
+    loop {
+        if page_count > 0 {
+            // Add a small sleep to allow Ctrl+C to be processed
+            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        }
+
+        let mut variables = variables.clone();
+        variables["cursor"] = cursor.into();
+        let request = serde_json::json!({ "query": query, "variables": variables });
+        let route = options.route;
+        let request = Some(&request);
+        let (_, _, body) =
+            super::send(&options.client, route, request, None, policy, on_status).await?;
+        let (connection, errors) = parse_page(&body, pointer)?;
+        if let Some(first) = errors.first() {
+            let more = match errors.len() {
+                1 => String::new(),
+                count => format!(" (and {} more)", count - 1),
+            };
+            on_status(format!("GitHub left out part of a page: {}{}", first, more));
+        }
+
+        page_count += 1;
+        let fetched = all_repos.len();
+        for node in connection.nodes.into_iter().flatten() {
+            let (repo, pull_requests) = node.into_rest(options.fork_parents);
+            let Some(mut repo) = convert(repo) else {
+                continue;
+            };
+            if let Some(count) = pull_requests.filter(|_| options.pull_request_counts) {
+                repo.open_merge_requests = Some(count);
+                repo.open_issues = repo.open_issues.map(|issues| issues.saturating_sub(count));
+            }
+            all_repos.push(repo);
+        }
+        super::update_progress(page_count, all_repos.len());
+        let expected = connection.total_count.map(|total| total.max(all_repos.len()));
+        on_page(username, &all_repos[fetched..], expected);
+
+        cursor = match connection.page_info {
+            PageInfo {
+                has_next_page: true,
+                end_cursor: Some(end_cursor),
+            } => Some(end_cursor),
+            _ => break,
+        };
+    }
+
+    Ok((all_repos, page_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{client, convert_repo, fetch_repos, OrgRepos};
+    use super::*;
+    use crate::formatter::{self, RepoSource};
+    use crate::repo_info::{Affiliation, FetchOptions};
+    use crate::retry::NO_WAIT;
+    use crate::test_server::{Response, TestServer};
+
+    // Everything taken over, from a test server
+    fn options(server: &TestServer) -> Options<'static> {
+        Options {
+            client: client(&server.url, "token").unwrap(),
+            route: "/graphql",
+            fork_parents: true,
+            pull_request_counts: true,
+        }
+    }
+
+    // A repository as the API returned it
+    fn recorded_node() -> serde_json::Value {
+        serde_json::json!({
+            "name": "ring",
+            "nameWithOwner": "acme/ring",
+            "owner": { "login": "acme", "__typename": "Organization" },
+            "description": "HTTP server abstraction",
+            "isFork": true,
+            "parent": { "nameWithOwner": "ring-clojure/ring" },
+            "isPrivate": true,
+            "isArchived": false,
+            "mirrorUrl": null,
+            "isTemplate": false,
+            "diskUsage": 2048,
+            "defaultBranchRef": { "name": "main" },
+            "primaryLanguage": { "name": "Clojure" },
+            "repositoryTopics": { "nodes": [{ "topic": { "name": "http" } }] },
+            "licenseInfo": { "spdxId": "MIT" },
+            "stargazerCount": 12,
+            "issues": { "totalCount": 4 },
+            "pullRequests": { "totalCount": 2 },
+            "pushedAt": "2024-05-01T10:00:00Z",
+            "createdAt": "2020-01-26T19:01:12Z",
+            "sshUrl": "git@github.com:acme/ring.git",
+            "url": "https://github.com/acme/ring"
+        })
+    }
+
+    fn node_named(name: &str) -> serde_json::Value {
+        let mut node = recorded_node();
+        node["name"] = name.into();
+        node["nameWithOwner"] = format!("acme/{}", name).into();
+        node
+    }
+
+    #[test]
+    fn test_convert_node() {
+        let node: Node = serde_json::from_value(recorded_node()).unwrap();
+        let (repo, pull_requests) = node.into_rest(true);
+        assert_eq!(pull_requests, Some(2));
+        assert_eq!(
+            convert_repo(repo, "dima"),
+            RepoInfo {
+                ssh_url: "git@github.com:acme/ring.git".to_string(),
+                description: "HTTP server abstraction".to_string(),
+                is_fork: true,
+                fork_parent: Some("ring-clojure/ring".to_string()),
+                visibility: formatter::Visibility::Private,
+                language: Some("Clojure".to_string()),
+                topics: vec!["http".to_string()],
+                license: Some("MIT".to_string()),
+                default_branch: Some("main".to_string()),
+                stars: Some(12),
+                size_kb: Some(2048),
+                // Pull requests count as issues, like with the REST API
+                open_issues: Some(6),
+                pushed_at: Some("2024-05-01T10:00:00Z".parse().unwrap()),
+                created_at: Some("2020-01-26T19:01:12Z".parse().unwrap()),
+                https_url: "https://github.com/acme/ring.git".to_string(),
+                web_url: "https://github.com/acme/ring".to_string(),
+                ..RepoInfo::new("ring", "acme", RepoSource::GitHub)
+            }
+        );
+
+        // Parents are only taken over when asked for, older servers leave fields out
+        let node: Node = serde_json::from_value(recorded_node()).unwrap();
+        assert_eq!(node.into_rest(false).0.parent.map(|_| ()), None);
+        let node: Node = serde_json::from_value(serde_json::json!({"name": "bare"})).unwrap();
+        let repo = convert_repo(node.into_rest(true).0, "dima");
+        assert_eq!((repo.owner.as_str(), repo.open_issues), ("dima", None));
+    }
+
+    #[test]
+    fn test_queries() {
+        let (text, pointer, variables) = query(&List::Affiliated(vec!["owner", "collaborator"]));
+        let affiliations = "affiliations: [OWNER, COLLABORATOR], \
+                            ownerAffiliations: [OWNER, COLLABORATOR]";
+        assert!(text.contains(affiliations));
+        assert!(text.contains("fragment repository on Repository"));
+        assert_eq!((pointer, variables), ("/viewer/repositories", serde_json::json!({})));
+
+        let (text, pointer, variables) = query(&List::Org("acme"));
+        assert!(text.starts_with("query($cursor: String, $login: String!)"));
+        assert_eq!(pointer, "/organization/repositories");
+        assert_eq!(variables, serde_json::json!({"login": "acme"}));
+        assert_eq!(query(&List::Starred).1, "/viewer/starredRepositories");
+        assert_eq!(endpoint(hosts::GITHUB_COM), ("https://api.github.com".to_string(), "/graphql"));
+        let enterprise = endpoint("https://github.mycorp.com");
+        assert_eq!(enterprise, ("https://github.mycorp.com".to_string(), "/api/graphql"));
+    }
+
+    // Answers the viewer's repositories in two pages, the second one with a repository
+    // GitHub couldn't deliver
+    async fn paginated_server() -> TestServer {
+        TestServer::start(|request| {
+            let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+            let connection = match body["variables"]["cursor"].as_str() {
+                None => serde_json::json!({
+                    "totalCount": 3,
+                    "pageInfo": { "endCursor": "Y3Vyc29yOjI=", "hasNextPage": true },
+                    "nodes": [node_named("api"), node_named("cli")]
+                }),
+                Some("Y3Vyc29yOjI=") => serde_json::json!({
+                    "totalCount": 3,
+                    "pageInfo": { "endCursor": "Y3Vyc29yOjM=", "hasNextPage": false },
+                    "nodes": [node_named("web"), null]
+                }),
+                Some(_) => return Response::json(r#"{"message": "Bad cursor"}"#).status(400),
+            };
+            let errors = match connection["nodes"][1].is_null() {
+                true => serde_json::json!([
+                    { "type": "FORBIDDEN", "message": "Resource protected by SAML enforcement" }
+                ]),
+                false => serde_json::json!([]),
+            };
+            Response::json(serde_json::json!({
+                "data": { "viewer": { "repositories": connection } },
+                "errors": errors
+            }))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_cursors() {
+        let server = paginated_server().await;
+        let options = options(&server);
+        let mut pages = Vec::new();
+        let mut statuses = Vec::new();
+        let (repos, page_count) = fetch_pages(
+            &options,
+            &List::Affiliated(vec!["owner"]),
+            "dima",
+            &mut |repo| Some(convert_repo(repo, "dima")),
+            &NO_WAIT,
+            &mut |_, repos, expected| pages.push((repos.len(), expected)),
+            &mut |status| statuses.push(status),
+        )
+        .await
+        .unwrap();
+
+        let names: Vec<_> = repos.iter().map(|repo| repo.name.as_str()).collect();
+        assert_eq!(names, ["api", "cli", "web"]);
+        assert_eq!((page_count, pages), (2, vec![(2, Some(3)), (1, Some(3))]));
+        assert_eq!((repos[0].open_issues, repos[0].open_merge_requests), (Some(4), Some(2)));
+        // The rest of a page with errors is kept
+        let saml = "GitHub left out part of a page: Resource protected by SAML enforcement";
+        assert_eq!(statuses, [saml]);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].target, "/graphql");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer token"));
+        assert!(requests[1].body.contains(r#""cursor":"Y3Vyc29yOjI=""#));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_fails_without_the_list() {
+        let server = TestServer::start(|_| {
+            Response::json(serde_json::json!({
+                "data": { "organization": null },
+                "errors": [{
+                    "type": "NOT_FOUND",
+                    "message": "Could not resolve to an Organization with the login of 'gone'."
+                }]
+            }))
+        })
+        .await;
+        let options = options(&server);
+        let error = fetch_pages(
+            &options,
+            &List::Org("gone"),
+            "dima",
+            &mut |repo| Some(convert_repo(repo, "dima")),
+            &NO_WAIT,
+            &mut |_, _, _| {},
+            &mut |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Could not resolve to an Organization with the login of 'gone'."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_repos_falls_back_to_rest() {
+        // A server without the GraphQL API
+        let server = TestServer::start(|request| match request.target.as_str() {
+            "/api/v3/user" => Response::json(r#"{"login": "dima"}"#),
+            target if target.starts_with("/api/v3/user/repos?") => {
+                Response::json(r#"[{"id": 1, "name": "api", "url": "https://github.mycorp.com"}]"#)
+            }
+            _ => Response::json(r#"{"message": "Not Found"}"#).status(404),
+        })
+        .await;
+        let mut statuses = Vec::new();
+        let options = FetchOptions {
+            affiliations: vec![Affiliation::Owner],
+            orgs: OrgRepos::Exclude,
+            retry: NO_WAIT,
+            merge_request_counts: true,
+            ..Default::default()
+        };
+        let on_status = &mut |status| statuses.push(status);
+        let on_page = &mut |_: &str, _: &[RepoInfo], _| {};
+        let fetched = fetch_repos("token", &server.url, &options, on_page, on_status);
+        let (username, repos) = fetched.await.unwrap();
+
+        assert_eq!((username.as_str(), repos.len()), ("dima", 1));
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].starts_with("Using the GitHub REST API: "));
+        let graphql = server.requests().into_iter().filter(|r| r.target == "/api/graphql");
+        assert_eq!(graphql.count(), 1);
+    }
+}
@@ -1,146 +1,400 @@
+use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::io::Write;
+use std::pin::Pin;
+use std::task::Poll;
 
-// Define our Repository type to match GitHub's format
-pub type Repository = (String, String, String, String, bool, bool); // (name, ssh_url, description, owner, is_fork, is_private)
+use crate::formatter::{self, RepoSource};
+use crate::hosts;
+use crate::pagination::{self, PageCallback};
+use crate::retry::{self, RetryPolicy};
+use crate::repo_info::{Affiliation, FetchOptions, Inclusion, RepoInfo};
+
+// How many language requests run at the same time
+const LANGUAGE_REQUESTS_AT_ONCE: usize = 10;
+
+// How many merge request count requests run at the same time
+const MERGE_REQUEST_COUNTS_AT_ONCE: usize = 10;
+
+// How many groups have their projects fetched at the same time
+const GROUPS_AT_ONCE: usize = 4;
+
+// The lowest access level of the groups whose projects are fetched, Guest
+const MIN_GROUP_ACCESS_LEVEL: &str = "10";
+
+// Where the API of an instance is, below its web address
+pub const API_PATH: &str = "/api/v4";
+
+// Projects are asked for this many at a time, instances may cap pages at fewer
+const PER_PAGE: &str = "100";
 
 // GitLab API response structures
 #[derive(Debug, Deserialize, Clone)]
 struct GitLabProject {
-    #[allow(dead_code)]
     id: u64,
     name: String,
     description: Option<String>,
     ssh_url_to_repo: String,
-    #[allow(dead_code)]
+    #[serde(default)]
+    http_url_to_repo: String,
+    #[serde(default)]
+    web_url: String,
     namespace: GitLabNamespace,
     forked_from_project: Option<GitLabForkedFrom>,
     visibility: String,
+    #[serde(default)]
+    archived: bool,
+    // Whether this is a pull mirror of a repository elsewhere
+    #[serde(default)]
+    mirror: bool,
+    // Whether the project has no commits yet
+    #[serde(default)]
+    empty_repo: bool,
+    #[serde(default)]
+    topics: Vec<String>,
+    // Null for empty projects without a branch
+    #[serde(default)]
+    default_branch: Option<String>,
+    // Only reported with statistics=true to members who can see them
+    #[serde(default)]
+    statistics: Option<GitLabStatistics>,
+    // Only reported when asked for with license=true
+    #[serde(default)]
+    license: Option<GitLabLicense>,
+    #[serde(default)]
+    star_count: u32,
+    // Missing when the project has issues turned off
+    #[serde(default)]
+    open_issues_count: Option<u32>,
+    last_activity_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct GitLabNamespace {
     #[allow(dead_code)]
     name: String,
-    #[allow(dead_code)]
     path: String,
+    // Full path including parent groups, like group/subgroup
+    #[serde(default)]
+    full_path: String,
+    // Either user or group
+    #[serde(default)]
+    kind: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GitLabGroup {
+    id: u64,
+    // Like group/subgroup
+    full_path: String,
+    // Null for top-level groups
+    #[serde(default)]
+    parent_id: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct GitLabForkedFrom {
     #[allow(dead_code)]
     id: u64,
+    // Like group/project, missing when the parent is not visible to the user
+    #[serde(default)]
+    path_with_namespace: String,
 }
 
-// Helper function to convert GitLab project to our Repository type
-fn convert_project(project: GitLabProject, username: &str) -> Repository {
-    (
-        project.name,
-        project.ssh_url_to_repo,
-        project.description.unwrap_or_default(),
-        username.to_string(),
-        project.forked_from_project.is_some(),
-        project.visibility != "public",
-    )
+#[derive(Debug, Deserialize, Clone)]
+struct GitLabStatistics {
+    // In bytes
+    repository_size: u64,
 }
 
-// Helper function to update progress display
-fn update_progress(page_count: usize, repos_count: usize) {
-    print!("\r                                                  "); // Clear the line
-    print!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
-    std::io::stdout().flush().unwrap();
+#[derive(Debug, Deserialize, Clone)]
+struct GitLabLicense {
+    // Lowercase identifier of the license, like mit or apache-2.0
+    key: String,
 }
 
-pub async fn fetch_repos(token: &str) -> Result<(String, Vec<Repository>), Box<dyn std::error::Error>> {
-    print!("Fetching GitLab user information... ");
-    std::io::stdout().flush().unwrap();
+// The SPDX identifiers of the license keys GitLab reports, which are the lowercase
+// identifiers for most licenses. Licenses GitLab could not identify are `other`
+fn spdx_license_id(key: &str) -> String {
+    const SPDX_IDS: [&str; 17] = [
+        "0BSD", "AFL-3.0", "AGPL-3.0", "Apache-2.0", "BSD-2-Clause", "BSD-3-Clause", "BSL-1.0",
+        "CC0-1.0", "EPL-2.0", "GPL-2.0", "GPL-3.0", "ISC", "LGPL-2.1", "LGPL-3.0", "MIT",
+        "MPL-2.0", "Unlicense",
+    ];
+    match key {
+        "other" => "NOASSERTION".to_string(),
+        key => SPDX_IDS
+            .iter()
+            .find(|id| id.eq_ignore_ascii_case(key))
+            .map_or_else(|| key.to_string(), |id| id.to_string()),
+    }
+}
 
-    // Create HTTP client with authorization header
-    let client = reqwest::Client::new();
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", token))?,
-    );
+// Helper function to convert GitLab project to our RepoInfo type, owned by its
+// namespace so group projects can be filtered by group
+fn convert_project(project: GitLabProject, username: &str, language: Option<String>) -> RepoInfo {
+    let owner = if project.namespace.full_path.is_empty() {
+        username
+    } else {
+        &project.namespace.full_path
+    };
+    RepoInfo {
+        ssh_url: project.ssh_url_to_repo,
+        description: formatter::sanitize_text(&project.description.unwrap_or_default()),
+        is_fork: project.forked_from_project.is_some(),
+        fork_parent: project
+            .forked_from_project
+            .map(|parent| parent.path_with_namespace)
+            .filter(|path| !path.is_empty()),
+        // Unknown levels are treated as private rather than shown as public
+        visibility: formatter::Visibility::from_name(&project.visibility)
+            .unwrap_or(formatter::Visibility::Private),
+        is_archived: project.archived,
+        is_mirror: project.mirror,
+        is_empty: project.empty_repo
+            || project.default_branch.is_none()
+            || project.statistics.as_ref().is_some_and(|stats| stats.repository_size == 0),
+        language,
+        topics: project.topics,
+        license: project.license.map(|license| spdx_license_id(&license.key)),
+        default_branch: project.default_branch,
+        stars: Some(project.star_count),
+        size_kb: project.statistics.map(|statistics| statistics.repository_size / 1024),
+        open_issues: project.open_issues_count,
+        pushed_at: project.last_activity_at,
+        created_at: project.created_at,
+        https_url: project.http_url_to_repo,
+        web_url: project.web_url,
+        ..RepoInfo::new(&project.name, owner, RepoSource::GitLab)
+    }
+}
 
-    // Get user information
-    let response = client
-        .get("https://gitlab.com/api/v4/user")
-        .headers(headers.clone())
-        .send()
-        .await?;
-
-    // Check if response is successful
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await?;
-        return Err(format!("GitLab API error: {} - {}", status, text).into());
+// How the user came by a project they are a member of, see Affiliation::infer
+fn project_affiliation(
+    project: &GitLabProject,
+    username: &str,
+    wanted: &[Affiliation],
+) -> Affiliation {
+    let namespace = &project.namespace;
+    let owned = namespace.kind == "user" && namespace.path.eq_ignore_ascii_case(username);
+    Affiliation::infer(owned, namespace.kind == "group", wanted)
+}
+
+// Picks the language with the highest percentage from a languages endpoint response,
+// ties go to the alphabetically first one so the choice is stable
+fn top_language(languages: &HashMap<String, f64>) -> Option<String> {
+    languages
+        .iter()
+        .max_by(|(a_name, a_share), (b_name, b_share)| {
+            a_share.total_cmp(b_share).then_with(|| b_name.cmp(a_name))
+        })
+        .map(|(name, _)| name.clone())
+}
+
+// Fetches the top language of each project, a few projects at a time. GitLab only
+// reports languages through a separate endpoint per project. Languages are just a
+// badge, so projects whose languages can't be fetched simply get none
+async fn fetch_top_languages(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    api: &str,
+    projects: &[GitLabProject],
+) -> Vec<Option<String>> {
+    let mut languages = Vec::with_capacity(projects.len());
+
+    for chunk in projects.chunks(LANGUAGE_REQUESTS_AT_ONCE) {
+        let requests: Vec<_> = chunk
+            .iter()
+            .map(|project| {
+                let request = client
+                    .get(format!("{}/projects/{}/languages", api, project.id))
+                    .headers(headers.clone());
+                tokio::spawn(async move {
+                    let response = request.send().await.ok()?;
+                    if !response.status().is_success() {
+                        return None;
+                    }
+                    let shares: HashMap<String, f64> = response.json().await.ok()?;
+                    top_language(&shares)
+                })
+            })
+            .collect();
+
+        for request in requests {
+            languages.push(request.await.ok().flatten());
+        }
     }
 
-    let user: serde_json::Value = response.json().await?;
+    languages
+}
 
-    let username = user["username"]
-        .as_str()
-        .ok_or("Failed to get GitLab username. Please check your GitLab token.")?
-        .to_string();
+// Counts the open merge requests of each project, a few projects at a time, from the
+// total GitLab reports alongside a page with a single merge request. Projects whose
+// merge requests can't be counted get no count
+async fn fetch_merge_request_counts(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    api: &str,
+    projects: &[GitLabProject],
+) -> Vec<Option<u32>> {
+    let mut counts = Vec::with_capacity(projects.len());
 
-    println!("✓"); // Show checkmark on its own line
-    print!("Fetching repositories for GitLab user {}... ", username);
-    std::io::stdout().flush().unwrap();
+    for chunk in projects.chunks(MERGE_REQUEST_COUNTS_AT_ONCE) {
+        let requests: Vec<_> = chunk
+            .iter()
+            .map(|project| {
+                let request = client
+                    .get(format!("{}/projects/{}/merge_requests", api, project.id))
+                    .headers(headers.clone())
+                    .query(&[("state", "opened"), ("per_page", "1")]);
+                tokio::spawn(async move {
+                    let response = request.send().await.ok()?;
+                    if !response.status().is_success() {
+                        return None;
+                    }
+                    response.headers().get("x-total")?.to_str().ok()?.parse().ok()
+                })
+            })
+            .collect();
 
-    let mut all_repos = Vec::new();
-    let mut page_count = 1;
-    let per_page = 100; // Maximum allowed per page
-
-    // Fetch first page
-    let response = client
-        .get("https://gitlab.com/api/v4/projects")
-        .headers(headers.clone())
-        .query(&[
-            ("membership", "true"), // Get projects user is a member of
-            ("per_page", &per_page.to_string()),
-            ("page", &page_count.to_string()),
-        ])
-        .send()
-        .await?;
-
-    // Check if response is successful
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await?;
-        return Err(format!("GitLab API error: {} - {}", status, text).into());
+        for request in requests {
+            counts.push(request.await.ok().flatten());
+        }
     }
 
-    // Parse the response as JSON
-    let mut projects: Vec<GitLabProject> = response.json().await?;
+    counts
+}
 
-    // Add repos from the first page
-    all_repos.extend(
-        projects.clone()
-            .into_iter()
-            .map(|project| convert_project(project, &username))
-    );
+// The API of an instance, with the headers that authorize its requests
+struct Api {
+    client: reqwest::Client,
+    headers: HeaderMap,
+    url: String,
+}
 
-    update_progress(page_count, all_repos.len());
+// Converts a page of projects, fetching what the project list leaves out
+async fn convert_page(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    api: &str,
+    projects: &[GitLabProject],
+    username: &str,
+    merge_request_counts: bool,
+) -> Vec<RepoInfo> {
+    let languages = fetch_top_languages(client, headers, api, projects).await;
+    let merge_requests = if merge_request_counts {
+        fetch_merge_request_counts(client, headers, api, projects).await
+    } else {
+        vec![None; projects.len()]
+    };
+    projects
+        .iter()
+        .cloned()
+        .zip(languages)
+        .zip(merge_requests)
+        .map(|((project, language), open_merge_requests)| RepoInfo {
+            open_merge_requests,
+            ..convert_project(project, username, language)
+        })
+        .collect()
+}
 
-    // Fetch all remaining pages
-    while !projects.is_empty() && projects.len() == per_page {
-        // Add a small sleep to allow Ctrl+C to be processed
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+// Helper function to update progress display, on stderr as stdout is left to --output
+fn update_progress(page_count: usize, repos_count: usize) {
+    eprint!("\r                                                  "); // Clear the line
+    eprint!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
+    std::io::stderr().flush().unwrap();
+}
 
-        page_count += 1;
+// How pages of projects are asked for. Keyset pagination stays fast however many
+// projects there are, older self-hosted instances only know offset pagination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pagination {
+    Keyset,
+    Offset,
+}
 
-        let response = client
-            .get("https://gitlab.com/api/v4/projects")
-            .headers(headers.clone())
-            .query(&[
-                ("membership", "true"),
-                ("per_page", &per_page.to_string()),
-                ("page", &page_count.to_string()),
-            ])
-            .send()
-            .await?;
+// The query for a page of the projects the user is a member of, or with owned only of
+// those in their namespace, with or without the archived ones. Keyset pagination only
+// needs it for the first page, the Link header has the query for the next one
+fn projects_query(
+    pagination: Pagination,
+    page: u32,
+    owned: bool,
+    archived: Inclusion,
+) -> Vec<(&'static str, String)> {
+    let mut query = vec![
+        (if owned { "owned" } else { "membership" }, "true".to_string()),
+        ("license", "true".to_string()),
+        ("statistics", "true".to_string()),
+        ("per_page", PER_PAGE.to_string()),
+    ];
+    match archived {
+        Inclusion::Include => {}
+        Inclusion::Exclude => query.push(("archived", "false".to_string())),
+        Inclusion::Only => query.push(("archived", "true".to_string())),
+    }
+    match pagination {
+        Pagination::Keyset => query.extend([
+            ("pagination", "keyset".to_string()),
+            ("order_by", "id".to_string()),
+            ("sort", "asc".to_string()),
+        ]),
+        Pagination::Offset => query.push(("page", page.to_string())),
+    }
+    query
+}
+
+// Instances without keyset pagination for projects turn it down with 400 Bad Request
+// or 405 Method Not Allowed
+fn rejects_keyset(status: StatusCode) -> bool {
+    status == StatusCode::BAD_REQUEST || status == StatusCode::METHOD_NOT_ALLOWED
+}
+
+// Fetches every page of the projects the user is a member of, returning them with the
+// number of pages. Pages are followed through their Link header, or through the page
+// number in X-Next-Page where an instance only sends that. Either way a page with
+// fewer projects than asked for is not taken as the last one, as instances can cap
+// the page size. Every page is handed to on_page with the total from X-Total, which
+// only offset pagination reports. Only the projects with the affiliations asked for
+// are kept, their IDs go into seen, and GitLab leaves out archived ones as asked for
+async fn fetch_projects(
+    api: &Api,
+    username: &str,
+    options: &FetchOptions,
+    seen: &mut HashSet<u64>,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(Vec<RepoInfo>, usize), Box<dyn std::error::Error>> {
+    let Api { client, headers, url: api } = api;
+    let (affiliations, archived) = (&options.affiliations, options.filter.archived);
+    let (merge_request_counts, policy) = (options.merge_request_counts, &options.retry);
+    let projects_url = format!("{}/projects", api);
+    let owned = affiliations.iter().all(|affiliation| *affiliation == Affiliation::Owner);
+    let mut pagination = Pagination::Keyset;
+    let first_query = |pagination| projects_query(pagination, 1, owned, archived);
+    let mut request = client.get(&projects_url).query(&first_query(pagination));
+    let mut all_repos = Vec::new();
+    let mut page_count = 0;
+
+    loop {
+        let attempt = request.headers(headers.clone()).build()?;
+        let response = retry::send("GitLab", client, attempt, policy, on_status).await?;
+
+        let first_keyset_page = pagination == Pagination::Keyset && page_count == 0;
+        if first_keyset_page && rejects_keyset(response.status()) {
+            pagination = Pagination::Offset;
+            request = client.get(&projects_url).query(&first_query(pagination));
+            continue;
+        }
 
         // Check if response is successful
         if !response.status().is_success() {
@@ -149,48 +403,791 @@ pub async fn fetch_repos(token: &str) -> Result<(String, Vec<Repository>), Box<d
             return Err(format!("GitLab API error: {} - {}", status, text).into());
         }
 
-        // Parse the response as JSON
-        projects = response.json().await?;
+        let link = match response.headers().get("link") {
+            Some(link) => {
+                pagination::next_page_url(link.to_str().map_err(|_| "Malformed Link header")?)?
+            }
+            None => None,
+        };
+        let next_page = response
+            .headers()
+            .get("x-next-page")
+            .and_then(|page| page.to_str().ok()?.parse::<u32>().ok());
+        let next = match (link, next_page) {
+            (Some(url), _) => Some(client.get(url)),
+            // Instances that ignore the keyset parameters page by number instead
+            (None, Some(page)) => {
+                pagination = Pagination::Offset;
+                let query = projects_query(pagination, page, owned, archived);
+                Some(client.get(&projects_url).query(&query))
+            }
+            (None, None) => None,
+        };
+        let total = response
+            .headers()
+            .get("x-total")
+            .and_then(|total| total.to_str().ok()?.parse::<usize>().ok());
 
-        all_repos.extend(
-            projects.clone()
-                .into_iter()
-                .map(|project| convert_project(project, &username))
-        );
+        // Parse the response as JSON
+        let mut projects: Vec<GitLabProject> = response.json().await?;
+        projects.retain(|project| {
+            let affiliation = project_affiliation(project, username, affiliations);
+            affiliations.contains(&affiliation) && seen.insert(project.id)
+        });
 
+        page_count += 1;
+        let mut page =
+            convert_page(client, headers, api, &projects, username, merge_request_counts).await;
+        for (repo, project) in page.iter_mut().zip(&projects) {
+            repo.affiliation = Some(project_affiliation(project, username, affiliations));
+        }
+        all_repos.extend_from_slice(&page);
         update_progress(page_count, all_repos.len());
+        on_page(username, &page, total.map(|total| total.max(all_repos.len())));
+
+        match next {
+            Some(next) => request = next,
+            None => break,
+        }
+
+        // Add a small sleep to allow Ctrl+C to be processed
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    }
+
+    Ok((all_repos, page_count))
+}
+
+// Fetches every page of a list that is paged by number, like the groups, following the
+// Link header or the page number in X-Next-Page
+async fn fetch_list<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    url: &str,
+    query: &[(&str, &str)],
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    let mut request = client.get(url).query(query).query(&[("page", "1")]);
+    let mut items = Vec::new();
+    loop {
+        let attempt = request.headers(headers.clone()).build()?;
+        let response = retry::send("GitLab", client, attempt, policy, on_status).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await?;
+            return Err(format!("GitLab API error: {} - {}", status, text).into());
+        }
+
+        let link = match response.headers().get("link") {
+            Some(link) => {
+                pagination::next_page_url(link.to_str().map_err(|_| "Malformed Link header")?)?
+            }
+            None => None,
+        };
+        let next_page = response
+            .headers()
+            .get("x-next-page")
+            .and_then(|page| page.to_str().ok()?.parse::<u32>().ok());
+        items.extend(response.json::<Vec<T>>().await?);
+
+        request = match (link, next_page) {
+            (Some(url), _) => client.get(url),
+            (None, Some(page)) => {
+                client.get(url).query(query).query(&[("page", page.to_string())])
+            }
+            (None, None) => return Ok(items),
+        };
+    }
+}
+
+// Runs futures at the same time on the current task, returning what they came to in
+// order. Unlike spawned tasks they can borrow, like the status callback
+async fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output> {
+    let mut futures: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|context| {
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                if let Poll::Ready(done) = future.as_mut().poll(context) {
+                    *output = Some(done);
+                }
+            }
+        }
+        if outputs.iter().all(Option::is_some) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
+
+// Fetches the projects of every group the user can access, subgroups included, which
+// finds the projects they are no direct member of. Projects in seen are left out.
+// Groups are fetched a few at a time, and one that fails is reported through on_status
+// and skipped
+async fn fetch_group_projects(
+    api: &Api,
+    username: &str,
+    options: &FetchOptions,
+    seen: &mut HashSet<u64>,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<Vec<RepoInfo>, Box<dyn std::error::Error>> {
+    let Api { client, headers, url: api } = api;
+    let (merge_request_counts, policy) = (options.merge_request_counts, &options.retry);
+    let groups_query = [("min_access_level", MIN_GROUP_ACCESS_LEVEL), ("per_page", PER_PAGE)];
+    let groups: Vec<GitLabGroup> =
+        fetch_list(client, headers, &format!("{}/groups", api), &groups_query, policy, on_status)
+            .await?;
+
+    // The projects of subgroups come with those of their parent
+    let ids: HashSet<u64> = groups.iter().map(|group| group.id).collect();
+    let top_groups: Vec<&GitLabGroup> = groups
+        .iter()
+        .filter(|group| group.parent_id.is_none_or(|parent| !ids.contains(&parent)))
+        .collect();
+
+    let projects_query = [
+        ("include_subgroups", "true"),
+        ("license", "true"),
+        ("statistics", "true"),
+        ("per_page", PER_PAGE),
+    ];
+    let on_status = RefCell::new(on_status);
+    let mut all_repos = Vec::new();
+    for chunk in top_groups.chunks(GROUPS_AT_ONCE) {
+        let requests = chunk.iter().map(|group| {
+            let url = format!("{}/groups/{}/projects", api, group.id);
+            let on_status = &on_status;
+            async move {
+                let mut on_status = |status| (on_status.borrow_mut())(status);
+                let query = &projects_query;
+                fetch_list::<GitLabProject>(client, headers, &url, query, policy, &mut on_status)
+                    .await
+            }
+        });
+
+        for (group, projects) in chunk.iter().zip(join_all(requests).await) {
+            let mut projects = match projects {
+                Ok(projects) => projects,
+                Err(e) => {
+                    (on_status.borrow_mut())(format!(
+                        "Skipped the projects of {}: {}",
+                        group.full_path, e
+                    ));
+                    continue;
+                }
+            };
+            projects.retain(|project| seen.insert(project.id));
+            let mut page =
+                convert_page(client, headers, api, &projects, username, merge_request_counts)
+                    .await;
+            for repo in &mut page {
+                repo.affiliation = Some(Affiliation::OrganizationMember);
+            }
+            all_repos.extend_from_slice(&page);
+            on_page(username, &page, None);
+        }
+    }
+    Ok(all_repos)
+}
+
+// Fetches the projects the user starred, leaving out those in seen so their own stay as
+// they are
+async fn fetch_starred_projects(
+    api: &Api,
+    user_id: u64,
+    username: &str,
+    options: &FetchOptions,
+    seen: &mut HashSet<u64>,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<Vec<RepoInfo>, Box<dyn std::error::Error>> {
+    let Api { client, headers, url: api } = api;
+    let (merge_request_counts, policy) = (options.merge_request_counts, &options.retry);
+    let url = format!("{}/users/{}/starred_projects", api, user_id);
+    let query = [("license", "true"), ("statistics", "true"), ("per_page", PER_PAGE)];
+    let mut projects: Vec<GitLabProject> =
+        fetch_list(client, headers, &url, &query, policy, on_status).await?;
+    projects.retain(|project| seen.insert(project.id));
+
+    let repos: Vec<RepoInfo> =
+        convert_page(client, headers, api, &projects, username, merge_request_counts)
+            .await
+            .into_iter()
+            .map(|repo| RepoInfo { is_starred: true, ..repo })
+            .collect();
+    on_page(username, &repos, None);
+    Ok(repos)
+}
+
+// Fetches the user the token belongs to, which tells apart an instance that can't be
+// reached from a token it turns down
+async fn fetch_user(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    host: &str,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let request = client.get(format!("{}{}/user", host, API_PATH)).headers(headers.clone());
+    let response = retry::send("GitLab", client, request.build()?, policy, on_status)
+        .await
+        .map_err(|e| format!("Could not reach GitLab at {}: {}", host, e))?;
+
+    let status = response.status();
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err(format!(
+            "GitLab at {} rejected the token ({}), check that it is valid and has the \
+             read_api scope",
+            host, status
+        )
+        .into());
+    }
+    if !status.is_success() {
+        let text = response.text().await?;
+        return Err(format!("GitLab API error: {} - {}", status, text).into());
+    }
+    // Something else answering at the address, like a login page, sends no JSON
+    response
+        .json()
+        .await
+        .map_err(|_| format!("{} doesn't look like a GitLab instance", host).into())
+}
+
+/// Fetches all projects the user the token belongs to is a member of
+///
+/// `host` is the web address of the instance, like `https://gitlab.com`. Only those with
+/// the affiliations in `options` are kept, and GitLab sends the archived ones among them
+/// as its filter asks for. With `gitlab_groups` the projects of every group they can
+/// access are fetched too, subgroups included, unless `organization_member` is left out.
+/// With `starred` the projects they starred are. `on_page` gets every page as it is
+/// fetched, `on_status` how long is left to wait for a rate limit or which groups were
+/// skipped.
+pub async fn fetch_repos(
+    token: &str,
+    host: &str,
+    options: &FetchOptions,
+    on_page: &mut PageCallback<'_>,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(String, Vec<RepoInfo>), Box<dyn std::error::Error>> {
+    eprint!("Fetching GitLab user information... ");
+    std::io::stderr().flush().unwrap();
+
+    // Create HTTP client with authorization header
+    let client = reqwest::Client::builder()
+        .connect_timeout(retry::CONNECT_TIMEOUT)
+        .timeout(retry::REQUEST_TIMEOUT)
+        .build()?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token))?,
+    );
+
+    // Get user information
+    let user = fetch_user(&client, &headers, host, &options.retry, on_status).await?;
+    let api = Api { client, headers, url: format!("{}{}", host, API_PATH) };
+
+    let username = user["username"]
+        .as_str()
+        .ok_or("Failed to get GitLab username. Please check your GitLab token.")?
+        .to_string();
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprint!("Fetching repositories for GitLab user {}... ", username);
+    std::io::stderr().flush().unwrap();
+
+    let mut seen = HashSet::new();
+    let (mut all_repos, page_count) =
+        fetch_projects(&api, &username, options, &mut seen, on_page, on_status).await?;
+    if options.gitlab_groups && options.affiliations.contains(&Affiliation::OrganizationMember) {
+        let group_repos =
+            fetch_group_projects(&api, &username, options, &mut seen, on_page, on_status);
+        all_repos.extend(group_repos.await?);
+    }
+    if options.starred {
+        let user_id = user["id"].as_u64().ok_or("Failed to get the GitLab user ID")?;
+        let seen = &mut seen;
+        let starred_repos =
+            fetch_starred_projects(&api, user_id, &username, options, seen, on_page, on_status);
+        all_repos.extend(starred_repos.await?);
     }
 
-    println!("✓"); // Show checkmark on its own line
-    println!("Fetched {} GitLab repositories from {} pages", all_repos.len(), page_count);
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} GitLab repositories from {} pages", all_repos.len(), page_count);
     Ok((username, all_repos))
 }
 
-pub fn extract_repo_info(selection: &str, username: &str) -> Option<(String, String, Option<String>)> {
-    // First, remove the GitLab indicator [GL] if present
-    let cleaned_selection = selection.replace(" [GL]", "");
+pub fn extract_repo_info(
+    selection: &str,
+    username: &str,
+    source_tag: &str,
+    host: &str,
+) -> Option<(String, String, Option<String>)> {
+    // Extract the repository name, everything after it is indicators, badges and the description
+    let repo_name = formatter::selected_name(selection, source_tag);
 
-    // Remove the private indicator if present
-    let cleaned_selection = cleaned_selection.replace(" 🔒", "");
+    // A name prefixed with its namespace, like group/subgroup/api, belongs to that namespace
+    let (namespace, repo_name) = repo_name.rsplit_once('/').unwrap_or((username, repo_name));
+    let (url, browser_url) = repo_urls(namespace, repo_name, host);
 
-    // Extract repository name and description from selection
-    let repo_name = if let Some((name, _description_part)) = cleaned_selection.split_once(" (") {
-        // Selection has a description in parentheses
-        name.trim()
-    } else {
-        // Selection is just the repo name without description
-        cleaned_selection.trim()
-    };
+    Some((repo_name.to_string(), url, Some(browser_url)))
+}
 
+/// The clone and browser URLs of the project of a namespace, like `group/subgroup`
+pub fn repo_urls(namespace: &str, repo_name: &str, host: &str) -> (String, String) {
     // Convert repo name to kebab-case for GitLab URLs
     // This is a simple conversion that replaces spaces with hyphens and makes lowercase
     let repo_path = repo_name.to_lowercase().replace(" ", "-");
 
-    // Construct a URL based on the repository name and username
-    let url = format!("git@gitlab.com:{}/{}.git", username, repo_path);
+    // Construct a URL based on the repository name and namespace
+    let url = format!("git@{}:{}/{}.git", hosts::ssh_host(host), namespace, repo_path);
 
     // Extract GitLab repo path for browser URL
-    let browser_url = Some(format!("https://gitlab.com/{}/{}", username, repo_path));
+    let browser_url = format!("{}/{}/{}", host, namespace, repo_path);
+
+    (url, browser_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_info::FetchFilter;
+    use crate::retry::NO_WAIT;
+    use crate::test_server::{Response, TestServer};
+
+    #[test]
+    fn test_top_language() {
+        let languages = HashMap::from([
+            ("Ruby".to_string(), 20.5),
+            ("Jupyter Notebook".to_string(), 66.2),
+            ("Shell".to_string(), 13.3),
+        ]);
+        assert_eq!(top_language(&languages), Some("Jupyter Notebook".to_string()));
+
+        let tied = HashMap::from([("Go".to_string(), 50.0), ("C".to_string(), 50.0)]);
+        assert_eq!(top_language(&tied), Some("C".to_string()));
+
+        assert_eq!(top_language(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_spdx_license_id() {
+        assert_eq!(spdx_license_id("mit"), "MIT");
+        assert_eq!(spdx_license_id("bsd-3-clause"), "BSD-3-Clause");
+        assert_eq!(spdx_license_id("other"), "NOASSERTION");
+        // Keys without a known identifier are kept as GitLab reports them
+        assert_eq!(spdx_license_id("wtfpl"), "wtfpl");
+    }
+
+    #[test]
+    fn test_convert_project_from_api_payload() {
+        let payload = serde_json::json!({
+            "id": 4,
+            "name": "Diaspora Client",
+            "description": "Client for the Diaspora network",
+            "ssh_url_to_repo": "git@gitlab.com:group/apps/diaspora-client.git",
+            "http_url_to_repo": "https://gitlab.com/group/apps/diaspora-client.git",
+            "web_url": "https://gitlab.com/group/apps/diaspora-client",
+            "namespace": {
+                "id": 3,
+                "name": "Apps",
+                "path": "apps",
+                "kind": "group",
+                "full_path": "group/apps"
+            },
+            "forked_from_project": { "id": 2, "path_with_namespace": "diaspora/diaspora-client" },
+            "visibility": "internal",
+            "archived": false,
+            "mirror": true,
+            "topics": ["ruby"],
+            "default_branch": "master",
+            "license": { "key": "apache-2.0", "name": "Apache License 2.0", "nickname": null },
+            "star_count": 7,
+            "statistics": { "commit_count": 37, "repository_size": 1_572_864 },
+            "open_issues_count": 4,
+            "created_at": "2013-09-30T13:46:02Z",
+            "last_activity_at": "2024-02-03T12:00:00Z"
+        });
+        let project: GitLabProject = serde_json::from_value(payload).unwrap();
+        let repo = convert_project(project, "gira", Some("Ruby".to_string()));
+
+        assert_eq!(
+            repo,
+            RepoInfo {
+                ssh_url: "git@gitlab.com:group/apps/diaspora-client.git".to_string(),
+                description: "Client for the Diaspora network".to_string(),
+                is_fork: true,
+                fork_parent: Some("diaspora/diaspora-client".to_string()),
+                visibility: formatter::Visibility::Internal,
+                is_mirror: true,
+                language: Some("Ruby".to_string()),
+                topics: vec!["ruby".to_string()],
+                license: Some("Apache-2.0".to_string()),
+                default_branch: Some("master".to_string()),
+                stars: Some(7),
+                size_kb: Some(1536),
+                open_issues: Some(4),
+                pushed_at: Some("2024-02-03T12:00:00Z".parse().unwrap()),
+                created_at: Some("2013-09-30T13:46:02Z".parse().unwrap()),
+                https_url: "https://gitlab.com/group/apps/diaspora-client.git".to_string(),
+                web_url: "https://gitlab.com/group/apps/diaspora-client".to_string(),
+                ..RepoInfo::new("Diaspora Client", "group/apps", RepoSource::GitLab)
+            }
+        );
+    }
+
+    #[test]
+    fn test_convert_project_visibility() {
+        let project = |visibility: &str| -> GitLabProject {
+            serde_json::from_value(serde_json::json!({
+                "id": 1,
+                "name": "handbook",
+                "description": null,
+                "ssh_url_to_repo": "git@gitlab.com:team/handbook.git",
+                "namespace": { "name": "team", "path": "team", "full_path": "team" },
+                "forked_from_project": null,
+                "visibility": visibility,
+                "default_branch": null,
+                "last_activity_at": null,
+            }))
+            .unwrap()
+        };
+        let visibility = |level| convert_project(project(level), "gira", None).visibility;
+        assert_eq!(visibility("public"), formatter::Visibility::Public);
+        assert_eq!(visibility("internal"), formatter::Visibility::Internal);
+        assert_eq!(visibility("private"), formatter::Visibility::Private);
+        assert_eq!(visibility("restricted"), formatter::Visibility::Private);
+
+        // Empty projects have no default branch yet
+        let empty = convert_project(project("public"), "gira", None);
+        assert_eq!(empty.default_branch, None);
+        assert!(empty.is_empty);
+    }
 
-    Some((repo_name.to_string(), url, browser_url))
+    // The API of the test server, without a token
+    fn test_api(server: &TestServer) -> Api {
+        Api {
+            client: reqwest::Client::new(),
+            headers: HeaderMap::new(),
+            url: format!("{}/api/v4", server.url),
+        }
+    }
+
+    fn api_project(id: u64) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": format!("project-{}", id),
+            "description": null,
+            "ssh_url_to_repo": format!("git@gitlab.com:gira/project-{}.git", id),
+            "namespace": { "name": "gira", "path": "gira", "full_path": "gira" },
+            "visibility": "private",
+            "last_activity_at": null
+        })
+    }
+
+    fn page(ids: &[u64]) -> Response {
+        Response::json(serde_json::Value::Array(ids.iter().copied().map(api_project).collect()))
+    }
+
+    async fn fetched_names(server: &TestServer) -> Vec<String> {
+        let options = FetchOptions { retry: NO_WAIT, ..Default::default() };
+        let (on_page, on_status) = (&mut |_: &str, _: &[RepoInfo], _| {}, &mut |_| {});
+        let api = test_api(server);
+        let mut seen = HashSet::new();
+        let fetched = fetch_projects(&api, "gira", &options, &mut seen, on_page, on_status);
+        let (repos, _) = fetched.await.unwrap();
+        repos.into_iter().map(|repo| repo.name).collect()
+    }
+
+    // A project in a user's or group's namespace
+    fn namespaced_project(id: u64, kind: &str, path: &str) -> serde_json::Value {
+        let mut project = api_project(id);
+        project["namespace"] = serde_json::json!({
+            "name": path, "path": path, "full_path": path, "kind": kind
+        });
+        project
+    }
+
+    // The targets of the project list requests, leaving out languages and the like
+    fn project_requests(server: &TestServer) -> Vec<String> {
+        server
+            .requests()
+            .into_iter()
+            .map(|request| request.target)
+            .filter(|target| target.starts_with("/api/v4/projects?"))
+            .collect()
+    }
+
+    const KEYSET_QUERY: &str = "/api/v4/projects?membership=true&license=true&statistics=true\
+        &per_page=100&pagination=keyset&order_by=id&sort=asc";
+
+    #[tokio::test]
+    async fn test_fetch_projects_with_keyset_pagination() {
+        let server = TestServer::start(|request| {
+            if request.target == KEYSET_QUERY {
+                let next = format!(
+                    "{}/api/v4/projects?id_after=2&membership=true&pagination=keyset",
+                    request.base_url()
+                );
+                page(&[1, 2]).header("link", &format!(r#"<{}>; rel="next""#, next))
+            } else if request.target.starts_with("/api/v4/projects?id_after=2&") {
+                page(&[3])
+            } else {
+                Response::json(r#"{"message": "404 Not Found"}"#).status(404)
+            }
+        })
+        .await;
+
+        assert_eq!(fetched_names(&server).await, ["project-1", "project-2", "project-3"]);
+        assert_eq!(project_requests(&server).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_projects_falls_back_to_offset_pagination() {
+        let server = TestServer::start(|request| {
+            let target = request.target.as_str();
+            if target.contains("pagination=keyset") {
+                Response::json(r#"{"message": "405 Method Not Allowed"}"#).status(405)
+            } else if target.ends_with("&page=1") {
+                page(&[1, 2]).header("x-next-page", "2")
+            } else if target.ends_with("&page=2") {
+                page(&[3]).header("x-next-page", "")
+            } else {
+                Response::json(r#"{"message": "404 Not Found"}"#).status(404)
+            }
+        })
+        .await;
+
+        assert_eq!(fetched_names(&server).await, ["project-1", "project-2", "project-3"]);
+        let requests = project_requests(&server);
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].ends_with("&per_page=100&page=2"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_projects_asks_for_archived_ones_as_wanted() {
+        let server = TestServer::start(|_| page(&[1])).await;
+        for archived in [Inclusion::Include, Inclusion::Exclude, Inclusion::Only] {
+            let filter = FetchFilter { archived, ..Default::default() };
+            let options = FetchOptions { filter, retry: NO_WAIT, ..Default::default() };
+            let (on_page, on_status) = (&mut |_: &str, _: &[RepoInfo], _| {}, &mut |_| {});
+            let api = test_api(&server);
+            fetch_projects(&api, "gira", &options, &mut HashSet::new(), on_page, on_status)
+                .await
+                .unwrap();
+        }
+        let requests = project_requests(&server);
+        assert!(!requests[0].contains("archived"));
+        assert!(requests[1].contains("&archived=false&"));
+        assert!(requests[2].contains("&archived=true&"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_projects_hands_out_every_page() {
+        let server = TestServer::start(|request| match request.target.as_str() {
+            target if target.ends_with("&page=2") => page(&[3]).header("x-total", "3"),
+            _ => page(&[1, 2]).header("x-next-page", "2").header("x-total", "3"),
+        })
+        .await;
+
+        let mut pages = Vec::new();
+        let mut on_page = |username: &str, repos: &[RepoInfo], total: Option<usize>| {
+            assert_eq!(username, "gira");
+            pages.push((repos.len(), total));
+        };
+        let options = FetchOptions { retry: NO_WAIT, ..Default::default() };
+        let api = test_api(&server);
+        fetch_projects(&api, "gira", &options, &mut HashSet::new(), &mut on_page, &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(pages, [(2, Some(3)), (1, Some(3))]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_projects_with_capped_page_size() {
+        // This instance ignores the keyset parameters and sends 20 projects a page
+        let server = TestServer::start(|request| {
+            let page_number = match request.target.as_str() {
+                target if target.contains("pagination=keyset") => 1,
+                target => target.rsplit("page=").next().unwrap().parse().unwrap(),
+            };
+            let first = (page_number - 1) * 20 + 1;
+            let ids: Vec<u64> = (first..(first + 20).min(46)).collect();
+            let next = if page_number < 3 { (page_number + 1).to_string() } else { String::new() };
+            page(&ids).header("x-next-page", &next)
+        })
+        .await;
+
+        assert_eq!(fetched_names(&server).await.len(), 45);
+        let requests = project_requests(&server);
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].ends_with("&page=2"));
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_until_the_server_recovers() {
+        // Fails twice with a flaky gateway, then answers
+        let failures = std::sync::atomic::AtomicUsize::new(0);
+        let server = TestServer::start(move |_| {
+            match failures.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => Response::json(r#"{"message": "502 Bad Gateway"}"#).status(502),
+                1 => Response::json(r#"{"message": "Retry later"}"#).status(429),
+                _ => page(&[1]),
+            }
+        })
+        .await;
+
+        assert_eq!(fetched_names(&server).await, ["project-1"]);
+        assert_eq!(project_requests(&server).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_connection_errors() {
+        // Nothing listens on the port of a server that was dropped
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/v4/user", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = reqwest::Client::new();
+        let request = client.get(&url).build().unwrap();
+        let error = retry::send("GitLab", &client, request, &NO_WAIT, &mut |_| {})
+            .await
+            .unwrap_err();
+        let error = error.to_string();
+        assert!(error.starts_with(&format!("GET {} failed after 3 attempts: ", url)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_group_projects() {
+        let server = TestServer::start(|request| match request.target.as_str() {
+            "/api/v4/groups?min_access_level=10&per_page=100&page=1" => Response::json(
+                r#"[{"id": 10, "full_path": "acme", "parent_id": null},
+                    {"id": 11, "full_path": "acme/backend", "parent_id": 10},
+                    {"id": 20, "full_path": "broken", "parent_id": null}]"#,
+            ),
+            target if target.starts_with("/api/v4/groups/10/projects?include_subgroups=true&") => {
+                if target.ends_with("&page=1") {
+                    page(&[1, 2]).header("x-next-page", "2")
+                } else {
+                    page(&[3])
+                }
+            }
+            _ => Response::json(r#"{"message": "500 Internal Server Error"}"#).status(500),
+        })
+        .await;
+
+        // Project 1 came with the projects the user is a member of
+        let mut seen = HashSet::from([1]);
+        let mut statuses = Vec::new();
+        let options = FetchOptions { retry: NO_WAIT, ..Default::default() };
+        let on_status = &mut |status| statuses.push(status);
+        let api = test_api(&server);
+        let on_page = &mut |_: &str, _: &[RepoInfo], _| {};
+        let fetched = fetch_group_projects(&api, "gira", &options, &mut seen, on_page, on_status);
+        let repos = fetched.await.unwrap();
+
+        let names: Vec<_> = repos.into_iter().map(|repo| repo.name).collect();
+        assert_eq!(names, ["project-2", "project-3"]);
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].starts_with("Skipped the projects of broken: "), "{}", statuses[0]);
+        // Subgroups come with their parent
+        let requests = server.requests();
+        assert!(!requests.iter().any(|request| request.target.contains("/groups/11/")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_starred_projects() {
+        let server = TestServer::start(|request| {
+            if request.target.starts_with("/api/v4/users/7/starred_projects?") {
+                page(&[1, 2])
+            } else {
+                Response::json(r#"{"message": "404 Not found"}"#).status(404)
+            }
+        })
+        .await;
+
+        // Project 1 is the user's own and stays as it is
+        let mut seen = HashSet::from([1]);
+        let options = FetchOptions { retry: NO_WAIT, ..Default::default() };
+        let api = test_api(&server);
+        let (on_page, on_status) = (&mut |_: &str, _: &[RepoInfo], _| {}, &mut |_| {});
+        let fetched =
+            fetch_starred_projects(&api, 7, "gira", &options, &mut seen, on_page, on_status);
+        let repos = fetched.await.unwrap();
+
+        let names: Vec<_> =
+            repos.iter().map(|repo| (repo.name.as_str(), repo.is_starred)).collect();
+        assert_eq!(names, [("project-2", true)]);
+        assert!(seen.contains(&2));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_projects_by_affiliation() {
+        let server = TestServer::start(|_| {
+            Response::json(serde_json::json!([
+                namespaced_project(1, "user", "gira"),
+                namespaced_project(2, "user", "dima"),
+                namespaced_project(3, "group", "acme"),
+            ]))
+        })
+        .await;
+        let fetch = async |affiliations: &[Affiliation]| {
+            let affiliations = affiliations.to_vec();
+            let options = FetchOptions { affiliations, retry: NO_WAIT, ..Default::default() };
+            let (on_page, on_status) = (&mut |_: &str, _: &[RepoInfo], _| {}, &mut |_| {});
+            let api = test_api(&server);
+            let mut seen = HashSet::new();
+            let fetched = fetch_projects(&api, "gira", &options, &mut seen, on_page, on_status);
+            let (repos, _) = fetched.await.unwrap();
+            repos.into_iter().map(|repo| (repo.name, repo.affiliation)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            fetch(&Affiliation::ALL).await,
+            [
+                ("project-1".to_string(), Some(Affiliation::Owner)),
+                ("project-2".to_string(), Some(Affiliation::Collaborator)),
+                ("project-3".to_string(), Some(Affiliation::OrganizationMember)),
+            ]
+        );
+        let members = fetch(&[Affiliation::OrganizationMember]).await;
+        assert_eq!(members, [("project-3".to_string(), Some(Affiliation::OrganizationMember))]);
+        // The user's own projects are asked for as such
+        fetch(&[Affiliation::Owner]).await;
+        let requests = project_requests(&server);
+        assert!(requests[0].contains("membership=true"));
+        assert!(requests.last().unwrap().starts_with("/api/v4/projects?owned=true&"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_user_tells_unreachable_hosts_from_rejected_tokens() {
+        let server = TestServer::start(|request| match request.header("authorization") {
+            Some("Bearer valid") => Response::json(r#"{"id": 7, "username": "gira"}"#),
+            Some("Bearer html") => Response::json("<html>Sign in</html>"),
+            _ => Response::json(r#"{"message": "401 Unauthorized"}"#).status(401),
+        })
+        .await;
+        let fetch = async |host: &str, token: &str| {
+            let mut headers = HeaderMap::new();
+            let bearer = format!("Bearer {}", token);
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(&bearer).unwrap());
+            let client = reqwest::Client::new();
+            fetch_user(&client, &headers, host, &NO_WAIT, &mut |_| {}).await
+        };
+
+        let user = fetch(&server.url, "valid").await.unwrap();
+        assert_eq!(user["username"], "gira");
+        let rejected = fetch(&server.url, "expired").await.unwrap_err().to_string();
+        assert!(rejected.starts_with(&format!("GitLab at {} rejected the token", server.url)));
+        let html = fetch(&server.url, "html").await.unwrap_err().to_string();
+        assert_eq!(html, format!("{} doesn't look like a GitLab instance", server.url));
+
+        // Nothing listens on port 9 of the loopback address
+        let unreachable = fetch("http://127.0.0.1:9", "valid").await.unwrap_err().to_string();
+        assert!(unreachable.starts_with("Could not reach GitLab at http://127.0.0.1:9: "));
+    }
 }
@@ -0,0 +1,101 @@
+//! Where the providers are, github.com and gitlab.com unless told otherwise
+//!
+//! A self-hosted GitLab is given by its address, like `https://gitlab.mycompany.io`,
+//! with `--gitlab-host`, `GITLAB_HOST` or `"gitlab_host"` in the config file. A GitHub
+//! Enterprise Server is given the same way with `github` in place of `gitlab`, by its
+//! address or its API base like `https://github.mycorp.com/api/v3`. The API and the
+//! URLs of selected repositories are built from them. Gitea and Forgejo instances are
+//! given with `gitea` in their place, Codeberg unless told otherwise.
+
+/// The address of gitlab.com, its API is below `/api/v4`
+pub const GITLAB_COM: &str = "https://gitlab.com";
+
+/// The address of github.com
+pub const GITHUB_COM: &str = "https://github.com";
+
+/// The address of Bitbucket Cloud, there is no self-hosted Bitbucket Server support
+pub const BITBUCKET_ORG: &str = "https://bitbucket.org";
+
+/// The address of Codeberg, the Forgejo instance Gitea repositories are fetched from
+/// unless another one is given
+pub const CODEBERG: &str = "https://codeberg.org";
+
+/// The web addresses of the providers, without a trailing slash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hosts {
+    pub github: String,
+    pub gitlab: String,
+    pub gitea: String,
+}
+
+impl Default for Hosts {
+    fn default() -> Self {
+        Hosts {
+            github: GITHUB_COM.to_string(),
+            gitlab: GITLAB_COM.to_string(),
+            gitea: CODEBERG.to_string(),
+        }
+    }
+}
+
+/// Cleans up an address as the user gave it, leaving the web address of the instance
+///
+/// `https://` is assumed when there is no scheme and trailing slashes are dropped, as is
+/// `api_path` for an address that points at the API, like `/api/v4` for GitLab.
+pub fn normalize(address: &str, api_path: &str) -> Result<String, String> {
+    let address = address.trim();
+    let with_scheme = if address.contains("://") {
+        address.to_string()
+    } else {
+        format!("https://{}", address)
+    };
+    let url = reqwest::Url::parse(&with_scheme)
+        .map_err(|e| format!("Invalid host {}: {}", address, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!("Invalid host {}, it needs to be an http or https address", address));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("Invalid host {}, it can't have a query", address));
+    }
+
+    let trimmed = url.as_str().trim_end_matches('/');
+    Ok(trimmed.strip_suffix(api_path).unwrap_or(trimmed).to_string())
+}
+
+/// The host name SSH clone URLs use, like `gitlab.mycompany.io` for
+/// `https://gitlab.mycompany.io:8443/gitlab`
+pub fn ssh_host(address: &str) -> &str {
+    let without_scheme = address.split_once("://").map_or(address, |(_, rest)| rest);
+    let authority = without_scheme.split('/').next().unwrap_or_default();
+    authority.split(':').next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let normalized = |address| normalize(address, "/api/v4");
+        let expected = Ok("https://gitlab.mycompany.io".to_string());
+        assert_eq!(normalized("gitlab.mycompany.io"), expected);
+        assert_eq!(normalized("https://gitlab.mycompany.io//"), expected);
+        assert_eq!(
+            normalized("http://10.0.0.5:8080/gitlab/api/v4/"),
+            Ok("http://10.0.0.5:8080/gitlab".to_string())
+        );
+        assert!(normalized("ftp://gitlab.mycompany.io").is_err());
+        assert!(normalized("https://gitlab.mycompany.io/?token=1").is_err());
+        assert!(normalized("").is_err());
+        assert_eq!(
+            normalize("https://github.mycorp.com/api/v3", "/api/v3"),
+            Ok("https://github.mycorp.com".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ssh_host() {
+        assert_eq!(ssh_host(GITLAB_COM), "gitlab.com");
+        assert_eq!(ssh_host("https://gitlab.mycompany.io:8443/gitlab"), "gitlab.mycompany.io");
+    }
+}
@@ -0,0 +1,94 @@
+//! Tokens in the system credential store
+//!
+//! Tokens are kept under the service `repo-searcher`, with the lowercase provider name
+//! like `github` as the account. The store is reached through the `keyring` crate: the
+//! Keychain on macOS, the Secret Service on Linux and the BSDs, and the Credential
+//! Manager on Windows.
+
+use keyring::{Entry, Error};
+
+use crate::formatter::RepoSource;
+
+/// The service the tokens are stored under
+pub const SERVICE: &str = "repo-searcher";
+
+/// Whether `keyring` has a store here, elsewhere it falls back to one in memory that
+/// forgets the tokens
+const SUPPORTED: bool = cfg!(any(
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "windows",
+    target_os = "freebsd",
+    target_os = "openbsd"
+));
+
+/// The account the token of a provider is stored as, like `github`
+pub fn account(provider: RepoSource) -> String {
+    provider.name().to_lowercase()
+}
+
+/// The stored token of a provider, `None` when there is none
+///
+/// The error says why the store couldn't be read, like when there is no Secret Service
+/// on a headless server.
+pub fn get(provider: RepoSource) -> Result<Option<String>, String> {
+    match entry(provider)?.get_password() {
+        Ok(token) => Ok(Some(token.trim_end_matches(['\r', '\n']).to_string())
+            .filter(|token| !token.is_empty())),
+        Err(Error::NoEntry) => Ok(None),
+        Err(e) => Err(describe(e)),
+    }
+}
+
+/// Stores the token of a provider, replacing the one stored before
+pub fn set(provider: RepoSource, token: &str) -> Result<(), String> {
+    entry(provider)?.set_password(token).map_err(describe)
+}
+
+/// Deletes the token of a provider, `false` when none was stored
+pub fn remove(provider: RepoSource) -> Result<bool, String> {
+    match entry(provider)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(Error::NoEntry) => Ok(false),
+        Err(e) => Err(describe(e)),
+    }
+}
+
+fn entry(provider: RepoSource) -> Result<Entry, String> {
+    if !SUPPORTED {
+        return Err("There is no supported keychain on this system".to_string());
+    }
+    Entry::new(SERVICE, &account(provider)).map_err(describe)
+}
+
+// A store that can't be reached at all is told apart from one that refused the token
+fn describe(error: Error) -> String {
+    match error {
+        Error::NoStorageAccess(e) | Error::PlatformFailure(e) => {
+            format!("No keychain is available ({})", e)
+        }
+        e => format!("The keychain can't be used: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account() {
+        assert_eq!(account(RepoSource::GitHub), "github");
+        assert_eq!(account(RepoSource::Bitbucket), "bitbucket");
+    }
+
+    #[test]
+    fn test_describe() {
+        let missing = Error::NoStorageAccess("no Secret Service".into());
+        assert_eq!(describe(missing), "No keychain is available (no Secret Service)");
+        let failed = Error::PlatformFailure("D-Bus is down".into());
+        assert_eq!(describe(failed), "No keychain is available (D-Bus is down)");
+
+        let too_long = describe(Error::TooLong("user".to_string(), 8));
+        assert!(too_long.starts_with("The keychain can't be used: "), "{}", too_long);
+    }
+}
@@ -0,0 +1,33 @@
+//! Repository searcher for GitHub and GitLab
+//!
+//! The binary is a thin wrapper around these modules: the provider clients fetch
+//! repositories, the formatter, its templates and the filter prepare them for
+//! display as styled text, and the fuzzy finder lets the user pick one.
+
+pub mod bitbucket;
+pub mod browser;
+pub mod cache;
+pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod credentials;
+pub mod filter;
+pub mod formatter;
+pub mod fuzzy_finder;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+pub mod hosts;
+pub mod keychain;
+pub mod pagination;
+pub mod rate_limit;
+pub mod repo_info;
+pub mod repository;
+pub mod retry;
+pub mod state;
+pub mod styled;
+pub mod template;
+pub mod terminal;
+#[cfg(test)]
+mod test_server;
+pub mod tokens;
@@ -1,19 +1,22 @@
 use std::error::Error;
 use std::process;
+use std::sync::{Arc, Mutex};
 
-mod browser;
-mod cache;
-mod cli;
-mod filter;
-mod formatter;
-mod fuzzy_finder;
-mod github;
-mod gitlab;
-mod repository;
-mod terminal;
+use repo_searcher_github_and_gitlab::fuzzy_finder::FinderUpdate;
+use repo_searcher_github_and_gitlab::repo_info::RepoInfo;
+use repo_searcher_github_and_gitlab::{
+    cli, filter, formatter, fuzzy_finder, repository, state, terminal,
+};
 
 use tokio::sync::mpsc;
 
+/// The repositories to select from and the usernames they belong to
+#[derive(Clone)]
+struct Loaded {
+    repos: Vec<RepoInfo>,
+    usernames: repository::Usernames,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Set up global Ctrl+C handler
@@ -22,157 +25,252 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Parse command line arguments
     let args = cli::parse_args();
 
-    // Use the RepoData struct from the cache module
-    use cache::RepoData;
+    // Use the RepoInfo struct every source is converted to
+    use repo_searcher_github_and_gitlab::repo_info;
 
     // Initialize repository data and usernames
-    let mut all_repos: Vec<RepoData> = Vec::new();
-    let mut github_username = String::new();
-    let mut gitlab_username = String::new();
+    let mut all_repos: Vec<RepoInfo> = Vec::new();
+    let mut usernames = repository::Usernames::default();
 
     // Create a channel for repository updates
     let (tx, mut rx) = mpsc::channel::<repository::RepoUpdateMessage>(100);
 
-    // Create a channel for updating the fuzzy finder
-    let (update_tx, mut update_rx) = mpsc::channel::<(Vec<String>, String)>(100);
-
     // Load repositories based on the mode (dummy or real)
     if args.use_dummy {
         // Use dummy data for testing
         repository::load_dummy_repositories(
             &mut all_repos,
-            &mut github_username,
-            &mut gitlab_username,
+            &mut usernames,
         );
     } else {
         // Load real repositories with background refresh
         repository::load_repositories_with_background_refresh(
             &args,
             &mut all_repos,
-            &mut github_username,
-            &mut gitlab_username,
+            &mut usernames,
             tx.clone(),
         )
         .await?;
     }
 
-    // Print summary of repositories found
-    let github_count = all_repos
-        .iter()
-        .filter(|r| matches!(r.source, formatter::RepoSource::GitHub))
-        .count();
-    let gitlab_count = all_repos
-        .iter()
-        .filter(|r| matches!(r.source, formatter::RepoSource::GitLab))
-        .count();
+    // Print the repositories for other tools instead of showing the finder
+    if args.output != cli::OutputFormat::Finder {
+        // Without a cache there is nothing to print until the background fetch is done
+        if all_repos.is_empty() && !args.use_dummy {
+            if let Some(repos) = repository::wait_for_background_load(&mut rx).await {
+                all_repos = repos;
+            }
+        }
+        repo_info::sort_repositories(&mut all_repos, args.sort, args.sort_direction);
+        match args.output {
+            cli::OutputFormat::Tsv => {
+                println!("{}", formatter::format_repositories_tsv(&all_repos, &args.columns));
+            }
+            _ if !all_repos.is_empty() => {
+                println!("{}", formatter::format_repositories_json(&all_repos));
+            }
+            _ => {}
+        }
+        process::exit(0);
+    }
+
+    // Print summary of repositories found, the other providers only when there are any
+    // from them
+    let count = |source| all_repos.iter().filter(|r| r.source == source).count();
+    let others: String = [formatter::RepoSource::Gitea, formatter::RepoSource::Bitbucket]
+        .into_iter()
+        .filter(|&source| count(source) > 0)
+        .map(|source| format!(", {} from {}", count(source), source.name()))
+        .collect();
     println!(
-        "Found {} repositories: {} from GitHub, {} from GitLab",
+        "Found {} repositories: {} from GitHub, {} from GitLab{}",
         all_repos.len(),
-        github_count,
-        gitlab_count
+        count(formatter::RepoSource::GitHub),
+        count(formatter::RepoSource::GitLab),
+        others
     );
 
+    // Order the list shown before anything is typed
+    repo_info::sort_repositories(&mut all_repos, args.sort, args.sort_direction);
+
     // Create formatted choices for the fuzzy finder
-    let choices: Vec<String> = all_repos
-        .iter()
-        .map(|repo| {
-            formatter::format_repository(
-                &repo.name,
-                &repo.description,
-                repo.is_fork,
-                repo.is_private,
-                repo.source,
-            )
-        })
-        .collect();
+    let format_options = formatter::FormatOptions {
+        pushed_time: args.pushed_time,
+        owner_prefix: args.owner_prefix,
+        template: args.template.clone(),
+        // Columns are fitted to the terminal as it is at startup
+        aligned_width: args.aligned.then(|| {
+            let (width, _) = termion::terminal_size().unwrap_or((80, 24));
+            fuzzy_finder::item_text_width(usize::from(width))
+        }),
+        icons: args.icons.clone(),
+        source_labels: args.source_labels.clone(),
+        default_branch: args.default_branch,
+        max_description_len: args.max_description_len,
+        licenses: args.licenses,
+        size_threshold_kb: args.size_threshold_kb,
+        popularity: args.popularity.clone(),
+        right_aligned: args.right_aligned,
+    };
+    let choices = repository::styled_finder_choices(&all_repos, &format_options);
 
-    // Create the fuzzy finder
-    let mut finder = fuzzy_finder::FuzzyFinder::new(choices);
+    // Create the fuzzy finder, restoring the preview pane from the last run
+    let ui_state = state::load_state();
+    let mut builder = fuzzy_finder::FuzzyFinder::builder()
+        .bind_intent(termion::event::Key::Ctrl('o'), "open")
+        .bind_intent(termion::event::Key::Ctrl('u'), "copy-url")
+        .preview(ui_state.preview_visible)
+        .on_preview_toggle(|visible| {
+            let mut ui_state = state::load_state();
+            ui_state.preview_visible = visible;
+            let _ = state::save_state(&ui_state);
+        });
+    if args.regex {
+        builder = builder.match_mode(filter::MatchMode::Regex);
+    }
+    if args.typos {
+        builder = builder.typo_tolerance(true);
+    }
+    builder = builder.ellipsis(args.icons.ellipsis.as_str());
+    if args.icon_set == formatter::IconSet::Ascii {
+        builder = builder.placeholder("type to filter repositories...");
+    }
+    if let Some(path) = &args.debug_input_log {
+        builder = builder.debug_input_log(path);
+    }
+    let mut finder = builder.build(Vec::new());
+    finder.update_styled_candidates(choices);
 
-    // Spawn a task to handle repository updates
-    let update_tx_clone = update_tx.clone();
-    tokio::spawn(async move {
+    // The repositories and usernames as far as they are loaded, updated while the
+    // finder is open
+    let loaded = Arc::new(Mutex::new(Loaded {
+        repos: all_repos,
+        usernames,
+    }));
 
+    // Show the URL of the selected repository in the footer
+    let detail_loaded = loaded.clone();
+    let detail_labels = args.source_labels.clone();
+    let detail_hosts = args.hosts.clone();
+    finder.set_detail_provider(move |selection| {
+        let loaded = detail_loaded.lock().unwrap();
+        repository::selection_browser_url(
+            selection,
+            &loaded.repos,
+            &loaded.usernames,
+            &detail_labels,
+            &detail_hosts,
+        )
+    });
+
+    // Spawn a task turning repository updates into finder updates, which the finder
+    // applies while it runs
+    let (finder_tx, finder_rx) = std::sync::mpsc::channel();
+    if !args.use_dummy {
+        finder.set_update_receiver(finder_rx);
+    }
+    let update_loaded = loaded.clone();
+    let (sort, sort_direction) = (args.sort, args.sort_direction);
+    tokio::spawn(async move {
         while let Some(message) = rx.recv().await {
             match message {
-                repository::RepoUpdateMessage::NewRepos { repos, github_username: _new_gh_username, gitlab_username: _new_gl_username } => {
+                repository::RepoUpdateMessage::NewRepos {
+                    mut repos,
+                    usernames,
+                } => {
+                    repo_info::sort_repositories(&mut repos, sort, sort_direction);
 
                     // Format the new repositories
-                    let new_choices: Vec<String> = repos
-                        .iter()
-                        .map(|repo| {
-                            formatter::format_repository(
-                                &repo.name,
-                                &repo.description,
-                                repo.is_fork,
-                                repo.is_private,
-                                repo.source,
-                            )
-                        })
-                        .collect();
-
-                    // Send update to the main thread
-                    let _ = update_tx_clone.send((new_choices, String::new())).await;
+                    let new_choices = repository::styled_finder_choices(&repos, &format_options);
+                    *update_loaded.lock().unwrap() = Loaded { repos, usernames };
+
+                    let _ = finder_tx.send(FinderUpdate::Replace(new_choices));
+                    let _ = finder_tx.send(FinderUpdate::Status(None));
+                },
+                repository::RepoUpdateMessage::Page {
+                    mut repos,
+                    loaded,
+                    expected,
+                    usernames,
+                } => {
+                    // Pages are added to the end, the full list that follows is sorted as a whole
+                    repo_info::sort_repositories(&mut repos, sort, sort_direction);
+                    let new_choices = repository::styled_finder_choices(&repos, &format_options);
+                    let mut shown = update_loaded.lock().unwrap();
+                    shown.repos.extend(repos);
+                    shown.usernames = usernames;
+                    drop(shown);
+
+                    let status = match expected {
+                        Some(expected) => format!("Loaded {}/~{}…", loaded, expected),
+                        None => format!("Loaded {}…", loaded),
+                    };
+                    let _ = finder_tx.send(FinderUpdate::Append(new_choices));
+                    let _ = finder_tx.send(FinderUpdate::Status(Some(status)));
                 },
                 repository::RepoUpdateMessage::Status(status) => {
-                    // Send status update to the main thread
-                    let _ = update_tx_clone.send((Vec::new(), status)).await;
+                    let _ = finder_tx.send(FinderUpdate::Status(Some(status)));
                 },
                 repository::RepoUpdateMessage::Error(error) => {
-                    // Send error update to the main thread
-                    let _ = update_tx_clone.send((Vec::new(), format!("ERROR: {}", error))).await;
+                    let _ = finder_tx.send(FinderUpdate::Error(Some(format!("ERROR: {}", error))));
                 },
                 repository::RepoUpdateMessage::LoadingComplete => {
-                    // Send completion message to the main thread
-                    let _ = update_tx_clone.send((Vec::new(), "Repository loading complete".to_string())).await;
+                    let status = "Repository loading complete".to_string();
+                    let _ = finder_tx.send(FinderUpdate::Status(Some(status)));
+                    let _ = finder_tx.send(FinderUpdate::LoadingComplete);
 
-                    // Clear the message after a delay
+                    // Clear the message after a delay, errors stay visible
                     tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-                    let _ = update_tx_clone.send((Vec::new(), String::new())).await;
+                    let _ = finder_tx.send(FinderUpdate::Status(None));
                 }
             }
         }
     });
 
-    // Run the fuzzy finder in a loop
+    // Run the fuzzy finder in a loop, it stays usable while repositories are loading
     loop {
-        // Check for updates before running the fuzzy finder
-        while let Ok((new_items, status)) = update_rx.try_recv() {
-            if !new_items.is_empty() {
-                finder.update_items(new_items);
+        // Run the fuzzy finder
+        let result = finder.run();
+        match result {
+            fuzzy_finder::FinderResult::Cancelled => {
+                println!("\nExiting...");
+                process::exit(0);
             }
-
-            if !status.is_empty() {
-                if status.starts_with("ERROR:") {
-                    finder.set_error_message(Some(status));
-                } else {
-                    finder.set_status_message(Some(status));
+            fuzzy_finder::FinderResult::ExportList(items) => {
+                // Print the full, untruncated list for shell pipelines
+                for (i, item) in items.iter().enumerate() {
+                    if args.numbered_export {
+                        println!("{}\t{}", i + 1, item);
+                    } else {
+                        println!("{}", item);
+                    }
                 }
-            } else {
-                finder.set_status_message(None);
-                finder.set_error_message(None);
-            }
-        }
-
-        // Run the fuzzy finder
-        let selection = match finder.run() {
-            Some(selected) => selected,
-            None => {
-                terminal::cleanup_terminal();
-                println!("No selection made");
                 process::exit(0);
             }
-        };
+            fuzzy_finder::FinderResult::Interrupted => {
+                // 130 is the conventional exit status after SIGINT
+                println!("\nExiting...");
+                process::exit(130);
+            }
+            _ => {}
+        }
 
-        // Process the selected repository
+        // Process the selected repository or chosen action with what is loaded by now
+        let Loaded { repos, usernames } = loaded.lock().unwrap().clone();
         if let Err(e) =
-            repository::process_repository_selection(&selection, &github_username, &gitlab_username)
-                .await
+            repository::process_finder_result(
+                &result,
+                &usernames,
+                &args.source_labels,
+                &args.hosts,
+                &repos,
+            )
+            .await
         {
             eprintln!("Error processing repository: {}", e);
         }
     }
 
     // The loop above never exits normally, only through Ctrl+C or Esc
-    // which call process::exit(0), so this is unreachable
+    // which call process::exit, so this is unreachable
 }
@@ -0,0 +1,78 @@
+//! Paging through API lists with the `Link` header both GitHub and GitLab send
+//!
+//! A page links to the next one like
+//! `<https://api.github.com/user/repos?page=2>; rel="next", <...>; rel="last"`, the
+//! last page has no `next` link.
+
+use crate::repo_info::RepoInfo;
+
+/// What a fetcher hands out after every page: the username, the repositories of the
+/// page and how many repositories there are in total if the provider tells
+pub type PageCallback<'a> = dyn FnMut(&str, &[RepoInfo], Option<usize>) + 'a;
+
+/// Finds the URL of the next page in a `Link` header, `None` on the last page
+///
+/// A header that can't be read is an error rather than the last page, stopping there
+/// would quietly leave out the remaining repositories.
+pub fn next_page_url(link: &str) -> Result<Option<String>, String> {
+    link_url(link, "next")
+}
+
+/// The number of the last page from the `page` parameter of its link, if there is one
+pub fn last_page_number(link: &str) -> Option<u32> {
+    let url = reqwest::Url::parse(&link_url(link, "last").ok()??).ok()?;
+    let mut query = url.query_pairs();
+    query.find(|(name, _)| name == "page")?.1.parse().ok()
+}
+
+// Finds the URL with the given relation in a Link header
+fn link_url(link: &str, relation: &str) -> Result<Option<String>, String> {
+    let malformed = || format!("Malformed Link header: {}", link);
+    for entry in link.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (url, params) = entry
+            .strip_prefix('<')
+            .and_then(|rest| rest.split_once('>'))
+            .ok_or_else(malformed)?;
+        let mut rels = params
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .flat_map(|(_, value)| value.trim().trim_matches('"').split_whitespace());
+        if rels.any(|rel| rel == relation) {
+            reqwest::Url::parse(url).map_err(|_| malformed())?;
+            return Ok(Some(url.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_page_url() {
+        let link = r#"<https://api.github.com/user/repos?page=2>; rel="next", <https://api.github.com/user/repos?page=5>; rel="last""#;
+        assert_eq!(
+            next_page_url(link),
+            Ok(Some("https://api.github.com/user/repos?page=2".to_string()))
+        );
+        let last = r#"<https://api.github.com/user/repos?page=1>; rel="prev first""#;
+        assert_eq!(next_page_url(last), Ok(None));
+        assert_eq!(next_page_url(""), Ok(None));
+
+        assert!(next_page_url(r#"https://api.github.com/user/repos?page=2; rel="next""#).is_err());
+        assert!(next_page_url(r#"<https://api.github.com/user/repos?page=2; rel="next""#).is_err());
+        assert!(next_page_url(r#"<not a url>; rel="next""#).is_err());
+    }
+
+    #[test]
+    fn test_last_page_number() {
+        let link = r#"<https://api.github.com/user/repos?per_page=100&page=2>; rel="next", <https://api.github.com/user/repos?per_page=100&page=8>; rel="last""#;
+        assert_eq!(last_page_number(link), Some(8));
+        let without_number = r#"<https://gitlab.com/api/v4/projects?id_after=5>; rel="last""#;
+        assert_eq!(last_page_number(without_number), None);
+        let only_next = r#"<https://api.github.com/user/repos?page=2>; rel="next""#;
+        assert_eq!(last_page_number(only_next), None);
+    }
+}
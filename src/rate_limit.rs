@@ -0,0 +1,176 @@
+//! Reading rate limits from API responses and waiting for them to reset
+//!
+//! GitHub answers with 403 or 429 once too many requests were sent, either with
+//! `X-RateLimit-Remaining: 0` and the reset time in `X-RateLimit-Reset`, or for its
+//! secondary limits with the seconds to wait in `Retry-After`. GitLab answers with 429
+//! and `Retry-After`, `RateLimit-Remaining` and `RateLimit-Reset`. GitHub Enterprise
+//! Server has no secondary limits and sends no rate limit headers at all when rate
+//! limiting is turned off, a 403 from it is then a missing permission.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+/// How long the fetchers wait for a rate limit to reset unless told otherwise
+pub const DEFAULT_MAX_WAIT_SECS: u64 = 30;
+
+/// How often a single request waits for a rate limit before giving up
+pub const MAX_WAITS: usize = 3;
+
+/// When the rate limit a response ran into resets, `None` when it didn't run into one
+pub fn limited_until(
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+
+    // Retry-After is either a number of seconds or a date
+    if let Some(retry_after) = headers.get(RETRY_AFTER).and_then(|value| value.to_str().ok()) {
+        if let Ok(seconds) = retry_after.trim().parse::<i64>() {
+            return Some(now + chrono::Duration::seconds(seconds.max(0)));
+        }
+        if let Ok(date) = DateTime::parse_from_rfc2822(retry_after.trim()) {
+            return Some(date.with_timezone(&Utc));
+        }
+    }
+
+    // The reset time is given in seconds since the epoch
+    ["x-ratelimit", "ratelimit"].iter().find_map(|prefix| {
+        if header(&format!("{}-remaining", prefix))? != "0" {
+            return None;
+        }
+        let reset = header(&format!("{}-reset", prefix))?.parse().ok()?;
+        DateTime::from_timestamp(reset, 0).map(|reset| reset.max(now))
+    })
+}
+
+/// Says that the rate limit of a provider was exceeded and when it resets
+///
+/// The time is local, like `GitHub rate limit exceeded, it resets at 14:05:09 (in 12
+/// minutes)`.
+pub fn reset_message(provider: &str, until: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = seconds_until(until, now);
+    let wait = match seconds {
+        0..=90 => format!("{} seconds", seconds),
+        _ => format!("{} minutes", seconds.div_ceil(60)),
+    };
+    format!(
+        "{} rate limit exceeded, it resets at {} (in {})",
+        provider,
+        until.with_timezone(&Local).format("%H:%M:%S"),
+        wait
+    )
+}
+
+/// Waits until a rate limit resets, telling `on_status` how many seconds are left
+/// every second
+///
+/// A limit that resets later than `max_wait` is not waited for, the error says when
+/// it resets instead.
+pub async fn wait_for_reset(
+    provider: &str,
+    until: DateTime<Utc>,
+    max_wait: Duration,
+    on_status: &mut dyn FnMut(String),
+) -> Result<(), String> {
+    let now = Utc::now();
+    let seconds = seconds_until(until, now);
+    if Duration::from_secs(seconds) > max_wait {
+        return Err(reset_message(provider, until, now));
+    }
+    for left in (1..=seconds).rev() {
+        on_status(format!("{} rate limit reached, retrying in {}s", provider, left));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    Ok(())
+}
+
+// Whole seconds from now until the given time, rounded up
+fn seconds_until(until: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    let millis = (until - now).num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_limited_until() {
+        let now: DateTime<Utc> = "2024-05-01T10:00:00Z".parse().unwrap();
+        let reset = now.timestamp() + 120;
+
+        // GitHub's primary limit
+        let github = headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", &reset.to_string())]);
+        let until = limited_until(StatusCode::FORBIDDEN, &github, now);
+        assert_eq!(until, Some(now + chrono::Duration::seconds(120)));
+        // A plain 403 without an exhausted limit is a missing permission
+        let permission = headers(&[("x-ratelimit-remaining", "4999"), ("x-ratelimit-reset", "0")]);
+        assert_eq!(limited_until(StatusCode::FORBIDDEN, &permission, now), None);
+        assert_eq!(limited_until(StatusCode::OK, &github, now), None);
+
+        // GitHub's secondary limits and GitLab
+        let retry = headers(&[("retry-after", "60")]);
+        let until = limited_until(StatusCode::FORBIDDEN, &retry, now);
+        assert_eq!(until, Some(now + chrono::Duration::seconds(60)));
+        let gitlab = headers(&[
+            ("retry-after", "30"),
+            ("ratelimit-remaining", "0"),
+            ("ratelimit-reset", &reset.to_string()),
+        ]);
+        let until = limited_until(StatusCode::TOO_MANY_REQUESTS, &gitlab, now);
+        assert_eq!(until, Some(now + chrono::Duration::seconds(30)));
+        let date = headers(&[("retry-after", "Wed, 01 May 2024 10:00:45 GMT")]);
+        let until = limited_until(StatusCode::TOO_MANY_REQUESTS, &date, now);
+        assert_eq!(until, Some(now + chrono::Duration::seconds(45)));
+
+        assert_eq!(limited_until(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_reset_message() {
+        let now: DateTime<Utc> = "2024-05-01T10:00:00Z".parse().unwrap();
+        let message = reset_message("GitHub", now + chrono::Duration::seconds(700), now);
+        assert!(message.starts_with("GitHub rate limit exceeded, it resets at "));
+        assert!(message.ends_with(" (in 12 minutes)"));
+        let message = reset_message("GitLab", now + chrono::Duration::milliseconds(44_200), now);
+        assert!(message.ends_with(" (in 45 seconds)"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_reset() {
+        let mut statuses = Vec::new();
+        let until = Utc::now() + chrono::Duration::milliseconds(1_500);
+        let max_wait = Duration::from_secs(DEFAULT_MAX_WAIT_SECS);
+        wait_for_reset("GitHub", until, max_wait, &mut |status| statuses.push(status))
+            .await
+            .unwrap();
+        assert_eq!(
+            statuses,
+            [
+                "GitHub rate limit reached, retrying in 2s",
+                "GitHub rate limit reached, retrying in 1s",
+            ]
+        );
+
+        // Limits that take longer to reset are not waited for
+        let until = Utc::now() + chrono::Duration::minutes(5);
+        let error = wait_for_reset("GitHub", until, max_wait, &mut |_| panic!("waited"))
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("GitHub rate limit exceeded"));
+    }
+}
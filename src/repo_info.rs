@@ -0,0 +1,548 @@
+//! The provider independent description of a repository
+//!
+//! Both fetchers convert their API responses into a [`RepoInfo`], which is what the
+//! cache stores and what the formatter and the filter work on.
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::PageCache;
+use crate::formatter::{RepoSource, Visibility};
+use crate::github::OrgRepos;
+use crate::retry::RetryPolicy;
+
+/// A repository from GitHub or GitLab
+///
+/// The field names are stable, they are what the cache stores and what `--output json`
+/// prints. Times are RFC 3339 and missing values are `null`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoInfo {
+    pub name: String,
+    /// SSH clone URL, caches written before the other URLs were kept call it `url`
+    #[serde(alias = "url")]
+    pub ssh_url: String,
+    pub description: String,
+    /// The user, organization or GitLab namespace, like `group/subgroup`
+    pub owner: String,
+    pub is_fork: bool,
+    /// What a fork is a fork of, like `rust-lang/cargo`, when the provider reported it
+    #[serde(default)]
+    pub fork_parent: Option<String>,
+    // Caches written before internal projects were told apart store `is_private`
+    #[serde(alias = "is_private", deserialize_with = "deserialize_visibility")]
+    pub visibility: Visibility,
+    pub source: RepoSource,
+    // Missing in caches written before these were fetched
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub is_mirror: bool,
+    /// A GitHub template repository, meant to start new repositories from
+    #[serde(default)]
+    pub is_template: bool,
+    /// Has no commits yet, so there is nothing to check out after cloning
+    #[serde(default)]
+    pub is_empty: bool,
+    /// Starred by the user rather than one of theirs, only fetched with `--starred`
+    #[serde(default)]
+    pub is_starred: bool,
+    /// How the user came by the repository, missing for starred ones and in caches
+    /// written before it was kept
+    #[serde(default)]
+    pub affiliation: Option<Affiliation>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// SPDX identifier like `MIT`, `NOASSERTION` when a license file was not recognized
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Like `main`, missing for empty repositories that have no branch yet
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    #[serde(default)]
+    pub stars: Option<u32>,
+    /// Size of the repository in kilobytes
+    #[serde(default)]
+    pub size_kb: Option<u64>,
+    /// Open issues, on GitHub this includes pull requests unless those were counted too
+    #[serde(default)]
+    pub open_issues: Option<u32>,
+    /// Open pull or merge requests, only counted when asked for
+    #[serde(default)]
+    pub open_merge_requests: Option<u32>,
+    #[serde(default)]
+    pub pushed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// HTTPS clone URL
+    #[serde(default)]
+    pub https_url: String,
+    /// The page of the repository in the browser
+    #[serde(default)]
+    pub web_url: String,
+}
+
+impl RepoInfo {
+    /// A repository with only a name, owner and source, every other field is empty
+    pub fn new(name: &str, owner: &str, source: RepoSource) -> RepoInfo {
+        RepoInfo {
+            name: name.to_string(),
+            ssh_url: String::new(),
+            description: String::new(),
+            owner: owner.to_string(),
+            is_fork: false,
+            fork_parent: None,
+            visibility: Visibility::Public,
+            source,
+            is_archived: false,
+            is_mirror: false,
+            is_template: false,
+            is_empty: false,
+            is_starred: false,
+            affiliation: None,
+            language: None,
+            topics: Vec::new(),
+            license: None,
+            default_branch: None,
+            stars: None,
+            size_kb: None,
+            open_issues: None,
+            open_merge_requests: None,
+            pushed_at: None,
+            created_at: None,
+            https_url: String::new(),
+            web_url: String::new(),
+        }
+    }
+}
+
+/// How the user came by a repository, named as in GitHub's `affiliation` parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Affiliation {
+    /// In the user's own account or namespace
+    Owner,
+    /// Someone else's repository the user was added to
+    Collaborator,
+    /// A repository of an organization or GitLab group the user belongs to
+    OrganizationMember,
+}
+
+impl Affiliation {
+    pub const ALL: [Affiliation; 3] =
+        [Affiliation::Owner, Affiliation::Collaborator, Affiliation::OrganizationMember];
+
+    /// Looks up an affiliation by its name as given on the command line
+    pub fn from_name(name: &str) -> Option<Affiliation> {
+        Affiliation::ALL.into_iter().find(|affiliation| affiliation.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Affiliation::Owner => "owner",
+            Affiliation::Collaborator => "collaborator",
+            Affiliation::OrganizationMember => "organization_member",
+        }
+    }
+
+    /// Tells the affiliation of a repository the user has access to, which neither
+    /// provider reports
+    ///
+    /// Whether the user was added to a repository of an organization or belongs to the
+    /// organization can't be told apart, so unless `organization_member` is in `wanted`
+    /// such a repository counts as a collaborator's.
+    pub fn infer(owned: bool, in_organization: bool, wanted: &[Affiliation]) -> Affiliation {
+        if owned {
+            Affiliation::Owner
+        } else if in_organization && wanted.contains(&Affiliation::OrganizationMember) {
+            Affiliation::OrganizationMember
+        } else {
+            Affiliation::Collaborator
+        }
+    }
+}
+
+/// Whether the repositories with a trait, like archived ones, are fetched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Inclusion {
+    /// Fetched like any other
+    #[default]
+    Include,
+    /// Left out
+    Exclude,
+    /// Fetched without the ones that lack the trait
+    Only,
+}
+
+impl Inclusion {
+    /// Looks up an inclusion by its name as given on the command line or in the config
+    /// file, `include`, `exclude` or `only`
+    pub fn from_name(name: &str) -> Option<Inclusion> {
+        match name {
+            "include" => Some(Inclusion::Include),
+            "exclude" => Some(Inclusion::Exclude),
+            "only" => Some(Inclusion::Only),
+            _ => None,
+        }
+    }
+
+    /// Whether a repository that has the trait or lacks it is kept
+    pub fn keeps(self, has_trait: bool) -> bool {
+        match self {
+            Inclusion::Include => true,
+            Inclusion::Exclude => !has_trait,
+            Inclusion::Only => has_trait,
+        }
+    }
+}
+
+/// Which repositories are kept while fetching, by whether they are archived or forks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FetchFilter {
+    pub archived: Inclusion,
+    pub forks: Inclusion,
+}
+
+impl FetchFilter {
+    pub fn keeps(&self, repo: &RepoInfo) -> bool {
+        self.archived.keeps(repo.is_archived) && self.forks.keeps(repo.is_fork)
+    }
+
+    /// Drops the repositories it doesn't keep, returning what it dropped like
+    /// `2 archived and 1 fork`, or `None` when it kept them all. Repositories it drops
+    /// for being archived or not archived count as such, not as forks
+    pub fn apply(&self, repos: &mut Vec<RepoInfo>) -> Option<String> {
+        let (mut archived, mut forks) = (0, 0);
+        repos.retain(|repo| {
+            if !self.archived.keeps(repo.is_archived) {
+                archived += 1;
+            } else if !self.forks.keeps(repo.is_fork) {
+                forks += 1;
+            }
+            self.keeps(repo)
+        });
+
+        let archived = match (archived, self.archived) {
+            (0, _) => None,
+            (count, Inclusion::Only) => Some(format!("{} not archived", count)),
+            (count, _) => Some(format!("{} archived", count)),
+        };
+        let forks = match (forks, self.forks) {
+            (0, _) => None,
+            (1, Inclusion::Only) => Some("1 not a fork".to_string()),
+            (count, Inclusion::Only) => Some(format!("{} not forks", count)),
+            (1, _) => Some("1 fork".to_string()),
+            (count, _) => Some(format!("{} forks", count)),
+        };
+        match (archived, forks) {
+            (Some(archived), Some(forks)) => Some(format!("{} and {}", archived, forks)),
+            (archived, forks) => archived.or(forks),
+        }
+    }
+}
+
+/// What the providers fetch and how, from the command line and the config file
+pub struct FetchOptions {
+    /// Which of the repositories the user has access to are fetched
+    pub affiliations: Vec<Affiliation>,
+    /// Which GitHub organizations' repositories are fetched too
+    pub orgs: OrgRepos,
+    /// Whether the projects of every GitLab group the user can access are fetched too
+    pub gitlab_groups: bool,
+    /// Whether the repositories the user starred are fetched too
+    pub starred: bool,
+    pub filter: FetchFilter,
+    pub retry: RetryPolicy,
+    /// The GitHub pages of the last fetch, which are only sent again when they changed,
+    /// and those of this one
+    pub pages: PageCache,
+    /// Whether the parents of forks are looked up
+    pub fork_parents: bool,
+    /// Whether the open pull and merge requests are counted
+    pub merge_request_counts: bool,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            affiliations: Affiliation::ALL.to_vec(),
+            orgs: OrgRepos::default(),
+            gitlab_groups: false,
+            starred: false,
+            filter: FetchFilter::default(),
+            retry: RetryPolicy::default(),
+            pages: PageCache::default(),
+            fork_parents: false,
+            merge_request_counts: false,
+        }
+    }
+}
+
+/// What the list is ordered by before anything is typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// The order the providers returned the repositories in
+    #[default]
+    Unsorted,
+    Name,
+    /// The time of the last push
+    Activity,
+    Stars,
+    /// The owner or namespace, then the name
+    Owner,
+}
+
+impl SortKey {
+    /// Looks up a key by its name as given on the command line or in the config file
+    pub fn from_name(name: &str) -> Option<SortKey> {
+        match name {
+            "none" => Some(SortKey::Unsorted),
+            "name" => Some(SortKey::Name),
+            "activity" => Some(SortKey::Activity),
+            "stars" => Some(SortKey::Stars),
+            "owner" => Some(SortKey::Owner),
+            _ => None,
+        }
+    }
+
+    /// Names sort from A to Z, activity and stars with the most recent or popular first
+    pub fn default_direction(self) -> SortDirection {
+        match self {
+            SortKey::Activity | SortKey::Stars => SortDirection::Descending,
+            _ => SortDirection::Ascending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    /// Looks up a direction by its name, `asc` or `desc`
+    pub fn from_name(name: &str) -> Option<SortDirection> {
+        match name {
+            "asc" => Some(SortDirection::Ascending),
+            "desc" => Some(SortDirection::Descending),
+            _ => None,
+        }
+    }
+
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortDirection::Ascending => ordering,
+            SortDirection::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// Orders repositories for the list shown before anything is typed
+///
+/// Repositories without a push time or star count come last in both directions, ties
+/// are ordered by name. Names are compared ignoring case.
+pub fn sort_repositories(repos: &mut [RepoInfo], key: SortKey, direction: SortDirection) {
+    let by_name = |a: &RepoInfo, b: &RepoInfo| a.name.to_lowercase().cmp(&b.name.to_lowercase());
+    match key {
+        SortKey::Unsorted => {}
+        SortKey::Name => repos.sort_by(|a, b| direction.apply(by_name(a, b))),
+        SortKey::Owner => repos.sort_by(|a, b| {
+            let by_owner = a.owner.to_lowercase().cmp(&b.owner.to_lowercase());
+            direction.apply(by_owner.then_with(|| by_name(a, b)))
+        }),
+        SortKey::Activity => repos.sort_by(|a, b| {
+            missing_last(a.pushed_at, b.pushed_at, direction).then_with(|| by_name(a, b))
+        }),
+        SortKey::Stars => repos.sort_by(|a, b| {
+            missing_last(a.stars, b.stars, direction).then_with(|| by_name(a, b))
+        }),
+    }
+}
+
+fn missing_last<T: Ord>(a: Option<T>, b: Option<T>, direction: SortDirection) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => direction.apply(a.cmp(&b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Reads a visibility level, or the private flag older caches store instead
+fn deserialize_visibility<'de, D>(deserializer: D) -> Result<Visibility, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Level(Visibility),
+        Private(bool),
+    }
+
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Level(visibility) => visibility,
+        Stored::Private(is_private) => Visibility::from_private(is_private),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visibility_and_urls_from_older_caches() {
+        let stored = |visibility: &str| -> RepoInfo {
+            serde_json::from_str(&format!(
+                r#"{{"name": "api", "url": "git@gitlab.com:dima/api.git", "description": "",
+                    "owner": "dima", "is_fork": false, {}, "source": "GitLab"}}"#,
+                visibility
+            ))
+            .unwrap()
+        };
+        assert_eq!(stored(r#""is_private": true"#).visibility, Visibility::Private);
+        assert_eq!(stored(r#""is_private": false"#).visibility, Visibility::Public);
+        assert_eq!(stored(r#""visibility": "internal""#).visibility, Visibility::Internal);
+        assert_eq!(stored(r#""is_private": true"#).ssh_url, "git@gitlab.com:dima/api.git");
+
+        let repo = stored(r#""visibility": "internal""#);
+        let written = serde_json::to_string(&repo).unwrap();
+        assert!(written.contains(r#""visibility":"internal""#));
+        assert_eq!(serde_json::from_str::<RepoInfo>(&written).unwrap(), repo);
+    }
+
+    #[test]
+    fn test_affiliations() {
+        let member = Affiliation::from_name("organization_member");
+        assert_eq!(member, Some(Affiliation::OrganizationMember));
+        assert_eq!(Affiliation::from_name("member"), None);
+
+        let all = &Affiliation::ALL;
+        assert_eq!(Affiliation::infer(true, false, all), Affiliation::Owner);
+        assert_eq!(Affiliation::infer(false, true, all), Affiliation::OrganizationMember);
+        assert_eq!(Affiliation::infer(false, false, all), Affiliation::Collaborator);
+        // Without organization members the organization's repositories were shared
+        let shared = &[Affiliation::Collaborator];
+        assert_eq!(Affiliation::infer(false, true, shared), Affiliation::Collaborator);
+
+        let repo = RepoInfo {
+            affiliation: Some(Affiliation::OrganizationMember),
+            ..RepoInfo::new("api", "acme", RepoSource::GitHub)
+        };
+        let written = serde_json::to_string(&repo).unwrap();
+        assert!(written.contains(r#""affiliation":"organization_member""#));
+        assert_eq!(serde_json::from_str::<RepoInfo>(&written).unwrap(), repo);
+    }
+
+    #[test]
+    fn test_inclusion() {
+        assert_eq!(Inclusion::from_name("only"), Some(Inclusion::Only));
+        assert_eq!(Inclusion::from_name("all"), None);
+        assert_eq!(Inclusion::default(), Inclusion::Include);
+
+        let kept = |inclusion: Inclusion| [inclusion.keeps(true), inclusion.keeps(false)];
+        assert_eq!(kept(Inclusion::Include), [true, true]);
+        assert_eq!(kept(Inclusion::Exclude), [false, true]);
+        assert_eq!(kept(Inclusion::Only), [true, false]);
+    }
+
+    #[test]
+    fn test_fetch_filter() {
+        let repo = |name: &str, is_archived: bool, is_fork: bool| RepoInfo {
+            is_archived,
+            is_fork,
+            ..RepoInfo::new(name, "dima", RepoSource::GitHub)
+        };
+        let all = vec![
+            repo("api", false, false),
+            repo("old-fork", true, true),
+            repo("cargo", false, true),
+            repo("legacy", true, false),
+        ];
+        let apply = |archived, forks| {
+            let mut repos = all.clone();
+            let left_out = FetchFilter { archived, forks }.apply(&mut repos);
+            let names = repos.into_iter().map(|repo| repo.name).collect::<Vec<_>>();
+            (names, left_out)
+        };
+
+        assert_eq!(apply(Inclusion::Include, Inclusion::Include).1, None);
+        assert_eq!(
+            apply(Inclusion::Exclude, Inclusion::Exclude),
+            (vec!["api".to_string()], Some("2 archived and 1 fork".to_string()))
+        );
+        // Forks can be cleaned up without the archived ones getting in the way
+        assert_eq!(
+            apply(Inclusion::Exclude, Inclusion::Only),
+            (vec!["cargo".to_string()], Some("2 archived and 1 not a fork".to_string()))
+        );
+        assert_eq!(
+            apply(Inclusion::Include, Inclusion::Exclude),
+            (vec!["api".to_string(), "legacy".to_string()], Some("2 forks".to_string()))
+        );
+        assert_eq!(apply(Inclusion::Only, Inclusion::Include).1, Some("2 not archived".into()));
+    }
+
+    fn names(repos: &[RepoInfo]) -> Vec<&str> {
+        repos.iter().map(|repo| repo.name.as_str()).collect()
+    }
+
+    fn sample() -> Vec<RepoInfo> {
+        let repo = |name: &str, owner: &str, stars: Option<u32>, pushed: Option<&str>| RepoInfo {
+            stars,
+            pushed_at: pushed.map(|time| time.parse().unwrap()),
+            ..RepoInfo::new(name, owner, RepoSource::GitHub)
+        };
+        vec![
+            repo("cli", "zed", Some(5), Some("2024-05-01T10:00:00Z")),
+            repo("Api", "acme", None, None),
+            repo("notes", "acme", Some(5), Some("2024-06-01T10:00:00Z")),
+            repo("blog", "zed", Some(40), None),
+        ]
+    }
+
+    #[test]
+    fn test_sort_by_name_and_owner() {
+        let mut repos = sample();
+        sort_repositories(&mut repos, SortKey::Name, SortDirection::Ascending);
+        assert_eq!(names(&repos), ["Api", "blog", "cli", "notes"]);
+        sort_repositories(&mut repos, SortKey::Name, SortDirection::Descending);
+        assert_eq!(names(&repos), ["notes", "cli", "blog", "Api"]);
+        sort_repositories(&mut repos, SortKey::Owner, SortDirection::Ascending);
+        assert_eq!(names(&repos), ["Api", "notes", "blog", "cli"]);
+
+        // Unsorted keeps the order the repositories came in
+        let mut repos = sample();
+        sort_repositories(&mut repos, SortKey::Unsorted, SortDirection::Descending);
+        assert_eq!(repos, sample());
+    }
+
+    #[test]
+    fn test_sort_by_activity_and_stars_puts_missing_values_last() {
+        let mut repos = sample();
+        sort_repositories(&mut repos, SortKey::Activity, SortKey::Activity.default_direction());
+        assert_eq!(names(&repos), ["notes", "cli", "Api", "blog"]);
+        sort_repositories(&mut repos, SortKey::Activity, SortDirection::Ascending);
+        assert_eq!(names(&repos), ["cli", "notes", "Api", "blog"]);
+
+        // Equal star counts are ordered by name
+        sort_repositories(&mut repos, SortKey::Stars, SortDirection::Descending);
+        assert_eq!(names(&repos), ["blog", "cli", "notes", "Api"]);
+        sort_repositories(&mut repos, SortKey::Stars, SortDirection::Ascending);
+        assert_eq!(names(&repos), ["cli", "notes", "blog", "Api"]);
+    }
+
+    #[test]
+    fn test_sort_names() {
+        assert_eq!(SortKey::from_name("activity"), Some(SortKey::Activity));
+        assert_eq!(SortKey::from_name("none"), Some(SortKey::Unsorted));
+        assert_eq!(SortKey::from_name("size"), None);
+        assert_eq!(SortDirection::from_name("desc"), Some(SortDirection::Descending));
+        assert_eq!(SortKey::Name.default_direction(), SortDirection::Ascending);
+    }
+}
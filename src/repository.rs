@@ -1,85 +1,438 @@
+use crate::bitbucket;
 use crate::browser;
 use crate::cache;
 use crate::cli;
+use crate::clipboard;
+use crate::filter;
 use crate::formatter;
+use crate::fuzzy_finder::{Action, FinderResult};
+use crate::gitea;
 use crate::github;
 use crate::gitlab;
+use crate::hosts::Hosts;
+use crate::pagination::PageCallback;
+use crate::repo_info::{FetchFilter, FetchOptions, RepoInfo};
+use crate::styled::StyledText;
+use crate::template;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
+/// Formats repositories for display in the fuzzy finder, paired with the fields the
+/// filter matches so the name counts more than the description
+///
+/// Only the content is matched, indicators like `[GH]` or `🔒` are left to the `is:`
+/// and `source:` qualifiers. Owner prefixes are not matched either, the owner is
+/// already a field of its own.
+pub fn finder_choices(
+    repos: &[RepoInfo],
+    options: &formatter::FormatOptions,
+) -> Vec<(String, filter::Candidate)> {
+    styled_finder_choices(repos, options)
+        .into_iter()
+        .map(|(display, candidate)| (display.to_plain_string(), candidate))
+        .collect()
+}
+
+/// [`finder_choices`] with the entries in the styles of the template or aligned layout
+pub fn styled_finder_choices(
+    repos: &[RepoInfo],
+    options: &formatter::FormatOptions,
+) -> Vec<(StyledText, filter::Candidate)> {
+    // Ambiguous names are only known after looking at the whole list
+    let duplicates = if options.owner_prefix == formatter::OwnerPrefix::Auto {
+        formatter::duplicate_names(repos.iter().map(|repo| repo.name.as_str()))
+    } else {
+        HashSet::new()
+    };
+
+    let names: Vec<String> = repos
+        .iter()
+        .map(|repo| {
+            formatter::display_name(&repo.name, &repo.owner, options.owner_prefix, &duplicates)
+        })
+        .collect();
+    // Long descriptions are cut before the layout so the badges after them stay visible,
+    // line breaks and escape sequences in ones from older caches would break up rows
+    let descriptions: Vec<String> = repos
+        .iter()
+        .map(|repo| {
+            let description = formatter::sanitize_text(&repo.description);
+            match options.max_description_len {
+                Some(max_len) => {
+                    formatter::truncate_description(&description, max_len, &options.icons.ellipsis)
+                }
+                None => description,
+            }
+        })
+        .collect();
+    let fields: Vec<template::Fields> = repos
+        .iter()
+        .zip(names.iter().zip(&descriptions))
+        .map(|(repo, (name, description))| template::Fields {
+            name,
+            description,
+            source_tag: Some(options.source_labels.tag(repo.source)),
+            pushed_at: repo.pushed_at.filter(|_| options.pushed_time),
+            license: options.licenses.badge(repo.license.as_deref()),
+            size_kb: repo.size_kb.filter(|&size| size >= options.size_threshold_kb),
+            popularity: options.popularity.as_ref().and_then(|tiers| {
+                repo.stars.and_then(|stars| formatter::popularity_marker(stars, tiers))
+            }),
+            default_branch: repo.default_branch.as_deref().filter(|_| options.default_branch),
+            ..template::Fields::from(repo)
+        })
+        .collect();
+
+    // Aligned columns depend on every entry, templates render each one on its own
+    let mut displays = match options.aligned_width {
+        Some(width) => {
+            formatter::format_repositories_aligned_styled(&fields, width, &options.icons)
+        }
+        None if options.right_aligned => fields
+            .iter()
+            .map(|fields| {
+                let mut display = options.template.render_styled(&template::Fields {
+                    language: None,
+                    stars: None,
+                    pushed_at: None,
+                    ..*fields
+                });
+                display.align_right(formatter::right_badges(fields, &options.icons));
+                display
+            })
+            .collect(),
+        None => fields.iter().map(|fields| options.template.render_styled(fields)).collect(),
+    };
+    formatter::mark_cross_source_duplicates(repos, &mut displays);
+
+    repos
+        .iter()
+        .zip(displays)
+        .map(|(repo, display)| {
+            // The language and all topics are matched like any other text, so `rust` finds
+            // Rust projects and `#cli` or `cli` the ones tagged with it
+            let topics: Vec<String> =
+                repo.topics.iter().map(|topic| format!("#{}", topic)).collect();
+            let candidate =
+                filter::Candidate::repository(&repo.name, &repo.owner, &repo.description)
+                    .field(
+                        filter::FieldKind::Text,
+                        repo.language.clone().unwrap_or_default(),
+                        filter::DESCRIPTION_WEIGHT,
+                    )
+                    .field(filter::FieldKind::Text, topics.join(" "), filter::DESCRIPTION_WEIGHT)
+                    .metadata(filter::Metadata {
+                        fork: repo.is_fork,
+                        visibility: repo.visibility,
+                        archived: repo.is_archived,
+                        mirror: repo.is_mirror,
+                        starred: repo.is_starred,
+                        source: repo.source,
+                    });
+            (display, candidate)
+        })
+        .collect()
+}
+
+/// The usernames the repositories of each provider were fetched for, empty for the
+/// providers without a token
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usernames {
+    pub github: String,
+    pub gitlab: String,
+    pub gitea: String,
+    pub bitbucket: String,
+}
+
+impl Usernames {
+    /// The username at a source
+    pub fn of(&self, source: formatter::RepoSource) -> &str {
+        match source {
+            formatter::RepoSource::GitHub => &self.github,
+            formatter::RepoSource::GitLab => &self.gitlab,
+            formatter::RepoSource::Gitea => &self.gitea,
+            formatter::RepoSource::Bitbucket => &self.bitbucket,
+        }
+    }
+}
+
+/// Extracts the repository name, clone URL, browser URL and username from a selection
+///
+/// The URLs are those of `repo`, the repository the selection was made from. Only when
+/// it isn't loaded are they made up from the name in the selection, which has no owner
+/// unless `--owner-prefix` shows one, so the repository is taken to be the user's.
+fn extract_selection_info<'a>(
+    selection: &str,
+    repo: Option<&RepoInfo>,
+    usernames: &'a Usernames,
+    labels: &formatter::SourceLabels,
+    hosts: &Hosts,
+) -> Option<(String, String, Option<String>, &'a str)> {
+    // Determine the provider of the repository by its source tag, like [GH] or [GL]
+    let source = labels.source_of(selection).unwrap_or(formatter::RepoSource::GitHub);
+    let tag = labels.tag(source);
+    let username = usernames.of(repo.map_or(source, |repo| repo.source));
+    if let Some(repo) = repo {
+        let (url, browser_url) = repo_urls(repo, username, hosts);
+        return Some((repo.name.clone(), url, Some(browser_url), username));
+    }
+    // Cut at configured indicators first, the default ones are left to the providers
+    let selection = labels.selected_name(selection, source);
+
+    // Extract repository information based on the source
+    let (repo_name, url, browser_url) = match source {
+        formatter::RepoSource::GitHub => {
+            github::extract_repo_info(selection, username, tag, &hosts.github)
+        }
+        formatter::RepoSource::GitLab => {
+            gitlab::extract_repo_info(selection, username, tag, &hosts.gitlab)
+        }
+        formatter::RepoSource::Gitea => {
+            gitea::extract_repo_info(selection, username, tag, &hosts.gitea)
+        }
+        formatter::RepoSource::Bitbucket => bitbucket::extract_repo_info(selection, username, tag),
+    }?;
+
+    Some((repo_name, url, browser_url, username))
+}
+
+/// The clone and browser URLs of a repository, made up from its owner and name when the
+/// provider didn't report them, like in caches written before they were kept
+fn repo_urls(repo: &RepoInfo, username: &str, hosts: &Hosts) -> (String, String) {
+    let owner = if repo.owner.is_empty() { username } else { &repo.owner };
+    let (ssh_url, web_url) = match repo.source {
+        formatter::RepoSource::GitHub => github::repo_urls(owner, &repo.name, &hosts.github),
+        formatter::RepoSource::GitLab => gitlab::repo_urls(owner, &repo.name, &hosts.gitlab),
+        formatter::RepoSource::Gitea => gitea::repo_urls(owner, &repo.name, &hosts.gitea),
+        formatter::RepoSource::Bitbucket => bitbucket::repo_urls(owner, &repo.name),
+    };
+    let reported = |url: &str, made_up: String| {
+        if url.is_empty() {
+            made_up
+        } else {
+            url.to_string()
+        }
+    };
+    (reported(&repo.ssh_url, ssh_url), reported(&repo.web_url, web_url))
+}
+
+/// Returns the browser URL for a formatted repository selection, `repos` are those
+/// loaded so far
+pub fn selection_browser_url(
+    selection: &str,
+    repos: &[RepoInfo],
+    usernames: &Usernames,
+    labels: &formatter::SourceLabels,
+    hosts: &Hosts,
+) -> Option<String> {
+    let repo = selected_repository(selection, repos, usernames, labels);
+    extract_selection_info(selection, repo, usernames, labels, hosts)
+        .and_then(|(_, _, browser_url, _)| browser_url)
+}
+
+/// Processes the outcome of the fuzzy finder
+pub async fn process_finder_result(
+    result: &FinderResult,
+    usernames: &Usernames,
+    labels: &formatter::SourceLabels,
+    hosts: &Hosts,
+    repos: &[RepoInfo],
+) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        FinderResult::Selected { item, .. } => {
+            let repo = selected_repository(item, repos, usernames, labels);
+            process_repository_selection(
+                item,
+                repo,
+                usernames,
+                labels,
+                hosts,
+            )
+            .await
+        }
+        FinderResult::Action { item, action, .. } => {
+            let repo = selected_repository(item, repos, usernames, labels);
+            process_repository_action(
+                item,
+                repo,
+                *action,
+                usernames,
+                labels,
+                hosts,
+            )
+            .await
+        }
+        FinderResult::Intent { item, intent, .. } => {
+            let action = match intent.as_str() {
+                "open" => Action::OpenInBrowser,
+                "copy-url" => Action::CopyUrl,
+                "clone" => Action::Clone,
+                "details" => Action::ShowDetails,
+                other => return Err(format!("Unknown intent: {}", other).into()),
+            };
+            let repo = selected_repository(item, repos, usernames, labels);
+            process_repository_action(
+                item,
+                repo,
+                action,
+                usernames,
+                labels,
+                hosts,
+            )
+            .await
+        }
+        FinderResult::Query(query) => {
+            println!("Query: {}", query);
+            Ok(())
+        }
+        FinderResult::ExportList(_) | FinderResult::Cancelled | FinderResult::Interrupted => Ok(()),
+    }
+}
+
+/// Finds the repository a formatted entry was made from by its name and source tag
+///
+/// The name may have an owner prefix, like `group/api`, then only that owner's
+/// repository matches. Without one the user's own repository with the name is picked
+/// before those of others, like a starred `other/api`.
+pub fn selected_repository<'a>(
+    selection: &str,
+    repos: &'a [RepoInfo],
+    usernames: &Usernames,
+    labels: &formatter::SourceLabels,
+) -> Option<&'a RepoInfo> {
+    let source = labels.source_of(selection)?;
+    let name = labels.selected_name(selection, source);
+    let from_source = || repos.iter().filter(move |repo| repo.source == source);
+    if let Some(repo) =
+        from_source().find(|repo| format!("{}/{}", repo.owner, repo.name) == name)
+    {
+        return Some(repo);
+    }
+    let mut named = from_source().filter(|repo| repo.name == name).peekable();
+    let first = named.peek().copied();
+    named.find(|repo| repo.owner == usernames.of(source)).or(first)
+}
+
 /// Processes a selected repository by extracting its information and opening it in the browser
 pub async fn process_repository_selection(
     selection: &str,
-    github_username: &str,
-    gitlab_username: &str
+    repo: Option<&RepoInfo>,
+    usernames: &Usernames,
+    labels: &formatter::SourceLabels,
+    hosts: &Hosts,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Determine if this is a GitHub or GitLab repository based on the [GH] or [GL] tag
-    let is_gitlab = selection.contains(" [GL]");
+    process_repository_action(
+        selection,
+        repo,
+        Action::OpenInBrowser,
+        usernames,
+        labels,
+        hosts,
+    )
+    .await
+}
 
-    // Extract repository information based on the source
-    let repo_info = if is_gitlab {
-        gitlab::extract_repo_info(selection, gitlab_username)
-    } else {
-        github::extract_repo_info(selection, github_username)
+/// Runs an action from the finder's action menu on the selected repository, `repo` is
+/// what the selection was made from when it is still loaded
+pub async fn process_repository_action(
+    selection: &str,
+    repo: Option<&RepoInfo>,
+    action: Action,
+    usernames: &Usernames,
+    labels: &formatter::SourceLabels,
+    hosts: &Hosts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Process the repository information
+    let Some((repo_name, url, browser_url, username)) =
+        extract_selection_info(selection, repo, usernames, labels, hosts)
+    else {
+        println!("Error: Could not parse repository information from selection");
+        return Ok(());
     };
+    let default_branch = repo.and_then(|repo| repo.default_branch.as_deref());
+
+    match action {
+        Action::OpenInBrowser => {
+            // Open in browser if URL is available
+            let Some(browser_url) = browser_url else {
+                println!("No browser URL available for repository: {}", repo_name);
+                return Ok(());
+            };
 
-    // Process the repository information
-    if let Some((repo_name, _url, browser_url)) = repo_info {
-        // Open in browser if URL is available
-        if let Some(browser_url) = browser_url {
             // Display repository information
-            let username = if is_gitlab { gitlab_username } else { github_username };
             println!("Repository: {}", repo_name);
             println!("Username: {}", username);
+            if let Some(default_branch) = default_branch {
+                println!("Default branch: {}", default_branch);
+            }
 
             // Open the URL in the browser
             browser::open_in_browser(&browser_url).await?;
-
-            // Continue running the fuzzy finder
-            println!("\nPress any key to continue searching or Ctrl+C/Esc to exit...");
-            tokio::time::sleep(Duration::from_secs(1)).await;
-        } else {
-            println!("No browser URL available for repository: {}", repo_name);
         }
-    } else {
-        println!("Error: Could not parse repository information from selection");
+        Action::CopyUrl => {
+            clipboard::copy_to_clipboard(&url)?;
+            println!("Copied to clipboard: {}", url);
+        }
+        Action::Clone => {
+            println!("Cloning {}...", url);
+            let status = std::process::Command::new("git")
+                .args(["clone", &url])
+                .status()
+                .map_err(|e| format!("Failed to run git: {}", e))?;
+            if !status.success() {
+                return Err(format!("git clone exited with {}", status).into());
+            }
+        }
+        Action::ShowDetails => {
+            println!("Repository: {}", repo_name);
+            println!("Username: {}", username);
+            println!("Clone URL: {}", url);
+            if let Some(browser_url) = browser_url {
+                println!("Browser URL: {}", browser_url);
+            }
+            if let Some(default_branch) = default_branch {
+                println!("Default branch: {}", default_branch);
+            }
+        }
     }
 
+    // Continue running the fuzzy finder
+    println!("\nPress any key to continue searching or Ctrl+C/Esc to exit...");
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
     Ok(())
 }
 
 /// Loads dummy repositories for testing
 pub fn load_dummy_repositories(
-    all_repos: &mut Vec<cache::RepoData>,
-    github_username: &mut String,
-    gitlab_username: &mut String
+    all_repos: &mut Vec<RepoInfo>,
+    usernames: &mut Usernames
 ) {
     // Get dummy GitHub repositories
     let (dummy_username, dummy_repos) = github::generate_dummy_repos();
-    *github_username = dummy_username.clone();
-    *gitlab_username = "Gira".to_string(); // Default GitLab username for dummy data
+    usernames.github = dummy_username.clone();
+    usernames.gitlab = "Gira".to_string(); // Default GitLab username for dummy data
 
-    // Convert to RepoData with GitHub source
-    all_repos.extend(dummy_repos.into_iter().map(|(name, url, description, owner, is_fork, is_private)| {
-        cache::RepoData {
-            name,
-            url,
-            description,
-            owner,
-            is_fork,
-            is_private,
-            source: formatter::RepoSource::GitHub,
-        }
-    }));
+    all_repos.extend(dummy_repos);
 }
 
 /// Message type for repository updates
 pub enum RepoUpdateMessage {
     /// New repositories have been loaded
     NewRepos {
-        repos: Vec<cache::RepoData>,
-        github_username: String,
-        gitlab_username: String,
+        repos: Vec<RepoInfo>,
+        usernames: Usernames,
+    },
+    /// A page of repositories was fetched, to be shown next to those sent so far
+    Page {
+        repos: Vec<RepoInfo>,
+        /// How many repositories the providers fetched so far
+        loaded: usize,
+        /// How many there are in total, if the providers tell
+        expected: Option<usize>,
+        usernames: Usernames,
     },
     /// Background loading has completed
     LoadingComplete,
@@ -92,38 +445,61 @@ pub enum RepoUpdateMessage {
 /// Loads repositories with background refresh
 pub async fn load_repositories_with_background_refresh(
     args: &cli::AppArgs,
-    all_repos: &mut Vec<cache::RepoData>,
-    github_username: &mut String,
-    gitlab_username: &mut String,
+    all_repos: &mut Vec<RepoInfo>,
+    usernames: &mut Usernames,
     tx: mpsc::Sender<RepoUpdateMessage>
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Check if we should use cache
     let use_cache = !args.force_download;
     let mut cache_loaded = false;
+    let mut options = FetchOptions {
+        affiliations: args.affiliations.clone(),
+        orgs: args.orgs.clone(),
+        gitlab_groups: args.gitlab_groups,
+        starred: args.starred,
+        filter: FetchFilter { archived: args.archived, forks: args.forks },
+        retry: args.retry,
+        pages: cache::PageCache::default(),
+        fork_parents: args.fork_parents,
+        merge_request_counts: args.merge_request_counts,
+    };
 
     if use_cache {
         // Try to load from cache first
-        if let Some(cache_data) = cache::load_cache() {
-            if !cache_data.is_expired() {
+        if let Some(mut cache_data) = cache::load_cache() {
+            // Expired repositories are still fetched with the ETags of their pages
+            options.pages = cache::PageCache::new(std::mem::take(&mut cache_data.pages));
+            if cache_data.is_fresh_for(&options) {
                 // Send status message
                 let _ = tx.send(RepoUpdateMessage::Status("Using cached repositories".to_string())).await;
 
                 // Get all repositories from cache
                 *all_repos = cache_data.get_all_repositories();
+                all_repos.retain(|repo| {
+                    let wanted = |affiliation| args.affiliations.contains(&affiliation);
+                    (args.starred || !repo.is_starred) && repo.affiliation.is_none_or(wanted)
+                });
 
-                // Set usernames from GitHub or GitLab cache
+                // Set usernames from the cache of each provider
                 if let Some(github) = &cache_data.github {
-                    *github_username = github.cache_info.username.clone();
+                    usernames.github = github.cache_info.username.clone();
                 }
                 if let Some(gitlab) = &cache_data.gitlab {
-                    *gitlab_username = gitlab.cache_info.username.clone();
+                    usernames.gitlab = gitlab.cache_info.username.clone();
+                }
+                if let Some(gitea) = &cache_data.gitea {
+                    usernames.gitea = gitea.cache_info.username.clone();
+                }
+                if let Some(bitbucket) = &cache_data.bitbucket {
+                    usernames.bitbucket = bitbucket.cache_info.username.clone();
                 }
 
-                let _ = tx.send(RepoUpdateMessage::Status(
-                    format!("Loaded {} repositories from cache", all_repos.len())
-                )).await;
+                let status = format!("Loaded {} repositories from cache", all_repos.len());
+                let _ = tx.send(RepoUpdateMessage::Status(status)).await;
 
                 cache_loaded = true;
+            } else if !cache_data.is_expired() {
+                let _ = tx.send(RepoUpdateMessage::Status("Cache was fetched with other --archived, --forks, --starred or --affiliation, will fetch fresh data in background".to_string())).await;
             } else {
                 let _ = tx.send(RepoUpdateMessage::Status("Cache expired, will fetch fresh data in background".to_string())).await;
             }
@@ -134,13 +510,9 @@ pub async fn load_repositories_with_background_refresh(
         let _ = tx.send(RepoUpdateMessage::Status("Force downloading repositories in background".to_string())).await;
     }
 
-    // Clone arguments for the background task
-    let github_token = args.github_token.clone();
-    let gitlab_token = args.gitlab_token.clone();
-    let tx_clone = tx.clone();
-
-    // Start background task to fetch fresh data
-    spawn_background_task(github_token.clone(), gitlab_token.clone(), tx_clone.clone());
+    // Start background task to fetch fresh data. With nothing to show yet, every fetched
+    // page is shown right away
+    spawn_background_task(args, options, !cache_loaded, tx.clone());
 
     // If we didn't load from cache, we need to wait for the background task to provide initial data
     if !cache_loaded && all_repos.is_empty() {
@@ -150,12 +522,224 @@ pub async fn load_repositories_with_background_refresh(
     Ok(())
 }
 
-/// Spawns a background task to fetch repositories
+/// Waits until the background task is done, returning the last repositories it sent
+///
+/// Errors are reported on stderr, stdout is left to the output.
+pub async fn wait_for_background_load(
+    rx: &mut mpsc::Receiver<RepoUpdateMessage>,
+) -> Option<Vec<RepoInfo>> {
+    let mut repos = None;
+    while let Some(message) = rx.recv().await {
+        match message {
+            RepoUpdateMessage::NewRepos { repos: new_repos, .. } => repos = Some(new_repos),
+            RepoUpdateMessage::Error(error) => eprintln!("Error: {}", error),
+            RepoUpdateMessage::Page { .. } | RepoUpdateMessage::Status(_) => {}
+            RepoUpdateMessage::LoadingComplete => break,
+        }
+    }
+    repos
+}
+
+// What fetching the repositories of one provider came to
+type FetchResult = Result<(String, Vec<RepoInfo>), String>;
+
+// The repositories of one provider as far as they are fetched
+#[derive(Default)]
+struct FetchedProvider {
+    username: String,
+    repos: Vec<RepoInfo>,
+    expected: Option<usize>,
+}
+
+// The repositories of every provider as far as they are fetched
+#[derive(Default)]
+struct Fetched {
+    github: FetchedProvider,
+    gitlab: FetchedProvider,
+    gitea: FetchedProvider,
+    bitbucket: FetchedProvider,
+    errors: Vec<String>,
+}
+
+impl Fetched {
+    fn provider(&mut self, source: formatter::RepoSource) -> &mut FetchedProvider {
+        match source {
+            formatter::RepoSource::GitHub => &mut self.github,
+            formatter::RepoSource::GitLab => &mut self.gitlab,
+            formatter::RepoSource::Gitea => &mut self.gitea,
+            formatter::RepoSource::Bitbucket => &mut self.bitbucket,
+        }
+    }
+
+    fn providers(&self) -> [&FetchedProvider; 4] {
+        [&self.github, &self.gitlab, &self.gitea, &self.bitbucket]
+    }
+
+    fn usernames(&self) -> Usernames {
+        Usernames {
+            github: self.github.username.clone(),
+            gitlab: self.gitlab.username.clone(),
+            gitea: self.gitea.username.clone(),
+            bitbucket: self.bitbucket.username.clone(),
+        }
+    }
+
+    // Replaces the repositories of a provider, returning the message with those of all
+    fn update(
+        &mut self,
+        source: formatter::RepoSource,
+        username: &str,
+        repos: &[RepoInfo],
+    ) -> RepoUpdateMessage {
+        let provider = self.provider(source);
+        provider.username = username.to_string();
+        provider.repos = repos.to_vec();
+        provider.expected = None;
+        RepoUpdateMessage::NewRepos {
+            repos: self.providers().map(|provider| provider.repos.as_slice()).concat(),
+            usernames: self.usernames(),
+        }
+    }
+
+    // Adds a page to the repositories of a provider, returning the message with the
+    // page and how far the providers got. Providers that don't tell their total count
+    // with what they fetched so far
+    fn page(
+        &mut self,
+        source: formatter::RepoSource,
+        username: &str,
+        repos: &[RepoInfo],
+        expected: Option<usize>,
+    ) -> RepoUpdateMessage {
+        let provider = self.provider(source);
+        provider.username = username.to_string();
+        provider.repos.extend_from_slice(repos);
+        provider.expected = expected;
+
+        let providers = self.providers();
+        let loaded = providers.iter().map(|provider| provider.repos.len()).sum();
+        let expected = providers.iter().any(|provider| provider.expected.is_some()).then(|| {
+            let expected = providers.iter().map(|provider| {
+                provider.expected.unwrap_or(provider.repos.len())
+            });
+            expected.sum()
+        });
+        RepoUpdateMessage::Page {
+            repos: repos.to_vec(),
+            loaded,
+            expected,
+            usernames: self.usernames(),
+        }
+    }
+
+    // Adds the error of a provider, returning the message with the errors of all
+    fn error(&mut self, error: String) -> RepoUpdateMessage {
+        self.errors.push(error);
+        RepoUpdateMessage::Error(self.errors.join("; "))
+    }
+}
+
+// Fetches the repositories of one provider, sending its pages and how it went. The
+// fetch gets a callback for every page it fetched. The repositories filter doesn't keep
+// are left out before anything is sent, providers that can't leave out archived
+// repositories or forks themselves fetch them all
+async fn run_fetch(
+    source: formatter::RepoSource,
+    fetch: impl AsyncFnOnce(&mut PageCallback<'_>) -> FetchResult,
+    fetched: &RefCell<Fetched>,
+    filter: FetchFilter,
+    show_pages: bool,
+    tx: &mpsc::Sender<RepoUpdateMessage>,
+) -> FetchResult {
+    let name = source.name();
+    let _ = tx.send(RepoUpdateMessage::Status(format!("Fetching {} repositories...", name))).await;
+
+    // Pages are skipped rather than waited for when the finder is behind, the full list
+    // follows once every page is in
+    let mut on_page = |username: &str, repos: &[RepoInfo], expected: Option<usize>| {
+        if show_pages {
+            let repos: Vec<RepoInfo> =
+                repos.iter().filter(|repo| filter.keeps(repo)).cloned().collect();
+            let message = fetched.borrow_mut().page(source, username, &repos, expected);
+            let _ = tx.try_send(message);
+        }
+    };
+    let mut left_out = None;
+    let result = fetch(&mut on_page).await.map(|(username, mut repos)| {
+        left_out = filter.apply(&mut repos);
+        (username, repos)
+    });
+
+    match &result {
+        Ok((username, repos)) => {
+            let message = fetched.borrow_mut().update(source, username, repos);
+            let _ = tx.send(message).await;
+            let mut status = format!("Fetched {} {} repositories", repos.len(), name);
+            if let Some(left_out) = left_out {
+                status.push_str(&format!(", left out {}", left_out));
+            }
+            let _ = tx.send(RepoUpdateMessage::Status(status)).await;
+        }
+        Err(e) => {
+            let message = fetched.borrow_mut().error(format!("{} error: {}", name, e));
+            let _ = tx.send(message).await;
+        }
+    }
+    result
+}
+
+// Fetches from the providers at the same time, so a slow one doesn't hold up the
+// others. Every update has the repositories of all of them in the order of
+// RepoSource::ALL, and one failing doesn't stop the others
+async fn fetch_concurrently(
+    github: Option<impl AsyncFnOnce(&mut PageCallback<'_>) -> FetchResult>,
+    gitlab: Option<impl AsyncFnOnce(&mut PageCallback<'_>) -> FetchResult>,
+    gitea: Option<impl AsyncFnOnce(&mut PageCallback<'_>) -> FetchResult>,
+    bitbucket: Option<impl AsyncFnOnce(&mut PageCallback<'_>) -> FetchResult>,
+    filter: FetchFilter,
+    show_pages: bool,
+    tx: &mpsc::Sender<RepoUpdateMessage>,
+) -> [Option<FetchResult>; 4] {
+    let fetched = RefCell::new(Fetched::default());
+    let github = async {
+        let fetch = github?;
+        let source = formatter::RepoSource::GitHub;
+        Some(run_fetch(source, fetch, &fetched, filter, show_pages, tx).await)
+    };
+    let gitlab = async {
+        let fetch = gitlab?;
+        let source = formatter::RepoSource::GitLab;
+        Some(run_fetch(source, fetch, &fetched, filter, show_pages, tx).await)
+    };
+    let gitea = async {
+        let fetch = gitea?;
+        let source = formatter::RepoSource::Gitea;
+        Some(run_fetch(source, fetch, &fetched, filter, show_pages, tx).await)
+    };
+    let bitbucket = async {
+        let fetch = bitbucket?;
+        let source = formatter::RepoSource::Bitbucket;
+        Some(run_fetch(source, fetch, &fetched, filter, show_pages, tx).await)
+    };
+    let (github, gitlab, gitea, bitbucket) = tokio::join!(github, gitlab, gitea, bitbucket);
+    [github, gitlab, gitea, bitbucket]
+}
+
+/// Spawns a background task to fetch repositories from the providers of `args` as
+/// `options` asks for
 fn spawn_background_task(
-    github_token: Option<String>,
-    gitlab_token: Option<String>,
+    args: &cli::AppArgs,
+    options: FetchOptions,
+    show_pages: bool,
     tx: mpsc::Sender<RepoUpdateMessage>
 ) {
+    // Clone arguments for the background task
+    let github_token = args.github_token.clone();
+    let gitlab_token = args.gitlab_token.clone();
+    let gitea_token = args.gitea_token.clone();
+    let bitbucket = args.bitbucket.clone();
+    let hosts = args.hosts.clone();
+
     // Use a thread instead of a task to avoid Send issues
     std::thread::spawn(move || {
         // Create a new runtime for this thread
@@ -163,89 +747,88 @@ fn spawn_background_task(
 
         // Run the async code in the new runtime
         rt.block_on(async {
-            // Create a new cache
-            let mut cache_data = cache::CacheData::new();
-            let mut all_repos = Vec::new();
-            let mut github_username = String::new();
-            let mut gitlab_username = String::new();
-
-            // Fetch from GitHub if token is provided
-            if let Some(github_token) = &github_token {
-                let _ = tx.send(RepoUpdateMessage::Status("Fetching GitHub repositories...".to_string())).await;
-
-                match github::fetch_repos(github_token).await {
-                    Ok((gh_username, gh_repos)) => {
-                        github_username = gh_username.clone();
-
-                        // Convert GitHub repos to RepoData
-                        let github_repo_data: Vec<cache::RepoData> = gh_repos
-                            .iter()
-                            .map(|repo| cache::github_repo_to_repo_data(repo))
-                            .collect();
-
-                        // Add to all_repos
-                        all_repos.extend(github_repo_data.clone());
-
-                        // Update cache
-                        cache_data.update_github(github_username.clone(), github_repo_data);
-
-                        // Send update message with the GitHub repos
-                        let _ = tx.send(RepoUpdateMessage::NewRepos {
-                            repos: all_repos.clone(),
-                            github_username: github_username.clone(),
-                            gitlab_username: gitlab_username.clone(),
-                        }).await;
-
-                        let _ = tx.send(RepoUpdateMessage::Status(
-                            format!("Fetched {} GitHub repositories", gh_repos.len())
-                        )).await;
-                    },
-                    Err(e) => {
-                        // Format error message before sending to avoid Send issues
-                        let error_msg = format!("GitHub error: {}", e);
-                        let _ = tx.send(RepoUpdateMessage::Error(error_msg)).await;
-                    }
-                }
-            }
+            // The countdown while waiting for a rate limit goes to the status line
+            let on_status = |status: String| {
+                let _ = tx.try_send(RepoUpdateMessage::Status(status));
+            };
 
-            // Fetch from GitLab if token is provided
-            if let Some(gitlab_token) = &gitlab_token {
-                let _ = tx.send(RepoUpdateMessage::Status("Fetching GitLab repositories...".to_string())).await;
-
-                match gitlab::fetch_repos(gitlab_token).await {
-                    Ok((gl_username, gl_repos)) => {
-                        gitlab_username = gl_username.clone();
-
-                        // Convert GitLab repos to RepoData
-                        let gitlab_repo_data: Vec<cache::RepoData> = gl_repos
-                            .iter()
-                            .map(|repo| cache::gitlab_repo_to_repo_data(repo))
-                            .collect();
-
-                        // Add to all_repos
-                        all_repos.extend(gitlab_repo_data.clone());
-
-                        // Update cache
-                        cache_data.update_gitlab(gitlab_username.clone(), gitlab_repo_data);
-
-                        // Send update message with all repos
-                        let _ = tx.send(RepoUpdateMessage::NewRepos {
-                            repos: all_repos.clone(),
-                            github_username: github_username.clone(),
-                            gitlab_username: gitlab_username.clone(),
-                        }).await;
-
-                        let _ = tx.send(RepoUpdateMessage::Status(
-                            format!("Fetched {} GitLab repositories", gl_repos.len())
-                        )).await;
-                    },
-                    Err(e) => {
-                        // Format error message before sending to avoid Send issues
-                        let error_msg = format!("GitLab error: {}", e);
-                        let _ = tx.send(RepoUpdateMessage::Error(error_msg)).await;
-                    }
+            let (shared_options, hosts) = (&options, &hosts);
+            let github = github_token.as_deref().map(|token| {
+                async move |on_page: &mut PageCallback<'_>| {
+                    let mut on_status = on_status;
+                    let fetched = github::fetch_repos(
+                        token,
+                        &hosts.github,
+                        shared_options,
+                        on_page,
+                        &mut on_status,
+                    );
+                    fetched.await.map_err(|e| e.to_string())
+                }
+            });
+            let gitlab = gitlab_token.as_deref().map(|token| {
+                async move |on_page: &mut PageCallback<'_>| {
+                    let mut on_status = on_status;
+                    let fetched = gitlab::fetch_repos(
+                        token,
+                        &hosts.gitlab,
+                        shared_options,
+                        on_page,
+                        &mut on_status,
+                    );
+                    fetched.await.map_err(|e| e.to_string())
+                }
+            });
+            let gitea = gitea_token.as_deref().map(|token| {
+                async move |on_page: &mut PageCallback<'_>| {
+                    let mut on_status = on_status;
+                    let fetched = gitea::fetch_repos(
+                        token,
+                        &hosts.gitea,
+                        shared_options,
+                        on_page,
+                        &mut on_status,
+                    );
+                    fetched.await.map_err(|e| e.to_string())
+                }
+            });
+            let bitbucket = bitbucket.as_ref().map(|credentials| {
+                async move |on_page: &mut PageCallback<'_>| {
+                    let mut on_status = on_status;
+                    let fetched = bitbucket::fetch_repos(
+                        credentials,
+                        &shared_options.affiliations,
+                        &shared_options.retry,
+                        on_page,
+                        &mut on_status,
+                    );
+                    fetched.await.map_err(|e| e.to_string())
                 }
+            });
+            let filter = options.filter;
+            let [github, gitlab, gitea, bitbucket] =
+                fetch_concurrently(github, gitlab, gitea, bitbucket, filter, show_pages, &tx)
+                    .await;
+
+            // Only the providers that were fetched go into the new cache
+            let mut cache_data = cache::CacheData::new();
+            if let Some(Ok((username, repos))) = github {
+                cache_data.update_github(username, repos);
+            }
+            if let Some(Ok((username, repos))) = gitlab {
+                cache_data.update_gitlab(username, repos);
+            }
+            if let Some(Ok((username, repos))) = gitea {
+                cache_data.update_gitea(username, repos);
             }
+            if let Some(Ok((username, repos))) = bitbucket {
+                cache_data.update_bitbucket(username, repos);
+            }
+            cache_data.archived = filter.archived;
+            cache_data.forks = filter.forks;
+            cache_data.starred = options.starred;
+            cache_data.affiliations = options.affiliations;
+            cache_data.pages = options.pages.into_fetched();
 
             // Save the cache
             match cache::save_cache(&cache_data) {
@@ -264,3 +847,777 @@ fn spawn_background_task(
         });
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_info::{Affiliation, Inclusion};
+
+    fn repo(
+        name: &str,
+        description: &str,
+        is_fork: bool,
+        source: formatter::RepoSource,
+    ) -> RepoInfo {
+        RepoInfo {
+            description: description.to_string(),
+            is_fork,
+            visibility: formatter::Visibility::Private,
+            ..RepoInfo::new(name, "dima", source)
+        }
+    }
+
+    fn usernames() -> Usernames {
+        Usernames {
+            github: "dima".to_string(),
+            gitlab: "gira".to_string(),
+            gitea: "tea".to_string(),
+            bitbucket: "bucket".to_string(),
+        }
+    }
+
+    fn matching(repos: &[RepoInfo], query: &str) -> Vec<String> {
+        let choices = finder_choices(repos, &formatter::FormatOptions::default());
+        let candidates: Vec<filter::Candidate> =
+            choices.iter().map(|(_, candidate)| candidate.clone()).collect();
+        filter::filter_candidates(&candidates, query, filter::FilterOptions::default())
+            .into_iter()
+            .map(|(index, _)| repos[index].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_finder_choices_keep_display_decorated() {
+        let repos = [repo("api", "Service", true, formatter::RepoSource::GitHub)];
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert!(display.contains("[GH]"));
+        assert!(display.contains("🔒"));
+        assert!(display.contains("fork"));
+    }
+
+    #[test]
+    fn test_decorations_are_not_matched() {
+        let repos = [
+            repo("api", "Service", true, formatter::RepoSource::GitHub),
+            repo("web", "Frontend", false, formatter::RepoSource::GitLab),
+            repo("ghost", "Blog", false, formatter::RepoSource::GitHub),
+        ];
+        assert_eq!(matching(&repos, "gh"), vec!["ghost"]);
+        assert!(matching(&repos, "fork").is_empty());
+        assert!(matching(&repos, "🔒").is_empty());
+        assert_eq!(matching(&repos, "is:fork"), vec!["api"]);
+        assert_eq!(matching(&repos, "source:gl"), vec!["web"]);
+    }
+
+    #[test]
+    fn test_star_badge_is_not_part_of_the_selected_name() {
+        let mut starred = repo("api", "", false, formatter::RepoSource::GitHub);
+        starred.stars = Some(1_200);
+        let (display, _) = &finder_choices(&[starred], &formatter::FormatOptions::default())[0];
+        assert!(display.ends_with("★ 1.2k"));
+        assert_eq!(
+            selection_browser_url(
+                display,
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://github.com/dima/api".to_string())
+        );
+    }
+
+    #[test]
+    fn test_language_is_matched_but_not_part_of_the_selected_name() {
+        let mut notebook = repo("analysis", "", false, formatter::RepoSource::GitLab);
+        notebook.language = Some("Jupyter Notebook".to_string());
+        let repos = [notebook, repo("api", "Service", false, formatter::RepoSource::GitHub)];
+        assert_eq!(matching(&repos, "jupyter"), vec!["analysis"]);
+
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert!(display.contains("[Jupyter Notebook]"));
+        assert_eq!(
+            selection_browser_url(
+                display,
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://gitlab.com/gira/analysis".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pushed_time_can_be_turned_off() {
+        let mut pushed = repo("api", "", false, formatter::RepoSource::GitHub);
+        pushed.pushed_at = Some(chrono::Utc::now() - chrono::Duration::days(3));
+        let repos = [pushed];
+
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert!(display.ends_with("· 3d ago"));
+
+        let options = formatter::FormatOptions {
+            pushed_time: false,
+            ..Default::default()
+        };
+        let (display, _) = &finder_choices(&repos, &options)[0];
+        assert!(!display.contains("ago"));
+    }
+
+    #[test]
+    fn test_archived_repositories_are_marked_and_qualified() {
+        let mut archived = repo("legacy", "", false, formatter::RepoSource::GitHub);
+        archived.is_archived = true;
+        let repos = [archived, repo("api", "", false, formatter::RepoSource::GitHub)];
+        assert_eq!(matching(&repos, "is:archived"), vec!["legacy"]);
+
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert_eq!(display, "legacy 🔒 📦 [GH]");
+        assert_eq!(
+            selection_browser_url(
+                display,
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://github.com/dima/legacy".to_string())
+        );
+    }
+
+    #[test]
+    fn test_starred_repositories_are_marked_and_qualified() {
+        let mut starred = repo("rust", "", false, formatter::RepoSource::GitHub);
+        starred.is_starred = true;
+        let repos = [starred, repo("api", "", false, formatter::RepoSource::GitHub)];
+        assert_eq!(matching(&repos, "is:starred"), vec!["rust"]);
+        assert_eq!(matching(&repos, "!is:starred"), vec!["api"]);
+
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert!(display.contains("⭐"), "{}", display);
+    }
+
+    #[test]
+    fn test_starred_repositories_of_others_resolve_to_their_owner() {
+        let starred = |name, owner, source| RepoInfo {
+            is_starred: true,
+            ..RepoInfo::new(name, owner, source)
+        };
+        let repos = [
+            starred("api", "other", formatter::RepoSource::GitHub),
+            RepoInfo::new("api", "dima", formatter::RepoSource::GitHub),
+            starred("tools", "other", formatter::RepoSource::Gitea),
+            starred("Legacy App", "team", formatter::RepoSource::Bitbucket),
+        ];
+        let options = formatter::FormatOptions {
+            owner_prefix: formatter::OwnerPrefix::Auto,
+            ..Default::default()
+        };
+        let displays: Vec<String> =
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect();
+        assert_eq!(
+            displays,
+            vec!["other/api ⭐ [GH]", "dima/api [GH]", "tools ⭐ [GT]", "Legacy App ⭐ [BB]"]
+        );
+
+        let labels = formatter::SourceLabels::default();
+        let users = usernames();
+        for (display, repo) in displays.iter().zip(&repos) {
+            assert_eq!(selected_repository(display, &repos, &users, &labels), Some(repo));
+        }
+        let browser_urls: Vec<Option<String>> = displays
+            .iter()
+            .map(|display| {
+                selection_browser_url(display, &repos, &users, &labels, &Hosts::default())
+            })
+            .collect();
+        assert_eq!(
+            browser_urls,
+            vec![
+                Some("https://github.com/other/api".to_string()),
+                Some("https://github.com/dima/api".to_string()),
+                Some("https://codeberg.org/other/tools".to_string()),
+                Some("https://bitbucket.org/team/legacy-app".to_string()),
+            ]
+        );
+
+        // A bare name is the user's own repository before the starred one of another
+        let selected = selected_repository("api [GH]", &repos, &users, &labels);
+        assert_eq!(selected, Some(&repos[1]));
+    }
+
+    #[test]
+    fn test_auto_owner_prefix_only_names_ambiguous_repositories() {
+        let mut other_api = repo("API", "", false, formatter::RepoSource::GitLab);
+        other_api.owner = "group/subgroup".to_string();
+        let repos = [
+            repo("api", "", false, formatter::RepoSource::GitHub),
+            other_api,
+            repo("web", "", false, formatter::RepoSource::GitHub),
+        ];
+        let options = formatter::FormatOptions {
+            owner_prefix: formatter::OwnerPrefix::Auto,
+            ..Default::default()
+        };
+        let displays: Vec<String> =
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect();
+        assert_eq!(
+            displays,
+            vec!["dima/api 🔒 [GH]", "group/subgroup/API 🔒 [GL]", "web 🔒 [GH]"]
+        );
+
+        // Prefixed selections open the repository of that owner
+        assert_eq!(
+            selection_browser_url(
+                &displays[1],
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://gitlab.com/group/subgroup/api".to_string())
+        );
+        assert_eq!(
+            selection_browser_url(
+                &displays[0],
+                &[],
+                &Usernames {
+                    github: "someone".to_string(),
+                    ..usernames()
+                },
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://github.com/dima/api".to_string())
+        );
+    }
+
+    #[test]
+    fn test_self_hosted_gitlab_selections() {
+        let repos = [repo("api", "", false, formatter::RepoSource::GitLab)];
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        let hosts = Hosts {
+            gitlab: "https://gitlab.mycompany.io:8443".to_string(),
+            ..Hosts::default()
+        };
+        let labels = formatter::SourceLabels::default();
+        let (_, url, browser_url, _) =
+            extract_selection_info(display, None, &usernames(), &labels, &hosts).unwrap();
+        assert_eq!(url, "git@gitlab.mycompany.io:gira/api.git");
+        assert_eq!(browser_url, Some("https://gitlab.mycompany.io:8443/gira/api".to_string()));
+    }
+
+    #[test]
+    fn test_organization_repositories_resolve_to_their_owner() {
+        let api = RepoInfo {
+            ssh_url: "git@github.com:myorg/api.git".to_string(),
+            web_url: "https://github.com/myorg/api".to_string(),
+            ..RepoInfo::new("api", "myorg", formatter::RepoSource::GitHub)
+        };
+        // Caches written before the URLs were kept only have the owner
+        let web = RepoInfo::new("web", "myorg", formatter::RepoSource::GitHub);
+        let repos = [api, web];
+        let displays: Vec<String> = finder_choices(&repos, &formatter::FormatOptions::default())
+            .into_iter()
+            .map(|(display, _)| display)
+            .collect();
+        assert_eq!(displays, vec!["api [GH]", "web [GH]"]);
+
+        let labels = formatter::SourceLabels::default();
+        let hosts = Hosts::default();
+        let users = usernames();
+        let selected = selected_repository(&displays[0], &repos, &users, &labels);
+        let (name, url, browser_url, username) =
+            extract_selection_info(&displays[0], selected, &users, &labels, &hosts).unwrap();
+        assert_eq!(name, "api");
+        assert_eq!(url, "git@github.com:myorg/api.git");
+        assert_eq!(browser_url, Some("https://github.com/myorg/api".to_string()));
+        assert_eq!(username, "dima");
+        assert_eq!(
+            selection_browser_url(&displays[1], &repos, &users, &labels, &hosts),
+            Some("https://github.com/myorg/web".to_string())
+        );
+        let selected = selected_repository(&displays[1], &repos, &users, &labels);
+        let (_, url, _, _) =
+            extract_selection_info(&displays[1], selected, &users, &labels, &hosts).unwrap();
+        assert_eq!(url, "git@github.com:myorg/web.git");
+    }
+
+    #[test]
+    fn test_group_projects_resolve_to_their_namespace() {
+        let client = RepoInfo {
+            ssh_url: "git@gitlab.com:group/apps/diaspora-client.git".to_string(),
+            web_url: "https://gitlab.com/group/apps/diaspora-client".to_string(),
+            ..RepoInfo::new("Diaspora Client", "group/apps", formatter::RepoSource::GitLab)
+        };
+        let handbook = RepoInfo::new("Handbook", "team/docs", formatter::RepoSource::GitLab);
+        let repos = [client, handbook];
+        let displays: Vec<String> = finder_choices(&repos, &formatter::FormatOptions::default())
+            .into_iter()
+            .map(|(display, _)| display)
+            .collect();
+        assert_eq!(displays, vec!["Diaspora Client [GL]", "Handbook [GL]"]);
+
+        let labels = formatter::SourceLabels::default();
+        let hosts = Hosts::default();
+        let users = usernames();
+        let selected = selected_repository(&displays[0], &repos, &users, &labels);
+        let (name, url, browser_url, _) =
+            extract_selection_info(&displays[0], selected, &users, &labels, &hosts).unwrap();
+        assert_eq!(name, "Diaspora Client");
+        assert_eq!(url, "git@gitlab.com:group/apps/diaspora-client.git");
+        assert_eq!(browser_url, Some("https://gitlab.com/group/apps/diaspora-client".to_string()));
+        // Without the URLs the namespace path is used, not the user's
+        assert_eq!(
+            selection_browser_url(&displays[1], &repos, &users, &labels, &hosts),
+            Some("https://gitlab.com/team/docs/handbook".to_string())
+        );
+    }
+
+    #[test]
+    fn test_collaborator_repositories_resolve_to_their_owner() {
+        let repos = [RepoInfo {
+            affiliation: Some(Affiliation::Collaborator),
+            ..RepoInfo::new("dotfiles", "friend", formatter::RepoSource::GitHub)
+        }];
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        let labels = formatter::SourceLabels::default();
+        let users = usernames();
+        let selected = selected_repository(display, &repos, &users, &labels);
+        assert_eq!(selected, Some(&repos[0]));
+        let (_, url, browser_url, _) =
+            extract_selection_info(display, selected, &users, &labels, &Hosts::default()).unwrap();
+        assert_eq!(url, "git@github.com:friend/dotfiles.git");
+        assert_eq!(browser_url, Some("https://github.com/friend/dotfiles".to_string()));
+    }
+
+    #[test]
+    fn test_enterprise_github_selections() {
+        let repos = [repo("api", "", false, formatter::RepoSource::GitHub)];
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        let hosts = Hosts {
+            github: "https://github.mycorp.com".to_string(),
+            ..Hosts::default()
+        };
+        let labels = formatter::SourceLabels::default();
+        let (_, url, browser_url, _) =
+            extract_selection_info(display, None, &usernames(), &labels, &hosts).unwrap();
+        assert_eq!(url, "git@github.mycorp.com:dima/api.git");
+        assert_eq!(browser_url, Some("https://github.mycorp.com/dima/api".to_string()));
+    }
+
+    #[test]
+    fn test_custom_templates_still_resolve_selections() {
+        let mut api = repo("api", "Service", false, formatter::RepoSource::GitLab);
+        api.language = Some("Rust".to_string());
+        let options = formatter::FormatOptions {
+            template: template::Template::parse(
+                "{name}{private:🔒} {language} {source} {description}",
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let (display, _) = &finder_choices(&[api], &options)[0];
+        assert_eq!(display, "api🔒 Rust [GL] Service");
+        assert_eq!(
+            selection_browser_url(
+                display,
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://gitlab.com/gira/api".to_string())
+        );
+    }
+
+    #[test]
+    fn test_aligned_choices_still_resolve_selections() {
+        let repos = [
+            repo("api", "Service", false, formatter::RepoSource::GitHub),
+            repo("notebooks", "", true, formatter::RepoSource::GitLab),
+        ];
+        let options = formatter::FormatOptions {
+            aligned_width: Some(80),
+            ..Default::default()
+        };
+        let displays: Vec<String> =
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect();
+        assert_eq!(
+            displays,
+            vec!["api        [GH]  🔒  Service", "notebooks  [GL]  🔒  fork"]
+        );
+        assert_eq!(
+            selection_browser_url(
+                &displays[1],
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://gitlab.com/gira/notebooks".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mirrors_are_marked_and_qualified() {
+        let mut mirror = repo("upstream", "", true, formatter::RepoSource::GitLab);
+        mirror.is_mirror = true;
+        let repos = [mirror, repo("fork", "", true, formatter::RepoSource::GitLab)];
+        assert_eq!(matching(&repos, "is:mirror"), vec!["upstream"]);
+        assert_eq!(matching(&repos, "is:fork !is:mirror"), vec!["fork"]);
+
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert_eq!(display, "upstream 🔒 ⇄ [GL] (fork)");
+        assert_eq!(
+            selection_browser_url(
+                display,
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://gitlab.com/gira/upstream".to_string())
+        );
+    }
+
+    #[test]
+    fn test_all_topics_are_matched_but_only_some_shown() {
+        let mut tagged = repo("finder", "", false, formatter::RepoSource::GitHub);
+        tagged.topics =
+            ["cli", "terminal", "rust", "fuzzy"].iter().map(|topic| topic.to_string()).collect();
+        let repos = [tagged, repo("web", "Client", false, formatter::RepoSource::GitHub)];
+        assert_eq!(matching(&repos, "#cli"), vec!["finder"]);
+        let mut both = matching(&repos, "cli");
+        both.sort();
+        assert_eq!(both, vec!["finder", "web"]);
+        assert_eq!(matching(&repos, "fuzzy"), vec!["finder"]);
+
+        let (display, _) = &finder_choices(&repos, &formatter::FormatOptions::default())[0];
+        assert_eq!(display, "finder 🔒 [GH] #cli #terminal #rust +1");
+        assert_eq!(
+            selection_browser_url(
+                display,
+                &[],
+                &usernames(),
+                &formatter::SourceLabels::default(),
+                &Hosts::default(),
+            ),
+            Some("https://github.com/dima/finder".to_string())
+        );
+    }
+
+    #[test]
+    fn test_repositories_on_both_sources_are_pointed_out() {
+        let repos = [
+            repo("api", "Service", false, formatter::RepoSource::GitHub),
+            repo("api", "Service", false, formatter::RepoSource::GitLab),
+        ];
+        let displays: Vec<String> = finder_choices(&repos, &formatter::FormatOptions::default())
+            .into_iter()
+            .map(|(display, _)| display)
+            .collect();
+        assert_eq!(
+            displays,
+            vec![
+                "api 🔒 [GH] (Service) (also on GitLab)",
+                "api 🔒 [GL] (Service) (also on GitHub)"
+            ]
+        );
+        let labels = formatter::SourceLabels::default();
+        let selected = selected_repository(&displays[1], &repos, &usernames(), &labels).unwrap();
+        assert_eq!(selected.source, formatter::RepoSource::GitLab);
+    }
+
+    #[test]
+    fn test_only_large_repositories_show_their_size() {
+        let repos = [
+            RepoInfo {
+                size_kb: Some(2_202_010),
+                ..repo("monorepo", "", false, formatter::RepoSource::GitHub)
+            },
+            RepoInfo {
+                size_kb: Some(300),
+                ..repo("dotfiles", "", false, formatter::RepoSource::GitHub)
+            },
+        ];
+        let displays = |size_threshold_kb| -> Vec<String> {
+            let options = formatter::FormatOptions {
+                size_threshold_kb,
+                ..formatter::FormatOptions::default()
+            };
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect()
+        };
+        assert_eq!(
+            displays(formatter::DEFAULT_SIZE_THRESHOLD_KB),
+            vec!["monorepo 🔒 [GH] 2.1 GB", "dotfiles 🔒 [GH]"]
+        );
+        assert_eq!(displays(0), vec!["monorepo 🔒 [GH] 2.1 GB", "dotfiles 🔒 [GH] 300 KB"]);
+    }
+
+    #[test]
+    fn test_license_badges_follow_the_option() {
+        let repos = [
+            RepoInfo {
+                license: Some("MIT".to_string()),
+                ..repo("api", "", false, formatter::RepoSource::GitHub)
+            },
+            RepoInfo {
+                license: Some("NOASSERTION".to_string()),
+                ..repo("web", "", false, formatter::RepoSource::GitHub)
+            },
+        ];
+        let displays = |licenses| -> Vec<String> {
+            let options = formatter::FormatOptions {
+                licenses,
+                ..formatter::FormatOptions::default()
+            };
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect()
+        };
+        assert_eq!(displays(formatter::LicenseBadges::Never), vec!["api 🔒 [GH]", "web 🔒 [GH]"]);
+        assert_eq!(
+            displays(formatter::LicenseBadges::Known),
+            vec!["api 🔒 [GH] ‹MIT›", "web 🔒 [GH]"]
+        );
+        assert_eq!(
+            displays(formatter::LicenseBadges::All),
+            vec!["api 🔒 [GH] ‹MIT›", "web 🔒 [GH] ‹?›"]
+        );
+    }
+
+    #[test]
+    fn test_long_descriptions_are_cut_but_still_matched() {
+        let description = "Collects the repositories of every configured account. Caches them too";
+        let repos = [RepoInfo {
+            stars: Some(5),
+            ..repo("api", description, false, formatter::RepoSource::GitHub)
+        }];
+        let choices = |max_description_len| {
+            let options = formatter::FormatOptions {
+                max_description_len,
+                ..formatter::FormatOptions::default()
+            };
+            finder_choices(&repos, &options)
+        };
+        let (display, candidate) = choices(Some(40)).remove(0);
+        assert_eq!(display, "api 🔒 [GH] (Collects the repositories of every…) ★ 5");
+        assert!(candidate.fields.iter().any(|field| field.text == description));
+
+        let (display, _) = choices(None).remove(0);
+        assert_eq!(display, format!("api 🔒 [GH] ({}) ★ 5", description));
+    }
+
+    #[test]
+    fn test_default_branch_is_shown_and_found_from_selections() {
+        let repos = [
+            RepoInfo {
+                default_branch: Some("main".to_string()),
+                ..repo("api", "Service", false, formatter::RepoSource::GitHub)
+            },
+            // An empty repository without a branch yet
+            repo("notes", "", false, formatter::RepoSource::GitLab),
+        ];
+        let labels = formatter::SourceLabels::default();
+        let displays = |default_branch| -> Vec<String> {
+            let options = formatter::FormatOptions {
+                default_branch,
+                ..formatter::FormatOptions::default()
+            };
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect()
+        };
+        assert_eq!(displays(false), vec!["api 🔒 [GH] (Service)", "notes 🔒 [GL]"]);
+        let shown = displays(true);
+        assert_eq!(shown, vec!["api 🔒 [GH] (Service) (main)", "notes 🔒 [GL]"]);
+
+        let selected = selected_repository(&shown[0], &repos, &usernames(), &labels).unwrap();
+        assert_eq!(selected.default_branch.as_deref(), Some("main"));
+        let selected = selected_repository(&shown[1], &repos, &usernames(), &labels).unwrap();
+        assert_eq!(selected.name, "notes");
+        assert_eq!(selected.default_branch, None);
+        assert_eq!(selected_repository("notes 🔒 [GH]", &repos, &usernames(), &labels), None);
+    }
+
+    #[test]
+    fn test_source_labels_are_shown_and_looked_up() {
+        let repos = [
+            repo("api", "Service", false, formatter::RepoSource::GitHub),
+            repo("notes", "", false, formatter::RepoSource::GitLab),
+            repo("tools", "", false, formatter::RepoSource::Gitea),
+            repo("Old Shop", "", false, formatter::RepoSource::Bitbucket),
+        ];
+        let labels = formatter::SourceLabels::new("GHE", "GL:work", "CB", "BB:old").unwrap();
+        let options = formatter::FormatOptions {
+            source_labels: labels.clone(),
+            ..formatter::FormatOptions::default()
+        };
+        let displays: Vec<String> =
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect();
+        assert_eq!(
+            displays,
+            vec![
+                "api 🔒 [GHE] (Service)",
+                "notes 🔒 [GL:work]",
+                "tools 🔒 [CB]",
+                "Old Shop 🔒 [BB:old]",
+            ]
+        );
+        assert_eq!(
+            selection_browser_url(&displays[0], &[], &usernames(), &labels, &Hosts::default()),
+            Some("https://github.com/dima/api".to_string())
+        );
+        assert_eq!(
+            selection_browser_url(&displays[1], &[], &usernames(), &labels, &Hosts::default()),
+            Some("https://gitlab.com/gira/notes".to_string())
+        );
+        assert_eq!(
+            selection_browser_url(&displays[2], &[], &usernames(), &labels, &Hosts::default()),
+            Some("https://codeberg.org/tea/tools".to_string())
+        );
+        assert_eq!(
+            selection_browser_url(&displays[3], &[], &usernames(), &labels, &Hosts::default()),
+            Some("https://bitbucket.org/bucket/old-shop".to_string())
+        );
+    }
+
+    #[test]
+    fn test_configured_icons_still_resolve_selections() {
+        let repos = [
+            repo("api", "Service", false, formatter::RepoSource::GitHub),
+            repo("Pong", "", false, formatter::RepoSource::GitLab),
+        ];
+        let icons = formatter::Icons {
+            private: "P".to_string(),
+            ..formatter::Icons::default()
+        };
+        let labels =
+            formatter::SourceLabels::with_tags("gh", "gl", "gt", "bb").unwrap().markers(&icons);
+        let options = formatter::FormatOptions {
+            template: template::Template::default_with(&icons),
+            source_labels: labels.clone(),
+            ..formatter::FormatOptions::default()
+        };
+        let displays: Vec<String> =
+            finder_choices(&repos, &options).into_iter().map(|(display, _)| display).collect();
+        assert_eq!(displays, vec!["api P gh (Service)", "Pong P gl"]);
+        let selected = selected_repository(&displays[0], &repos, &usernames(), &labels);
+        assert_eq!(selected, Some(&repos[0]));
+        let selected = selected_repository(&displays[1], &repos, &usernames(), &labels);
+        assert_eq!(selected, Some(&repos[1]));
+        assert_eq!(
+            selection_browser_url(&displays[1], &[], &usernames(), &labels, &Hosts::default()),
+            Some("https://gitlab.com/gira/pong".to_string())
+        );
+    }
+
+    #[test]
+    fn test_right_aligned_badges_still_resolve_selections() {
+        let mut api = repo("api", "Service", false, formatter::RepoSource::GitHub);
+        api.stars = Some(321);
+        api.language = Some("Rust".to_string());
+        let repos = [api, repo("notes", "", false, formatter::RepoSource::GitLab)];
+        let options = formatter::FormatOptions {
+            right_aligned: true,
+            pushed_time: false,
+            ..formatter::FormatOptions::default()
+        };
+        let choices = styled_finder_choices(&repos, &options);
+        let (api, _) = &choices[0];
+        assert_eq!(api.left().to_plain_string(), "api 🔒 [GH] (Service)");
+        assert_eq!(api.right().unwrap().to_plain_string(), "★ 321 · Rust");
+        assert_eq!(api.to_plain_string(), "api 🔒 [GH] (Service)  ★ 321 · Rust");
+        assert_eq!(choices[1].0.right(), None);
+
+        let labels = formatter::SourceLabels::default();
+        let selected = selected_repository(&api.to_plain_string(), &repos, &usernames(), &labels);
+        assert_eq!(selected, Some(&repos[0]));
+    }
+
+    #[tokio::test]
+    async fn test_providers_are_fetched_concurrently() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let github_repo = repo("api", "", false, formatter::RepoSource::GitHub);
+        let gitlab_repo = repo("notes", "", false, formatter::RepoSource::GitLab);
+        let gitea_repo = repo("tools", "", false, formatter::RepoSource::Gitea);
+
+        // GitHub is slow, GitLab sends a page and then fails, Gitea is done in between
+        let github = Some(async |_: &mut PageCallback<'_>| {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(("dima".to_string(), vec![github_repo.clone()]))
+        });
+        let gitlab = Some(async |on_page: &mut PageCallback<'_>| {
+            on_page("gira", std::slice::from_ref(&gitlab_repo), Some(3));
+            Err("502 Bad Gateway".to_string())
+        });
+        let gitea = Some(async |_: &mut PageCallback<'_>| {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(("tea".to_string(), vec![gitea_repo.clone()]))
+        });
+        let bitbucket = None::<fn(&mut PageCallback<'_>) -> std::future::Ready<FetchResult>>;
+        let filter = FetchFilter::default();
+        let [github, gitlab, gitea, bitbucket] =
+            fetch_concurrently(github, gitlab, gitea, bitbucket, filter, true, &tx).await;
+        assert_eq!(bitbucket, None);
+        assert_eq!(github, Some(Ok(("dima".to_string(), vec![github_repo.clone()]))));
+        assert_eq!(gitlab, Some(Err("502 Bad Gateway".to_string())));
+        assert_eq!(gitea, Some(Ok(("tea".to_string(), vec![gitea_repo.clone()]))));
+
+        drop(tx);
+        let mut pages = Vec::new();
+        let mut updates = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                RepoUpdateMessage::Page { repos, loaded, expected, .. } => {
+                    pages.push((repos[0].name.clone(), loaded, expected))
+                }
+                RepoUpdateMessage::NewRepos { repos, .. } => {
+                    updates.push(repos.into_iter().map(|repo| repo.name).collect::<Vec<_>>())
+                }
+                RepoUpdateMessage::Error(error) => errors.push((updates.len(), error)),
+                _ => {}
+            }
+        }
+        // GitLab's page and error come before GitHub is done, which keeps the page
+        assert_eq!(pages, [("notes".to_string(), 1, Some(3))]);
+        assert_eq!(updates, [vec!["notes", "tools"], vec!["api", "notes", "tools"]]);
+        assert_eq!(errors, [(0, "GitLab error: 502 Bad Gateway".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_archived_repositories_and_forks_are_left_out_while_fetching() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let archived = |name: &str| RepoInfo {
+            is_archived: true,
+            ..repo(name, "", false, formatter::RepoSource::GitHub)
+        };
+        let repos = vec![
+            repo("api", "", false, formatter::RepoSource::GitHub),
+            archived("legacy"),
+            archived("old-web"),
+            repo("cargo", "", true, formatter::RepoSource::GitHub),
+        ];
+        let github = Some(async |on_page: &mut PageCallback<'_>| {
+            on_page("dima", &repos, None);
+            Ok(("dima".to_string(), repos.clone()))
+        });
+        let none = None::<fn(&mut PageCallback<'_>) -> std::future::Ready<FetchResult>>;
+        let filter = FetchFilter { archived: Inclusion::Exclude, forks: Inclusion::Exclude };
+        let [github, ..] = fetch_concurrently(github, none, none, none, filter, true, &tx).await;
+        assert_eq!(github, Some(Ok(("dima".to_string(), vec![repos[0].clone()]))));
+
+        drop(tx);
+        let mut pages = Vec::new();
+        let mut statuses = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                RepoUpdateMessage::Page { repos, .. } => pages.push(repos.len()),
+                RepoUpdateMessage::Status(status) => statuses.push(status),
+                _ => {}
+            }
+        }
+        assert_eq!(pages, [1]);
+        let status = statuses.last().unwrap();
+        assert_eq!(status, "Fetched 1 GitHub repositories, left out 2 archived and 1 fork");
+    }
+}
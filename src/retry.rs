@@ -0,0 +1,205 @@
+//! Retrying requests that failed for reasons that tend to go away
+//!
+//! Every provider sends a GET again when it could not connect, timed out or was
+//! answered with 429 or a 5xx status, waiting about twice as long after every
+//! failure. Rate limits that say when they reset are waited for instead, see
+//! [`rate_limit`](crate::rate_limit).
+
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::StatusCode;
+
+use crate::rate_limit;
+
+/// How often a failed request is sent again unless told otherwise
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// How long connecting may take before the request counts as failed
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a response may take before the request counts as failed
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How requests that failed or ran into a rate limit are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How often a failed request is sent again
+    pub retries: u32,
+    /// The wait before the first retry, doubled for every further one
+    pub base_delay: Duration,
+    /// The longest wait between two tries
+    pub max_delay: Duration,
+    /// How long a rate limit is waited for, one that resets later is an error
+    pub max_rate_limit_wait: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: DEFAULT_RETRIES,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            max_rate_limit_wait: Duration::from_secs(rate_limit::DEFAULT_MAX_WAIT_SECS),
+        }
+    }
+}
+
+/// Retries and rate limits without waiting, for the tests of the providers
+#[cfg(test)]
+pub(crate) const NO_WAIT: RetryPolicy = RetryPolicy {
+    retries: 2,
+    base_delay: Duration::ZERO,
+    max_delay: Duration::ZERO,
+    max_rate_limit_wait: Duration::ZERO,
+};
+
+impl RetryPolicy {
+    /// The wait before a retry, counted from 0
+    ///
+    /// It is picked at random between half and all of the doubled delay, so clients
+    /// that failed together don't all retry at the same moment.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let doubled = self.base_delay.saturating_mul(2u32.saturating_pow(retry));
+        let half = doubled.min(self.max_delay) / 2;
+        half + half.mul_f64(random_fraction())
+    }
+}
+
+/// Whether a response with this status is worth sending the request again for
+pub fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Counts the tries of one request
+pub struct Retries<'a> {
+    policy: &'a RetryPolicy,
+    /// The request, like `GET https://gitlab.com/api/v4/projects?page=3`
+    what: String,
+    retried: u32,
+}
+
+impl<'a> Retries<'a> {
+    pub fn new(policy: &'a RetryPolicy, what: impl Into<String>) -> Retries<'a> {
+        Retries {
+            policy,
+            what: what.into(),
+            retried: 0,
+        }
+    }
+
+    /// Waits before the next try after a transient failure
+    ///
+    /// Once every retry is used up the error is returned instead, saying which
+    /// request failed.
+    pub async fn retry(&mut self, error: impl Display) -> Result<(), String> {
+        if self.retried >= self.policy.retries {
+            return Err(format!(
+                "{} failed after {} attempts: {}",
+                self.what,
+                self.retried + 1,
+                error
+            ));
+        }
+        tokio::time::sleep(self.policy.delay(self.retried)).await;
+        self.retried += 1;
+        Ok(())
+    }
+}
+
+/// Sends a GET request, retrying failures that tend to go away and waiting for a rate
+/// limit to reset when it resets soon enough
+///
+/// `provider` names the provider in the rate limit messages, like `GitLab`.
+pub async fn send(
+    provider: &str,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+    policy: &RetryPolicy,
+    on_status: &mut dyn FnMut(String),
+) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+    let mut retries = Retries::new(policy, format!("GET {}", request.url()));
+    let mut waits = 0;
+    loop {
+        let attempt = request.try_clone().expect("requests without a body can be cloned");
+        let response = match client.execute(attempt).await {
+            Ok(response) => response,
+            Err(e) => {
+                retries.retry(e).await?;
+                continue;
+            }
+        };
+        let limited = rate_limit::limited_until(response.status(), response.headers(), Utc::now());
+        if let Some(until) = limited {
+            if waits == rate_limit::MAX_WAITS {
+                return Err(rate_limit::reset_message(provider, until, Utc::now()).into());
+            }
+            waits += 1;
+            let max_wait = policy.max_rate_limit_wait;
+            rate_limit::wait_for_reset(provider, until, max_wait, on_status).await?;
+            continue;
+        }
+        if is_transient(response.status()) {
+            retries.retry(response.status()).await?;
+            continue;
+        }
+        return Ok(response);
+    }
+}
+
+// A random number from 0 to 1, the standard library seeds its hashers randomly
+fn random_fraction() -> f64 {
+    RandomState::new().hash_one(0u8) as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_with_jitter() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(400),
+            max_delay: Duration::from_secs(2),
+            ..RetryPolicy::default()
+        };
+        for _ in 0..20 {
+            let first = policy.delay(0);
+            assert!(first >= Duration::from_millis(200) && first <= Duration::from_millis(400));
+            let third = policy.delay(2);
+            assert!(third >= Duration::from_millis(800) && third <= Duration::from_millis(1600));
+            // Capped at the longest wait
+            let late = policy.delay(30);
+            assert!(late >= Duration::from_secs(1) && late <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn test_transient_statuses() {
+        assert!(is_transient(StatusCode::BAD_GATEWAY));
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient(StatusCode::NOT_FOUND));
+        assert!(!is_transient(StatusCode::FORBIDDEN));
+    }
+
+    #[tokio::test]
+    async fn test_retries_give_up_with_context() {
+        let policy = RetryPolicy {
+            retries: 2,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let mut retries = Retries::new(&policy, "GET https://gitlab.com/api/v4/projects?page=3");
+        assert!(retries.retry("502 Bad Gateway").await.is_ok());
+        assert!(retries.retry("502 Bad Gateway").await.is_ok());
+        assert_eq!(
+            retries.retry("502 Bad Gateway").await,
+            Err("GET https://gitlab.com/api/v4/projects?page=3 failed after 3 attempts: \
+                 502 Bad Gateway"
+                .to_string())
+        );
+    }
+}
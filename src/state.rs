@@ -0,0 +1,39 @@
+//! UI preferences that persist between runs
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const STATE_FILE: &str = ".repo-searcher-state.json";
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct UiState {
+    #[serde(default)]
+    pub preview_visible: bool,
+}
+
+pub fn save_state(state: &UiState) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(state)?;
+    fs::write(STATE_FILE, json)?;
+    Ok(())
+}
+
+pub fn load_state() -> UiState {
+    if !Path::new(STATE_FILE).exists() {
+        return UiState::default();
+    }
+
+    match fs::read_to_string(STATE_FILE) {
+        Ok(json) => match serde_json::from_str(&json) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Error parsing state file: {}", e);
+                UiState::default()
+            },
+        },
+        Err(e) => {
+            eprintln!("Error reading state file: {}", e);
+            UiState::default()
+        },
+    }
+}
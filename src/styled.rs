@@ -0,0 +1,308 @@
+//! Text made of segments with display styles, like a red 🔒 or a dim source tag
+//!
+//! Styles are kept apart from the text instead of embedding escape codes in it, so the
+//! filter, width calculations and exported lists only ever see the plain text. The
+//! fuzzy finder draws the styles when it renders a row.
+//!
+//! Text can have a part that is drawn flush with the right edge of the row, like the
+//! star count and push time of a repository. Wherever rows are not laid out, it follows
+//! the rest of the text after [`RIGHT_GAP`].
+
+use unicode_width::UnicodeWidthStr;
+
+/// What comes between the text and its right-aligned part when they are not laid out
+pub const RIGHT_GAP: &str = "  ";
+
+/// How a segment is drawn, terminals without colors show every style as plain text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    #[default]
+    Plain,
+    /// Faint text for secondary details like the source tag
+    Dim,
+    /// Gray text for descriptions
+    Gray,
+    /// Red text for warnings like the private indicator
+    Red,
+}
+
+/// A run of text drawn in one style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub text: String,
+    pub style: Style,
+}
+
+/// Text made of styled segments
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StyledText {
+    segments: Vec<Segment>,
+    /// Drawn at the right edge, see the [module documentation](self)
+    right: Option<Box<StyledText>>,
+}
+
+impl StyledText {
+    /// Creates empty text
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends text in a style, merging it into the last segment if that has the same style
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::styled::{Style, StyledText};
+    ///
+    /// let mut text = StyledText::new();
+    /// text.push("api", Style::Plain);
+    /// text.push(" [GH]", Style::Dim);
+    /// assert_eq!(text.to_plain_string(), "api [GH]");
+    /// assert_eq!(text.segments().len(), 2);
+    /// ```
+    pub fn push(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        match self.segments.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(text),
+            _ => self.segments.push(Segment {
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+
+    /// Sets the part drawn flush with the right edge, empty text removes it
+    pub fn align_right(&mut self, right: StyledText) {
+        self.right = (!right.is_empty()).then(|| Box::new(right));
+    }
+
+    /// The part drawn flush with the right edge, if there is one
+    pub fn right(&self) -> Option<&StyledText> {
+        self.right.as_deref()
+    }
+
+    /// The text without its right-aligned part
+    pub fn left(&self) -> StyledText {
+        StyledText {
+            segments: self.segments.clone(),
+            right: None,
+        }
+    }
+
+    /// The text with its right-aligned part after a gap, as it is drawn without a layout
+    pub fn joined(&self) -> StyledText {
+        let mut joined = self.left();
+        if let Some(right) = &self.right {
+            joined.push(RIGHT_GAP, Style::Plain);
+            joined.append(right);
+        }
+        joined
+    }
+
+    /// Lays the text out in `width` columns with its right-aligned part at the right
+    /// edge, cutting the rest with an ellipsis to make room
+    ///
+    /// `None` when there is no right-aligned part or it leaves too little room.
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::styled::{Style, StyledText};
+    ///
+    /// let mut text = StyledText::from("api (Service)");
+    /// text.align_right(StyledText::from("★ 3"));
+    /// assert_eq!(text.to_plain_string(), "api (Service)  ★ 3");
+    /// assert_eq!(text.layout_right(20, "…").unwrap().to_plain_string(), "api (Service)    ★ 3");
+    /// assert_eq!(text.layout_right(12, "…").unwrap().to_plain_string(), "api (S…  ★ 3");
+    /// ```
+    pub fn layout_right(&self, width: usize, ellipsis: &str) -> Option<StyledText> {
+        /// The left text keeps at least this many columns, less and it is not worth it
+        const MIN_LEFT_WIDTH: usize = 6;
+
+        let right = self.right()?;
+        let right_width = right.to_plain_string().width();
+        let room = width.checked_sub(right_width + RIGHT_GAP.len())?;
+        if room < MIN_LEFT_WIDTH {
+            return None;
+        }
+        let mut line = self.left();
+        if line.to_plain_string().width() > room {
+            line = line.take_width(room.saturating_sub(ellipsis.width()));
+            line.push(ellipsis, Style::Plain);
+        }
+        let used = line.to_plain_string().width();
+        line.push(&" ".repeat(room - used + RIGHT_GAP.len()), Style::Plain);
+        line.append(right);
+        Some(line)
+    }
+
+    /// Appends all segments of other text, without its right-aligned part
+    pub fn append(&mut self, other: &StyledText) {
+        for segment in &other.segments {
+            self.push(&segment.text, segment.style);
+        }
+    }
+
+    /// The segments in order, none of them is empty
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The text without styles, as it is matched and exported, with the right-aligned
+    /// part after a gap
+    pub fn to_plain_string(&self) -> String {
+        let mut plain: String = self.segments.iter().map(|segment| segment.text.as_str()).collect();
+        if let Some(right) = &self.right {
+            plain.push_str(RIGHT_GAP);
+            plain.push_str(&right.to_plain_string());
+        }
+        plain
+    }
+
+    /// Whether there is no text at all
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty() && self.right.is_none()
+    }
+
+    /// The characters that fit into `width` columns, keeping their styles and leaving out
+    /// the right-aligned part
+    pub fn take_width(&self, width: usize) -> StyledText {
+        let mut taken = StyledText::new();
+        let mut remaining = width;
+        for segment in &self.segments {
+            let mut text = String::new();
+            for c in segment.text.chars() {
+                let char_width = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+                if char_width > remaining {
+                    remaining = 0;
+                    break;
+                }
+                remaining -= char_width;
+                text.push(c);
+            }
+            taken.push(&text, segment.style);
+            if remaining == 0 {
+                break;
+            }
+        }
+        taken
+    }
+
+    /// The first `count` characters, keeping their styles and leaving out the
+    /// right-aligned part
+    pub fn take_chars(&self, count: usize) -> StyledText {
+        let mut taken = StyledText::new();
+        let mut remaining = count;
+        for segment in &self.segments {
+            if remaining == 0 {
+                break;
+            }
+            let text: String = segment.text.chars().take(remaining).collect();
+            remaining -= text.chars().count();
+            taken.push(&text, segment.style);
+        }
+        taken
+    }
+
+    /// Removes trailing whitespace before the right-aligned part, dropping segments that
+    /// become empty
+    pub fn trim_end(&mut self) {
+        while let Some(last) = self.segments.last_mut() {
+            let trimmed = last.text.trim_end().len();
+            last.text.truncate(trimmed);
+            if !last.text.is_empty() {
+                break;
+            }
+            self.segments.pop();
+        }
+    }
+}
+
+impl From<&str> for StyledText {
+    fn from(text: &str) -> Self {
+        let mut styled = StyledText::new();
+        styled.push(text, Style::Plain);
+        styled
+    }
+}
+
+impl From<String> for StyledText {
+    fn from(text: String) -> Self {
+        StyledText::from(text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> StyledText {
+        let mut text = StyledText::new();
+        text.push("api", Style::Plain);
+        text.push(" 🔒", Style::Red);
+        text.push(" [GH]", Style::Dim);
+        text.push(" (Service)  ", Style::Gray);
+        text
+    }
+
+    #[test]
+    fn test_push_merges_and_skips_empty_text() {
+        let mut text = StyledText::new();
+        text.push("a", Style::Dim);
+        text.push("", Style::Red);
+        text.push("b", Style::Dim);
+        assert_eq!(
+            text.segments(),
+            [Segment {
+                text: "ab".to_string(),
+                style: Style::Dim
+            }]
+        );
+        assert!(StyledText::new().is_empty());
+    }
+
+    #[test]
+    fn test_take_chars_keeps_styles() {
+        let taken = sample().take_chars(5);
+        assert_eq!(taken.to_plain_string(), "api 🔒");
+        assert_eq!(taken.segments()[1].style, Style::Red);
+        assert_eq!(sample().take_chars(100), sample());
+        assert!(sample().take_chars(0).is_empty());
+    }
+
+    #[test]
+    fn test_trim_end() {
+        let mut text = sample();
+        text.push("   ", Style::Plain);
+        text.trim_end();
+        assert_eq!(text.to_plain_string(), "api 🔒 [GH] (Service)");
+        assert_eq!(text.segments().len(), 4);
+    }
+
+    #[test]
+    fn test_right_aligned_part() {
+        let mut text = sample();
+        text.trim_end();
+        let mut right = StyledText::from("★ 3 · ");
+        right.push("3d ago", Style::Dim);
+        text.align_right(right.clone());
+        assert_eq!(text.to_plain_string(), "api 🔒 [GH] (Service)  ★ 3 · 3d ago");
+        assert_eq!(text.right(), Some(&right));
+        assert_eq!(text.left().to_plain_string(), "api 🔒 [GH] (Service)");
+
+        let line = text.layout_right(40, "…").unwrap();
+        assert_eq!(line.to_plain_string().width(), 40);
+        assert!(line.to_plain_string().ends_with("(Service)       ★ 3 · 3d ago"));
+        assert_eq!(line.segments().last().unwrap().style, Style::Dim);
+
+        // The left part is cut first, wide characters are never split
+        let line = text.layout_right(24, "…").unwrap();
+        assert_eq!(line.to_plain_string(), "api 🔒 [G…  ★ 3 · 3d ago");
+        assert_eq!(text.layout_right(20, "…").unwrap().to_plain_string(), "api …   ★ 3 · 3d ago");
+        assert_eq!(text.layout_right(19, "…"), None);
+        assert_eq!(text.joined().to_plain_string(), text.to_plain_string());
+        assert_eq!(text.joined().right(), None);
+        assert_eq!(sample().layout_right(80, "…"), None);
+
+        text.align_right(StyledText::new());
+        assert_eq!(text.right(), None);
+    }
+}
@@ -0,0 +1,714 @@
+//! Display templates for repository entries
+//!
+//! A template is a format string with placeholders for the fields of a repository,
+//! parsed once and rendered for every entry. [`DEFAULT_TEMPLATE`] is the layout the
+//! formatter uses when the user did not pick another one with `--format`.
+//!
+//! # Syntax
+//!
+//! - `{field}` - The value of a field, nothing when it is empty
+//! - `{field:text}` - `text` when the field is set, where `{}` stands for its value,
+//!   like `{stars: ★ {}}`. The whole segment disappears when the field is empty or false
+//! - `{{` and `}}` - Literal braces outside of placeholders
+//!
+//! # Fields
+//!
+//! - `name` - Repository name, with the owner prefix if one is configured
+//! - `owner` - Owner or namespace
+//! - `description` - Trimmed description
+//! - `details` - `fork: description`, `fork` or the description, as in the default format
+//! - `fork`, `private`, `internal`, `archived`, `mirror`, `template`, `empty`, `starred` -
+//!   Set for such repositories, their values are `fork`, `🔒`, `🏢`, `📦`, `⇄`, `▤`, `∅`
+//!   and `⭐`, or `[private]`, `[internal]`, `[archived]`, `[mirror]`, `[template]`,
+//!   `[empty]` and `[starred]` with the ASCII icon set
+//! - `source` - `[GH]` or `[GL]`
+//! - `language` - Primary language, after its Nerd Font glyph with `--nerd-font`
+//! - `license` - License identifier like `MIT`, `?` when it was not recognized
+//! - `topics` - The first few topics as hashtags, like `#cli #terminal +2`
+//! - `stars` - Abbreviated star count, empty without stars
+//! - `popularity` - A marker like `🔥` for the popularity tier of the star count, empty
+//!   unless tiers are turned on
+//! - `size` - Repository size like `1.4 GB`, empty for repositories below the size threshold
+//! - `issues`, `merge_requests` - Open issue and pull or merge request counts, empty
+//!   when there are none or they were not counted
+//! - `pushed` - Time since the last push, like `3d ago`
+//! - `branch` - The default branch, like `main`, empty for empty repositories
+//!
+//! Selections are mapped back to repositories by their name and source tag, so a
+//! template has to start with `{name}` and contain `{source}`.
+
+use chrono::{DateTime, Utc};
+
+use crate::formatter::{self, IconSet, Icons, RepoSource, Visibility};
+use crate::repo_info::RepoInfo;
+use crate::styled::{Style, StyledText};
+
+/// The layout of the default display format
+pub const DEFAULT_TEMPLATE: &str = "{name}{private: {}}{internal: {}}{archived: {}}\
+                                    {mirror: {}}{template: {}}{empty: {}}{starred: {}} {source}\
+                                    {popularity: {}}{language: [{}]}{license: ‹{}›}\
+                                    {details: ({})}{topics: {}}{stars: ★ {}}{size: {}}\
+                                    {issues: ⊙ {}}{merge_requests: ⇵ {}}{pushed: · {}}\
+                                    {branch: ({})}";
+
+/// The default layout with plain text in place of the symbols
+pub const DEFAULT_ASCII_TEMPLATE: &str =
+    "{name}{private: {}}{internal: {}}{archived: {}}{mirror: {}}{template: {}}{empty: {}}\
+     {starred: {}} {source}{popularity: {}}{language: [{}]}{license: <{}>}{details: ({})}\
+     {topics: {}}{stars: * {}}{size: {}}{issues: issues {}}{merge_requests: requests {}}\
+     {pushed: - {}}{branch: ({})}";
+
+/// A field of a repository that a placeholder refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Owner,
+    Description,
+    Details,
+    Fork,
+    Private,
+    Internal,
+    Archived,
+    Mirror,
+    Template,
+    Empty,
+    Starred,
+    Source,
+    Language,
+    License,
+    Topics,
+    Stars,
+    Popularity,
+    Size,
+    Issues,
+    MergeRequests,
+    Pushed,
+    Branch,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 23] = [
+        ("name", Field::Name),
+        ("owner", Field::Owner),
+        ("description", Field::Description),
+        ("details", Field::Details),
+        ("fork", Field::Fork),
+        ("private", Field::Private),
+        ("internal", Field::Internal),
+        ("archived", Field::Archived),
+        ("mirror", Field::Mirror),
+        ("template", Field::Template),
+        ("empty", Field::Empty),
+        ("starred", Field::Starred),
+        ("source", Field::Source),
+        ("language", Field::Language),
+        ("license", Field::License),
+        ("topics", Field::Topics),
+        ("stars", Field::Stars),
+        ("popularity", Field::Popularity),
+        ("size", Field::Size),
+        ("issues", Field::Issues),
+        ("merge_requests", Field::MergeRequests),
+        ("pushed", Field::Pushed),
+        ("branch", Field::Branch),
+    ];
+
+    fn from_name(name: &str) -> Option<Field> {
+        Field::ALL
+            .iter()
+            .find(|(field_name, _)| *field_name == name)
+            .map(|(_, field)| *field)
+    }
+
+    /// How the field is drawn, conditional text around it is drawn the same way
+    fn style(self) -> Style {
+        match self {
+            Field::Private => Style::Red,
+            Field::Source | Field::License | Field::Pushed | Field::Branch => Style::Dim,
+            Field::Description | Field::Details => Style::Gray,
+            _ => Style::Plain,
+        }
+    }
+
+    /// Renders the value of the field, empty when the field is not set
+    fn value(self, repo: &Fields, icons: &Icons) -> String {
+        match self {
+            Field::Name => repo.name.to_string(),
+            Field::Owner => repo.owner.to_string(),
+            Field::Description => repo.description.trim().to_string(),
+            Field::Details => repo.details(icons),
+            Field::Fork => flag(repo.is_fork, &repo.fork_status(icons)),
+            Field::Private => flag(repo.visibility == Visibility::Private, &icons.private),
+            Field::Internal => flag(repo.visibility == Visibility::Internal, &icons.internal),
+            Field::Archived => flag(repo.is_archived, &icons.archived),
+            Field::Mirror => flag(repo.is_mirror, &icons.mirror),
+            Field::Template => flag(repo.is_template, &icons.template),
+            Field::Empty => flag(repo.is_empty, &icons.empty),
+            Field::Starred => flag(repo.is_starred, &icons.starred),
+            Field::Source => repo.tag(),
+            Field::Language => formatter::format_language(repo.language.unwrap_or_default(), icons),
+            Field::License => repo.license.unwrap_or_default().to_string(),
+            Field::Topics => formatter::format_topics(repo.topics),
+            Field::Stars => count(repo.stars),
+            Field::Popularity => repo.popularity.unwrap_or_default().to_string(),
+            Field::Size => repo.size_kb.map(formatter::format_size).unwrap_or_default(),
+            Field::Issues => count(repo.open_issues),
+            Field::MergeRequests => count(repo.open_merge_requests),
+            Field::Pushed => repo.pushed_at.map(formatter::format_relative_time).unwrap_or_default(),
+            Field::Branch => repo.default_branch.unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// An abbreviated count, empty when it is zero or unknown
+fn count(count: Option<u32>) -> String {
+    match count {
+        Some(count) if count > 0 => formatter::format_star_count(count),
+        _ => String::new(),
+    }
+}
+
+fn flag(set: bool, text: &str) -> String {
+    if set {
+        text.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// The fields of a repository entry that templates can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fields<'a> {
+    pub name: &'a str,
+    pub owner: &'a str,
+    pub description: &'a str,
+    pub is_fork: bool,
+    pub fork_parent: Option<&'a str>,
+    pub visibility: Visibility,
+    pub is_archived: bool,
+    pub is_mirror: bool,
+    pub is_template: bool,
+    pub is_empty: bool,
+    pub is_starred: bool,
+    pub source: RepoSource,
+    /// Shown instead of `[GH]` or `[GL]`, like `[GL:work]`
+    pub source_tag: Option<&'a str>,
+    pub language: Option<&'a str>,
+    /// The text of the license badge, see [`LicenseBadges`](formatter::LicenseBadges)
+    pub license: Option<&'a str>,
+    pub topics: &'a [String],
+    pub stars: Option<u32>,
+    /// The marker of the popularity tier, see [`Tiers`](formatter::Tiers)
+    pub popularity: Option<&'a str>,
+    /// Only set for repositories that are large enough to point out
+    pub size_kb: Option<u64>,
+    pub open_issues: Option<u32>,
+    pub open_merge_requests: Option<u32>,
+    pub pushed_at: Option<DateTime<Utc>>,
+    pub default_branch: Option<&'a str>,
+}
+
+impl Fields<'_> {
+    /// The fork status and description, like `fork of rust-lang/cargo: description`
+    ///
+    /// An empty fork icon leaves the fork status out.
+    pub fn details(&self, icons: &Icons) -> String {
+        let description = self.description.trim();
+        let fork_status = self.fork_status(icons);
+        match (fork_status.is_empty(), description.is_empty()) {
+            (false, true) => fork_status,
+            (false, false) => format!("{}: {}", fork_status, description),
+            (true, _) => description.to_string(),
+        }
+    }
+
+    /// `fork of rust-lang/cargo`, or just `fork` when the parent is unknown
+    fn fork_status(&self, icons: &Icons) -> String {
+        match self.fork_parent {
+            _ if !self.is_fork || icons.fork.is_empty() => String::new(),
+            Some(parent) => format!("{} of {}", icons.fork, parent),
+            None => icons.fork.clone(),
+        }
+    }
+
+    /// The source indicator, like `[GH]` or `[GL]`, unless the source has another tag
+    pub fn tag(&self) -> String {
+        match self.source_tag {
+            Some(tag) => tag.to_string(),
+            None => match self.source {
+                RepoSource::GitHub => format!("[{}]", formatter::DEFAULT_GITHUB_LABEL),
+                RepoSource::GitLab => format!("[{}]", formatter::DEFAULT_GITLAB_LABEL),
+                RepoSource::Gitea => format!("[{}]", formatter::DEFAULT_GITEA_LABEL),
+                RepoSource::Bitbucket => format!("[{}]", formatter::DEFAULT_BITBUCKET_LABEL),
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a RepoInfo> for Fields<'a> {
+    fn from(repo: &'a RepoInfo) -> Self {
+        Fields {
+            name: &repo.name,
+            owner: &repo.owner,
+            description: &repo.description,
+            is_fork: repo.is_fork,
+            fork_parent: repo.fork_parent.as_deref(),
+            visibility: repo.visibility,
+            is_archived: repo.is_archived,
+            is_mirror: repo.is_mirror,
+            is_template: repo.is_template,
+            is_empty: repo.is_empty,
+            is_starred: repo.is_starred,
+            source: repo.source,
+            source_tag: None,
+            language: repo.language.as_deref(),
+            license: formatter::LicenseBadges::Known.badge(repo.license.as_deref()),
+            topics: &repo.topics,
+            stars: repo.stars,
+            // Off by default, see FormatOptions::popularity
+            popularity: None,
+            size_kb: repo.size_kb.filter(|&size| size >= formatter::DEFAULT_SIZE_THRESHOLD_KB),
+            open_issues: repo.open_issues,
+            open_merge_requests: repo.open_merge_requests,
+            pushed_at: repo.pushed_at,
+            // Off by default, see FormatOptions::default_branch
+            default_branch: None,
+        }
+    }
+}
+
+/// A piece of the text of a conditional segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+    Conditional { field: Field, parts: Vec<Part> },
+}
+
+/// A parsed display template, see the [module documentation](self) for the syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+    icons: Icons,
+}
+
+impl Template {
+    /// Parses a template, failing on unknown placeholders and unbalanced braces
+    ///
+    /// ```
+    /// use repo_searcher_github_and_gitlab::template::Template;
+    ///
+    /// assert!(Template::parse("{name} {source}{stars: ★ {}}").is_ok());
+    /// assert!(Template::parse("{name} {source} {colour}").is_err());
+    /// ```
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_placeholder(&mut chars, template)?);
+                }
+                '}' => {
+                    return Err(format!(
+                        "Unmatched '}}' in format template \"{}\", write '}}}}' for a literal brace",
+                        template
+                    ));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        let starts_with_name = matches!(segments.first(), Some(Segment::Field(Field::Name)));
+        let has_source = segments.iter().any(|segment| match segment {
+            Segment::Field(field) | Segment::Conditional { field, .. } => *field == Field::Source,
+            Segment::Literal(_) => false,
+        });
+        if !starts_with_name || !has_source {
+            return Err(format!(
+                "Format template \"{}\" has to start with {{name}} and contain {{source}}, \
+                 selections are looked up by them",
+                template
+            ));
+        }
+
+        Ok(Template {
+            segments,
+            icons: Icons::default(),
+        })
+    }
+
+    /// The default layout for an icon set
+    pub fn default_for(icons: IconSet) -> Template {
+        Template::default_with(&icons.into())
+    }
+
+    /// The default layout drawn with icons from the config file
+    pub fn default_with(icons: &Icons) -> Template {
+        Template::parse(&default_layout(icons))
+            .expect("the default templates are valid")
+            .icons(icons.clone())
+    }
+
+    /// Sets the icons the `private`, `archived` and other flag placeholders are drawn with
+    pub fn icons(mut self, icons: impl Into<Icons>) -> Self {
+        self.icons = icons.into();
+        self
+    }
+
+    /// Renders the template for one repository
+    pub fn render(&self, repo: &Fields) -> String {
+        self.render_styled(repo).to_plain_string()
+    }
+
+    /// Renders the template for one repository with the private indicator red, the
+    /// source tag and push time dim and the description gray
+    pub fn render_styled(&self, repo: &Fields) -> StyledText {
+        let mut text = StyledText::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => text.push(literal, Style::Plain),
+                Segment::Field(field) => text.push(&field.value(repo, &self.icons), field.style()),
+                Segment::Conditional { field, parts } => {
+                    let value = field.value(repo, &self.icons);
+                    if value.is_empty() {
+                        continue;
+                    }
+                    for part in parts {
+                        match part {
+                            Part::Literal(literal) => text.push(literal, field.style()),
+                            Part::Value => text.push(&value, field.style()),
+                        }
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template::default_for(IconSet::default())
+    }
+}
+
+/// The layout of [`DEFAULT_TEMPLATE`] with the icons in its literal text, which are
+/// [`DEFAULT_ASCII_TEMPLATE`] for the ASCII icon set
+///
+/// Icons can't contain braces, conditional text has no way to escape them.
+fn default_layout(icons: &Icons) -> String {
+    format!(
+        "{{name}}{{private: {{}}}}{{internal: {{}}}}{{archived: {{}}}}{{mirror: {{}}}}\
+         {{template: {{}}}}{{empty: {{}}}}{{starred: {{}}}} {{source}}{{popularity: {{}}}}\
+         {{language: [{{}}]}}\
+         {{license: {}{{}}{}}}{{details: ({{}})}}{{topics: {{}}}}{{stars: {} {{}}}}{{size: {{}}}}\
+         {{issues: {} {{}}}}{{merge_requests: {} {{}}}}{{pushed: {} {{}}}}{{branch: ({{}})}}",
+        icons.license_start,
+        icons.license_end,
+        icons.star,
+        icons.issues,
+        icons.merge_requests,
+        icons.separator,
+    )
+}
+
+/// Parses a placeholder after its opening brace, up to and including the closing one
+fn parse_placeholder(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    template: &str,
+) -> Result<Segment, String> {
+    let unterminated = || format!("Unterminated placeholder in format template \"{}\"", template);
+
+    let mut name = String::new();
+    let conditional = loop {
+        match chars.next() {
+            Some('}') => break false,
+            Some(':') => break true,
+            Some(c) => name.push(c),
+            None => return Err(unterminated()),
+        }
+    };
+
+    let field = Field::from_name(name.trim()).ok_or_else(|| {
+        let known: Vec<&str> = Field::ALL.iter().map(|(name, _)| *name).collect();
+        format!(
+            "Unknown placeholder {{{}}} in format template, known placeholders are {}",
+            name,
+            known.join(", ")
+        )
+    })?;
+    if !conditional {
+        return Ok(Segment::Field(field));
+    }
+
+    // Conditional text ends at the first closing brace, `{}` inside it is the value
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some('{') => {
+                match chars.next() {
+                    Some('}') => {}
+                    None => return Err(unterminated()),
+                    Some(_) => {
+                        return Err(format!(
+                            "Only {{}} can be used inside {{{}:...}} in format template \"{}\"",
+                            name, template
+                        ));
+                    }
+                }
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(Part::Value);
+            }
+            Some(c) => literal.push(c),
+            None => return Err(unterminated()),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+
+    Ok(Segment::Conditional { field, parts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(name: &str) -> Fields<'_> {
+        Fields {
+            name,
+            owner: "dima",
+            description: "",
+            is_fork: false,
+            fork_parent: None,
+            visibility: Visibility::Public,
+            is_archived: false,
+            is_mirror: false,
+            is_template: false,
+            is_empty: false,
+            is_starred: false,
+            source: RepoSource::GitHub,
+            source_tag: None,
+            language: None,
+            license: None,
+            topics: &[],
+            stars: None,
+            popularity: None,
+            size_kb: None,
+            open_issues: None,
+            open_merge_requests: None,
+            pushed_at: None,
+            default_branch: None,
+        }
+    }
+
+    #[test]
+    fn test_default_template_matches_format_repository() {
+        let template = Template::default();
+        let repo = Fields {
+            description: "  Unity project ",
+            is_fork: true,
+            visibility: Visibility::Private,
+            is_archived: true,
+            source: RepoSource::GitLab,
+            language: Some("C#"),
+            stars: Some(1234),
+            ..fields("game-demo")
+        };
+        assert_eq!(
+            template.render(&repo),
+            formatter::format_repository("game-demo", "  Unity project ", true, Visibility::Private, true, RepoSource::GitLab, Some("C#"), Some(1234), None)
+        );
+        assert_eq!(template.render(&repo), "game-demo 🔒 📦 [GL] [C#] (fork: Unity project) ★ 1.2k");
+        assert_eq!(template.render(&fields("plain")), "plain [GH]");
+    }
+
+    #[test]
+    fn test_default_layouts() {
+        assert_eq!(default_layout(&IconSet::Unicode.into()), DEFAULT_TEMPLATE);
+        assert_eq!(default_layout(&IconSet::Ascii.into()), DEFAULT_ASCII_TEMPLATE);
+    }
+
+    #[test]
+    fn test_configured_icons() {
+        let icons = Icons {
+            private: "P".to_string(),
+            archived: String::new(),
+            fork: "⑂".to_string(),
+            star: "☆".to_string(),
+            ..IconSet::Unicode.into()
+        };
+        let repo = Fields {
+            visibility: Visibility::Private,
+            is_archived: true,
+            is_fork: true,
+            fork_parent: Some("rust-lang/cargo"),
+            stars: Some(5),
+            ..fields("cargo")
+        };
+        assert_eq!(
+            Template::default_with(&icons).render(&repo),
+            "cargo P [GH] (⑂ of rust-lang/cargo) ☆ 5"
+        );
+
+        // Without a fork icon only the description is left
+        let icons = Icons { fork: String::new(), ..icons };
+        let repo = Fields { description: "Patched", ..repo };
+        assert_eq!(Template::default_with(&icons).render(&repo), "cargo P [GH] (Patched) ☆ 5");
+    }
+
+    #[test]
+    fn test_fork_parent() {
+        let fork = Fields {
+            is_fork: true,
+            fork_parent: Some("rust-lang/cargo"),
+            description: "Patched build",
+            ..fields("cargo")
+        };
+        assert_eq!(
+            Template::default().render(&fork),
+            "cargo [GH] (fork of rust-lang/cargo: Patched build)"
+        );
+        let fork = Fields { description: "", ..fork };
+        assert_eq!(Template::default().render(&fork), "cargo [GH] (fork of rust-lang/cargo)");
+        let template = Template::parse("{name} {source} {fork}").unwrap();
+        assert_eq!(template.render(&fork), "cargo [GH] fork of rust-lang/cargo");
+
+        // Without a known parent forks stay a bare fork
+        let fork = Fields { fork_parent: None, ..fork };
+        assert_eq!(Template::default().render(&fork), "cargo [GH] (fork)");
+    }
+
+    #[test]
+    fn test_open_counts() {
+        let repo = Fields {
+            open_issues: Some(12),
+            open_merge_requests: Some(3),
+            ..fields("api")
+        };
+        assert_eq!(Template::default().render(&repo), "api [GH] ⊙ 12 ⇵ 3");
+        assert_eq!(
+            Template::default_for(IconSet::Ascii).render(&repo),
+            "api [GH] issues 12 requests 3"
+        );
+
+        // Zero counts leave the line as short as without them
+        let repo = Fields {
+            open_merge_requests: Some(0),
+            ..repo
+        };
+        assert_eq!(Template::default().render(&repo), "api [GH] ⊙ 12");
+        let template = Template::parse("{name} {source} {issues}/{merge_requests}").unwrap();
+        assert_eq!(template.render(&repo), "api [GH] 12/");
+    }
+
+    #[test]
+    fn test_default_branch() {
+        let repo = Fields {
+            stars: Some(3),
+            default_branch: Some("main"),
+            ..fields("api")
+        };
+        let styled = Template::default().render_styled(&repo);
+        assert_eq!(styled.to_plain_string(), "api [GH] ★ 3 (main)");
+        assert_eq!(styled.segments().last().unwrap().style, Style::Dim);
+
+        // Empty repositories have no branch to show
+        let repo = Fields { default_branch: None, ..repo };
+        assert_eq!(Template::default().render(&repo), "api [GH] ★ 3");
+    }
+
+    #[test]
+    fn test_render_styled() {
+        let repo = Fields {
+            description: "Service",
+            visibility: Visibility::Private,
+            stars: Some(3),
+            ..fields("api")
+        };
+        let styled = Template::default().render_styled(&repo);
+        assert_eq!(styled.to_plain_string(), Template::default().render(&repo));
+        let segments: Vec<(&str, Style)> = styled
+            .segments()
+            .iter()
+            .map(|segment| (segment.text.as_str(), segment.style))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                ("api", Style::Plain),
+                (" 🔒", Style::Red),
+                (" ", Style::Plain),
+                ("[GH]", Style::Dim),
+                (" (Service)", Style::Gray),
+                (" ★ 3", Style::Plain),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditional_segments_disappear() {
+        let template = Template::parse("{name} {source}{private: private}{stars: ({} stars)}").unwrap();
+        assert_eq!(template.render(&fields("api")), "api [GH]");
+
+        let repo = Fields {
+            visibility: Visibility::Private,
+            stars: Some(12),
+            ..fields("api")
+        };
+        assert_eq!(template.render(&repo), "api [GH] private (12 stars)");
+    }
+
+    #[test]
+    fn test_plain_placeholders_and_escaped_braces() {
+        let template = Template::parse("{name} {{{owner}}} {source} {language}|{fork}").unwrap();
+        let repo = Fields {
+            is_fork: true,
+            ..fields("api")
+        };
+        assert_eq!(template.render(&repo), "api {dima} [GH] |fork");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let error = Template::parse("{name} {source} {colour}").unwrap_err();
+        assert!(error.contains("Unknown placeholder {colour}"));
+        assert!(error.contains("name, owner"));
+
+        assert!(Template::parse("{name} {source").unwrap_err().contains("Unterminated"));
+        assert!(Template::parse("{name} {source}{stars: {").unwrap_err().contains("Unterminated"));
+        assert!(Template::parse("{name} {source} }").unwrap_err().contains("Unmatched"));
+        assert!(Template::parse("{name} {source}{stars:{x}}").unwrap_err().contains("Only {}"));
+
+        // The name and source tag identify selections
+        assert!(Template::parse("{source} {name}").unwrap_err().contains("start with {name}"));
+        assert!(Template::parse("{name} ({description})").unwrap_err().contains("{source}"));
+    }
+}
@@ -1,6 +1,5 @@
 use std::io::Write;
 use std::process;
-use termion;
 use termion::input::TermRead;
 
 /// Cleans up the terminal state before exiting
@@ -10,7 +9,7 @@ pub fn cleanup_terminal() {
     std::io::stdout().flush().unwrap();
     
     // Reset terminal attributes to ensure proper cleanup
-    if let Ok(_) = termion::get_tty() {
+    if termion::get_tty().is_ok() {
         let _ = termion::async_stdin().keys().next(); // Consume any pending input
         let _ = termion::terminal_size(); // Force terminal refresh
     }
@@ -22,6 +21,7 @@ pub fn setup_ctrl_c_handler() {
     ctrlc::set_handler(move || {
         cleanup_terminal();
         println!("\nReceived Ctrl+C, exiting...");
-        process::exit(0);
+        // 130 is the conventional exit status after SIGINT
+        process::exit(130);
     }).expect("Error setting Ctrl+C handler");
 }
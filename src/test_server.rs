@@ -0,0 +1,156 @@
+//! A local HTTP server standing in for the provider APIs in tests
+//!
+//! Every request is answered with whatever the handler returns for it, and the
+//! connection is closed after each response. The server runs until the test ends.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request as the handler sees it
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// The path with its query, like `/user/repos?per_page=100`
+    pub target: String,
+    /// Header names are lowercase
+    pub headers: Vec<(String, String)>,
+    /// Empty for requests without one, like GETs
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The address of the server as the client called it, for absolute URLs in responses
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.header("host").unwrap_or_default())
+    }
+}
+
+/// A canned response, JSON unless a header says otherwise
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(body: impl ToString) -> Response {
+        Response {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Response {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+pub struct TestServer {
+    /// Like `http://127.0.0.1:40123`, without a trailing slash
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub async fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler = Arc::new(handler);
+
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (handler, seen) = (handler.clone(), seen.clone());
+                tokio::spawn(async move {
+                    let _ = serve(stream, |request| {
+                        seen.lock().unwrap().push(request.clone());
+                        handler(request)
+                    })
+                    .await;
+                });
+            }
+        });
+
+        TestServer { url, requests }
+    }
+
+    /// The requests received so far, in order
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    handler: impl FnOnce(&Request) -> Response,
+) -> std::io::Result<()> {
+    // The headers end with an empty line, a body of Content-Length bytes follows
+    let mut received = Vec::new();
+    let mut buffer = [0; 4096];
+    let head_end = loop {
+        if let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        received.extend_from_slice(&buffer[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&received[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let target = lines.next().and_then(|line| line.split(' ').nth(1)).unwrap_or("/");
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    while received.len() < head_end + length {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        received.extend_from_slice(&buffer[..read]);
+    }
+    let body_end = received.len().min(head_end + length);
+    let response = handler(&Request {
+        target: target.to_string(),
+        headers,
+        body: String::from_utf8_lossy(&received[head_end..body_end]).into_owned(),
+    });
+
+    let mut written = format!(
+        "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        written.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    written.push_str("\r\n");
+    written.push_str(&response.body);
+    stream.write_all(written.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,337 @@
+//! Where the tokens of the providers come from
+//!
+//! Every provider looks its token up the same way, the first source that has one wins:
+//!
+//! 1. the command line flag, like `--github-token`
+//! 2. the environment, `GITHUB_TOKEN` or `GH_TOKEN` for GitHub, `GITLAB_TOKEN` or
+//!    `GL_TOKEN` for GitLab, `GITEA_TOKEN` for Gitea and `BITBUCKET_TOKEN` for Bitbucket
+//! 3. the config file, like `"github_token"`
+//! 4. the system keychain, where `auth set github` stores them
+//! 5. for GitHub and GitLab, what `gh auth login` and `glab auth login` stored, when
+//!    `"cli_credentials": true` in the config file turns it on
+//!
+//! Empty values are skipped. Tokens are never printed, errors only name the sources
+//! that were checked. A keychain that can't be read, like on a headless server without
+//! a Secret Service, is skipped with a warning.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::config::{Config, CONFIG_FILE};
+use crate::formatter::RepoSource;
+use crate::hosts::Hosts;
+
+/// A place a token can come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    /// A command line flag, like `--github-token`
+    Flag(&'static str),
+    /// An environment variable, like `GITHUB_TOKEN`
+    Env(&'static str),
+    /// A key of the config file, like `github_token`
+    Config(&'static str),
+    /// The system keychain
+    Keychain,
+    /// A provider's command line tool, `gh` or `glab`
+    Cli(&'static str),
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::Flag(flag) => write!(f, "--{}", flag),
+            TokenSource::Env(name) => write!(f, "${}", name),
+            TokenSource::Config(key) => write!(f, "\"{}\" in {}", key, CONFIG_FILE),
+            TokenSource::Keychain => write!(f, "the keychain"),
+            TokenSource::Cli(tool) => write!(f, "{} auth login", tool),
+        }
+    }
+}
+
+/// A token and where it was found
+///
+/// Its `Debug` output leaves the token out, so it can't end up in logs by accident.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token {
+    pub value: String,
+    pub source: TokenSource,
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("value", &"<hidden>")
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+/// The sources a provider's token is looked up in, in order
+pub fn sources(provider: RepoSource) -> Vec<TokenSource> {
+    let (flag, env, key): (_, &[_], _) = match provider {
+        RepoSource::GitHub => ("github-token", &["GITHUB_TOKEN", "GH_TOKEN"], "github_token"),
+        RepoSource::GitLab => ("gitlab-token", &["GITLAB_TOKEN", "GL_TOKEN"], "gitlab_token"),
+        RepoSource::Gitea => ("gitea-token", &["GITEA_TOKEN"], "gitea_token"),
+        RepoSource::Bitbucket => ("bitbucket-token", &["BITBUCKET_TOKEN"], "bitbucket_token"),
+    };
+    let tool = match provider {
+        RepoSource::GitHub => Some(TokenSource::Cli("gh")),
+        RepoSource::GitLab => Some(TokenSource::Cli("glab")),
+        RepoSource::Gitea | RepoSource::Bitbucket => None,
+    };
+    let env = env.iter().map(|&name| TokenSource::Env(name));
+    std::iter::once(TokenSource::Flag(flag))
+        .chain(env)
+        .chain([TokenSource::Config(key), TokenSource::Keychain])
+        .chain(tool)
+        .collect()
+}
+
+/// Looks a flag or environment variable up by its name
+pub type Lookup<'a> = Box<dyn Fn(&str) -> Option<String> + 'a>;
+
+/// Looks the token of a provider up in the keychain
+pub type KeychainLookup<'a> = Box<dyn Fn(RepoSource) -> Result<Option<String>, String> + 'a>;
+
+/// Asks the command line tool of a provider for its token
+pub type CliLookup<'a> = Box<dyn Fn(RepoSource) -> Option<String> + 'a>;
+
+/// Looks the tokens of the providers up in the flags, the environment, the config and
+/// the keychain
+pub struct TokenResolver<'a> {
+    flag: Lookup<'a>,
+    env: Lookup<'a>,
+    config: &'a Config,
+    keychain: KeychainLookup<'a>,
+    // Why the keychain couldn't be read, it isn't asked again after that
+    keychain_error: RefCell<Option<String>>,
+    // Only there when the config file opts in
+    cli: Option<CliLookup<'a>>,
+}
+
+impl<'a> TokenResolver<'a> {
+    /// A resolver reading flags with `flag`, given the flag name like `github-token`,
+    /// the real environment and the system keychain, and the tokens of `gh` and `glab`
+    /// for the given hosts when the config opts in
+    pub fn new(
+        flag: impl Fn(&str) -> Option<String> + 'a,
+        config: &'a Config,
+        hosts: &'a Hosts,
+    ) -> Self {
+        let resolver = TokenResolver::with_lookups(
+            flag,
+            |name| std::env::var(name).ok(),
+            crate::keychain::get,
+            config,
+        );
+        match config.cli_credentials {
+            Some(true) => resolver.with_cli(|provider| crate::credentials::token(provider, hosts)),
+            _ => resolver,
+        }
+    }
+
+    /// A resolver reading the environment with `env` and the keychain with `keychain`
+    /// instead
+    pub fn with_lookups(
+        flag: impl Fn(&str) -> Option<String> + 'a,
+        env: impl Fn(&str) -> Option<String> + 'a,
+        keychain: impl Fn(RepoSource) -> Result<Option<String>, String> + 'a,
+        config: &'a Config,
+    ) -> Self {
+        TokenResolver {
+            flag: Box::new(flag),
+            env: Box::new(env),
+            config,
+            keychain: Box::new(keychain),
+            keychain_error: RefCell::new(None),
+            cli: None,
+        }
+    }
+
+    /// Also asks the command line tools with `cli` after every other source
+    pub fn with_cli(mut self, cli: impl Fn(RepoSource) -> Option<String> + 'a) -> Self {
+        self.cli = Some(Box::new(cli));
+        self
+    }
+
+    /// The sources a provider's token is looked up in, without the command line tools
+    /// unless they are asked
+    pub fn sources(&self, provider: RepoSource) -> Vec<TokenSource> {
+        let mut sources = sources(provider);
+        if self.cli.is_none() {
+            sources.retain(|source| !matches!(source, TokenSource::Cli(_)));
+        }
+        sources
+    }
+
+    /// Why the keychain was skipped, `None` when it could be read or wasn't needed
+    pub fn keychain_error(&self) -> Option<String> {
+        self.keychain_error.borrow().clone()
+    }
+
+    /// The token of a provider from the first source that has one
+    pub fn resolve(&self, provider: RepoSource) -> Option<Token> {
+        self.sources(provider).into_iter().find_map(|source| {
+            let value = match source {
+                TokenSource::Flag(flag) => (self.flag)(flag),
+                TokenSource::Env(name) => (self.env)(name),
+                TokenSource::Config(_) => self.config_token(provider).cloned(),
+                TokenSource::Keychain => self.keychain_token(provider),
+                TokenSource::Cli(_) => self.cli.as_ref().and_then(|cli| cli(provider)),
+            }?;
+            let value = value.trim();
+            (!value.is_empty()).then(|| Token {
+                value: value.to_string(),
+                source,
+            })
+        })
+    }
+
+    fn keychain_token(&self, provider: RepoSource) -> Option<String> {
+        if self.keychain_error.borrow().is_some() {
+            return None;
+        }
+        (self.keychain)(provider).unwrap_or_else(|e| {
+            *self.keychain_error.borrow_mut() = Some(e);
+            None
+        })
+    }
+
+    fn config_token(&self, provider: RepoSource) -> Option<&String> {
+        match provider {
+            RepoSource::GitHub => self.config.github_token.as_ref(),
+            RepoSource::GitLab => self.config.gitlab_token.as_ref(),
+            RepoSource::Gitea => self.config.gitea_token.as_ref(),
+            RepoSource::Bitbucket => self.config.bitbucket_token.as_ref(),
+        }
+    }
+
+    /// Says that no provider has a token and where they were looked for
+    pub fn missing_message(&self) -> String {
+        let checked: Vec<String> = RepoSource::ALL
+            .iter()
+            .map(|&provider| {
+                let sources: Vec<String> =
+                    self.sources(provider).iter().map(|s| s.to_string()).collect();
+                format!("  {}: {}", provider.name(), sources.join(", "))
+            })
+            .collect();
+        format!("No token found, checked these sources:\n{}", checked.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup<'a>(pairs: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| pairs.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    }
+
+    fn no_keychain(_: RepoSource) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    #[test]
+    fn test_precedence() {
+        let config = Config {
+            github_token: Some("from-config".to_string()),
+            gitlab_token: Some("gitlab-config".to_string()),
+            ..Config::default()
+        };
+        let flags = [("github-token", "from-flag")];
+        let env = [("GH_TOKEN", "from-gh"), ("GITHUB_TOKEN", "from-env"), ("GL_TOKEN", " ")];
+        let resolver =
+            TokenResolver::with_lookups(lookup(&flags), lookup(&env), no_keychain, &config);
+
+        let github = resolver.resolve(RepoSource::GitHub).unwrap();
+        assert_eq!(github.value, "from-flag");
+        assert_eq!(github.source, TokenSource::Flag("github-token"));
+        // Blank values are skipped
+        let gitlab = resolver.resolve(RepoSource::GitLab).unwrap();
+        assert_eq!(gitlab.source, TokenSource::Config("gitlab_token"));
+        assert_eq!(resolver.resolve(RepoSource::Gitea), None);
+
+        // GITHUB_TOKEN wins over GH_TOKEN, both over the config
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&env), no_keychain, &config);
+        let github = resolver.resolve(RepoSource::GitHub).unwrap();
+        assert_eq!(github.value, "from-env");
+        assert_eq!(github.source, TokenSource::Env("GITHUB_TOKEN"));
+        let env = [("GH_TOKEN", "from-gh")];
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&env), no_keychain, &config);
+        assert_eq!(resolver.resolve(RepoSource::GitHub).unwrap().value, "from-gh");
+    }
+
+    #[test]
+    fn test_keychain() {
+        let config = Config {
+            gitlab_token: Some("from-config".to_string()),
+            ..Config::default()
+        };
+        let keychain = |provider| match provider {
+            RepoSource::GitHub | RepoSource::GitLab => Ok(Some("from-keychain".to_string())),
+            _ => Ok(None),
+        };
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&[]), keychain, &config);
+        let github = resolver.resolve(RepoSource::GitHub).unwrap();
+        assert_eq!(github.value, "from-keychain");
+        assert_eq!(github.source, TokenSource::Keychain);
+        assert_eq!(resolver.resolve(RepoSource::GitLab).unwrap().value, "from-config");
+        assert_eq!(resolver.resolve(RepoSource::Gitea), None);
+        assert_eq!(resolver.keychain_error(), None);
+
+        // A keychain that can't be read is asked once and skipped after that
+        let asked = std::cell::Cell::new(0);
+        let broken = |_| {
+            asked.set(asked.get() + 1);
+            Err("no Secret Service".to_string())
+        };
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&[]), broken, &config);
+        assert_eq!(resolver.resolve(RepoSource::GitHub), None);
+        assert_eq!(resolver.resolve(RepoSource::Gitea), None);
+        assert_eq!(asked.get(), 1);
+        assert_eq!(resolver.keychain_error(), Some("no Secret Service".to_string()));
+    }
+
+    #[test]
+    fn test_cli_credentials() {
+        let config = Config {
+            github_token: Some("from-config".to_string()),
+            ..Config::default()
+        };
+        let cli = |provider| Some(format!("from-{}", crate::keychain::account(provider)));
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&[]), no_keychain, &config)
+            .with_cli(cli);
+        // Below everything configured, and only for GitHub and GitLab
+        assert_eq!(resolver.resolve(RepoSource::GitHub).unwrap().value, "from-config");
+        let gitlab = resolver.resolve(RepoSource::GitLab).unwrap();
+        assert_eq!(gitlab.value, "from-gitlab");
+        assert_eq!(gitlab.source, TokenSource::Cli("glab"));
+        assert_eq!(resolver.resolve(RepoSource::Gitea), None);
+
+        // Not asked without opting in
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&[]), no_keychain, &config);
+        assert_eq!(resolver.resolve(RepoSource::GitLab), None);
+    }
+
+    #[test]
+    fn test_tokens_are_not_printed() {
+        let token = Token {
+            value: "ghp_secret".to_string(),
+            source: TokenSource::Env("GH_TOKEN"),
+        };
+        assert!(!format!("{:?}", token).contains("ghp_secret"));
+
+        let config = Config::default();
+        let resolver = TokenResolver::with_lookups(lookup(&[]), lookup(&[]), no_keychain, &config);
+        let message = resolver.missing_message();
+        assert!(message.contains("GitHub: --github-token, $GITHUB_TOKEN, $GH_TOKEN"));
+        assert!(message.contains(&format!("\"gitlab_token\" in {}", CONFIG_FILE)));
+        assert!(message.contains("Bitbucket: --bitbucket-token, $BITBUCKET_TOKEN"));
+        assert!(message.contains("in .repo-searcher-config.json, the keychain\n"));
+        assert!(!message.contains("gh auth login"));
+        let message = resolver.with_cli(|_| None).missing_message();
+        assert!(message.contains("the keychain, gh auth login\n"));
+        assert!(message.contains("the keychain, glab auth login\n"));
+    }
+}
@@ -0,0 +1,69 @@
+//! Runs the binary against a local server standing in for GitHub, checking that what
+//! `--output` prints is all there is on stdout
+
+#[allow(dead_code)]
+#[path = "../src/test_server.rs"]
+mod test_server;
+
+use std::path::PathBuf;
+
+use test_server::{Response, TestServer};
+
+// An empty working directory, so there is no cache or config file yet
+fn working_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("repo-searcher-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn test_output_is_all_there_is_on_stdout() {
+    let server = TestServer::start(|request| match request.target.as_str() {
+        "/api/v3/user" => Response::json(r#"{"login": "dima"}"#),
+        target if target.starts_with("/api/v3/user/repos?") => Response::json(serde_json::json!([
+            {"id": 1, "name": "api", "full_name": "dima/api", "url": "https://github.mycorp.com"},
+            {"id": 2, "name": "web", "full_name": "dima/web", "url": "https://github.mycorp.com"},
+        ])),
+        _ => Response::json(r#"{"message": "Not Found"}"#).status(404),
+    })
+    .await;
+
+    let dir = working_dir("output");
+    for format in ["json", "tsv"] {
+        let _ = std::fs::remove_file(dir.join(".repo-cache.json"));
+        let binary = env!("CARGO_BIN_EXE_repo-searcher-github-and-gitlab");
+        let output = tokio::process::Command::new(binary)
+            .args(["--output", format, "--affiliation", "owner", "--github-host", &server.url])
+            .current_dir(&dir)
+            .env_clear()
+            .env("HOME", &dir)
+            .env("GITHUB_TOKEN", "token")
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        match format {
+            "json" => {
+                let names: Vec<String> = lines
+                    .iter()
+                    .map(|line| {
+                        let repo: serde_json::Value = serde_json::from_str(line).unwrap();
+                        repo["name"].as_str().unwrap().to_string()
+                    })
+                    .collect();
+                assert_eq!(names, ["api", "web"]);
+            }
+            _ => {
+                assert_eq!(lines.len(), 3, "{}", stdout);
+                assert!(lines.iter().all(|line| line.contains('\t')), "{}", stdout);
+            }
+        }
+        // The progress went to stderr
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Fetched"));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}